@@ -0,0 +1,52 @@
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId},
+    prelude::RwLock,
+};
+
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library::Database;
+
+//How often the announcement task checks whether any announcements are due
+const ANNOUNCEMENT_CHECK_SECS: u64 = 60;
+
+//Background task that posts the announcements scheduled with !announce schedule. When they were
+//last posted is saved with the library, so restarting the bot neither loses them nor posts them
+//twice
+pub async fn announcement_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(ANNOUNCEMENT_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            post_announcements(&http, guild, &library_arc).await;
+        }
+    }
+}
+
+async fn post_announcements(
+    http: &Arc<Http>,
+    guild: Option<GuildId>,
+    library_arc: &RwLock<Database>,
+) {
+    let due = {
+        let mut library = library_arc.write().await;
+        let due = library.due_announcements(chrono::Local::now());
+        if !due.is_empty() {
+            crate::autosave::persist_change(&library).await;
+        }
+        due
+    };
+
+    for (channel, message) in due {
+        if let Err(err) = ChannelId(channel).say(http, message).await {
+            println!(
+                "Failed to post announcement in guild {:?}: {:?}",
+                guild, err
+            );
+        }
+    }
+}
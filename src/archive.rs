@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::games::GameUuid;
+use crate::library::{Database, TimeType};
+use crate::otb::OtbUuid;
+use crate::tournaments::{Outcome, TournamentUuid};
+
+//Every finished game between members, wherever it was played, kept in one place so that members'
+//histories and head to head records don't have to look in several. Games are added as they finish:
+//games played with the bot once they end, over the board results once they are confirmed,
+//tournament games once their result counts, and Lichess games between linked members when someone
+//imports them with !archive lichess. Games that finished before the archive was added are archived
+//when the library is loaded
+
+pub type ArchiveUuid = u32;
+
+//Where an archived game was played. Each game is only archived once
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum GameSource {
+    //Played with the bot
+    Discord(GameUuid),
+    //Reported with !report-result
+    OverTheBoard(OtbUuid),
+    //The tournament, round and board, counting rounds and boards from 1
+    Tournament(TournamentUuid, usize, usize),
+    //The Lichess game id
+    Lichess(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, new)]
+pub struct ArchivedGame {
+    pub uuid: ArchiveUuid,
+    //Discord ids of the players
+    pub white: String,
+    pub black: String,
+    pub played: TimeType,
+    //The tournament or site the game was played in, if any
+    pub event: Option<String>,
+    pub outcome: Outcome,
+    //None for games played over the board, whose moves the bot never sees
+    pub pgn: Option<String>,
+    pub source: GameSource,
+    //The opening and its ECO code, like "B33 Sicilian Defense: Sveshnikov Variation", for games
+    //whose moves are known
+    #[new(default)]
+    #[serde(default)]
+    pub opening: Option<String>,
+}
+
+impl ArchivedGame {
+    pub fn has_player(&self, discord_id: &str) -> bool {
+        self.white == discord_id || self.black == discord_id
+    }
+
+    //Puts discord id `to` wherever the game has `from`, for members who are merged or forgotten
+    pub fn replace_player(&mut self, from: &str, to: &str) {
+        for id in vec![&mut self.white, &mut self.black] {
+            if *id == from {
+                *id = to.to_owned();
+            }
+        }
+    }
+
+    //What `discord_id`, who played in the game, scored: 1 for a win, ½ for a draw, 0 for a loss
+    pub fn score_of(&self, discord_id: &str) -> f64 {
+        if self.white == discord_id {
+            self.outcome.white_score()
+        } else {
+            1.0 - self.outcome.white_score()
+        }
+    }
+
+    //Whether the game was played in an opening with ECO code `query`, like B33, or with `query` in
+    //its name, like "sicilian". Codes can be given in part, so that B3 finds B30 to B39
+    pub fn in_opening(&self, query: &str) -> bool {
+        let opening = match &self.opening {
+            Some(opening) => opening.to_lowercase(),
+            None => return false,
+        };
+        let query = query.trim().to_lowercase();
+        let (eco, name) = opening.split_once(' ').unwrap_or((opening.as_str(), ""));
+        !query.is_empty() && (eco.starts_with(&query) || name.contains(&query))
+    }
+
+    //A line for lists of games, like "ABCDEFG Mar 3, 2024: @white vs @black 1-0 (Spring open)"
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} {}: <@{}> vs <@{}> {}",
+            Database::encode_uuid(self.uuid),
+            self.played.format("%b %-d, %Y"),
+            self.white,
+            self.black,
+            self.outcome
+        );
+        if let Some(event) = &self.event {
+            text.push_str(&format!(" ({})", event));
+        }
+        text
+    }
+}
@@ -0,0 +1,161 @@
+use serenity::{http::Http, model::id::ChannelId};
+
+use std::sync::Arc;
+
+use crate::games::{Game, GameStatus};
+use crate::guilds::Libraries;
+use crate::library::Database;
+use crate::tournaments::{Outcome, TournamentFormat, TournamentStatus, TournamentUuid};
+
+//Arenas are tournaments where members play as many games as they can in a fixed time, like on
+//Lichess. The arena task looks at every running arena every few seconds. It takes the results of
+//its games that have finished, pairs the players who are free with each other and starts a game
+//with the arena's clock for each new pairing, then edits the standings message in the tournament's
+//channel. The games are ordinary games, played with !chess move. Once time is up nobody else is
+//paired, and the arena ends when its last game does
+
+//How often the arena task looks at the running arenas
+const ARENA_CHECK_SECS: u64 = 10;
+
+//Background task that pairs the players of running arenas and takes the results of their games
+pub async fn arena_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(ARENA_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (_, library_arc) in libraries.all().await {
+            //Most guilds have no arena running, so the library is only written to when one has
+            let arenas: Vec<TournamentUuid> = library_arc
+                .read()
+                .await
+                .tournaments
+                .values()
+                .filter(|tournament| {
+                    tournament.is_arena() && tournament.status == TournamentStatus::Running
+                })
+                .map(|tournament| tournament.uuid)
+                .collect();
+            if arenas.is_empty() {
+                continue;
+            }
+
+            let mut library = library_arc.write().await;
+            let mut changed = false;
+            for uuid in arenas {
+                changed |= update(&http, &mut library, uuid).await;
+            }
+            if changed {
+                crate::autosave::persist_change(&library).await;
+            }
+        }
+    }
+}
+
+//Takes the results of arena `uuid`'s finished games, then pairs its free players and starts their
+//games. Returns whether anything changed
+async fn update(http: &Http, library: &mut Database, uuid: TournamentUuid) -> bool {
+    let tournament = match library.tournaments.get(&uuid) {
+        Some(tournament) => tournament,
+        None => return false,
+    };
+    let clock = match tournament.format {
+        TournamentFormat::Arena { clock, .. } => clock,
+        _ => return false,
+    };
+    let pairings = match tournament.rounds.last() {
+        Some(round) => &round.pairings,
+        None => return false,
+    };
+    let finished: Vec<(usize, Outcome)> = tournament
+        .games
+        .iter()
+        .zip(pairings)
+        .enumerate()
+        .filter(|(_, (_, pairing))| pairing.result.is_none())
+        .filter_map(|(i, (game, _))| {
+            let outcome = match library.games.get(game)?.status {
+                GameStatus::WhiteWon => Outcome::WhiteWon,
+                GameStatus::BlackWon => Outcome::BlackWon,
+                GameStatus::Drawn => Outcome::Draw,
+                _ => return None,
+            };
+            Some((i + 1, outcome))
+        })
+        .collect();
+
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    let mut ended = false;
+    for (board, outcome) in &finished {
+        ended |= tournament.arena_result(*board, *outcome, &library.club_ratings);
+    }
+    //Time may have run out with no games going
+    ended |= tournament.close_arena(&library.club_ratings);
+
+    //Players who already have a game against each other can't be given another one
+    let games = &library.games;
+    let busy = |a: &str, b: &str| {
+        games.values().any(|game| {
+            !game.status.is_over()
+                && ((game.white == a && game.black == b) || (game.white == b && game.black == a))
+        })
+    };
+    let boards = tournament.pair_arena(&library.club_ratings, &busy);
+    let channel = tournament.channel;
+    let name = tournament.name.clone();
+    let pairs: Vec<(String, String)> = boards
+        .iter()
+        .filter_map(|board| {
+            let pairing = tournament.rounds.last()?.pairings.get(board - 1)?;
+            Some((pairing.white.clone(), pairing.black.clone()?))
+        })
+        .collect();
+
+    let mut posts = Vec::new();
+    for (white, black) in pairs {
+        let game_uuid = library.new_game_uuid();
+        let mut game = Game::new(
+            game_uuid,
+            white.clone(),
+            black.clone(),
+            white.clone(),
+            channel,
+        );
+        game.status = GameStatus::Playing;
+        game.clock = Some(clock);
+        library.games.insert(game_uuid, game);
+        library
+            .tournaments
+            .get_mut(&uuid)
+            .unwrap()
+            .games
+            .push(game_uuid);
+        posts.push(format!(
+            "**{}**: <@{}> (white) vs <@{}> (black) with a {} clock, game {}. <@{}> to move, play with !chess move",
+            name,
+            white,
+            black,
+            clock,
+            Database::encode_uuid(game_uuid),
+            white
+        ));
+    }
+    for text in posts {
+        if let Err(err) = ChannelId(channel).say(http, text).await {
+            println!(
+                "Failed to post a game of arena {}: {:?}",
+                Database::encode_uuid(uuid),
+                err
+            );
+        }
+    }
+
+    if finished.is_empty() && boards.is_empty() && !ended {
+        return false;
+    }
+    crate::tournaments::update_bracket(http, library, uuid).await;
+    if ended {
+        println!("Arena {} ended", Database::encode_uuid(uuid));
+        crate::tournaments::post_round(http, library, uuid).await;
+    }
+    true
+}
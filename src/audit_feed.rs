@@ -0,0 +1,81 @@
+use serenity::{
+    model::id::{ChannelId, GuildId},
+    prelude::*,
+};
+
+use crate::library::{AuditEntry, ChannelKind};
+
+//Besides being kept with the library, every audit log entry is posted as one line in the guild's
+//audit channel, set with !config channel audit, so that officers can follow what happens to the
+//library as it happens
+
+//Discord refuses longer messages
+const MAX_MESSAGE_LEN: usize = 2000;
+
+fn format_entry(entry: &AuditEntry) -> String {
+    //Forgotten members no longer have a discord id to mention
+    let actor = match entry.actor.parse::<u64>() {
+        Ok(id) => format!("<@{}>", id),
+        Err(_) => entry.actor.clone(),
+    };
+    let line = format!(
+        "`{}` {} {}",
+        entry.time.format("%b %-d %H:%M"),
+        actor,
+        entry.description
+    );
+    //Some entries quote a whole welcome message, which could be too long to post
+    if line.len() <= MAX_MESSAGE_LEN {
+        return line;
+    }
+    let mut cut: String = line.chars().take(MAX_MESSAGE_LEN / 2).collect();
+    cut.push('…');
+    cut
+}
+
+//Posts the entries added to the audit log since the last call. Entries made while the guild has
+//no audit channel are dropped rather than posted once it gets one
+pub async fn mirror(ctx: &Context, guild: Option<GuildId>) {
+    let (channel, lines) = {
+        let library_arc = crate::library_for(ctx, guild).await;
+        let mut library = library_arc.write().await;
+        let channel = library.channel(ChannelKind::Audit);
+        let lines: Vec<String> = library
+            .take_unmirrored_audit()
+            .iter()
+            .map(format_entry)
+            .collect();
+        (channel, lines)
+    };
+    let channel = match channel {
+        Some(channel) if !lines.is_empty() => ChannelId(channel),
+        _ => return,
+    };
+
+    //Commands that change several things at once are reported together
+    let mut messages = vec![String::new()];
+    for line in lines {
+        let message = messages.last().unwrap();
+        if !message.is_empty() && message.len() + 1 + line.len() > MAX_MESSAGE_LEN {
+            messages.push(String::new());
+        }
+        let message = messages.last_mut().unwrap();
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message.push_str(&line);
+    }
+
+    for message in messages {
+        //The feed shouldn't ping everyone it mentions
+        let sent = channel
+            .send_message(ctx, |m| {
+                m.content(message)
+                    .allowed_mentions(|mentions| mentions.empty_parse())
+            })
+            .await;
+        if let Err(err) = sent {
+            println!("Failed to mirror audit log in guild {:?}: {:?}", guild, err);
+        }
+    }
+}
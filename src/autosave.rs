@@ -0,0 +1,128 @@
+use serenity::{model::id::GuildId, prelude::TypeMapKey};
+
+use std::collections::HashSet;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+
+use crate::guilds::Libraries;
+use crate::library::Database;
+
+const DEFAULT_INTERVAL_MINUTES: u64 = 5;
+//How long the library has to go without changes before they are saved
+const DEFAULT_DEBOUNCE_SECS: u64 = 5;
+//Save straight away once this many changes have piled up, even if they keep coming
+const BURST_CHANGES: usize = 10;
+
+//Tracks which libraries were changed since they were last written to disk. Changes are saved once
+//things go quiet for a few seconds, so a burst of commands is written once instead of after every
+//command. Changes are also journaled as they happen, so saving is what keeps the journal short and
+//startup quick
+pub struct Autosave {
+    //Guilds whose library has unsaved changes
+    dirty: Mutex<HashSet<Option<GuildId>>>,
+    changes: AtomicUsize,
+    changed: Notify,
+    interval: Duration,
+    debounce: Duration,
+}
+
+//Background tasks have no Context to find the autosave in, so there is just the one
+static AUTOSAVE: Lazy<Arc<Autosave>> = Lazy::new(|| Arc::new(Autosave::new()));
+
+pub fn handle() -> Arc<Autosave> {
+    AUTOSAVE.clone()
+}
+
+//For background tasks, what save_after_change is for commands: journals what they just changed in
+//`library` and lets the autosave know. Journaling that failed is logged by persist_change and
+//counts as a change, so the autosave writes the library in full instead
+pub async fn persist_change(library: &Database) {
+    if library.persist_change().await {
+        AUTOSAVE.record_change(library.guild.map(GuildId));
+    }
+}
+
+pub struct AutosaveData;
+
+impl TypeMapKey for AutosaveData {
+    type Value = Arc<Autosave>;
+}
+
+impl Autosave {
+    //Reads AUTOSAVE_INTERVAL_MINUTES and AUTOSAVE_DEBOUNCE_SECS from the environment
+    fn new() -> Autosave {
+        let minutes = env::var("AUTOSAVE_INTERVAL_MINUTES")
+            .ok()
+            .and_then(|minutes| minutes.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(DEFAULT_INTERVAL_MINUTES);
+        let debounce = env::var("AUTOSAVE_DEBOUNCE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_DEBOUNCE_SECS);
+
+        Autosave {
+            dirty: Mutex::new(HashSet::new()),
+            changes: AtomicUsize::new(0),
+            changed: Notify::new(),
+            interval: Duration::from_secs(minutes * 60),
+            debounce: Duration::from_secs(debounce),
+        }
+    }
+
+    //Marks the library of `guild` as needing a save
+    pub fn record_change(&self, guild: Option<GuildId>) {
+        self.dirty.lock().unwrap().insert(guild);
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+
+    //Returns once no changes have come in for the debounce window, or once enough have piled up
+    async fn wait_for_quiet(&self) {
+        while self.changes.load(Ordering::SeqCst) < BURST_CHANGES {
+            if tokio::time::timeout(self.debounce, self.changed.notified())
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+pub async fn autosave_task(autosave: Arc<Autosave>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(autosave.interval);
+    //The first tick completes immediately and there is nothing to save right after loading
+    interval.tick().await;
+    loop {
+        //Background tasks like the escalation policy change the library too, so every library is
+        //saved on the interval rather than only the ones commands have changed
+        let save_all = tokio::select! {
+            _ = interval.tick() => true,
+            _ = autosave.changed.notified() => {
+                autosave.wait_for_quiet().await;
+                false
+            },
+        };
+
+        autosave.changes.store(0, Ordering::SeqCst);
+        let dirty: Vec<Option<GuildId>> = autosave.dirty.lock().unwrap().drain().collect();
+        if save_all {
+            for (_, library_arc) in libraries.all().await {
+                let library = library_arc.read().await;
+                library.try_save().await;
+            }
+        } else {
+            for guild in dirty {
+                let library_arc = libraries.get(guild).await;
+                let library = library_arc.read().await;
+                library.try_save().await;
+            }
+        }
+    }
+}
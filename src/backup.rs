@@ -0,0 +1,326 @@
+use hmac::{Hmac, Mac, NewMac};
+use serenity::{async_trait, prelude::TypeMapKey};
+use sha2::{Digest, Sha256};
+
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::guilds::Libraries;
+use crate::library::TimeType;
+
+type BackupError = Box<dyn std::error::Error + Send + Sync>;
+
+const DEFAULT_INTERVAL_HOURS: u64 = 24;
+const UPLOAD_ATTEMPTS: u32 = 3;
+//Doubled after every failed attempt
+const RETRY_DELAY_SECS: u64 = 30;
+
+//Somewhere off the bot's machine that saved libraries can be copied to
+#[async_trait]
+pub trait BackupTarget: Send + Sync {
+    fn describe(&self) -> String;
+    async fn upload(&self, name: &str, data: Vec<u8>) -> Result<(), BackupError>;
+}
+
+//Any WebDAV share (Nextcloud, a NAS...). Configured with BACKUP_WEBDAV_URL, pointing at the folder
+//to upload into, and optionally BACKUP_WEBDAV_USER and BACKUP_WEBDAV_PASSWORD
+pub struct WebDavTarget {
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl BackupTarget for WebDavTarget {
+    fn describe(&self) -> String {
+        format!("WebDAV {}", self.url)
+    }
+
+    async fn upload(&self, name: &str, data: Vec<u8>) -> Result<(), BackupError> {
+        let mut request = self
+            .client
+            .put(format!("{}/{}", self.url.trim_end_matches('/'), name))
+            .body(data);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+//Any S3 compatible bucket (AWS, Backblaze, MinIO...). Configured with BACKUP_S3_ENDPOINT (eg.
+//https://s3.us-east-1.amazonaws.com), BACKUP_S3_BUCKET, BACKUP_S3_REGION, BACKUP_S3_ACCESS_KEY
+//and BACKUP_S3_SECRET_KEY
+pub struct S3Target {
+    endpoint: reqwest::Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    client: reqwest::Client,
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    data_encoding::HEXLOWER.encode(&Sha256::digest(data))
+}
+
+impl S3Target {
+    //Signs a PUT of `data` to `path` with AWS signature version 4. Returns the headers to send
+    fn sign(&self, path: &str, host: &str, data: &[u8]) -> Vec<(&'static str, String)> {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = sha256_hex(data);
+
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            path, host, payload_hash, amz_date, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+
+        let key = hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), &date);
+        let key = hmac_sha256(&key, &self.region);
+        let key = hmac_sha256(&key, "s3");
+        let key = hmac_sha256(&key, "aws4_request");
+        let signature = data_encoding::HEXLOWER.encode(&hmac_sha256(&key, &string_to_sign));
+
+        vec![
+            ("x-amz-date", amz_date),
+            ("x-amz-content-sha256", payload_hash),
+            (
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                    self.access_key, scope, signature
+                ),
+            ),
+        ]
+    }
+}
+
+#[async_trait]
+impl BackupTarget for S3Target {
+    fn describe(&self) -> String {
+        format!("S3 bucket {} at {}", self.bucket, self.endpoint)
+    }
+
+    async fn upload(&self, name: &str, data: Vec<u8>) -> Result<(), BackupError> {
+        //Path style addressing works with every S3 compatible service. Backup names only use
+        //characters that don't need escaping
+        let path = format!("/{}/{}", self.bucket, name);
+        let host = match (self.endpoint.host_str(), self.endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err("BACKUP_S3_ENDPOINT has no host".into()),
+        };
+
+        let mut request = self.client.put(self.endpoint.join(&path)?);
+        for (header, value) in self.sign(&path, &host, &data) {
+            request = request.header(header, value);
+        }
+        request.body(data).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+fn targets_from_env() -> Vec<Box<dyn BackupTarget>> {
+    let mut targets: Vec<Box<dyn BackupTarget>> = Vec::new();
+    let client = reqwest::Client::new();
+
+    if let Ok(url) = env::var("BACKUP_WEBDAV_URL") {
+        targets.push(Box::new(WebDavTarget {
+            url,
+            username: env::var("BACKUP_WEBDAV_USER").ok(),
+            password: env::var("BACKUP_WEBDAV_PASSWORD").ok(),
+            client: client.clone(),
+        }));
+    }
+
+    let s3 = (
+        env::var("BACKUP_S3_ENDPOINT"),
+        env::var("BACKUP_S3_BUCKET"),
+        env::var("BACKUP_S3_REGION"),
+        env::var("BACKUP_S3_ACCESS_KEY"),
+        env::var("BACKUP_S3_SECRET_KEY"),
+    );
+    if let (Ok(endpoint), Ok(bucket), Ok(region), Ok(access_key), Ok(secret_key)) = s3 {
+        match reqwest::Url::parse(&endpoint) {
+            Ok(endpoint) => targets.push(Box::new(S3Target {
+                endpoint,
+                bucket,
+                region,
+                access_key,
+                secret_key,
+                client,
+            })),
+            Err(err) => println!("Ignoring invalid BACKUP_S3_ENDPOINT {}: {}", endpoint, err),
+        }
+    }
+
+    targets
+}
+
+#[derive(Default)]
+pub struct TargetStatus {
+    pub last_success: Option<TimeType>,
+    pub last_failure: Option<(TimeType, String)>,
+}
+
+pub struct Backups {
+    targets: Vec<Box<dyn BackupTarget>>,
+    //Same order as targets
+    status: Mutex<Vec<TargetStatus>>,
+    interval: Duration,
+}
+
+pub struct BackupData;
+
+impl TypeMapKey for BackupData {
+    type Value = Arc<Backups>;
+}
+
+impl Backups {
+    //Reads the targets and BACKUP_INTERVAL_HOURS from the environment
+    pub fn new() -> Backups {
+        let targets = targets_from_env();
+        let hours = env::var("BACKUP_INTERVAL_HOURS")
+            .ok()
+            .and_then(|hours| hours.parse::<u64>().ok())
+            .filter(|hours| *hours > 0)
+            .unwrap_or(DEFAULT_INTERVAL_HOURS);
+
+        Backups {
+            status: Mutex::new(targets.iter().map(|_| TargetStatus::default()).collect()),
+            targets,
+            interval: Duration::from_secs(hours * 60 * 60),
+        }
+    }
+
+    //A line for each target describing how its backups are going
+    pub fn status_report(&self) -> Vec<String> {
+        let status = self.status.lock().unwrap();
+        self.targets
+            .iter()
+            .zip(status.iter())
+            .map(|(target, status)| {
+                let mut line = format!("**{}**: ", target.describe());
+                match status.last_success {
+                    Some(time) => line.push_str(&format!(
+                        "last successful backup {}",
+                        time.format("%b %-d %H:%M")
+                    )),
+                    None => line.push_str("no successful backup yet"),
+                }
+                if let Some((time, err)) = &status.last_failure {
+                    if status.last_success.map_or(true, |success| success < *time) {
+                        line.push_str(&format!(
+                            ". Last attempt failed {}: {}",
+                            time.format("%b %-d %H:%M"),
+                            err
+                        ));
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+
+    async fn upload_with_retries(
+        &self,
+        target: &dyn BackupTarget,
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), BackupError> {
+        let mut delay = Duration::from_secs(RETRY_DELAY_SECS);
+        let mut attempt = 1;
+        loop {
+            match target.upload(name, data.to_vec()).await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt < UPLOAD_ATTEMPTS => {
+                    println!(
+                        "Backup of {} to {} failed (attempt {}): {}",
+                        name,
+                        target.describe(),
+                        attempt,
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    //Uploads every library to every target
+    pub async fn run(&self, libraries: &Libraries) {
+        let now = chrono::Local::now();
+        let mut uploads = Vec::new();
+        for (_, library_arc) in libraries.all().await {
+            let library = library_arc.read().await;
+            //The same bytes that are saved to disk, encrypted if that is turned on
+            let data = crate::migrations::encode(&library)
+                .map_err(|err| err.to_string())
+                .and_then(|data| crate::crypto::encrypt(data).map_err(|err| err.to_string()));
+            match data {
+                Ok(data) => {
+                    let name = format!(
+                        "{}-{}",
+                        now.format("%Y-%m-%d-%H%M"),
+                        crate::storage::file_name(library.guild, "bin")
+                    );
+                    uploads.push((name, data));
+                }
+                Err(err) => println!("Failed to serialize library for backup: {}", err),
+            }
+        }
+
+        for (i, target) in self.targets.iter().enumerate() {
+            let mut result = Ok(());
+            for (name, data) in &uploads {
+                if let Err(err) = self.upload_with_retries(target.as_ref(), name, data).await {
+                    result = Err(err.to_string());
+                    break;
+                }
+            }
+
+            let mut status = self.status.lock().unwrap();
+            match result {
+                Ok(()) => status[i].last_success = Some(chrono::Local::now()),
+                Err(err) => {
+                    println!("Backup to {} failed: {}", target.describe(), err);
+                    status[i].last_failure = Some((chrono::Local::now(), err));
+                }
+            }
+        }
+    }
+}
+
+pub async fn backup_task(backups: Arc<Backups>, libraries: Arc<Libraries>) {
+    if backups.targets.is_empty() {
+        println!("No backup targets configured. Remote backups are disabled");
+        return;
+    }
+
+    let mut interval = tokio::time::interval(backups.interval);
+    loop {
+        interval.tick().await;
+        backups.run(&libraries).await;
+    }
+}
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use crate::engine::{self, EngineError, Limit, Score};
+use crate::pgn::ImportedGame;
+use crate::rules::GameState;
+
+//Blunder checks for games members upload with !chess blundercheck, like Lichess' computer analysis.
+//Every position in the game is given to the engine, and moves are judged by how much they threw
+//away of the winning chances the player had before them, so that giving up a pawn in a won
+//position counts for less than in an equal one
+
+//How long the engine looks at each position. Games are checked a position at a time, so this is
+//kept short
+const POSITION_TIME: Duration = Duration::from_millis(300);
+//Longer games are only checked up to here, so that one upload doesn't hold an engine for too long
+pub const MAX_PLIES: usize = 300;
+//Centipawn losses are counted up to this, so that one missed mate doesn't swamp the average
+const MAX_CP_LOSS: i32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    //How much of the player's winning chances, from 0 to 1, a move has to lose to be judged. These
+    //are Lichess' thresholds
+    fn of(loss: f64) -> Option<Judgement> {
+        if loss >= 0.15 {
+            Some(Judgement::Blunder)
+        } else if loss >= 0.1 {
+            Some(Judgement::Mistake)
+        } else if loss >= 0.05 {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    //The annotation written after the move, like ??
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+
+    //The annotation as a numeric annotation glyph, which is how PGN files carry it
+    fn nag(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "$6",
+            Judgement::Mistake => "$2",
+            Judgement::Blunder => "$4",
+        }
+    }
+}
+
+impl std::fmt::Display for Judgement {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Judgement::Inaccuracy => write!(fmt, "Inaccuracy"),
+            Judgement::Mistake => write!(fmt, "Mistake"),
+            Judgement::Blunder => write!(fmt, "Blunder"),
+        }
+    }
+}
+
+//A position in the game as the engine saw it
+#[derive(Clone, Copy)]
+enum Evaluation {
+    //The engine's score for the side to move
+    Engine(Score),
+    //The game was over
+    Won(shakmaty::Color),
+    Drawn,
+}
+
+impl Evaluation {
+    //White's winning chances, from 0 to 1
+    fn white_chances(self, turn: shakmaty::Color) -> f64 {
+        match self {
+            Evaluation::Engine(score) if turn == shakmaty::Color::White => score.winning_chances(),
+            Evaluation::Engine(score) => 1.0 - score.winning_chances(),
+            Evaluation::Won(shakmaty::Color::White) => 1.0,
+            Evaluation::Won(shakmaty::Color::Black) => 0.0,
+            Evaluation::Drawn => 0.5,
+        }
+    }
+
+    //White's score in centipawns, with mates counting as MAX_CP_LOSS
+    fn white_centipawns(self, turn: shakmaty::Color) -> i32 {
+        let sign = if turn == shakmaty::Color::White {
+            1
+        } else {
+            -1
+        };
+        match self {
+            Evaluation::Engine(Score::Centipawns(cp)) => {
+                (cp * sign).clamp(-MAX_CP_LOSS, MAX_CP_LOSS)
+            }
+            Evaluation::Engine(Score::Mate(moves)) => MAX_CP_LOSS * moves.signum() * sign,
+            Evaluation::Won(shakmaty::Color::White) => MAX_CP_LOSS,
+            Evaluation::Won(shakmaty::Color::Black) => -MAX_CP_LOSS,
+            Evaluation::Drawn => 0,
+        }
+    }
+
+    //White's score the way PGN [%eval] comments write it, like 0.35 or #-3
+    fn pgn_eval(self, turn: shakmaty::Color) -> String {
+        let sign = if turn == shakmaty::Color::White {
+            1
+        } else {
+            -1
+        };
+        match self {
+            Evaluation::Engine(Score::Centipawns(cp)) => {
+                format!("{:.2}", (cp * sign) as f64 / 100.0)
+            }
+            Evaluation::Engine(Score::Mate(moves)) => format!("#{}", moves * sign),
+            Evaluation::Won(_) | Evaluation::Drawn => String::new(),
+        }
+    }
+}
+
+//A move that lost enough to be judged
+pub struct Flagged {
+    //Where the move is in the game's moves, counting from 0
+    pub ply: usize,
+    //The move as numbered in the game, like "14..." for black's 14th move
+    pub number: String,
+    pub san: String,
+    pub judgement: Judgement,
+    pub mover: shakmaty::Color,
+    //How much the move lost of the player's winning chances, from 0 to 1
+    pub loss: f64,
+    //What the engine would have played instead, in SAN
+    pub best: String,
+}
+
+pub struct Report {
+    //The moves that were judged, in the order they were played
+    pub flagged: Vec<Flagged>,
+    //White's and black's average centipawn loss
+    pub average_loss: [f64; 2],
+    //How many moves were checked
+    pub plies: usize,
+    //Each move checked, with its judgement and the comment written after it
+    annotations: Vec<(String, Option<Judgement>, String)>,
+}
+
+async fn evaluate(state: &GameState) -> Result<(Evaluation, Option<String>), EngineError> {
+    if let Some(end) = state.end() {
+        return Ok((
+            end.winner().map_or(Evaluation::Drawn, Evaluation::Won),
+            None,
+        ));
+    }
+    let analysis = engine::analyse(&state.fen(), Limit::MoveTime(POSITION_TIME)).await?;
+    let best = state
+        .parse_move(&analysis.best_move)
+        .map(|m| shakmaty::san::SanPlus::from_move(state.position().clone(), &m).to_string());
+    Ok((Evaluation::Engine(analysis.score), best))
+}
+
+//Runs the engine over the moves of `game`, up to MAX_PLIES of them
+pub async fn check(game: &ImportedGame) -> Result<Report, EngineError> {
+    let mut state = GameState::start(game.start_fen.as_deref());
+    let (mut before, mut best) = evaluate(&state).await?;
+    let mut flagged = Vec::new();
+    let mut annotations = Vec::new();
+    let mut losses = [Vec::new(), Vec::new()];
+
+    for (ply, san) in game.moves.iter().enumerate().take(MAX_PLIES) {
+        let m = match state.parse_move(san) {
+            Some(m) => m,
+            None => break,
+        };
+        let mover = state.turn();
+        let number = if mover == shakmaty::Color::White {
+            format!("{}.", state.move_number())
+        } else {
+            format!("{}...", state.move_number())
+        };
+        let before_chances = before.white_chances(mover);
+        let before_cp = before.white_centipawns(mover);
+        let played = state.play(&m);
+        let (after, next_best) = evaluate(&state).await?;
+        let after_chances = after.white_chances(state.turn());
+        let after_cp = after.white_centipawns(state.turn());
+
+        let (loss, cp_loss, index) = if mover == shakmaty::Color::White {
+            (before_chances - after_chances, before_cp - after_cp, 0)
+        } else {
+            (after_chances - before_chances, after_cp - before_cp, 1)
+        };
+        losses[index].push(cp_loss.max(0));
+        //Playing the engine's move is never a mistake, even when a deeper look at the next position
+        //changes its mind
+        let judgement = Judgement::of(loss).filter(|_| best.as_deref() != Some(played.as_str()));
+        if let Some(judgement) = judgement {
+            flagged.push(Flagged {
+                ply,
+                number,
+                san: played.clone(),
+                judgement,
+                mover,
+                loss,
+                best: best.clone().unwrap_or_default(),
+            });
+        }
+        let mut comment = match after.pgn_eval(state.turn()) {
+            eval if eval.is_empty() => String::new(),
+            eval => format!("[%eval {}]", eval),
+        };
+        if let (Some(judgement), Some(best)) = (judgement, &best) {
+            comment.push_str(&format!(" {}. {} was best.", judgement, best));
+        }
+        annotations.push((played, judgement, comment.trim().to_owned()));
+
+        before = after;
+        best = next_best;
+    }
+
+    let average = |losses: &Vec<i32>| {
+        if losses.is_empty() {
+            0.0
+        } else {
+            losses.iter().sum::<i32>() as f64 / losses.len() as f64
+        }
+    };
+    Ok(Report {
+        flagged,
+        average_loss: [average(&losses[0]), average(&losses[1])],
+        plies: annotations.len(),
+        annotations,
+    })
+}
+
+impl Report {
+    //How many of `judgement` the player with `colour` made
+    pub fn count(&self, colour: shakmaty::Color, judgement: Judgement) -> usize {
+        self.flagged
+            .iter()
+            .filter(|flagged| flagged.mover == colour && flagged.judgement == judgement)
+            .count()
+    }
+
+    //`game` in PGN with the engine's evaluation after every move, and the judged moves annotated
+    //with what was best
+    pub fn annotated_pgn(&self, game: &ImportedGame) -> String {
+        let mut pgn = String::new();
+        for (name, value) in game.tags.iter().filter(|(name, _)| *name != "Annotator") {
+            pgn.push_str(&format!(
+                "[{} \"{}\"]\n",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+        pgn.push_str("[Annotator \"Blunder check\"]\n\n");
+
+        let start = GameState::start(game.start_fen.as_deref());
+        let mut number = start.move_number();
+        let mut white = start.turn() == shakmaty::Color::White;
+        let mut movetext = String::new();
+        //Black's moves are numbered when they come first or after a comment
+        let mut number_black = true;
+        for (san, judgement, comment) in &self.annotations {
+            if white {
+                movetext.push_str(&format!("{}. ", number));
+            } else if number_black {
+                movetext.push_str(&format!("{}... ", number));
+            }
+            movetext.push_str(san);
+            if let Some(judgement) = judgement {
+                movetext.push_str(&format!(" {}", judgement.nag()));
+            }
+            if !comment.is_empty() {
+                movetext.push_str(&format!(" {{ {} }}", comment));
+            }
+            movetext.push(' ');
+            number_black = !comment.is_empty();
+            if !white {
+                number += 1;
+            }
+            white = !white;
+        }
+        //Games cut short at MAX_PLIES are left without a result
+        let result = if self.plies == game.moves.len() {
+            game.tags
+                .get("Result")
+                .map_or("*", |result| result.as_str())
+        } else {
+            "*"
+        };
+        movetext.push_str(result);
+        pgn.push_str(&crate::games::wrap_movetext(&movetext));
+        pgn
+    }
+}
@@ -0,0 +1,191 @@
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgb, RgbImage};
+use shakmaty::{Board, Color, File, Rank, Role, Square};
+
+use crate::label::glyph;
+
+//Width of one square of the board in pixels
+const SQUARE: u32 = 64;
+//Each sprite pixel is drawn as a square this many pixels wide
+const SPRITE_SCALE: u32 = 3;
+const SPRITE_SIZE: u32 = 16;
+//Space left and below the board for the coordinates
+const BORDER: u32 = 24;
+const TEXT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+const LIGHT_SQUARE: Rgb<u8> = Rgb([240, 217, 181]);
+const DARK_SQUARE: Rgb<u8> = Rgb([181, 136, 99]);
+//The squares the last move was played from and to
+const LIGHT_HIGHLIGHT: Rgb<u8> = Rgb([205, 210, 106]);
+const DARK_HIGHLIGHT: Rgb<u8> = Rgb([170, 162, 58]);
+const BACKGROUND: Rgb<u8> = Rgb([49, 46, 43]);
+const COORDINATES: Rgb<u8> = Rgb([200, 200, 200]);
+const WHITE_FILL: Rgb<u8> = Rgb([250, 250, 250]);
+const WHITE_OUTLINE: Rgb<u8> = Rgb([20, 20, 20]);
+const BLACK_FILL: Rgb<u8> = Rgb([20, 20, 20]);
+const BLACK_OUTLINE: Rgb<u8> = Rgb([230, 230, 230]);
+
+//16x16 silhouettes of the pieces. Each row is a u16 with the left most pixel in the high bit
+fn sprite(role: Role) -> [u16; 16] {
+    match role {
+        Role::Pawn => [
+            0x0000, 0x0000, 0x0180, 0x03C0, 0x03C0, 0x0180, 0x03C0, 0x07E0, 0x03C0, 0x03C0, 0x07E0,
+            0x0FF0, 0x1FF8, 0x1FF8, 0x0000, 0x0000,
+        ],
+        Role::Knight => [
+            0x0000, 0x0140, 0x03F0, 0x07F8, 0x0FFC, 0x1EFE, 0x1CFE, 0x00FE, 0x01FC, 0x03F8, 0x07F0,
+            0x07F0, 0x0FF8, 0x1FFC, 0x1FFC, 0x0000,
+        ],
+        Role::Bishop => [
+            0x0000, 0x0180, 0x03C0, 0x0760, 0x06E0, 0x07E0, 0x03C0, 0x0180, 0x03C0, 0x03C0, 0x07E0,
+            0x0FF0, 0x1FF8, 0x1FF8, 0x0000, 0x0000,
+        ],
+        Role::Rook => [
+            0x0000, 0x1BD8, 0x1BD8, 0x1FF8, 0x0FF0, 0x07E0, 0x07E0, 0x07E0, 0x07E0, 0x07E0, 0x0FF0,
+            0x1FF8, 0x3FFC, 0x3FFC, 0x0000, 0x0000,
+        ],
+        Role::Queen => [
+            0x0000, 0x2244, 0x2244, 0x366C, 0x3FFC, 0x1FF8, 0x0FF0, 0x07E0, 0x07E0, 0x0FF0, 0x0FF0,
+            0x1FF8, 0x3FFC, 0x3FFC, 0x0000, 0x0000,
+        ],
+        Role::King => [
+            0x0180, 0x03C0, 0x0180, 0x1DB8, 0x3E7C, 0x3FFC, 0x3FFC, 0x1FF8, 0x0FF0, 0x07E0, 0x0FF0,
+            0x0FF0, 0x1FF8, 0x3FFC, 0x3FFC, 0x0000,
+        ],
+    }
+}
+
+fn fill_rect(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, colour: Rgb<u8>) {
+    for dy in 0..height {
+        for dx in 0..width {
+            image.put_pixel(x + dx, y + dy, colour);
+        }
+    }
+}
+
+//Draws `rows` scaled up with its top left corner at (x, y), moved by `shift` pixels
+fn draw_sprite(
+    image: &mut RgbImage,
+    rows: &[u16; 16],
+    x: u32,
+    y: u32,
+    shift: (i32, i32),
+    colour: Rgb<u8>,
+) {
+    for (row, bits) in rows.iter().enumerate() {
+        for col in 0..SPRITE_SIZE {
+            if bits & (1 << (SPRITE_SIZE - 1 - col)) == 0 {
+                continue;
+            }
+            let px = (x + col * SPRITE_SCALE) as i32 + shift.0;
+            let py = (y + row as u32 * SPRITE_SCALE) as i32 + shift.1;
+            fill_rect(
+                image,
+                px as u32,
+                py as u32,
+                SPRITE_SCALE,
+                SPRITE_SCALE,
+                colour,
+            );
+        }
+    }
+}
+
+fn draw_glyph(image: &mut RgbImage, c: char, x: u32, y: u32) {
+    let rows = match glyph(c) {
+        Some(rows) => rows,
+        None => return,
+    };
+    for (row, bits) in rows.iter().enumerate() {
+        for col in 0..GLYPH_WIDTH {
+            if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                fill_rect(
+                    image,
+                    x + col * TEXT_SCALE,
+                    y + row as u32 * TEXT_SCALE,
+                    TEXT_SCALE,
+                    TEXT_SCALE,
+                    COORDINATES,
+                );
+            }
+        }
+    }
+}
+
+//Renders `board` as a PNG, with the files and ranks written along the edges. `last_move` holds the
+//squares the last move was played from and to, which are highlighted. With `flipped`, black's side
+//of the board is at the bottom
+pub fn render_board(
+    board: &Board,
+    last_move: Option<(Square, Square)>,
+    flipped: bool,
+) -> Result<Vec<u8>, image::ImageError> {
+    let size = BORDER + 8 * SQUARE;
+    let mut image: RgbImage = ImageBuffer::from_pixel(size, size, BACKGROUND);
+
+    //Margin around the sprite inside its square
+    let inset = (SQUARE - SPRITE_SIZE * SPRITE_SCALE) / 2;
+    for row in 0..8 {
+        for col in 0..8 {
+            let (file, rank) = if flipped {
+                (7 - col, row)
+            } else {
+                (col, 7 - row)
+            };
+            let square = Square::from_coords(File::new(file), Rank::new(rank));
+            let x = BORDER + col * SQUARE;
+            let y = row * SQUARE;
+
+            let light = (file + rank) % 2 == 1;
+            let highlighted = last_move.map_or(false, |(from, to)| square == from || square == to);
+            let colour = match (light, highlighted) {
+                (true, false) => LIGHT_SQUARE,
+                (false, false) => DARK_SQUARE,
+                (true, true) => LIGHT_HIGHLIGHT,
+                (false, true) => DARK_HIGHLIGHT,
+            };
+            fill_rect(&mut image, x, y, SQUARE, SQUARE, colour);
+
+            if let Some(piece) = board.piece_at(square) {
+                let rows = sprite(piece.role);
+                let (fill, outline) = match piece.color {
+                    Color::White => (WHITE_FILL, WHITE_OUTLINE),
+                    Color::Black => (BLACK_FILL, BLACK_OUTLINE),
+                };
+                //The outline is the silhouette drawn shifted by a pixel in every direction,
+                //then covered by the piece itself
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if (dx, dy) != (0, 0) {
+                            draw_sprite(&mut image, &rows, x + inset, y + inset, (dx, dy), outline);
+                        }
+                    }
+                }
+                draw_sprite(&mut image, &rows, x + inset, y + inset, (0, 0), fill);
+            }
+        }
+    }
+
+    let glyph_width = GLYPH_WIDTH * TEXT_SCALE;
+    let glyph_height = GLYPH_HEIGHT * TEXT_SCALE;
+    for i in 0..8 {
+        let (file, rank) = if flipped { (7 - i, i) } else { (i, 7 - i) };
+        draw_glyph(
+            &mut image,
+            (b'A' + file as u8) as char,
+            BORDER + i * SQUARE + (SQUARE - glyph_width) / 2,
+            8 * SQUARE + (BORDER - glyph_height) / 2,
+        );
+        draw_glyph(
+            &mut image,
+            (b'1' + rank as u8) as char,
+            (BORDER - glyph_width) / 2,
+            i * SQUARE + (SQUARE - glyph_height) / 2,
+        );
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png)
+}
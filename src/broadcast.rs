@@ -0,0 +1,57 @@
+use serenity::{http::Http, model::id::ChannelId};
+
+use crate::games::GameUuid;
+use crate::library::{ChannelKind, Database};
+
+//Players can broadcast a game with !chess broadcast, so that the rest of the club can follow it.
+//Every move posts the board and the moves so far in the guild's broadcast channel, which admins
+//set with !config channel broadcast, and the result is posted there when the game ends.
+//Spectators can react to the posts and talk about the game there, but only the players' own
+//commands move in it
+
+//Posts the board of game `uuid` in the broadcast channel, if the game is broadcast and the channel
+//is set
+pub async fn update(http: &Http, library: &Database, uuid: GameUuid) {
+    let game = match library.games.get(&uuid) {
+        Some(game) if game.broadcast => game,
+        _ => return,
+    };
+    let channel = match library.channel(ChannelKind::Broadcast) {
+        Some(channel) => ChannelId(channel),
+        None => return,
+    };
+    let png = match game.render_image(false) {
+        Ok(png) => png,
+        Err(err) => {
+            println!(
+                "Failed to draw the board of broadcast game {}: {:?}",
+                Database::encode_uuid(uuid),
+                err
+            );
+            return;
+        }
+    };
+    let title = if game.status.is_over() {
+        format!("Final result: {}", game.status)
+    } else {
+        format!("Live: move {}", game.state().move_number())
+    };
+    let summary = crate::game_summary(game);
+    let result = channel
+        .send_message(http, |m| {
+            m.add_file((png.as_slice(), "board.png")).embed(|e| {
+                e.colour(crate::response::Tone::Info.colour())
+                    .title(title)
+                    .description(summary)
+                    .image("attachment://board.png")
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        println!(
+            "Failed to post broadcast game {}: {:?}",
+            Database::encode_uuid(uuid),
+            err
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::env;
+
+//Stats for chess.com players, from chess.com's public API. It needs no signing in, so anyone's
+//stats can be looked up and linking an account with !chesscom link is only a shortcut for looking
+//up your own. The API asks to be told who is calling in the User-Agent
+
+type ChessComError = Box<dyn std::error::Error + Send + Sync>;
+
+const DEFAULT_URL: &str = "https://api.chess.com";
+const USER_AGENT: &str = "chess-club-bot (Discord bot)";
+//How many of the player's latest games are shown
+const RECENT_GAMES: usize = 5;
+//Time controls shown, with what chess.com calls them in stats
+const TIME_CONTROLS: [(&str, &str); 4] = [
+    ("Bullet", "chess_bullet"),
+    ("Blitz", "chess_blitz"),
+    ("Rapid", "chess_rapid"),
+    ("Daily", "chess_daily"),
+];
+//Results that count as draws. Every other result of a game someone didn't win is a loss
+const DRAWS: [&str; 7] = [
+    "agreed",
+    "repetition",
+    "stalemate",
+    "insufficient",
+    "50move",
+    "timevsinsufficient",
+    "abandoned_draw",
+];
+
+//Overridable with CHESSCOM_URL, for testing against something else
+fn base_url() -> String {
+    env::var("CHESSCOM_URL").unwrap_or_else(|_| DEFAULT_URL.to_owned())
+}
+
+pub struct Rating {
+    pub time_control: &'static str,
+    pub rating: u64,
+    pub best: Option<u64>,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+}
+
+pub struct RecentGame {
+    pub opponent: String,
+    //Won, Lost or Drew
+    pub result: &'static str,
+    pub time_class: String,
+    pub url: String,
+}
+
+pub struct Stats {
+    //As the player spells it
+    pub username: String,
+    pub profile_url: String,
+    pub avatar: Option<String>,
+    //Only the time controls they have played
+    pub ratings: Vec<Rating>,
+    //Newest first
+    pub recent: Vec<RecentGame>,
+}
+
+async fn get(client: &reqwest::Client, url: &str) -> Result<serde_json::Value, ChessComError> {
+    let response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(serde_json::from_str(&response.text().await?)?)
+}
+
+//chess.com usernames are letters, digits, underscores and hyphens. Anything else would change
+//which address is asked for
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn player_url(username: &str) -> String {
+    format!("{}/pub/player/{}", base_url(), username.to_lowercase())
+}
+
+//The player's profile, or None if chess.com has no such player
+async fn profile(
+    client: &reqwest::Client,
+    username: &str,
+) -> Result<Option<serde_json::Value>, ChessComError> {
+    let response = client
+        .get(&player_url(username))
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(
+        &response.error_for_status()?.text().await?,
+    )?))
+}
+
+//The player's username as they spell it, or None if chess.com has no such player
+pub async fn find_player(username: &str) -> Result<Option<String>, ChessComError> {
+    let profile = profile(&reqwest::Client::new(), username).await?;
+    Ok(profile.map(|profile| profile["username"].as_str().unwrap_or(username).to_owned()))
+}
+
+fn result_of(result: &str) -> &'static str {
+    if result == "win" {
+        "Won"
+    } else if DRAWS.contains(&result) {
+        "Drew"
+    } else {
+        "Lost"
+    }
+}
+
+//Fetches the player's ratings and latest games. None if chess.com has no such player
+pub async fn fetch(username: &str) -> Result<Option<Stats>, ChessComError> {
+    let client = reqwest::Client::new();
+    let profile = match profile(&client, username).await? {
+        Some(profile) => profile,
+        None => return Ok(None),
+    };
+    let username = profile["username"].as_str().unwrap_or(username).to_owned();
+    let player = player_url(&username);
+
+    let stats = get(&client, &format!("{}/stats", player)).await?;
+    let ratings = TIME_CONTROLS
+        .iter()
+        .filter_map(|(time_control, key)| {
+            let stats = &stats[*key];
+            Some(Rating {
+                time_control: *time_control,
+                rating: stats["last"]["rating"].as_u64()?,
+                best: stats["best"]["rating"].as_u64(),
+                wins: stats["record"]["win"].as_u64().unwrap_or(0),
+                losses: stats["record"]["loss"].as_u64().unwrap_or(0),
+                draws: stats["record"]["draw"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect();
+
+    //Games are archived by month. The latest month might not have enough of them, so the one
+    //before it is looked at too
+    let archives = get(&client, &format!("{}/games/archives", player)).await?;
+    let mut recent = Vec::new();
+    for archive in archives["archives"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .rev()
+        .take(2)
+        .filter_map(|archive| archive.as_str())
+    {
+        let games = get(&client, archive).await?;
+        for game in games["games"].as_array().into_iter().flatten().rev() {
+            let white = game["white"]["username"].as_str().unwrap_or_default();
+            let (me, opponent) = if white.eq_ignore_ascii_case(&username) {
+                (&game["white"], &game["black"])
+            } else {
+                (&game["black"], &game["white"])
+            };
+            recent.push(RecentGame {
+                opponent: opponent["username"].as_str().unwrap_or("?").to_owned(),
+                result: result_of(me["result"].as_str().unwrap_or_default()),
+                time_class: game["time_class"].as_str().unwrap_or_default().to_owned(),
+                url: game["url"].as_str().unwrap_or_default().to_owned(),
+            });
+            if recent.len() == RECENT_GAMES {
+                break;
+            }
+        }
+        if recent.len() == RECENT_GAMES {
+            break;
+        }
+    }
+
+    Ok(Some(Stats {
+        profile_url: profile["url"].as_str().unwrap_or_default().to_owned(),
+        avatar: profile["avatar"].as_str().map(str::to_owned),
+        username,
+        ratings,
+        recent,
+    }))
+}
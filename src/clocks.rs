@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use serenity::{http::Http, model::id::ChannelId};
+use shakmaty::Color;
+
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+
+//Real-time clocks for games challenged with a time control like 10+5: ten minutes each, with five
+//seconds added after every move. A player's clock runs from the previous move, or from when the
+//challenge was accepted, until they move. Moving after it has run out loses the game, and the flag
+//task ends games whose player to move ran out of time without moving
+
+//How often the flag task looks for players who ran out of time
+const FLAG_CHECK_SECS: u64 = 2;
+//Longest time control that can be asked for, in minutes
+pub const MAX_MINUTES: u32 = 180;
+//Largest increment that can be asked for, in seconds
+pub const MAX_INCREMENT_SECS: u32 = 60;
+//How long after a player's clock runs out the flag task waits before ending the game, in
+//milliseconds. Moves count from when Discord received them, so one sent in time but still on its
+//way to the bot saves the game
+pub const LAG_ALLOWANCE_MS: i64 = 2000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    pub minutes: u32,
+    //Added to the clock of whoever moved after every move
+    pub increment_secs: u32,
+    //What white and black had left, in milliseconds, the last time their clocks were stopped
+    pub white_ms: i64,
+    pub black_ms: i64,
+}
+
+impl Clock {
+    pub fn new(minutes: u32, increment_secs: u32) -> Clock {
+        let initial = minutes as i64 * 60 * 1000;
+        Clock {
+            minutes,
+            increment_secs,
+            white_ms: initial,
+            black_ms: initial,
+        }
+    }
+
+    //A time control written like 10+5, for 10 minutes and a 5 second increment. None when it
+    //isn't written like that or is out of range
+    pub fn parse(input: &str) -> Option<Clock> {
+        let (minutes, increment) = input.split_once('+')?;
+        let minutes = minutes.trim().parse::<u32>().ok()?;
+        let increment = increment.trim().parse::<u32>().ok()?;
+        if !(1..=MAX_MINUTES).contains(&minutes) || increment > MAX_INCREMENT_SECS {
+            return None;
+        }
+        Some(Clock::new(minutes, increment))
+    }
+
+    //What `colour` had left when their clock was last stopped
+    pub fn stopped_ms(&self, colour: Color) -> i64 {
+        match colour {
+            Color::White => self.white_ms,
+            Color::Black => self.black_ms,
+        }
+    }
+
+    fn stopped_ms_mut(&mut self, colour: Color) -> &mut i64 {
+        match colour {
+            Color::White => &mut self.white_ms,
+            Color::Black => &mut self.black_ms,
+        }
+    }
+
+    //Stops the clock of `colour` after a move that took them `spent`, adding the increment
+    pub fn punch(&mut self, colour: Color, spent: chrono::Duration) {
+        let increment = self.increment_secs as i64 * 1000;
+        *self.stopped_ms_mut(colour) += increment - spent.num_milliseconds();
+    }
+
+    //Leaves `colour` with no time, when they lose on time
+    pub fn flag(&mut self, colour: Color) {
+        *self.stopped_ms_mut(colour) = 0;
+    }
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "{}+{}", self.minutes, self.increment_secs)
+    }
+}
+
+//A time left on a clock, like 4:05, or 0:09.3 in the last ten seconds. Never below 0:00
+pub fn format_ms(ms: i64) -> String {
+    let ms = ms.max(0);
+    let (minutes, seconds) = (ms / 60_000, ms / 1000 % 60);
+    if ms < 10_000 {
+        format!("{}:{:02}.{}", minutes, seconds, ms / 100 % 10)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+//Background task that ends games whose player to move has run out of time
+pub async fn flag_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(FLAG_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            //Most of the time nobody has run out, so the library is only written to when someone has
+            let flagged: Vec<_> = library_arc
+                .read()
+                .await
+                .games
+                .values()
+                .filter(|game| game.out_of_time())
+                .map(|game| game.uuid)
+                .collect();
+            if flagged.is_empty() {
+                continue;
+            }
+
+            let mut posts = Vec::new();
+            {
+                let mut library = library_arc.write().await;
+                for uuid in flagged {
+                    let game = match library.games.get_mut(&uuid) {
+                        //It may have ended in the meantime
+                        Some(game) if game.out_of_time() => game,
+                        _ => continue,
+                    };
+                    let turn = game.state().turn();
+                    if let Some(clock) = &mut game.clock {
+                        clock.flag(turn);
+                    }
+                    if let Some(post) =
+                        crate::correspondence::lose_on_time(&http, guild, &mut library, uuid).await
+                    {
+                        posts.push(post);
+                    }
+                }
+                crate::autosave::persist_change(&library).await;
+            }
+            for (channel, text) in posts {
+                if let Err(err) = ChannelId(channel).say(&http, text).await {
+                    println!("Failed to post that a player lost on time: {:?}", err);
+                }
+            }
+        }
+    }
+}
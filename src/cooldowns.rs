@@ -0,0 +1,70 @@
+use serenity::framework::standard::{buckets::LimitedFor, StandardFramework};
+
+use std::env;
+
+//Commands that are expensive or post long messages are put in a bucket with #[bucket = "..."], so
+//that they can only be used so often. The wait can be changed without a rebuild by setting
+//COOLDOWN_<BUCKET> to a number of seconds, for example COOLDOWN_LISTING=30. 0 turns it off
+
+//Who has to wait once the command was used
+pub enum Per {
+    User,
+    Channel,
+}
+
+pub struct Cooldown {
+    pub bucket: &'static str,
+    pub seconds: u64,
+    pub per: Per,
+}
+
+pub const COOLDOWNS: &[Cooldown] = &[
+    //Book listings are long, so once one is posted the channel has to scroll past it
+    Cooldown {
+        bucket: "listing",
+        seconds: 10,
+        per: Per::Channel,
+    },
+    //Lookups about a single member
+    Cooldown {
+        bucket: "lookup",
+        seconds: 3,
+        per: Per::User,
+    },
+    //Analysis keeps one of the few chess engines busy for several seconds
+    Cooldown {
+        bucket: "engine",
+        seconds: 30,
+        per: Per::User,
+    },
+];
+
+fn seconds(cooldown: &Cooldown) -> u64 {
+    let var = format!("COOLDOWN_{}", cooldown.bucket.to_ascii_uppercase());
+    match env::var(&var).map(|seconds| seconds.parse::<u64>()) {
+        Ok(Ok(seconds)) => seconds,
+        Ok(Err(_)) => {
+            println!(
+                "{} isn't a number of seconds. Using {}",
+                var, cooldown.seconds
+            );
+            cooldown.seconds
+        }
+        Err(_) => cooldown.seconds,
+    }
+}
+
+pub async fn add_buckets(mut framework: StandardFramework) -> StandardFramework {
+    for cooldown in COOLDOWNS {
+        let seconds = seconds(cooldown);
+        framework = framework
+            .bucket(cooldown.bucket, |b| {
+                b.delay(seconds).limit_for(match cooldown.per {
+                    Per::User => LimitedFor::User,
+                    Per::Channel => LimitedFor::Channel,
+                })
+            })
+            .await;
+    }
+    framework
+}
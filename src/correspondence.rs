@@ -0,0 +1,189 @@
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId, UserId},
+};
+
+use std::sync::Arc;
+
+use crate::games::{Game, GameStatus, GameUuid};
+use crate::guilds::Libraries;
+use crate::library::Database;
+
+//Correspondence games, challenged with a number of days per move, give each player that long for
+//every move. The clock task DMs players whose time is running low, once a move, and ends the game
+//in their opponent's favour when it runs out. Clocks are worked out from when the last move was
+//played, which is saved with the game, so they keep running while the bot is down
+
+//How often the clock task looks at the games being played
+const CLOCK_CHECK_SECS: u64 = 10 * 60;
+//Players are reminded once a quarter of their time is left, but never more than this many hours
+//before it runs out
+const MAX_REMINDER_HOURS: i64 = 24;
+
+//What the clock task has to tell players, worked out while the library is held
+enum Notice {
+    //The Discord id of the player to move, their opponent and when their time runs out
+    Reminder(String, String, crate::library::TimeType),
+    //The game's channel and what to post there
+    Forfeit(u64, String),
+}
+
+//Background task that reminds players whose time is running low and forfeits those out of time
+pub async fn clock_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(CLOCK_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            let notices = {
+                let mut library = library_arc.write().await;
+                let notices = check_clocks(&http, guild, &mut library).await;
+                if !notices.is_empty() {
+                    crate::autosave::persist_change(&library).await;
+                }
+                notices
+            };
+            for notice in notices {
+                send(&http, notice).await;
+            }
+        }
+    }
+}
+
+//Marks the players due a reminder as reminded and ends the games that ran out of time, returning
+//what to tell them
+async fn check_clocks(http: &Http, guild: Option<GuildId>, library: &mut Database) -> Vec<Notice> {
+    let now = chrono::Local::now();
+    let mut notices = Vec::new();
+    let mut timed_out = Vec::new();
+    for game in library.games.values_mut() {
+        let (deadline, days) = match (game.move_deadline(), game.days_per_move) {
+            (Some(deadline), Some(days)) => (deadline, days),
+            _ => continue,
+        };
+        if now >= deadline {
+            timed_out.push(game.uuid);
+        } else if !game.reminded && deadline - now <= reminder_window(days) {
+            game.reminded = true;
+            let to_move = game.to_move().to_owned();
+            let opponent = game.opponent_of(&to_move).to_owned();
+            notices.push(Notice::Reminder(to_move, opponent, deadline));
+        }
+    }
+
+    for uuid in timed_out {
+        if let Some((channel, text)) = lose_on_time(http, guild, library, uuid).await {
+            notices.push(Notice::Forfeit(channel, text));
+        }
+    }
+    notices
+}
+
+//Ends game `uuid` with the player to move losing on time, then saves its PGN, archives it and rates
+//it like games that end with a move. Returns the game's channel and what to post there
+pub async fn lose_on_time(
+    http: &Http,
+    guild: Option<GuildId>,
+    library: &mut Database,
+    uuid: GameUuid,
+) -> Option<(u64, String)> {
+    let game = library.games.get_mut(&uuid)?;
+    let loser = game.to_move().to_owned();
+    let winner = game.opponent_of(&loser).to_owned();
+    game.finish(if game.white == loser {
+        GameStatus::BlackWon
+    } else {
+        GameStatus::WhiteWon
+    });
+    game.pgn = Some(game_pgn(http, guild, game).await);
+    let channel = game.channel;
+    println!(
+        "Game {} in guild {:?} was lost on time",
+        Database::encode_uuid(uuid),
+        guild
+    );
+
+    library.archive_discord_game(uuid);
+    let mut text = format!(
+        "<@{}> ran out of time in game {}, so <@{}> wins",
+        loser,
+        Database::encode_uuid(uuid),
+        winner
+    );
+    if let Some([white, black]) = library.rate_game(uuid) {
+        text.push_str(&format!("\nClub ratings: {}, {}", white, black));
+    }
+    crate::broadcast::update(http, library, uuid).await;
+    Some((channel, text))
+}
+
+//How long before their time runs out players with `days` per move are reminded
+fn reminder_window(days: u32) -> chrono::Duration {
+    std::cmp::min(
+        chrono::Duration::hours(days as i64 * 24 / 4),
+        chrono::Duration::hours(MAX_REMINDER_HOURS),
+    )
+}
+
+async fn send(http: &Http, notice: Notice) {
+    let result = match notice {
+        Notice::Reminder(player, opponent, deadline) => {
+            let text = format!(
+                "Your time to move in your correspondence game against <@{}> runs out {}. Play your move with !chess move before then, or you lose the game on time",
+                opponent,
+                deadline.format("%b %-d at %H:%M")
+            );
+            match player.parse::<u64>() {
+                Ok(id) => match UserId(id).create_dm_channel(http).await {
+                    Ok(channel) => channel.say(http, text).await.map(|_| ()),
+                    Err(err) => Err(err),
+                },
+                Err(_) => Ok(()),
+            }
+        }
+        Notice::Forfeit(channel, text) => ChannelId(channel).say(http, text).await.map(|_| ()),
+    };
+    if let Err(err) = result {
+        println!("Failed to send correspondence clock notice: {:?}", err);
+    }
+}
+
+//The game in PGN, like the one saved when games end with a move, with the players' names looked up
+//over HTTP since the clock task has no cache
+async fn game_pgn(http: &Http, guild: Option<GuildId>, game: &Game) -> String {
+    let club = match guild {
+        Some(guild) => guild
+            .to_partial_guild(http)
+            .await
+            .ok()
+            .map(|guild| guild.name),
+        None => None,
+    };
+    let event = match club {
+        Some(club) => format!("{} club game", club),
+        None => "Club game".to_owned(),
+    };
+    let white = player_name(http, guild, &game.white).await;
+    let black = player_name(http, guild, &game.black).await;
+    game.to_pgn(&event, &white, &black)
+}
+
+async fn player_name(http: &Http, guild: Option<GuildId>, discord_id: &str) -> String {
+    let user = match discord_id.parse::<u64>() {
+        Ok(id) => UserId(id).to_user(http).await.ok(),
+        Err(_) => None,
+    };
+    let user = match user {
+        Some(user) => user,
+        None => return discord_id.to_owned(),
+    };
+    let nick = match guild {
+        Some(guild) => guild
+            .member(http, user.id)
+            .await
+            .ok()
+            .and_then(|member| member.nick),
+        None => None,
+    };
+    nick.unwrap_or(user.name)
+}
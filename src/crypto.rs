@@ -0,0 +1,90 @@
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::Rng;
+
+//Set to 64 hex characters (a 256 bit key) to encrypt the saved library, its backups, journal and
+//failed save dumps with AES-256-GCM. Files that were saved before it was set are still read and
+//get encrypted the next time they are written
+const KEY_VAR: &str = "LIBRARY_ENCRYPTION_KEY";
+
+//Encrypted files start with these bytes, followed by the nonce and then the ciphertext
+const MAGIC: &[u8; 4] = b"CBEN";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub enum CryptoError {
+    BadKey,
+    //The data is encrypted but LIBRARY_ENCRYPTION_KEY isn't set
+    MissingKey,
+    //Wrong key or the data was tampered with
+    Failed,
+}
+
+impl std::error::Error for CryptoError {}
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            CryptoError::BadKey => write!(fmt, "{} must be 64 hex characters", KEY_VAR),
+            CryptoError::MissingKey => {
+                write!(fmt, "The data is encrypted but {} isn't set", KEY_VAR)
+            }
+            CryptoError::Failed => write!(fmt, "Decryption failed. Is {} the right key?", KEY_VAR),
+        }
+    }
+}
+
+fn cipher() -> Result<Option<Aes256Gcm>, CryptoError> {
+    let key = match std::env::var(KEY_VAR) {
+        Ok(key) => key,
+        Err(_) => return Ok(None),
+    };
+    let key = data_encoding::HEXLOWER_PERMISSIVE
+        .decode(key.trim().as_bytes())
+        .map_err(|_| CryptoError::BadKey)?;
+    if key.len() != 32 {
+        return Err(CryptoError::BadKey);
+    }
+    Ok(Some(Aes256Gcm::new(Key::from_slice(&key))))
+}
+
+pub fn enabled() -> bool {
+    std::env::var(KEY_VAR).is_ok()
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+//Returns `data` unchanged when encryption isn't turned on
+pub fn encrypt(data: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+    let cipher = match cipher()? {
+        Some(cipher) => cipher,
+        None => return Ok(data),
+    };
+    let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), data.as_ref())
+        .map_err(|_| CryptoError::Failed)?;
+
+    let mut result = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    result.extend_from_slice(MAGIC);
+    result.extend_from_slice(&nonce);
+    result.extend_from_slice(&ciphertext);
+    Ok(result)
+}
+
+//Returns `data` unchanged when it isn't encrypted
+pub fn decrypt(data: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    let cipher = cipher()?.ok_or(CryptoError::MissingKey)?;
+    if data.len() < MAGIC.len() + NONCE_LEN {
+        return Err(CryptoError::Failed);
+    }
+    let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::Failed)
+}
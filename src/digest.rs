@@ -0,0 +1,137 @@
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId},
+    prelude::RwLock,
+};
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library::{self, ChannelKind, CheckoutStatus, Database, TimeType};
+
+//How often the digest task checks whether the scheduled time has passed
+const DIGEST_CHECK_SECS: u64 = 60;
+
+//Builds the summary of the past 7 days of library activity ending at `now`
+pub fn build_digest(library: &Database, now: TimeType) -> Result<String, std::fmt::Error> {
+    let week_ago = now - chrono::Duration::days(7);
+    let mut digest = String::new();
+
+    write!(digest, "**Weekly library digest**")?;
+
+    let acquisitions: Vec<&library::Book> = library
+        .books
+        .values()
+        .filter(|book| book.added >= week_ago)
+        .collect();
+    write!(digest, "\n\nNew acquisitions ({}):", acquisitions.len())?;
+    for book in &acquisitions {
+        write!(digest, "\n  *{}* by {}", book.name, book.author)?;
+    }
+
+    let returned: Vec<&library::CheckoutInstance> = library
+        .archived_checkouts
+        .values()
+        .filter(|checkout| match &checkout.checkin_approval {
+            Some(approval) => approval.time >= week_ago,
+            None => false,
+        })
+        .collect();
+    write!(digest, "\n\nBooks returned ({}):", returned.len())?;
+    for checkout in &returned {
+        write!(digest, "\n  {}", book_name(library, checkout.book))?;
+    }
+
+    let waiting: Vec<&library::CheckoutInstance> = library
+        .checkouts
+        .values()
+        .filter(|checkout| checkout.status == CheckoutStatus::PreTransact)
+        .collect();
+    write!(digest, "\n\nWaiting to be handed out ({}):", waiting.len())?;
+    for checkout in &waiting {
+        write!(digest, "\n  {}", book_name(library, checkout.book))?;
+    }
+
+    let overdue: Vec<&library::CheckoutInstance> = library
+        .checkouts
+        .values()
+        .filter(|checkout| checkout.status == CheckoutStatus::Reading)
+        .filter(|checkout| match checkout.due_date {
+            Some(due_date) => due_date < now,
+            None => false,
+        })
+        .collect();
+    write!(digest, "\n\nOverdue ({}):", overdue.len())?;
+    for checkout in &overdue {
+        write!(
+            digest,
+            "\n  {} - due {}",
+            book_name(library, checkout.book),
+            checkout.due_date.unwrap().format("%b %-d")
+        )?;
+    }
+
+    Ok(digest)
+}
+
+fn book_name(library: &Database, uuid: library::BookUuid) -> String {
+    match library.books.get(&uuid) {
+        Some(book) => format!("*{}*", book.name),
+        None => Database::encode_uuid(uuid),
+    }
+}
+
+//Background task that posts the weekly digest to each guild's digest channel at the time set with
+//`!library digest-schedule`. Guilds without a digest channel don't get one
+pub async fn digest_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(DIGEST_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            post_digest(&http, guild, &library_arc).await;
+        }
+    }
+}
+
+//Posts the guild's digest if it is due
+async fn post_digest(http: &Arc<Http>, guild: Option<GuildId>, library_arc: &RwLock<Database>) {
+    let (channel, digest) = {
+        let mut library = library_arc.write().await;
+        let now = chrono::Local::now();
+
+        let channel = match library.channel(ChannelKind::Digest) {
+            Some(channel) => ChannelId(channel),
+            None => return,
+        };
+        let slot = match library.digest_schedule.last_slot(now) {
+            Some(slot) => slot,
+            None => return,
+        };
+        let already_posted = match library.last_digest {
+            Some(last) => last >= slot,
+            None => false,
+        };
+        //Don't post a stale digest if the bot was offline when it was due
+        if already_posted || now - slot > chrono::Duration::hours(1) {
+            return;
+        }
+        library.last_digest = Some(now);
+        crate::autosave::persist_change(&library).await;
+
+        (channel, build_digest(&library, now))
+    };
+
+    match digest {
+        Ok(digest) => {
+            if let Err(err) = channel.say(http, digest).await {
+                println!(
+                    "Failed to post weekly digest for guild {:?}: {:?}",
+                    guild, err
+                );
+            }
+        }
+        Err(err) => println!("Failed to build weekly digest: {:?}", err),
+    }
+}
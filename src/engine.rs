@@ -0,0 +1,311 @@
+use once_cell::sync::Lazy;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{Mutex, Semaphore};
+
+use std::env;
+use std::process::Stdio;
+use std::time::Duration;
+
+//Positions are analysed by a chess engine that speaks UCI, Stockfish unless ENGINE_PATH points at
+//another one. Starting an engine takes a moment, so up to ENGINE_POOL_SIZE of them are kept
+//running and handed out to one analysis at a time. How deep they search is set with ENGINE_DEPTH,
+//or ENGINE_MOVETIME_MS to give them a fixed time instead. SYZYGY_PATH points them at tablebases
+
+const DEFAULT_PATH: &str = "stockfish";
+const DEFAULT_POOL_SIZE: usize = 2;
+const DEFAULT_DEPTH: u32 = 18;
+//Stockfish's Skill Level option goes from 0 to this. Analysis is done at full strength
+const MAX_SKILL: u8 = 20;
+//Engines that take longer than this to answer are assumed to be stuck and are killed
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum EngineError {
+    //The engine couldn't be started, usually because it isn't installed
+    Spawn(std::io::Error),
+    Io(std::io::Error),
+    Timeout,
+    //The engine exited or said something that isn't UCI
+    Protocol(String),
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            EngineError::Spawn(err) => write!(fmt, "The chess engine couldn't be started: {}", err),
+            EngineError::Io(err) => write!(fmt, "Lost contact with the chess engine: {}", err),
+            EngineError::Timeout => write!(fmt, "The chess engine took too long to answer"),
+            EngineError::Protocol(line) => {
+                write!(fmt, "The chess engine gave an unexpected answer: {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<std::io::Error> for EngineError {
+    fn from(err: std::io::Error) -> Self {
+        EngineError::Io(err)
+    }
+}
+
+//How good the position is for the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    //In hundredths of a pawn
+    Centipawns(i32),
+    //Moves until mate. Negative when the side to move is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    //The side to move's chances of winning, from 0 to 1, the way Lichess works them out from
+    //centipawns. Mates count as certain
+    pub fn winning_chances(self) -> f64 {
+        match self {
+            Score::Centipawns(cp) => 1.0 / (1.0 + 10f64.powf(-cp as f64 / 400.0)),
+            Score::Mate(moves) if moves > 0 => 1.0,
+            Score::Mate(_) => 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    //In UCI notation, like the rest of the moves here
+    pub best_move: String,
+    pub score: Score,
+    pub depth: u32,
+    //The line the engine expects, starting with the best move
+    pub pv: Vec<String>,
+}
+
+//How long the engine searches for
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    Depth(u32),
+    MoveTime(Duration),
+}
+
+impl Limit {
+    fn go(self) -> String {
+        match self {
+            Limit::Depth(depth) => format!("go depth {}", depth),
+            Limit::MoveTime(time) => format!("go movetime {}", time.as_millis()),
+        }
+    }
+}
+
+//The search limit set in the environment
+pub fn default_limit() -> Limit {
+    let movetime = env::var("ENGINE_MOVETIME_MS")
+        .ok()
+        .and_then(|ms| ms.parse::<u64>().ok());
+    if let Some(ms) = movetime {
+        return Limit::MoveTime(Duration::from_millis(ms));
+    }
+    Limit::Depth(
+        env::var("ENGINE_DEPTH")
+            .ok()
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(DEFAULT_DEPTH),
+    )
+}
+
+struct Engine {
+    //Killed when dropped, so that engines that misbehaved don't linger
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Engine {
+    async fn start() -> Result<Engine, EngineError> {
+        let path = env::var("ENGINE_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_owned());
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(EngineError::Spawn)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+        let mut engine = Engine {
+            _child: child,
+            stdin,
+            stdout,
+        };
+        engine.send("uci").await?;
+        engine.wait_for("uciok").await?;
+        //Syzygy tablebases on the bot's machine, if there are any, make endgames exact
+        if let Ok(path) = env::var("SYZYGY_PATH") {
+            engine
+                .send(&format!("setoption name SyzygyPath value {}", path))
+                .await?;
+        }
+        Ok(engine)
+    }
+
+    async fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        self.stdin.write_all(command.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn read_line(&mut self) -> Result<String, EngineError> {
+        match self.stdout.next_line().await? {
+            Some(line) => Ok(line),
+            None => Err(EngineError::Protocol("the engine exited".to_owned())),
+        }
+    }
+
+    async fn wait_for(&mut self, expected: &str) -> Result<(), EngineError> {
+        while self.read_line().await?.trim() != expected {}
+        Ok(())
+    }
+
+    async fn analyse(
+        &mut self,
+        fen: &str,
+        limit: Limit,
+        skill: u8,
+    ) -> Result<Analysis, EngineError> {
+        self.send("ucinewgame").await?;
+        //Engines without the option ignore it
+        self.send(&format!("setoption name Skill Level value {}", skill))
+            .await?;
+        //Engines are reused, so this is set for every position. In Chess960 mode castling moves
+        //are written as the king taking its own rook, which parse_move reads too
+        self.send(&format!(
+            "setoption name UCI_Chess960 value {}",
+            crate::rules::is_chess960_fen(fen)
+        ))
+        .await?;
+        self.send("isready").await?;
+        self.wait_for("readyok").await?;
+        self.send(&format!("position fen {}", fen)).await?;
+        self.send(&limit.go()).await?;
+
+        let mut score = None;
+        let mut depth = 0;
+        let mut pv = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("info") => {
+                    if let Some(info) = parse_info(&line) {
+                        //Only complete lines with a score are kept, not the ones about the
+                        //current move
+                        score = Some(info.0);
+                        depth = info.1;
+                        pv = info.2;
+                    }
+                }
+                Some("bestmove") => {
+                    let best_move = words
+                        .next()
+                        .ok_or_else(|| EngineError::Protocol(line.clone()))?
+                        .to_owned();
+                    //Positions without legal moves have no score
+                    let score = score.ok_or_else(|| EngineError::Protocol(line.clone()))?;
+                    if pv.first() != Some(&best_move) {
+                        pv = vec![best_move.clone()];
+                    }
+                    return Ok(Analysis {
+                        best_move,
+                        score,
+                        depth,
+                        pv,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+//Reads the score, depth and principal variation from an info line that has all three
+fn parse_info(line: &str) -> Option<(Score, u32, Vec<String>)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut score = None;
+    let mut depth = None;
+    let mut pv = None;
+    let mut i = 1;
+    while i < words.len() {
+        match words[i] {
+            "depth" => depth = words.get(i + 1)?.parse().ok(),
+            "score" => {
+                let value = words.get(i + 2)?.parse().ok()?;
+                score = match *words.get(i + 1)? {
+                    "cp" => Some(Score::Centipawns(value)),
+                    "mate" => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            //The principal variation is always last
+            "pv" => {
+                pv = Some(words[i + 1..].iter().map(|m| m.to_string()).collect());
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Some((score?, depth?, pv?))
+}
+
+fn pool_size() -> usize {
+    env::var("ENGINE_POOL_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_POOL_SIZE)
+}
+
+//Limits how many engines run at once
+static SLOTS: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(pool_size()));
+//Engines that are running but not analysing anything
+static IDLE: Lazy<Mutex<Vec<Engine>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+//Analyses the position described by `fen`. Waits for an engine if they are all busy
+pub async fn analyse(fen: &str, limit: Limit) -> Result<Analysis, EngineError> {
+    search(fen, limit, MAX_SKILL).await
+}
+
+//Members can play the engine at these levels, from 1 to 8. Lower levels play at a lower skill and
+//look fewer moves ahead, so that beginners have a chance
+pub const LEVELS: u8 = 8;
+const LEVEL_SKILLS: [u8; LEVELS as usize] = [0, 3, 6, 9, 12, 15, 18, 20];
+const LEVEL_DEPTHS: [u32; LEVELS as usize] = [1, 2, 3, 5, 7, 10, 13, 18];
+
+//The move the engine plays at `level` in the position described by `fen`, in UCI notation
+pub async fn play(fen: &str, level: u8) -> Result<String, EngineError> {
+    let index = (level.clamp(1, LEVELS) - 1) as usize;
+    let analysis = search(fen, Limit::Depth(LEVEL_DEPTHS[index]), LEVEL_SKILLS[index]).await?;
+    Ok(analysis.best_move)
+}
+
+async fn search(fen: &str, limit: Limit, skill: u8) -> Result<Analysis, EngineError> {
+    let _slot = SLOTS
+        .acquire()
+        .await
+        .expect("the semaphore is never closed");
+    let idle = IDLE.lock().await.pop();
+    let mut engine = match idle {
+        Some(engine) => engine,
+        None => Engine::start().await?,
+    };
+    let analysis = tokio::time::timeout(ENGINE_TIMEOUT, engine.analyse(fen, limit, skill))
+        .await
+        .unwrap_or(Err(EngineError::Timeout));
+    //Engines that failed are dropped, which kills them, and replaced next time
+    if analysis.is_ok() {
+        IDLE.lock().await.push(engine);
+    }
+    analysis
+}
@@ -0,0 +1,108 @@
+use serenity::{
+    framework::standard::{ArgError, CommandError},
+    model::{
+        channel::Message,
+        id::ChannelId,
+        misc::{ChannelIdParseError, UserIdParseError},
+    },
+    prelude::*,
+    utils::Colour,
+};
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::num::ParseIntError;
+
+use crate::library::{ChannelKind, ManipulationError};
+use crate::pgn::PgnError;
+
+//Errors a command didn't expect are also sent to the guild's errors channel, which is set with
+//the config channel command, so that admins notice when something breaks without having to read
+//the bot's logs. Mistakes like an unknown book or a missing argument are only shown to whoever
+//made them
+
+//Discord refuses embeds with longer descriptions or field values
+const MAX_DESCRIPTION_LEN: usize = 4096;
+const MAX_FIELD_LEN: usize = 1024;
+
+//Whether the error was caused by how the command was used, rather than by the bot
+fn is_user_error(error: &CommandError) -> bool {
+    error.is::<ManipulationError>()
+        || error.is::<ArgError<Infallible>>()
+        || error.is::<ArgError<ParseIntError>>()
+        || error.is::<ArgError<UserIdParseError>>()
+        || error.is::<ArgError<ChannelIdParseError>>()
+        || error.is::<ChannelIdParseError>()
+        || error.is::<UserIdParseError>()
+        || error.is::<PgnError>()
+}
+
+fn truncate(text: &str, max_len: usize) -> String {
+    if text.len() <= max_len {
+        return text.to_owned();
+    }
+    let mut cut = String::new();
+    for c in text.chars() {
+        if cut.len() + c.len_utf8() + '…'.len_utf8() > max_len {
+            break;
+        }
+        cut.push(c);
+    }
+    cut.push('…');
+    cut
+}
+
+//The error and everything that caused it, one per line
+fn error_chain(error: &CommandError) -> String {
+    let mut chain = format!("{:?}", error);
+    let mut source = error.source();
+    while let Some(cause) = source {
+        chain.push_str(&format!("\ncaused by: {:?}", cause));
+        source = cause.source();
+    }
+    chain
+}
+
+//Sends `error` from `command_name`, used by `msg`, to the errors channel of the guild it was used
+//in, unless it was the user's own mistake or the guild has no errors channel
+pub async fn report(ctx: &Context, msg: &Message, command_name: &str, error: &CommandError) {
+    if is_user_error(error) {
+        return;
+    }
+    let channel = crate::library_for(ctx, msg.guild_id)
+        .await
+        .read()
+        .await
+        .channel(ChannelKind::Errors);
+    let channel = match channel {
+        Some(channel) => ChannelId(channel),
+        None => return,
+    };
+
+    //Code blocks can't be closed early by the error text
+    let chain = error_chain(error).replace("```", "'''");
+    let description = format!(
+        "```\n{}\n```",
+        truncate(&chain, MAX_DESCRIPTION_LEN - "```\n\n```".len())
+    );
+    let sent = channel
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.colour(Colour::RED)
+                    .title(format!("Command '{}' failed", command_name))
+                    .description(description)
+                    .field("Used by", format!("<@{}>", msg.author.id), true)
+                    .field("In", format!("<#{}>", msg.channel_id), true)
+                    .field("Message", truncate(&msg.content, MAX_FIELD_LEN), false)
+                    .timestamp(&msg.timestamp)
+            })
+            .allowed_mentions(|mentions| mentions.empty_parse())
+        })
+        .await;
+    if let Err(err) = sent {
+        println!(
+            "Failed to report error in guild {:?}: {:?}",
+            msg.guild_id, err
+        );
+    }
+}
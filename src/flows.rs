@@ -0,0 +1,137 @@
+use once_cell::sync::Lazy;
+use serenity::{
+    http::Http,
+    model::{
+        channel::{Message, ReactionType},
+        id::{ChannelId, GuildId, MessageId, UserId},
+    },
+    prelude::*,
+};
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::library::{CheckoutStatus, MessageRef};
+use crate::permissions::is_officer;
+
+//Keeps track of the bot's interactive messages. Menus only work for a while, so once they expire
+//their components are taken off the message, which tells members to run the command again
+//instead of leaving them with a menu that does nothing, and whatever was waiting on them is
+//forgotten. Flows that have to outlive the bot, like checkouts waiting on an officer's approval,
+//are saved with the library and picked up again by resume_approvals
+
+//How often expired menus are looked for
+const FLOW_CHECK_SECS: u64 = 30;
+
+struct Tracked {
+    channel: ChannelId,
+    message: MessageId,
+    expires: Instant,
+}
+
+static TRACKED: Lazy<Mutex<Vec<Tracked>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+//Takes the components off `message` once `timeout` has passed. A message that was already tracked,
+//because it was edited to show a new menu, gets the new timeout
+pub fn expire_components(message: &Message, timeout: Duration) {
+    let mut tracked = TRACKED.lock().unwrap();
+    tracked.retain(|other| other.message != message.id);
+    tracked.push(Tracked {
+        channel: message.channel_id,
+        message: message.id,
+        expires: Instant::now() + timeout,
+    });
+}
+
+//Stops tracking a message whose menu was answered
+pub fn finished(message: MessageId) {
+    TRACKED
+        .lock()
+        .unwrap()
+        .retain(|other| other.message != message);
+}
+
+//Background task that cleans up after expired flows
+pub async fn flow_task(http: Arc<Http>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(FLOW_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        crate::picker::forget_expired();
+        crate::replay::forget_expired();
+        crate::puzzles::forget_expired();
+        crate::lichess::forget_expired();
+
+        let expired: Vec<Tracked> = {
+            let mut tracked = TRACKED.lock().unwrap();
+            let now = Instant::now();
+            let (expired, live) = tracked.drain(..).partition(|t| t.expires <= now);
+            *tracked = live;
+            expired
+        };
+        for flow in expired {
+            let edited = flow
+                .channel
+                .edit_message(&http, flow.message, |m| m.components(|c| c))
+                .await;
+            //The message may have been deleted in the meantime, which is fine
+            if let Err(err) = edited {
+                println!("Failed to remove expired menu: {:?}", err);
+            }
+        }
+    }
+}
+
+//Officers may have reacted to approve a handout or a return while the bot was offline. The
+//reactions are still on the messages, so this looks for them and carries out the approvals that
+//were missed
+pub async fn resume_approvals(ctx: &Context, guild: GuildId) {
+    let waiting: Vec<MessageRef> = {
+        let library_arc = crate::library_for(ctx, Some(guild)).await;
+        let library = library_arc.read().await;
+        let mut waiting = Vec::new();
+        for checkout in library.checkouts.values() {
+            let message = match checkout.status {
+                CheckoutStatus::PreTransact => checkout.approval_message,
+                CheckoutStatus::ReturnVerifyNeeded => checkout.return_message,
+                _ => None,
+            };
+            if let Some(message) = message {
+                if !waiting.contains(&message) {
+                    waiting.push(message);
+                }
+            }
+        }
+        waiting
+    };
+
+    let bot = ctx.cache.current_user_id().await;
+    let emoji = ReactionType::Unicode(crate::APPROVE_EMOJI.to_owned());
+    for message in waiting {
+        let channel = ChannelId(message.channel);
+        let users = match channel
+            .reaction_users(ctx, message.message, emoji.clone(), None, None::<UserId>)
+            .await
+        {
+            Ok(users) => users,
+            Err(err) => {
+                println!("Failed to check message for missed approvals: {:?}", err);
+                continue;
+            }
+        };
+        for user in users {
+            if user.id != bot && is_officer(ctx, Some(guild), user.id).await {
+                println!("Carrying out an approval made while offline");
+                crate::approve_by_reaction(
+                    ctx,
+                    Some(guild),
+                    channel,
+                    MessageId(message.message),
+                    user.id,
+                )
+                .await;
+                break;
+            }
+        }
+    }
+}
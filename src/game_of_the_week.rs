@@ -0,0 +1,274 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId},
+};
+
+use std::sync::Arc;
+
+use crate::archive::{ArchiveUuid, ArchivedGame};
+use crate::games::Game;
+use crate::guilds::Libraries;
+use crate::library::{ChannelKind, ManipulationError, ManipulationErrorType, MessageRef, TimeType};
+use crate::rules::GameState;
+
+//Game of the week. Officers nominate games from the archive with !gotw nominate, members vote for
+//their favourite with !gotw vote, and a week after the first nomination the game with the most
+//votes wins, ties going to whichever was nominated first. The winner is posted and pinned in the
+//game of the week channel with its PGN, its final position and its key moment: the move that
+//lost the most according to the engine, on a board showing what the engine would have played
+
+//How often the task checks whether voting has closed
+const CHECK_SECS: u64 = 60 * 60;
+//How long voting stays open after the first nomination
+const VOTING_DAYS: i64 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GameOfTheWeek {
+    //The games nominated this week and the Discord id of the officer who nominated each, in the
+    //order they were nominated
+    pub nominations: IndexMap<ArchiveUuid, String>,
+    //Discord id of each voter and the game they voted for
+    pub votes: IndexMap<String, ArchiveUuid>,
+    //When voting closes. None until the week's first nomination
+    pub closes: Option<TimeType>,
+    //Past winners and when they won, oldest first
+    pub winners: Vec<(ArchiveUuid, TimeType)>,
+    //The post of the last winner, which is unpinned when the next one is pinned
+    pub pinned: Option<MessageRef>,
+}
+
+impl GameOfTheWeek {
+    //Nominates archived game `uuid`, opening voting if it is the week's first nomination
+    pub fn nominate(
+        &mut self,
+        uuid: ArchiveUuid,
+        officer: String,
+        id: String,
+    ) -> Result<(), ManipulationError> {
+        if self.nominations.contains_key(&uuid) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::AlreadyNominated(id),
+            ));
+        }
+        self.nominations.insert(uuid, officer);
+        if self.closes.is_none() {
+            self.closes = Some(chrono::Local::now() + chrono::Duration::days(VOTING_DAYS));
+        }
+        Ok(())
+    }
+
+    //Records `voter`'s vote for nominated game `uuid`, replacing any vote they already made
+    pub fn vote(
+        &mut self,
+        voter: String,
+        uuid: ArchiveUuid,
+        id: String,
+    ) -> Result<(), ManipulationError> {
+        if !self.nominations.contains_key(&uuid) {
+            return Err(ManipulationError::new(ManipulationErrorType::NotNominated(
+                id,
+            )));
+        }
+        self.votes.insert(voter, uuid);
+        Ok(())
+    }
+
+    //Every nominated game and how many votes it has, most first. Ties are in the order the games
+    //were nominated
+    pub fn tally(&self) -> Vec<(ArchiveUuid, usize)> {
+        let mut tally: Vec<(ArchiveUuid, usize)> = self
+            .nominations
+            .keys()
+            .map(|uuid| {
+                let votes = self.votes.values().filter(|vote| *vote == uuid).count();
+                (*uuid, votes)
+            })
+            .collect();
+        tally.sort_by(|a, b| b.1.cmp(&a.1));
+        tally
+    }
+
+    //Picks the winner once voting has closed and starts the next week. Returns the winner and its
+    //votes. When nothing was nominated there is no winner
+    fn close(&mut self, now: TimeType) -> Option<(ArchiveUuid, usize)> {
+        if self.closes.map_or(true, |closes| now < closes) {
+            return None;
+        }
+        let winner = self.tally().into_iter().next();
+        if let Some((uuid, _)) = winner {
+            self.winners.push((uuid, now));
+        }
+        self.nominations.clear();
+        self.votes.clear();
+        self.closes = None;
+        winner
+    }
+}
+
+//Background task that picks the game of the week once voting closes
+pub async fn game_of_the_week_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            let (game, votes, channel, unpin) = {
+                let mut library = library_arc.write().await;
+                let (uuid, votes) = match library.game_of_the_week.close(chrono::Local::now()) {
+                    Some(winner) => winner,
+                    None => continue,
+                };
+                crate::autosave::persist_change(&library).await;
+                let game = match library.archive.get(&uuid) {
+                    Some(game) => game.clone(),
+                    None => continue,
+                };
+                let channel = match library.channel(ChannelKind::GameOfTheWeek) {
+                    Some(channel) => ChannelId(channel),
+                    None => {
+                        println!(
+                            "No game of the week channel set for guild {:?}, can't post the winner",
+                            guild
+                        );
+                        continue;
+                    }
+                };
+                (game, votes, channel, library.game_of_the_week.pinned)
+            };
+
+            //The library isn't held while the engine looks for the key moment
+            let pinned = match post(&http, guild, channel, &game, votes).await {
+                Some(pinned) => pinned,
+                None => continue,
+            };
+            if let Some(old) = unpin {
+                if let Err(err) = ChannelId(old.channel).unpin(&http, old.message).await {
+                    println!("Failed to unpin last week's game of the week: {:?}", err);
+                }
+            }
+            let mut library = library_arc.write().await;
+            library.game_of_the_week.pinned = Some(pinned);
+            crate::autosave::persist_change(&library).await;
+        }
+    }
+}
+
+//Posts `game` as the game of the week in `channel` and pins it
+async fn post(
+    http: &Http,
+    guild: Option<GuildId>,
+    channel: ChannelId,
+    game: &ArchivedGame,
+    votes: usize,
+) -> Option<MessageRef> {
+    let pgn = game.pgn.as_deref()?;
+    let imported = match crate::pgn::parse(pgn) {
+        Ok(imported) => imported,
+        Err(err) => {
+            println!("Can't read the game of the week's PGN: {:?}", err);
+            return None;
+        }
+    };
+
+    let mut text = format!("{}\n", game.describe());
+    if let Some(opening) = &game.opening {
+        text.push_str(&format!("Opening: {}\n", opening));
+    }
+    text.push_str(&format!("Chosen with {} vote(s)\n", votes));
+
+    let final_state = GameState::replay(imported.start_fen.as_deref(), &imported.moves);
+    let final_png = crate::board_image::render_board(
+        final_state.position().board(),
+        Game::last_squares(&final_state),
+        false,
+    )
+    .ok()?;
+
+    //The key moment is shown from the side of whoever went wrong, with the engine's move
+    let report = match crate::blunders::check(&imported).await {
+        Ok(report) => Some(report),
+        Err(err) => {
+            println!(
+                "The engine couldn't check guild {:?}'s game of the week: {:?}",
+                guild, err
+            );
+            None
+        }
+    };
+    let key = report.as_ref().and_then(|report| {
+        report.flagged.iter().max_by(|a, b| {
+            a.loss
+                .partial_cmp(&b.loss)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    });
+    let key_png = match key {
+        Some(key) => {
+            let state =
+                GameState::replay(imported.start_fen.as_deref(), &imported.moves[..key.ply]);
+            let best = state
+                .parse_move(&key.best)
+                .and_then(|m| Game::move_squares(&m));
+            text.push_str(&format!(
+                "\nKey moment: {} {}{}, losing {:.0}% of {}'s winning chances. The engine preferred {}, shown on the board below",
+                key.number,
+                key.san,
+                key.judgement.symbol(),
+                key.loss * 100.0,
+                if key.mover == shakmaty::Color::White {
+                    "white"
+                } else {
+                    "black"
+                },
+                key.best
+            ));
+            crate::board_image::render_board(
+                state.position().board(),
+                best,
+                key.mover == shakmaty::Color::Black,
+            )
+            .ok()
+        }
+        None => {
+            if report.is_some() {
+                text.push_str("\nThe engine found no mistakes in it");
+            }
+            None
+        }
+    };
+
+    let result = channel
+        .send_message(http, |m| {
+            m.add_file((final_png.as_slice(), "final.png"))
+                .add_file((pgn.as_bytes(), "game.pgn"));
+            if let Some(key_png) = &key_png {
+                m.add_file((key_png.as_slice(), "key-moment.png"));
+            }
+            m.embed(|e| {
+                e.colour(crate::response::Tone::Success.colour())
+                    .title("Game of the week")
+                    .description(text)
+                    .image("attachment://final.png")
+            })
+        })
+        .await;
+    let message = match result {
+        Ok(message) => message,
+        Err(err) => {
+            println!(
+                "Failed to post guild {:?}'s game of the week: {:?}",
+                guild, err
+            );
+            return None;
+        }
+    };
+    if let Err(err) = message.pin(http).await {
+        println!(
+            "Failed to pin guild {:?}'s game of the week: {:?}",
+            guild, err
+        );
+    }
+    Some(MessageRef::new(message.channel_id.0, message.id.0))
+}
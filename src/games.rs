@@ -0,0 +1,833 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use shakmaty::{Board, Color, File, Move, Piece, Position, Rank, Role, Square};
+
+use crate::clocks::Clock;
+use crate::library::{Database, ManipulationError, ManipulationErrorType, TimeType};
+use crate::rules::GameState;
+
+//Members can play each other with !chess. A game starts as a challenge, which the other member
+//accepts or declines, and is then played one !chess move at a time. Games are saved with the
+//library, as the moves played so far, so they survive restarts and can go on for days
+
+pub type GameUuid = u32;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    //Waiting on the challenged member to accept or decline
+    Challenged,
+    Declined,
+    Playing,
+    WhiteWon,
+    BlackWon,
+    Drawn,
+}
+
+impl GameStatus {
+    pub fn is_over(self) -> bool {
+        !matches!(self, GameStatus::Challenged | GameStatus::Playing)
+    }
+
+    //What white scored in a finished game: 1 for a win, ½ for a draw, 0 for a loss
+    pub fn white_score(self) -> Option<f64> {
+        match self {
+            GameStatus::WhiteWon => Some(1.0),
+            GameStatus::BlackWon => Some(0.0),
+            GameStatus::Drawn => Some(0.5),
+            _ => None,
+        }
+    }
+
+    //The result as written in PGN
+    pub fn pgn_result(self) -> &'static str {
+        match self {
+            GameStatus::WhiteWon => "1-0",
+            GameStatus::BlackWon => "0-1",
+            GameStatus::Drawn => "1/2-1/2",
+            _ => "*",
+        }
+    }
+}
+
+//PGN movetext lines are kept below this length
+const PGN_LINE_LEN: usize = 80;
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            GameStatus::Challenged => write!(fmt, "waiting for the challenge to be accepted"),
+            GameStatus::Declined => write!(fmt, "challenge declined"),
+            GameStatus::Playing => write!(fmt, "in progress"),
+            GameStatus::WhiteWon => write!(fmt, "1-0"),
+            GameStatus::BlackWon => write!(fmt, "0-1"),
+            GameStatus::Drawn => write!(fmt, "½-½"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct Game {
+    pub uuid: GameUuid,
+    //Discord ids of the players
+    pub white: String,
+    pub black: String,
+    //Discord id of whoever sent the challenge. The other player has to accept it
+    pub challenger: String,
+    //Where the challenge was made. Updates about the game are posted there
+    pub channel: u64,
+    #[new(value = "GameStatus::Challenged")]
+    pub status: GameStatus,
+    //Every move played so far in standard algebraic notation, starting from start_fen
+    #[new(default)]
+    pub moves: Vec<String>,
+    #[new(value = "chrono::Local::now()")]
+    pub started: TimeType,
+    #[new(default)]
+    pub finished: Option<TimeType>,
+    //The position the game started from, for games set up with !chess fen and Chess960 games. None
+    //for the usual starting position
+    #[new(default)]
+    pub start_fen: Option<String>,
+    //The game in Portable Game Notation, saved when it ends so that it keeps the names the players
+    //had then
+    #[new(default)]
+    pub pgn: Option<String>,
+    //The engine's level, from 1 to 8, in games against it. The engine plays with the bot's own
+    //discord id
+    #[new(default)]
+    pub engine_level: Option<u8>,
+    //How many days each player has for a move in correspondence games. None for games without a
+    //clock
+    #[new(default)]
+    pub days_per_move: Option<u32>,
+    //When the last move was played. The first move's clock runs from when the game started
+    #[new(default)]
+    pub last_move_at: Option<TimeType>,
+    //Whether the player to move was reminded that their time is running out. Reset every move
+    #[new(default)]
+    pub reminded: bool,
+    //The players' clocks in games with a real-time control like 10+5
+    #[new(default)]
+    pub clock: Option<Clock>,
+    //Whether every move is posted in the guild's broadcast channel, for spectators
+    #[new(default)]
+    pub broadcast: bool,
+    //The hints the member asked for in games against the engine, oldest first
+    #[new(default)]
+    pub hints: Vec<Hint>,
+    //Casual games don't count towards club ratings, so moves can be taken back in them
+    #[new(default)]
+    pub casual: bool,
+    //Discord id of the player offering a draw, until their opponent accepts or a move is played
+    #[new(default)]
+    pub draw_offer: Option<String>,
+    //Discord id of the player asking to take back their last move, until their opponent allows it
+    //or a move is played
+    #[new(default)]
+    pub takeback_request: Option<String>,
+    //When each move reached Discord and how long it took, oldest first. Moves played before this
+    //was kept aren't in it
+    #[new(default)]
+    #[serde(default)]
+    pub move_times: Vec<MoveTime>,
+}
+
+//A hint from the engine, asked for with !chess hint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hint {
+    //The move it was given for, counting from 0
+    pub ply: usize,
+    //Whether it showed the whole move, or only the piece to move
+    pub full: bool,
+}
+
+//When a move was played and how long the player thought about it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveTime {
+    //The move, counting from 0
+    pub ply: usize,
+    //When Discord received the move, which can be a little before the bot got to it
+    pub received: TimeType,
+    //How long the player thought, in milliseconds
+    pub spent_ms: i64,
+    //What the player had left on their clock after the move, in games with a real-time clock
+    pub clock_ms: Option<i64>,
+}
+
+impl Game {
+    pub fn has_player(&self, discord_id: &str) -> bool {
+        self.white == discord_id || self.black == discord_id
+    }
+
+    //The other player, for someone playing in this game
+    pub fn opponent_of(&self, discord_id: &str) -> &str {
+        if self.white == discord_id {
+            &self.black
+        } else {
+            &self.white
+        }
+    }
+
+    //Puts discord id `to` wherever the game has `from`, for members who are merged or forgotten
+    pub fn replace_player(&mut self, from: &str, to: &str) {
+        let mut ids = vec![&mut self.white, &mut self.black, &mut self.challenger];
+        ids.extend(self.draw_offer.as_mut());
+        ids.extend(self.takeback_request.as_mut());
+        for id in ids {
+            if *id == from {
+                *id = to.to_owned();
+            }
+        }
+    }
+
+    //Analysis boards, set up with !chess fen, have the same member play both sides
+    pub fn is_analysis(&self) -> bool {
+        self.white == self.black
+    }
+
+    //Games between two members count towards their club ratings, unless they are casual
+    pub fn is_rated(&self) -> bool {
+        !self.is_analysis() && self.engine_level.is_none() && !self.casual
+    }
+
+    //Rated games have to be played as they stand, but moves can be taken back in the others
+    pub fn allows_takebacks(&self) -> bool {
+        !self.is_rated()
+    }
+
+    //The player who was challenged
+    pub fn challenged(&self) -> &str {
+        self.opponent_of(&self.challenger)
+    }
+
+    //Discord id the engine plays with, in games against it. The member is always the challenger
+    pub fn engine_player(&self) -> Option<&str> {
+        self.engine_level.map(|_| self.challenged())
+    }
+
+    //The game as it stands after every move so far
+    pub fn state(&self) -> GameState {
+        GameState::replay(self.start_fen.as_deref(), &self.moves)
+    }
+
+    //The squares the last move was played from and to. For castling that is where the king went
+    pub fn last_squares(state: &GameState) -> Option<(Square, Square)> {
+        Game::move_squares(state.last_move()?)
+    }
+
+    //The squares `m` is played from and to, as last_squares
+    pub fn move_squares(m: &Move) -> Option<(Square, Square)> {
+        match m {
+            Move::Castle { king, rook } => {
+                let file = if rook.file() > king.file() {
+                    File::G
+                } else {
+                    File::C
+                };
+                Some((*king, Square::from_coords(file, king.rank())))
+            }
+            m => Some((m.from()?, m.to())),
+        }
+    }
+
+    //A picture of the board as a PNG
+    pub fn render_image(&self, flipped: bool) -> Result<Vec<u8>, image::ImageError> {
+        let state = self.state();
+        let black_below = self.black_below(&state, flipped);
+        crate::board_image::render_board(
+            state.position().board(),
+            Game::last_squares(&state),
+            black_below,
+        )
+    }
+
+    //Discord id of the player whose turn it is
+    pub fn to_move(&self) -> &str {
+        match self.state().turn() {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    //Plays `input`, in SAN or UCI, for `player`. Ends the game if the move mates or draws. Players
+    //whose clock had run out when Discord `received` the move lose on time instead
+    pub fn play(
+        &mut self,
+        player: &str,
+        input: &str,
+        received: TimeType,
+    ) -> Result<(), ManipulationError> {
+        if self.status != GameStatus::Playing {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::GameNotInProgress(Database::encode_uuid(self.uuid)),
+            ));
+        }
+        if self.to_move() != player {
+            return Err(ManipulationError::new(ManipulationErrorType::NotYourTurn));
+        }
+
+        //The move counts from when Discord received it, so that time the bot took to get to it
+        //isn't taken off the player's clock. Clocks that are off can't make it count from before
+        //the player started thinking or from the future
+        let since = self.clock_started();
+        let received = received.max(since).min(chrono::Local::now());
+        let mut state = self.state();
+        let turn = state.turn();
+        if self.out_of_time_at(received) {
+            if let Some(clock) = &mut self.clock {
+                clock.flag(turn);
+            }
+            self.resign(player);
+            return Ok(());
+        }
+        let m = state.parse_move(input).ok_or_else(|| {
+            ManipulationError::new(ManipulationErrorType::IllegalMove(input.to_owned()))
+        })?;
+        //Saved in the canonical form, so that e.g. "Nge2" is stored as "Ne2" when there is no
+        //ambiguity
+        self.moves.push(state.play(&m));
+        let spent = received - since;
+        if let Some(clock) = &mut self.clock {
+            clock.punch(turn, spent);
+        }
+        self.move_times.push(MoveTime {
+            ply: self.moves.len() - 1,
+            received,
+            spent_ms: spent.num_milliseconds(),
+            clock_ms: self.clock.map(|clock| clock.stopped_ms(turn)),
+        });
+        self.last_move_at = Some(received);
+        self.reminded = false;
+        //Moving turns down whatever the opponent offered or asked for
+        self.draw_offer = None;
+        self.takeback_request = None;
+
+        if let Some(end) = state.end() {
+            self.finish(match end.winner() {
+                Some(Color::White) => GameStatus::WhiteWon,
+                Some(Color::Black) => GameStatus::BlackWon,
+                None => GameStatus::Drawn,
+            });
+        }
+        Ok(())
+    }
+
+    //Offers `player`'s opponent a draw, or accepts the draw they offered. Returns whether the game
+    //was drawn
+    pub fn offer_draw(&mut self, player: &str) -> Result<bool, ManipulationError> {
+        self.check_playing()?;
+        if self.is_analysis() || self.engine_level.is_some() {
+            return Err(ManipulationError::new(ManipulationErrorType::NoDrawOffers));
+        }
+        if self.draw_offer.as_deref() == Some(self.opponent_of(player)) {
+            self.finish(GameStatus::Drawn);
+            return Ok(true);
+        }
+        self.draw_offer = Some(player.to_owned());
+        Ok(false)
+    }
+
+    //Asks to take back `player`'s last move, or allows the takeback their opponent asked for.
+    //Returns whether moves were taken back. On analysis boards and against the engine nobody has
+    //to allow it
+    pub fn request_takeback(&mut self, player: &str) -> Result<bool, ManipulationError> {
+        self.check_playing()?;
+        if !self.allows_takebacks() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::TakebacksNotAllowed,
+            ));
+        }
+        let opponent = self.opponent_of(player).to_owned();
+        let (asker, allowed) = if self.is_analysis() || self.engine_level.is_some() {
+            (player.to_owned(), true)
+        } else if self.takeback_request.as_deref() == Some(&opponent) {
+            (opponent, true)
+        } else {
+            (player.to_owned(), false)
+        };
+        //The asker's last move, and the reply to it if it was played already
+        let plies = if self.is_analysis() || self.to_move() != asker {
+            1
+        } else {
+            2
+        };
+        if self.moves.len() < plies {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::NothingToTakeBack,
+            ));
+        }
+        if !allowed {
+            self.takeback_request = Some(asker);
+            return Ok(false);
+        }
+
+        self.moves.truncate(self.moves.len() - plies);
+        let played = self.moves.len();
+        self.hints.retain(|hint| hint.ply < played);
+        self.move_times.retain(|time| time.ply < played);
+        self.draw_offer = None;
+        self.takeback_request = None;
+        //The clock of the player to move runs from now. Time already spent isn't given back
+        self.last_move_at = Some(chrono::Local::now());
+        self.reminded = false;
+        Ok(true)
+    }
+
+    fn check_playing(&self) -> Result<(), ManipulationError> {
+        if self.status != GameStatus::Playing {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::GameNotInProgress(Database::encode_uuid(self.uuid)),
+            ));
+        }
+        Ok(())
+    }
+
+    //Ends the game with `player` giving up
+    pub fn resign(&mut self, player: &str) {
+        self.finish(if self.white == player {
+            GameStatus::BlackWon
+        } else {
+            GameStatus::WhiteWon
+        });
+    }
+
+    //When the player to move started thinking: when the last move was played, or for the first
+    //move when the game started
+    fn clock_started(&self) -> TimeType {
+        self.last_move_at.unwrap_or(self.started)
+    }
+
+    //When the player to move runs out of time, in correspondence games that are being played
+    pub fn move_deadline(&self) -> Option<TimeType> {
+        let days = self.days_per_move?;
+        if self.status != GameStatus::Playing {
+            return None;
+        }
+        Some(self.clock_started() + chrono::Duration::days(days as i64))
+    }
+
+    //How many milliseconds `colour` has left on their clock, counting the time the player to move
+    //has been thinking. None for games without a real-time clock
+    pub fn time_left(&self, colour: Color) -> Option<i64> {
+        self.time_left_at(colour, chrono::Local::now())
+    }
+
+    //How many milliseconds `colour` had left on their clock at `at`
+    fn time_left_at(&self, colour: Color, at: TimeType) -> Option<i64> {
+        let clock = self.clock?;
+        let mut left = clock.stopped_ms(colour);
+        if self.status == GameStatus::Playing && self.state().turn() == colour {
+            left -= (at - self.clock_started()).num_milliseconds();
+        }
+        Some(left)
+    }
+
+    //Whether the player to move ran out of time on their clock at least LAG_ALLOWANCE_MS ago
+    pub fn out_of_time(&self) -> bool {
+        let allowance = chrono::Duration::milliseconds(crate::clocks::LAG_ALLOWANCE_MS);
+        self.out_of_time_at(chrono::Local::now() - allowance)
+    }
+
+    //Whether the player to move had run out of time on their clock at `at`
+    fn out_of_time_at(&self, at: TimeType) -> bool {
+        self.status == GameStatus::Playing
+            && self.clock.is_some()
+            && self
+                .time_left_at(self.state().turn(), at)
+                .map_or(false, |left| left <= 0)
+    }
+
+    //Whether the game was lost on time, on a real-time clock or by correspondence
+    pub fn lost_on_time(&self) -> bool {
+        let loser = match self.status {
+            GameStatus::WhiteWon => Color::Black,
+            GameStatus::BlackWon => Color::White,
+            _ => return false,
+        };
+        let correspondence = match (self.days_per_move, self.finished) {
+            (Some(days), Some(finished)) => {
+                finished >= self.clock_started() + chrono::Duration::days(days as i64)
+            }
+            _ => false,
+        };
+        correspondence
+            || self
+                .clock
+                .map_or(false, |clock| clock.stopped_ms(loser) <= 0)
+    }
+
+    //How a game that ended off the board ended, like "Black resigns". None for games that ended on
+    //the board or haven't ended
+    pub fn ending(&self) -> Option<String> {
+        if self.state().end().is_some() {
+            return None;
+        }
+        let loser = match self.status {
+            GameStatus::Drawn => return Some("Draw agreed".to_owned()),
+            GameStatus::WhiteWon => "Black",
+            GameStatus::BlackWon => "White",
+            _ => return None,
+        };
+        Some(if self.lost_on_time() {
+            format!("{} loses on time", loser)
+        } else {
+            format!("{} resigns", loser)
+        })
+    }
+
+    pub fn finish(&mut self, status: GameStatus) {
+        self.status = status;
+        self.finished = Some(chrono::Local::now());
+    }
+
+    //The number of the Chess960 starting position the game started from, in Chess960 games
+    pub fn chess960(&self) -> Option<u32> {
+        crate::rules::chess960_number(self.start_fen.as_deref()?)
+    }
+
+    //The opening played so far, if it is one openings.rs knows
+    pub fn opening(&self) -> Option<crate::openings::Opening> {
+        crate::openings::identify(self.start_fen.as_deref(), &self.moves)
+    }
+
+    //Notes that the player to move was given a hint, showing the whole move when `full`
+    pub fn record_hint(&mut self, full: bool) {
+        let ply = self.moves.len();
+        match self.hints.iter_mut().find(|hint| hint.ply == ply) {
+            Some(hint) => hint.full |= full,
+            None => self.hints.push(Hint { ply, full }),
+        }
+    }
+
+    //The moves so far with how long each took, numbered like "1. e4 (0:03.2) e5 (1:15) 2. Nf3".
+    //Moves played before move times were kept are left without one
+    pub fn move_list(&self) -> String {
+        self.movetext(
+            |i, san| match self.move_time(i) {
+                Some(time) => format!("{} ({})", san, crate::clocks::format_ms(time.spent_ms)),
+                None => san.to_owned(),
+            },
+            |_| None,
+        )
+    }
+
+    //When the i-th move, counting from 0, was played and how long it took
+    fn move_time(&self, ply: usize) -> Option<&MoveTime> {
+        self.move_times.iter().find(|time| time.ply == ply)
+    }
+
+    //The moves numbered like "1. e4 e5 2. Nf3", with each move written as `write(i, san)` for the
+    //i-th move, counting from 0, and `comment(i)` after it when it has one
+    fn movetext(
+        &self,
+        write: impl Fn(usize, &str) -> String,
+        comment: impl Fn(usize) -> Option<String>,
+    ) -> String {
+        let start = GameState::start(self.start_fen.as_deref());
+        let mut number = start.move_number();
+        let mut white = start.turn() == Color::White;
+        let mut commented = false;
+        let mut list = String::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            if i > 0 {
+                list.push(' ');
+            }
+            if white {
+                list.push_str(&format!("{}. ", number));
+            } else {
+                //Games set up with black to move start half way through a move, and black's moves
+                //after a comment are numbered again
+                if i == 0 || commented {
+                    list.push_str(&format!("{}... ", number));
+                }
+                if i > 0 {
+                    number += 1;
+                }
+            }
+            list.push_str(&write(i, san));
+            commented = match comment(i) {
+                Some(text) => {
+                    list.push_str(&format!(" {{{}}}", text));
+                    true
+                }
+                None => false,
+            };
+            white = !white;
+        }
+        list
+    }
+
+    //The game in Portable Game Notation, which other chess tools can import. `event` names where it
+    //was played
+    pub fn to_pgn(&self, event: &str, white_name: &str, black_name: &str) -> String {
+        let result = self.status.pgn_result();
+        let tag = |name: &str, value: &str| {
+            format!(
+                "[{} \"{}\"]\n",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        };
+        let mut pgn = String::new();
+        pgn.push_str(&tag("Event", event));
+        pgn.push_str(&tag("Site", "Discord"));
+        pgn.push_str(&tag("Date", &self.started.format("%Y.%m.%d").to_string()));
+        pgn.push_str(&tag("Round", "-"));
+        pgn.push_str(&tag("White", white_name));
+        pgn.push_str(&tag("Black", black_name));
+        pgn.push_str(&tag("Result", result));
+        if let Some(fen) = &self.start_fen {
+            if self.chess960().is_some() {
+                pgn.push_str(&tag("Variant", "Chess960"));
+            }
+            pgn.push_str(&tag("SetUp", "1"));
+            pgn.push_str(&tag("FEN", fen));
+        }
+        if let Some(clock) = &self.clock {
+            let time_control = format!("{}+{}", clock.minutes * 60, clock.increment_secs);
+            pgn.push_str(&tag("TimeControl", &time_control));
+        }
+        if matches!(
+            self.status,
+            GameStatus::WhiteWon | GameStatus::BlackWon | GameStatus::Drawn
+        ) {
+            let termination = if self.lost_on_time() {
+                "Time forfeit"
+            } else {
+                "Normal"
+            };
+            pgn.push_str(&tag("Termination", termination));
+        }
+        if let Some(opening) = self.opening() {
+            pgn.push_str(&tag("ECO", opening.eco));
+            pgn.push_str(&tag("Opening", opening.name));
+        }
+        pgn.push('\n');
+        let movetext = self.movetext(
+            |_, san| san.to_owned(),
+            |i| {
+                //The clock after each move in games with a real-time clock, and otherwise the time
+                //each move took
+                let time = self.move_time(i).map(|time| match time.clock_ms {
+                    Some(clock_ms) => format!("[%clk {}]", pgn_time(clock_ms)),
+                    None => format!("[%emt {}]", pgn_time(time.spent_ms)),
+                });
+                let hint = self.hints.iter().find(|hint| hint.ply == i).map(|hint| {
+                    if hint.full {
+                        "Played after a hint showing the move"
+                    } else {
+                        "Played after a hint showing the piece to move"
+                    }
+                });
+                match (time, hint) {
+                    (Some(time), Some(hint)) => Some(format!("{} {}", time, hint)),
+                    (Some(time), None) => Some(time),
+                    (None, hint) => hint.map(str::to_owned),
+                }
+            },
+        );
+        let ending = self
+            .ending()
+            .map(|ending| format!(" {{{}}}", ending))
+            .unwrap_or_default();
+        pgn.push_str(&wrap_movetext(&format!(
+            "{}{} {}",
+            movetext, ending, result
+        )));
+        pgn
+    }
+
+    //Whether black's side of the board is shown at the bottom. Boards are shown from the point of
+    //view of whoever is to move, or the other player with `flipped`
+    fn black_below(&self, state: &GameState, flipped: bool) -> bool {
+        (state.turn() == Color::Black) != flipped
+    }
+
+    //A diagram of the position in a code block, drawn with the chess symbols in Unicode
+    pub fn render_text(&self, flipped: bool) -> String {
+        let state = self.state();
+        render_text(state.position().board(), self.black_below(&state, flipped))
+    }
+
+    //A diagram of the position made of the guild's piece emojis. None when one of them is missing
+    pub fn render_emoji(&self, flipped: bool, emojis: &IndexMap<String, String>) -> Option<String> {
+        let state = self.state();
+        render_emoji(
+            state.position().board(),
+            self.black_below(&state, flipped),
+            emojis,
+        )
+    }
+}
+
+//`pgn` with the White and Black tags that name `from` naming `to` instead
+pub fn rename_in_pgn(pgn: &str, from: &str, to: &str) -> String {
+    let escape = |name: &str| name.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut pgn = pgn.to_owned();
+    for side in ["White", "Black"].iter() {
+        pgn = pgn.replace(
+            &format!("[{} \"{}\"]", side, escape(from)),
+            &format!("[{} \"{}\"]", side, escape(to)),
+        );
+    }
+    pgn
+}
+
+//A time in milliseconds as PGN clock comments write it, like 0:04:55
+fn pgn_time(ms: i64) -> String {
+    let seconds = ms.max(0) / 1000;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+//PGN move text broken into lines no longer than PGN_LINE_LEN, ending with a newline
+pub fn wrap_movetext(movetext: &str) -> String {
+    let mut text = String::new();
+    let mut line = String::new();
+    for word in movetext.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > PGN_LINE_LEN {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+//The rows of the board from the top down, each from left to right
+fn rows(black_below: bool) -> Vec<Vec<Square>> {
+    let mut ranks: Vec<u32> = (0..8).rev().collect();
+    let mut files: Vec<u32> = (0..8).collect();
+    if black_below {
+        ranks.reverse();
+        files.reverse();
+    }
+    ranks
+        .iter()
+        .map(|rank| {
+            files
+                .iter()
+                .map(|file| Square::from_coords(File::new(*file), Rank::new(*rank)))
+                .collect()
+        })
+        .collect()
+}
+
+//A diagram of `board` in a code block, drawn with the chess symbols in Unicode
+pub fn render_text(board: &Board, black_below: bool) -> String {
+    let rows = rows(black_below);
+
+    let mut diagram = String::from("```\n");
+    for row in &rows {
+        diagram.push_str(&format!("{} ", row[0].rank().char()));
+        for square in row {
+            diagram.push(' ');
+            match board.piece_at(*square) {
+                Some(piece) => diagram.push_str(piece_symbol(piece)),
+                None => diagram.push('·'),
+            }
+        }
+        diagram.push('\n');
+    }
+    diagram.push_str("  ");
+    for square in &rows[0] {
+        diagram.push(' ');
+        diagram.push(square.file().char());
+    }
+    diagram.push_str("\n```");
+    diagram
+}
+
+//A diagram of `board` made of emojis, with pieces drawn with the guild's custom emojis. `emojis`
+//maps emoji names to how they are written in a message. None when the emoji for one of the pieces
+//on the board is missing
+pub fn render_emoji(
+    board: &Board,
+    black_below: bool,
+    emojis: &IndexMap<String, String>,
+) -> Option<String> {
+    let mut diagram = String::new();
+    for row in &rows(black_below) {
+        for square in row {
+            match board.piece_at(*square) {
+                Some(piece) => diagram.push_str(emojis.get(&piece_emoji_name(piece))?),
+                None if square.is_light() => diagram.push('⬜'),
+                None => diagram.push('🟩'),
+            }
+        }
+        diagram.push('\n');
+    }
+    Some(diagram)
+}
+
+//White's pieces are outlined and black's filled in. The black pawn is followed by a variation
+//selector, so that it is shown as a symbol like the others instead of as an emoji
+fn piece_symbol(piece: Piece) -> &'static str {
+    match (piece.color, piece.role) {
+        (Color::White, Role::King) => "♔",
+        (Color::White, Role::Queen) => "♕",
+        (Color::White, Role::Rook) => "♖",
+        (Color::White, Role::Bishop) => "♗",
+        (Color::White, Role::Knight) => "♘",
+        (Color::White, Role::Pawn) => "♙",
+        (Color::Black, Role::King) => "♚",
+        (Color::Black, Role::Queen) => "♛",
+        (Color::Black, Role::Rook) => "♜",
+        (Color::Black, Role::Bishop) => "♝",
+        (Color::Black, Role::Knight) => "♞",
+        (Color::Black, Role::Pawn) => "♟\u{FE0E}",
+    }
+}
+
+//Name of the custom emoji a guild has to upload for the piece, like chess_wk for the white king
+//and chess_bp for a black pawn
+pub fn piece_emoji_name(piece: Piece) -> String {
+    format!("chess_{}{}", piece.color.char(), piece.role.char())
+}
+
+//How boards are shown in a guild, set with !config board
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardStyle {
+    //A picture of the board
+    Image,
+    //Unicode chess symbols in a code block, for guilds where uploading images is slow or not
+    //allowed
+    Text,
+    //The guild's own piece emojis, see piece_emoji_name
+    Emoji,
+}
+
+pub const BOARD_STYLES: [BoardStyle; 3] = [BoardStyle::Image, BoardStyle::Text, BoardStyle::Emoji];
+
+impl Default for BoardStyle {
+    fn default() -> Self {
+        BoardStyle::Image
+    }
+}
+
+impl BoardStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            BoardStyle::Image => "image",
+            BoardStyle::Text => "text",
+            BoardStyle::Emoji => "emoji",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<BoardStyle> {
+        BOARD_STYLES
+            .iter()
+            .copied()
+            .find(|style| style.name().eq_ignore_ascii_case(input))
+    }
+}
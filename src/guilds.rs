@@ -0,0 +1,102 @@
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::RwLock;
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+
+use crate::library::Database;
+
+//The guild whose library lives in the original library-db.bin and who the channels set in the
+//environment belong to. Commands sent in DMs also use its library
+pub fn home_guild() -> Option<GuildId> {
+    env::var("HOME_GUILD_ID")
+        .ok()
+        .and_then(|id| id.parse::<u64>().ok())
+        .map(GuildId)
+}
+
+//Every guild gets its own library so that running the bot in two servers doesn't mix them.
+//Libraries are keyed by guild, with None standing for the home library
+pub struct Libraries {
+    home: Option<GuildId>,
+    loaded: RwLock<HashMap<Option<GuildId>, Arc<RwLock<Database>>>>,
+}
+
+impl Libraries {
+    pub fn new(home_library: Database) -> Libraries {
+        let mut loaded = HashMap::new();
+        loaded.insert(None, Arc::new(RwLock::new(home_library)));
+        Libraries {
+            home: home_guild(),
+            loaded: RwLock::new(loaded),
+        }
+    }
+
+    pub fn key(&self, guild: Option<GuildId>) -> Option<GuildId> {
+        match guild {
+            Some(guild) if Some(guild) != self.home => Some(guild),
+            _ => None,
+        }
+    }
+
+    //Loads the guild's library from disk the first time it is asked for
+    pub async fn get(&self, guild: Option<GuildId>) -> Arc<RwLock<Database>> {
+        let key = self.key(guild);
+        if let Some(library) = self.loaded.read().await.get(&key) {
+            return library.clone();
+        }
+
+        let mut loaded = self.loaded.write().await;
+        //Someone else might have loaded it while we were waiting for the write lock
+        if let Some(library) = loaded.get(&key) {
+            return library.clone();
+        }
+        let guild_id = key.map(|guild| guild.0);
+        let library = match Database::load(guild_id).await {
+            Some(library) => library,
+            None => {
+                println!("Starting a new library for guild {:?}", guild_id);
+                let mut library = Database::new();
+                library.guild = guild_id;
+                library
+            }
+        };
+        let library = Arc::new(RwLock::new(library));
+        loaded.insert(key, library.clone());
+        library
+    }
+
+    //The guild whose library commands sent to the bot in DMs by `user` should use. That is the one
+    //they are registered in, preferring the home library if they are registered in several.
+    //Members that aren't registered anywhere get the home library, shown as None
+    pub async fn guild_for_dm(&self, user: UserId) -> Option<GuildId> {
+        let discord_id = user.to_string();
+        let mut registered = Vec::new();
+        for (key, library) in self.all().await {
+            if library
+                .read()
+                .await
+                .find_user_by_discord_id(&discord_id)
+                .is_some()
+            {
+                match key {
+                    None => return None,
+                    Some(guild) => registered.push(guild),
+                }
+            }
+        }
+        //Loaded libraries aren't kept in any order, so pick the same guild every time
+        registered.into_iter().min()
+    }
+
+    //Every library loaded so far, for background tasks that need to go over all of them
+    pub async fn all(&self) -> Vec<(Option<GuildId>, Arc<RwLock<Database>>)> {
+        self.loaded
+            .read()
+            .await
+            .iter()
+            .map(|(key, library)| (*key, library.clone()))
+            .collect()
+    }
+}
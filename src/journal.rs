@@ -0,0 +1,250 @@
+use data_encoding::BASE64;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use std::collections::HashMap;
+
+use crate::library::{Database, TimeType};
+use crate::storage::StorageError;
+
+//Every change to the library is appended to a journal next to the saved file as soon as it is
+//made. On startup the journal is replayed on top of the last save, and every full save starts a
+//new journal. That way a crash loses nothing without having to write the whole library after
+//every command.
+//
+//Entries are diffs of the library's json: maps (books, users...) record the records that were
+//added, changed or removed, lists record what was appended to them and anything else is replaced
+//outright
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Change {
+    Set {
+        field: String,
+        key: String,
+        value: Value,
+    },
+    Remove {
+        field: String,
+        key: String,
+    },
+    Append {
+        field: String,
+        values: Vec<Value>,
+    },
+    Replace {
+        field: String,
+        value: Value,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    time: TimeType,
+    changes: Vec<Change>,
+}
+
+//The library as of the last journal entry for each journal file. New entries are diffed against
+//it. Holding the lock while writing also keeps entries in order
+static JOURNALED: Lazy<Mutex<HashMap<String, Value>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn journal_name(file_name: &str) -> String {
+    format!("{}.journal", file_name)
+}
+
+fn diff(old: Option<&Value>, new: &Value) -> Vec<Change> {
+    let (old, new) = match (old, new) {
+        (Some(Value::Object(old)), Value::Object(new)) => (old, new),
+        //Nothing to diff against, so record every field
+        (_, Value::Object(new)) => {
+            return new
+                .iter()
+                .map(|(field, value)| Change::Replace {
+                    field: field.clone(),
+                    value: value.clone(),
+                })
+                .collect()
+        }
+        _ => return Vec::new(),
+    };
+
+    let mut changes = Vec::new();
+    for (field, new_value) in new {
+        let old_value = match old.get(field) {
+            Some(old_value) if old_value == new_value => continue,
+            old_value => old_value,
+        };
+        match (old_value, new_value) {
+            (Some(Value::Object(old_map)), Value::Object(new_map)) => {
+                for (key, value) in new_map {
+                    if old_map.get(key) != Some(value) {
+                        changes.push(Change::Set {
+                            field: field.clone(),
+                            key: key.clone(),
+                            value: value.clone(),
+                        });
+                    }
+                }
+                for key in old_map.keys() {
+                    if !new_map.contains_key(key) {
+                        changes.push(Change::Remove {
+                            field: field.clone(),
+                            key: key.clone(),
+                        });
+                    }
+                }
+            }
+            (Some(Value::Array(old_list)), Value::Array(new_list))
+                if new_list.starts_with(old_list) =>
+            {
+                changes.push(Change::Append {
+                    field: field.clone(),
+                    values: new_list[old_list.len()..].to_vec(),
+                });
+            }
+            _ => changes.push(Change::Replace {
+                field: field.clone(),
+                value: new_value.clone(),
+            }),
+        }
+    }
+    changes
+}
+
+fn apply(state: &mut Value, changes: Vec<Change>) {
+    let state = match state.as_object_mut() {
+        Some(state) => state,
+        None => return,
+    };
+    for change in changes {
+        match change {
+            Change::Set { field, key, value } => {
+                if let Some(map) = state
+                    .entry(field)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                    .as_object_mut()
+                {
+                    map.insert(key, value);
+                }
+            }
+            Change::Remove { field, key } => {
+                if let Some(map) = state.get_mut(&field).and_then(Value::as_object_mut) {
+                    map.remove(&key);
+                }
+            }
+            Change::Append { field, values } => {
+                if let Some(list) = state
+                    .entry(field)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                    .as_array_mut()
+                {
+                    list.extend(values);
+                }
+            }
+            Change::Replace { field, value } => {
+                state.insert(field, value);
+            }
+        }
+    }
+}
+
+//Appends whatever changed since the last entry to the journal of the library saved in `file_name`.
+//Returns false if nothing had changed
+pub async fn record(file_name: &str, db: &Database) -> Result<bool, StorageError> {
+    let state = serde_json::to_value(db)?;
+    let mut journaled = JOURNALED.lock().await;
+
+    let changes = diff(journaled.get(file_name), &state);
+    if changes.is_empty() {
+        return Ok(false);
+    }
+
+    let mut line = serde_json::to_vec(&Entry {
+        time: chrono::Local::now(),
+        changes,
+    })?;
+    //Encrypted entries are base64 so that they still fit on one line
+    if crate::crypto::enabled() {
+        line = BASE64.encode(&crate::crypto::encrypt(line)?).into_bytes();
+    }
+    line.push(b'\n');
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_name(file_name))
+        .await?;
+    file.write_all(&line).await?;
+    file.sync_data().await?;
+
+    journaled.insert(file_name.to_owned(), state);
+    Ok(true)
+}
+
+fn parse_entry(line: &[u8]) -> Result<Entry, StorageError> {
+    if line.starts_with(b"{") {
+        return Ok(serde_json::from_slice(line)?);
+    }
+    let data = crate::crypto::decrypt(BASE64.decode(line)?)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+//Applies the journal of the library saved in `file_name` on top of `db`, which should be what was
+//loaded from that file. Returns the library and how many entries were replayed
+pub async fn replay(file_name: &str, db: Database) -> Result<(Database, usize), StorageError> {
+    let mut state = serde_json::to_value(&db)?;
+    let guild = db.guild;
+
+    let data = match tokio::fs::read(journal_name(file_name)).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            JOURNALED.lock().await.insert(file_name.to_owned(), state);
+            return Ok((db, 0));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut replayed = 0;
+    //Length of the part of the journal that replayed cleanly
+    let mut good_len = 0;
+    for line in data.split(|byte| *byte == b'\n') {
+        if line.is_empty() {
+            good_len += 1;
+            continue;
+        }
+        match parse_entry(line) {
+            Ok(entry) => {
+                apply(&mut state, entry.changes);
+                replayed += 1;
+                good_len += line.len() + 1;
+            }
+            //A crash in the middle of writing an entry leaves half a line at the end
+            Err(err) => {
+                println!(
+                    "Stopping journal replay of {} at a damaged entry: {}",
+                    file_name, err
+                );
+                //Cut the damaged part off, otherwise new entries would be appended after it and
+                //never replayed
+                tokio::fs::write(journal_name(file_name), &data[..good_len]).await?;
+                break;
+            }
+        }
+    }
+
+    let mut db: Database = serde_json::from_value(state.clone())?;
+    db.guild = guild;
+    JOURNALED.lock().await.insert(file_name.to_owned(), state);
+    Ok((db, replayed))
+}
+
+//Starts a new journal once `db` has been fully saved to `file_name`
+pub async fn reset(file_name: &str, db: &Database) -> Result<(), StorageError> {
+    let state = serde_json::to_value(db)?;
+    let mut journaled = JOURNALED.lock().await;
+    tokio::fs::write(journal_name(file_name), b"").await?;
+    journaled.insert(file_name.to_owned(), state);
+    Ok(())
+}
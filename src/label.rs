@@ -0,0 +1,103 @@
+use image::{DynamicImage, GenericImage, ImageBuffer, ImageOutputFormat, Luma};
+use qrcode::QrCode;
+
+const QR_SIZE: u32 = 256;
+//Each glyph pixel is drawn as a square this many pixels wide
+const TEXT_SCALE: u32 = 4;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const MARGIN: u32 = 16;
+
+//5x7 bitmaps for the base32 alphabet used by book ids, and the other digits that boards and rating
+//graphs are labelled with. Each row is the low 5 bits of a byte with the left most pixel in the high bit
+pub fn glyph(c: char) -> Option<[u8; 7]> {
+    let rows = match c {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+//Renders a printable sticker for a book: a QR code of its id with the id written underneath so
+//it can still be typed in by hand
+pub fn render_label(id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let qr = QrCode::new(id.as_bytes())?
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build();
+
+    let char_advance = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+    let text_width = id.chars().count() as u32 * char_advance;
+    let text_height = GLYPH_HEIGHT * TEXT_SCALE;
+
+    let width = std::cmp::max(qr.width(), text_width + 2 * MARGIN);
+    let height = qr.height() + text_height + MARGIN;
+    let mut label = ImageBuffer::from_pixel(width, height, Luma([255u8]));
+
+    label.copy_from(&qr, (width - qr.width()) / 2, 0)?;
+
+    let text_x = (width - text_width) / 2;
+    let text_y = qr.height();
+    for (i, c) in id.chars().enumerate() {
+        let rows = match glyph(c.to_ascii_uppercase()) {
+            Some(rows) => rows,
+            None => continue,
+        };
+        let glyph_x = text_x + i as u32 * char_advance;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..TEXT_SCALE {
+                    for dx in 0..TEXT_SCALE {
+                        label.put_pixel(
+                            glyph_x + col * TEXT_SCALE + dx,
+                            text_y + row as u32 * TEXT_SCALE + dy,
+                            Luma([0u8]),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(label).write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png)
+}
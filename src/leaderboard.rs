@@ -0,0 +1,276 @@
+use serenity::{
+    builder::CreateComponents,
+    model::{
+        channel::Message,
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use std::time::Duration;
+
+use crate::library::Database;
+
+//Leaderboards list the members who are best at something, ten at a time. The buttons under one
+//turn the page, with custom ids of the form leaderboard:<board>:<page>. Everything needed is in the
+//id, so pages are worked out again from the library when a button is pressed and nothing has to be
+//kept around
+
+const PAGE_SIZE: usize = 10;
+//The buttons are taken off by flows.rs after this long
+const PAGE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Board {
+    //Club ratings from games between members
+    Rating,
+    Puzzles,
+    //Current daily puzzle streaks
+    Streaks,
+    //Books returned to the library
+    BooksRead,
+    //Lichess ratings of members who have linked their accounts, from the last sync
+    LichessBlitz,
+    LichessRapid,
+    LichessClassical,
+}
+
+pub const BOARDS: [Board; 7] = [
+    Board::Rating,
+    Board::Puzzles,
+    Board::Streaks,
+    Board::BooksRead,
+    Board::LichessBlitz,
+    Board::LichessRapid,
+    Board::LichessClassical,
+];
+
+impl Board {
+    pub fn name(self) -> &'static str {
+        match self {
+            Board::Rating => "rating",
+            Board::Puzzles => "puzzles",
+            Board::Streaks => "streaks",
+            Board::BooksRead => "books-read",
+            Board::LichessBlitz => "lichess-blitz",
+            Board::LichessRapid => "lichess-rapid",
+            Board::LichessClassical => "lichess-classical",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Board> {
+        BOARDS
+            .iter()
+            .copied()
+            .find(|board| board.name().eq_ignore_ascii_case(input))
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Board::Rating => "Club ratings",
+            Board::Puzzles => "Puzzle ratings",
+            Board::Streaks => "Daily puzzle streaks",
+            Board::BooksRead => "Books read",
+            Board::LichessBlitz => "Lichess blitz ratings",
+            Board::LichessRapid => "Lichess rapid ratings",
+            Board::LichessClassical => "Lichess classical ratings",
+        }
+    }
+
+    //Every member on the board, best first, by discord id with what they are ranked by
+    fn entries(self, library: &Database) -> Vec<(String, String)> {
+        let mut ranked: Vec<(f64, String, String)> = match self {
+            Board::Rating => {
+                let system = library.config.rating_system;
+                library
+                    .club_ratings
+                    .iter()
+                    .map(|(member, rating)| {
+                        (rating.rating, member.clone(), rating.describe(system))
+                    })
+                    .collect()
+            }
+            Board::Puzzles => library
+                .puzzle_ratings
+                .iter()
+                .map(|(member, rating)| {
+                    let provisional = if rating.is_provisional() { "?" } else { "" };
+                    (
+                        rating.rating as f64,
+                        member.clone(),
+                        format!(
+                            "{}{} ({} solved)",
+                            rating.rating, provisional, rating.solved
+                        ),
+                    )
+                })
+                .collect(),
+            Board::Streaks => {
+                let today = chrono::Local::now().date_naive();
+                library
+                    .puzzle_streaks
+                    .iter()
+                    .map(|(member, streak)| (streak.current(today), member, streak.best))
+                    .filter(|(current, _, _)| *current > 0)
+                    .map(|(current, member, best)| {
+                        let days = if current == 1 { "day" } else { "days" };
+                        (
+                            current as f64,
+                            member.clone(),
+                            format!("{} {} (best {})", current, days, best),
+                        )
+                    })
+                    .collect()
+            }
+            Board::BooksRead => library
+                .users
+                .values()
+                .map(|user| (library.books_read(user.uuid), user))
+                .filter(|(read, _)| *read > 0)
+                .map(|(read, user)| {
+                    let books = if read == 1 { "book" } else { "books" };
+                    (
+                        read as f64,
+                        user.discord_id.clone(),
+                        format!("{} {}", read, books),
+                    )
+                })
+                .collect(),
+            Board::LichessBlitz | Board::LichessRapid | Board::LichessClassical => library
+                .lichess_ratings
+                .iter()
+                .filter_map(|(member, snapshots)| {
+                    let latest = snapshots.last()?;
+                    let rating = match self {
+                        Board::LichessBlitz => latest.blitz,
+                        Board::LichessRapid => latest.rapid,
+                        _ => latest.classical,
+                    }?;
+                    Some((rating.rating as f64, member.clone(), rating.to_string()))
+                })
+                .collect(),
+        };
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+            .into_iter()
+            .map(|(_, member, value)| (member, value))
+            .collect()
+    }
+}
+
+//The text of `page` of `board`, counting from 0, and how many pages there are
+fn page_text(library: &Database, board: Board, page: usize) -> (String, usize) {
+    let entries = board.entries(library);
+    let pages = ((entries.len() + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    let mut text = format!("**{}**", board.title());
+    if entries.is_empty() {
+        text.push_str("\nNobody is on this leaderboard yet");
+    }
+    for (i, (member, value)) in entries
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+    {
+        let place = match MEDALS.get(i) {
+            Some(medal) => medal.to_string(),
+            None => format!("{}.", i + 1),
+        };
+        text.push_str(&format!("\n{} <@{}> {}", place, member, value));
+    }
+    if pages > 1 {
+        text.push_str(&format!("\nPage {} of {}", page + 1, pages));
+    }
+    (text, pages)
+}
+
+fn components(
+    board: Board,
+    page: usize,
+    pages: usize,
+    components: &mut CreateComponents,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        for (target, label, disabled) in [
+            (page.saturating_sub(1), "◀", page == 0),
+            (page + 1, "▶", page + 1 >= pages),
+        ] {
+            row.create_button(|b| {
+                b.style(ButtonStyle::Secondary)
+                    .label(label)
+                    .custom_id(format!("leaderboard:{}:{}", board.name(), target))
+                    .disabled(disabled)
+            });
+        }
+        row
+    })
+}
+
+//Posts the first page of `board` in reply to `msg`, with buttons for the other pages if there are
+//any
+pub async fn send(ctx: &Context, msg: &Message, board: Board) -> serenity::Result<()> {
+    let (text, pages) = {
+        let library_arc = crate::library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        page_text(&library, board, 0)
+    };
+    let message = msg
+        .channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .content(text)
+                //Nobody should be pinged for being on a leaderboard
+                .allowed_mentions(|a| a.empty_parse());
+            if pages > 1 {
+                m.components(|c| components(board, 0, pages, c));
+            }
+            m
+        })
+        .await?;
+    if pages > 1 {
+        crate::flows::expire_components(&message, PAGE_TIMEOUT);
+    }
+    Ok(())
+}
+
+//Called when someone presses one of the buttons under a leaderboard
+pub async fn handle_page(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let (board, page) = match id.split_once(':') {
+        Some((board, page)) => match (Board::parse(board), page.parse::<usize>()) {
+            (Some(board), Ok(page)) => (board, page),
+            _ => return,
+        },
+        None => return,
+    };
+    let (text, pages) = {
+        let library_arc = crate::library_for(ctx, component.guild_id).await;
+        let library = library_arc.read().await;
+        page_text(&library, board, page)
+    };
+    let page = page.min(pages - 1);
+
+    let mut buttons = CreateComponents::default();
+    components(board, page, pages, &mut buttons);
+    let response = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .allowed_mentions(|a| a.empty_parse())
+                        .components(|c| {
+                            *c = buttons;
+                            c
+                        })
+                })
+        })
+        .await;
+    if let Err(err) = response {
+        println!("Failed to turn the leaderboard page: {:?}", err);
+    }
+}
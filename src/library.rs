@@ -2,15 +2,41 @@ use indexmap::IndexMap;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+use crate::archive::{ArchiveUuid, ArchivedGame, GameSource};
+use crate::game_of_the_week::GameOfTheWeek;
+use crate::games::{BoardStyle, Game, GameStatus, GameUuid};
+use crate::lichess::LichessRatings;
+use crate::lichess_live::LiveWatch;
+use crate::lichess_relay::LichessRelay;
+use crate::lichess_team::LichessTeam;
+use crate::otb::{OtbGame, OtbStatus, OtbUuid};
+use crate::permissions::Tier;
+use crate::puzzles::{PuzzleRating, PuzzleStreak};
+use crate::ratings::{ClubRating, RatingChange, RatingSnapshot, RatingSystem};
+use crate::simul::Simul;
+use crate::tournaments::{Outcome, Tournament, TournamentUuid};
+use crate::vote_chess::VoteGame;
+
 #[path = "utils.rs"]
 mod utils;
 
 pub type UserUuid = u32;
 pub type BookUuid = u32;
 pub type CheckoutUuid = u32;
+pub type WishUuid = u32;
+pub type ExtensionUuid = u32;
+pub type CopyUuid = u32;
+pub type AnnouncementUuid = u32;
 
 pub type TimeType = chrono::DateTime<chrono::offset::Local>;
 
+//How long a book can be checked out for when it doesn't have its own loan period
+pub const DEFAULT_LOAN_DAYS: u32 = 7;
+//Longest loan period a book can be given
+pub const MAX_LOAN_DAYS: u32 = 365;
+//Most days a rentee can ask for at once in an extension request
+pub const MAX_EXTENSION_DAYS: u32 = 90;
+
 //The following types all have uuids that can be passed around as "referencnes" because they
 //uniquely identify an object
 #[derive(Serialize, Deserialize, Debug, new)]
@@ -19,6 +45,39 @@ pub struct Book {
     pub name: String,
     pub author: String,
     pub quantity: u32,
+    //Overrides DEFAULT_LOAN_DAYS for this book. Reference books might only go out for a few days
+    //while novels can go out for weeks
+    #[new(default)]
+    pub loan_days: Option<u32>,
+    #[new(value = "chrono::Local::now()")]
+    pub added: TimeType,
+    //Multi volume works share a series name and are grouped together in listings
+    #[new(default)]
+    pub series: Option<String>,
+    #[new(default)]
+    pub volume: Option<u32>,
+    //Individually tracked copies. Not every copy has to be listed here, only the ones that are
+    //different enough (eg. the annotated 2nd edition) that members might want to reserve them
+    #[new(default)]
+    pub copies: Vec<BookCopy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct BookCopy {
+    pub uuid: CopyUuid,
+    pub edition: String,
+}
+
+impl Book {
+    pub fn loan_days(&self) -> u32 {
+        self.loan_days.unwrap_or(DEFAULT_LOAN_DAYS)
+    }
+
+    //Computes when a copy of this book is due back if the rentee got it at `start`. None if that
+    //is too far off to represent
+    pub fn due_date_from(&self, start: TimeType) -> Option<TimeType> {
+        start.checked_add_signed(chrono::Duration::days(self.loan_days() as i64))
+    }
 }
 
 //Represents the 4 stages of a handout
@@ -33,7 +92,7 @@ pub struct Book {
 //react to a corrorsponding message from the bot to sign off that the book was returned.
 //At this point the checkout is complete (Done phase) and the book is ready to be checked out
 //again.
-#[derive(Serialize, Deserialize, Debug, new)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, new)]
 pub enum CheckoutStatus {
     PreTransact,
     Reading,
@@ -41,10 +100,24 @@ pub enum CheckoutStatus {
     DONE,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl std::fmt::Display for CheckoutStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            CheckoutStatus::PreTransact => write!(fmt, "waiting for an officer to hand it out"),
+            CheckoutStatus::Reading => write!(fmt, "reading"),
+            CheckoutStatus::ReturnVerifyNeeded => {
+                write!(fmt, "returned, waiting for an officer to confirm")
+            }
+            CheckoutStatus::DONE => write!(fmt, "done"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, new)]
 pub struct OfficerApproval {
-    user: UserUuid,
-    time: TimeType,
+    //Discord id of the officer. Officers don't need to have registered with the library
+    pub discord_id: String,
+    pub time: TimeType,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -56,6 +129,55 @@ pub struct CheckoutInstance {
     pub due_date: Option<TimeType>,
     pub checkout_approval: Option<OfficerApproval>,
     pub checkin_approval: Option<OfficerApproval>,
+    //How many steps of the escalation policy have already been carried out for this checkout
+    pub escalation_level: usize,
+    //The bot message officers react to in order to approve the handout. Books checked out
+    //together share one message
+    pub approval_message: Option<MessageRef>,
+    //The bot message officers react to in order to confirm the book was returned
+    pub return_message: Option<MessageRef>,
+    //Set when the rentee reserved a specific copy rather than whichever one is free
+    pub copy: Option<CopyUuid>,
+}
+
+//Points at a discord message so that it can be linked to later
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, new)]
+pub struct MessageRef {
+    pub channel: u64,
+    pub message: u64,
+}
+
+impl MessageRef {
+    pub fn link(&self, guild: Option<u64>) -> String {
+        match guild {
+            Some(guild) => format!(
+                "https://discord.com/channels/{}/{}/{}",
+                guild, self.channel, self.message
+            ),
+            None => format!(
+                "https://discord.com/channels/@me/{}/{}",
+                self.channel, self.message
+            ),
+        }
+    }
+}
+
+impl CheckoutInstance {
+    pub fn new(uuid: CheckoutUuid, rentee: UserUuid, book: BookUuid) -> CheckoutInstance {
+        CheckoutInstance {
+            uuid,
+            rentee,
+            book,
+            status: CheckoutStatus::PreTransact,
+            due_date: None,
+            checkout_approval: None,
+            checkin_approval: None,
+            escalation_level: 0,
+            approval_message: None,
+            return_message: None,
+            copy: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, new)]
@@ -63,13 +185,380 @@ pub struct User {
     pub discord_id: String,
     pub read_name: String,
     pub uuid: UserUuid,
+    //Set by the overdue escalation policy. Suspended users cannot start new checkouts
+    #[new(default)]
+    pub suspended: bool,
+    #[new(value = "chrono::Local::now()")]
+    pub registered: TimeType,
+    //The member's Lichess username, once they have proved the account is theirs with !lichess link
+    #[new(default)]
+    #[serde(default)]
+    pub lichess: Option<String>,
+    //The member's chess.com username, set with !chesscom link. chess.com has no way of checking
+    //that the account is theirs, so it is only used to look up their stats
+    #[new(default)]
+    #[serde(default)]
+    pub chesscom: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+//A member asking for more time with a book. Officers approve or deny it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExtensionRequest {
+    pub uuid: ExtensionUuid,
+    pub checkout: CheckoutUuid,
+    pub days: u32,
+    pub reason: String,
+    pub requested: TimeType,
+    pub status: ExtensionStatus,
+    pub decision: Option<OfficerApproval>,
+}
+
+//One line in the audit trail. Records who did what to the library and when
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct AuditEntry {
+    #[new(value = "chrono::Local::now()")]
+    pub time: TimeType,
+    //Discord id of whoever caused the change
+    pub actor: String,
+    pub description: String,
+}
+
+//A book a member suggested the club should buy
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct WishlistEntry {
+    pub uuid: WishUuid,
+    pub name: String,
+    pub author: String,
+    //Discord id of the member who made the suggestion
+    pub suggested_by: String,
+    //Discord ids of everyone who upvoted the suggestion, including the person who suggested it
+    pub votes: Vec<String>,
+    #[new(value = "chrono::Local::now()")]
+    pub added: TimeType,
+}
+
+//A message posted on a schedule, set up with !announce schedule
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct Announcement {
+    pub uuid: AnnouncementUuid,
+    pub channel: u64,
+    pub message: String,
+    pub schedule: WeeklySchedule,
+    //Discord id of who scheduled it
+    pub created_by: String,
+    #[new(default)]
+    pub last_posted: Option<TimeType>,
+}
+
+//What the reminder task does once a checkout has been overdue for long enough
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscalationAction {
+    DirectMessage,
+    OfficerChannel,
+    SuspendBorrowing,
+}
+
+impl EscalationAction {
+    pub fn parse(input: &str) -> Option<EscalationAction> {
+        match input.to_ascii_lowercase().as_str() {
+            "dm" => Some(EscalationAction::DirectMessage),
+            "officers" => Some(EscalationAction::OfficerChannel),
+            "suspend" => Some(EscalationAction::SuspendBorrowing),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EscalationAction {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            EscalationAction::DirectMessage => write!(fmt, "DM the rentee"),
+            EscalationAction::OfficerChannel => write!(fmt, "post in the officers channel"),
+            EscalationAction::SuspendBorrowing => write!(fmt, "suspend borrowing"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, new)]
+pub struct EscalationStep {
+    pub days_overdue: u32,
+    pub action: EscalationAction,
+}
+
+pub fn default_escalation_policy() -> Vec<EscalationStep> {
+    vec![
+        EscalationStep::new(1, EscalationAction::DirectMessage),
+        EscalationStep::new(3, EscalationAction::OfficerChannel),
+        EscalationStep::new(14, EscalationAction::SuspendBorrowing),
+    ]
+}
+
+//An escalation step that is due to be carried out for an overdue checkout
+#[derive(Debug)]
+pub struct PendingEscalation {
+    pub checkout: CheckoutUuid,
+    pub rentee: UserUuid,
+    pub book: BookUuid,
+    pub days_overdue: i64,
+    pub action: EscalationAction,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Database {
     pub books: IndexMap<BookUuid, Book>,
+    //Checkouts that haven't been completed yet. Once a checkout is DONE it is moved to
+    //archived_checkouts by set_checkout_status
     pub checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+    //Completed checkouts, in the order they were completed. Only needed for history and stats
+    #[serde(default)]
+    pub archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
     pub users: IndexMap<UserUuid, User>,
+    //Books members would like the club to buy
+    pub wishlist: IndexMap<WishUuid, WishlistEntry>,
+    pub extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+    pub audit_log: Vec<AuditEntry>,
+    //Sorted by days_overdue
+    pub escalation_policy: Vec<EscalationStep>,
+    //When set, commands that modify the library are refused
+    pub maintenance: bool,
+    pub digest_schedule: WeeklySchedule,
+    pub last_digest: Option<TimeType>,
+    #[serde(default)]
+    pub config: GuildConfig,
+    #[serde(default)]
+    pub announcements: IndexMap<AnnouncementUuid, Announcement>,
+    //Chess games between members, including finished ones
+    #[serde(default)]
+    pub games: IndexMap<GameUuid, Game>,
+    //Members' puzzle ratings, by discord id. Members show up once they have tried a puzzle
+    #[serde(default)]
+    pub puzzle_ratings: IndexMap<String, PuzzleRating>,
+    //Members' club ratings from rated games, by discord id
+    #[serde(default)]
+    pub club_ratings: IndexMap<String, ClubRating>,
+    //Tournaments, including finished ones
+    #[serde(default)]
+    pub tournaments: IndexMap<TournamentUuid, Tournament>,
+    //Snapshots of linked members' Lichess ratings, oldest first, by discord id
+    #[serde(default)]
+    pub lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+    //Results of games played over the board, including ones that weren't confirmed
+    #[serde(default)]
+    pub otb_games: IndexMap<OtbUuid, OtbGame>,
+    //Every finished game between members, in the order they were archived
+    #[serde(default)]
+    pub archive: IndexMap<ArchiveUuid, ArchivedGame>,
+    //The server's game against the engine, or the last one played until another is started
+    #[serde(default)]
+    pub vote_game: Option<VoteGame>,
+    //The simul being played, or the last one played until another is hosted
+    #[serde(default)]
+    pub simul: Option<Simul>,
+    //This week's nominations and votes, and past winners
+    #[serde(default)]
+    pub game_of_the_week: GameOfTheWeek,
+    //Members' daily puzzle streaks, by discord id. Members show up once they have tried one
+    #[serde(default)]
+    pub puzzle_streaks: IndexMap<String, PuzzleStreak>,
+    //Members' club ratings after each of their rated games, oldest first, by discord id. Games
+    //rated before this was kept aren't in it
+    #[serde(default)]
+    pub rating_history: IndexMap<String, Vec<RatingSnapshot>>,
+    //The club's Lichess team, set with !lichess team, whose linked members are given a role
+    #[serde(default)]
+    pub lichess_team: Option<LichessTeam>,
+    //Lichess tournaments whose standings are being relayed, started with !lichess relay
+    #[serde(default)]
+    pub lichess_relays: Vec<LichessRelay>,
+    //Members who turned on posts about their Lichess games with !lichess live, by discord id
+    #[serde(default)]
+    pub lichess_live: IndexMap<String, LiveWatch>,
+    //Normalized author name -> the books they wrote. Rebuilt on load and kept up to date by
+    //add_book and remove_book
+    #[serde(skip)]
+    author_index: IndexMap<String, Vec<BookUuid>>,
+    //Normalized titles, sorted so that titles starting with what is typed can be found quickly.
+    //Rebuilt on load and kept up to date by add_book and remove_book
+    #[serde(skip)]
+    title_index: Vec<(String, BookUuid)>,
+    //Book -> checkouts of it that are not DONE yet. Rebuilt on load and kept up to date by
+    //add_checkout and set_checkout_status
+    #[serde(skip)]
+    active_checkouts: IndexMap<BookUuid, Vec<CheckoutUuid>>,
+    //The guild this library belongs to. None for the home library. Decides which file it is saved
+    //in, so it isn't saved itself
+    #[serde(skip)]
+    pub guild: Option<u64>,
+    //How much of audit_log has been posted to the audit channel. Starts out at the end of the log
+    //when loading, so that entries from before a restart aren't posted again
+    #[serde(skip)]
+    audit_mirrored: usize,
+}
+
+fn normalize_author(author: &str) -> String {
+    author
+        .split_whitespace()
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//Settings a guild's admins can change with !config
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GuildConfig {
+    //Used instead of DEFAULT_PREFIX when set
+    pub prefix: Option<String>,
+    //Tiers granted with !config grant, by role id and by user id. See permissions::tier_of
+    #[serde(default)]
+    pub role_tiers: IndexMap<u64, Tier>,
+    #[serde(default)]
+    pub member_tiers: IndexMap<u64, Tier>,
+    //Set with !config channel. Kinds that aren't set fall back, see Database::channel
+    #[serde(default)]
+    pub channels: IndexMap<ChannelKind, u64>,
+    //DMed to new members, with welcome::DEFAULT_WELCOME used when it isn't set
+    #[serde(default)]
+    pub welcome_message: Option<String>,
+    #[serde(default)]
+    pub welcome_disabled: bool,
+    #[serde(default)]
+    pub board_style: BoardStyle,
+    //Set with !config k-factor. ratings::DEFAULT_K_FACTOR is used when it isn't set
+    #[serde(default)]
+    pub k_factor: Option<u32>,
+    //Set with !config rating-system
+    #[serde(default)]
+    pub rating_system: RatingSystem,
+}
+
+//The kinds of messages the bot posts on its own, each of which can go to its own channel
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    //Approval requests and other messages for officers
+    LibraryLog,
+    //Reports about overdue books
+    Overdue,
+    //The weekly digest
+    Digest,
+    //A live feed of the audit log, for staff
+    Audit,
+    //Errors commands ran into, for admins
+    Errors,
+    //Live boards of the games players broadcast, for spectators
+    Broadcast,
+    //The game of the week
+    GameOfTheWeek,
+    //Links to watch the Lichess games of members who turned on !lichess live
+    LichessLive,
+}
+
+pub const CHANNEL_KINDS: [ChannelKind; 8] = [
+    ChannelKind::LibraryLog,
+    ChannelKind::Overdue,
+    ChannelKind::Digest,
+    ChannelKind::Audit,
+    ChannelKind::Errors,
+    ChannelKind::Broadcast,
+    ChannelKind::GameOfTheWeek,
+    ChannelKind::LichessLive,
+];
+
+impl ChannelKind {
+    //What the kind is called in !config channel
+    pub fn name(self) -> &'static str {
+        match self {
+            ChannelKind::LibraryLog => "library-log",
+            ChannelKind::Overdue => "overdue",
+            ChannelKind::Digest => "digest",
+            ChannelKind::Audit => "audit",
+            ChannelKind::Errors => "errors",
+            ChannelKind::Broadcast => "broadcast",
+            ChannelKind::GameOfTheWeek => "game-of-the-week",
+            ChannelKind::LichessLive => "lichess-live",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<ChannelKind> {
+        CHANNEL_KINDS
+            .iter()
+            .copied()
+            .find(|kind| kind.name().eq_ignore_ascii_case(input))
+    }
+
+    //Where the home library's channel was set before it could be configured. Kinds added since
+    //then have to be set with !config channel
+    fn env_var(self) -> Option<&'static str> {
+        match self {
+            ChannelKind::LibraryLog | ChannelKind::Overdue => Some("OFFICERS_CHANNEL_ID"),
+            ChannelKind::Digest => Some("LIBRARY_CHANNEL_ID"),
+            ChannelKind::Audit
+            | ChannelKind::Errors
+            | ChannelKind::Broadcast
+            | ChannelKind::GameOfTheWeek
+            | ChannelKind::LichessLive => None,
+        }
+    }
+}
+
+pub const DEFAULT_PREFIX: &str = "!";
+pub const DEFAULT_OFFICER_ROLE: &str = "Minor Pieces";
+
+impl GuildConfig {
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(DEFAULT_PREFIX)
+    }
+}
+
+//A time every week, like when the weekly digest is posted
+#[derive(Serialize, Deserialize, Debug, Clone, new)]
+pub struct WeeklySchedule {
+    pub weekday: chrono::Weekday,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl WeeklySchedule {
+    //Reads a day like "fri" or "friday" and a time like 18:00. Errors are meant for the user
+    pub fn parse(day: &str, time: &str) -> Result<WeeklySchedule, &'static str> {
+        use chrono::Timelike;
+
+        let weekday: chrono::Weekday = day.parse().map_err(|_| "Unknown day of the week")?;
+        let time = chrono::NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| "Expected a time like 18:00")?;
+        Ok(WeeklySchedule::new(weekday, time.hour(), time.minute()))
+    }
+
+    //The most recent time at or before `now` that something on this schedule was due
+    pub fn last_slot(&self, now: TimeType) -> Option<TimeType> {
+        use chrono::Datelike;
+
+        let days_back =
+            (now.weekday().num_days_from_monday() + 7 - self.weekday.num_days_from_monday()) % 7;
+        let date = now.date() - chrono::Duration::days(days_back as i64);
+        let slot = date.and_hms_opt(self.hour, self.minute, 0)?;
+        if slot > now {
+            Some(slot - chrono::Duration::days(7))
+        } else {
+            Some(slot)
+        }
+    }
+}
+
+impl std::fmt::Display for WeeklySchedule {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            fmt,
+            "{} at {:02}:{:02}",
+            self.weekday, self.hour, self.minute
+        )
+    }
 }
 
 #[derive(Debug, new)]
@@ -93,6 +582,220 @@ impl std::fmt::Display for ManipulationError {
                 write!(fmt, "\nUse !library list to see more checkout information")
             },
             ManipulationErrorType::UnknownBook(input) => write!(fmt, "Unknown book: \"{}\"", input),
+            ManipulationErrorType::UnknownWish(input) => write!(fmt, "Unknown wishlist entry: \"{}\"", input),
+            ManipulationErrorType::AlreadyWished(input) => write!(
+                fmt,
+                "That book is already on the wishlist ({}). Use !library upvote {} to vote for it",
+                input, input
+            ),
+            ManipulationErrorType::UnknownUser(input) => write!(
+                fmt,
+                "{} isn't registered with the library yet. Use !library register <your name> first",
+                input
+            ),
+            ManipulationErrorType::AlreadyRegistered(name) => write!(
+                fmt,
+                "That account is already registered as {}",
+                name
+            ),
+            ManipulationErrorType::SameUser => write!(fmt, "Those are the same user"),
+            ManipulationErrorType::HasActiveCheckouts => write!(
+                fmt,
+                "You still have books checked out. Please return them first"
+            ),
+            ManipulationErrorType::Suspended => write!(
+                fmt,
+                "Your borrowing privileges are suspended because of an overdue book. Talk to an officer to get them back"
+            ),
+            ManipulationErrorType::NoCopiesAvailable(input) => write!(
+                fmt,
+                "All copies of \"{}\" are checked out right now",
+                input
+            ),
+            ManipulationErrorType::UnknownCheckout(input) => write!(fmt, "Unknown checkout: \"{}\"", input),
+            ManipulationErrorType::UnknownCopy(input) => write!(
+                fmt,
+                "Unknown copy: \"{}\". Use !library copies <book> to see the copies of a book",
+                input
+            ),
+            ManipulationErrorType::UnknownExtension(input) => write!(
+                fmt,
+                "Extension request {} doesn't exist or was already decided",
+                input
+            ),
+            ManipulationErrorType::NotReading(input) => write!(
+                fmt,
+                "Checkout {} can't be returned because it isn't being read. Use !library mine to see your checkouts",
+                input
+            ),
+            ManipulationErrorType::NoGameWith(input) => write!(fmt, "You have no game with <@{}>", input),
+            ManipulationErrorType::NoGames => write!(
+                fmt,
+                "You have no games going. Challenge someone with !chess challenge @member"
+            ),
+            ManipulationErrorType::UnknownGame(input) => write!(fmt, "Unknown game: \"{}\"", input),
+            ManipulationErrorType::SeveralGames(_) => write!(
+                fmt,
+                "You have more than one game going. Mention your opponent to say which one"
+            ),
+            ManipulationErrorType::AlreadyPlaying(input) => write!(
+                fmt,
+                "You already have a game or challenge with <@{}>",
+                input
+            ),
+            ManipulationErrorType::GameNotInProgress(input) => write!(fmt, "Game {} isn't being played", input),
+            ManipulationErrorType::NotYourTurn => write!(fmt, "It's not your turn"),
+            ManipulationErrorType::IllegalMove(input) => write!(
+                fmt,
+                "\"{}\" isn't a legal move. Moves are written like e4, Nf3, exd5, O-O or e8=Q",
+                input
+            ),
+            ManipulationErrorType::CantPlayYourself => write!(fmt, "You can't challenge yourself"),
+            ManipulationErrorType::UnknownTournament(input) => write!(fmt, "Unknown tournament: \"{}\"", input),
+            ManipulationErrorType::RegistrationClosed(input) => write!(
+                fmt,
+                "Registration for tournament {} is closed",
+                input
+            ),
+            ManipulationErrorType::AlreadyJoined(input) => write!(fmt, "You already joined tournament {}", input),
+            ManipulationErrorType::NotJoined(input) => write!(fmt, "You aren't in tournament {}", input),
+            ManipulationErrorType::TournamentAlreadyStarted(input) => write!(
+                fmt,
+                "Tournament {} has already started",
+                input
+            ),
+            ManipulationErrorType::NotEnoughPlayers(input) => write!(
+                fmt,
+                "Tournament {} needs at least two players to start",
+                input
+            ),
+            ManipulationErrorType::TournamentNotRunning(input) => write!(fmt, "Tournament {} isn't running", input),
+            ManipulationErrorType::CheckInNotOpen(input) => write!(
+                fmt,
+                "Check-in for tournament {} hasn't opened",
+                input
+            ),
+            ManipulationErrorType::CheckInClosed(input) => write!(
+                fmt,
+                "Check-in for tournament {} has closed",
+                input
+            ),
+            ManipulationErrorType::ResultsFromGames(tournament) => write!(
+                fmt,
+                "Results in arena {} are taken from the games, so they don't have to be reported",
+                tournament
+            ),
+            ManipulationErrorType::NeedsWinner(input) => write!(
+                fmt,
+                "Tournament {} is a knockout, so drawn games have to be settled with a tiebreak. Enter the tiebreak's result instead",
+                input
+            ),
+            ManipulationErrorType::NotYourBoard(tournament, board) => write!(
+                fmt,
+                "You aren't playing on board {} of tournament {}. Only the players and officers can enter its result",
+                board, tournament
+            ),
+            ManipulationErrorType::ResultAlreadyEntered(tournament, board) => write!(
+                fmt,
+                "Board {} of tournament {} already has a result. Ask an officer if it is wrong",
+                board, tournament
+            ),
+            ManipulationErrorType::NoClaim(tournament, board) => write!(
+                fmt,
+                "There is no result waiting to be confirmed for board {} of tournament {}",
+                board, tournament
+            ),
+            ManipulationErrorType::CantAnswerClaim(tournament, board) => write!(
+                fmt,
+                "Only the opponent of whoever reported board {} of tournament {}, or an officer, can confirm or dispute it",
+                board, tournament
+            ),
+            ManipulationErrorType::UnknownBoard(tournament, board) => write!(
+                fmt,
+                "There is no game on board {} of tournament {}'s current round. Use !tournament pairings {} to see the boards",
+                board, tournament, tournament
+            ),
+            ManipulationErrorType::UnknownReport(input) => write!(fmt, "Unknown reported result: \"{}\"", input),
+            ManipulationErrorType::UnknownArchivedGame(input) => write!(fmt, "Unknown archived game: \"{}\"", input),
+            ManipulationErrorType::ReportSettled(input) => write!(
+                fmt,
+                "Reported result {} has already been settled",
+                input
+            ),
+            ManipulationErrorType::CantAnswerReport(input) => write!(
+                fmt,
+                "Only the opponent of whoever reported result {} can confirm or dispute it. Officers settle results with !settle-result",
+                input
+            ),
+            ManipulationErrorType::NotYourGame(_) => write!(
+                fmt,
+                "You can only report results of games you played. Officers can report anyone's"
+            ),
+            ManipulationErrorType::NoVoteGame => write!(
+                fmt,
+                "There is no vote chess game going. Officers can start one with !vote start"
+            ),
+            ManipulationErrorType::VoteGameInProgress => write!(
+                fmt,
+                "The server is already playing the engine. Officers can stop the game with !vote stop"
+            ),
+            ManipulationErrorType::NoSimul => write!(
+                fmt,
+                "There is no simul going. Host one with !simul host"
+            ),
+            ManipulationErrorType::SimulInProgress(input) => write!(
+                fmt,
+                "<@{}> is already hosting a simul. It has to finish or be cancelled first",
+                input
+            ),
+            ManipulationErrorType::SimulSignUpClosed => write!(fmt, "Sign-up for the simul is closed"),
+            ManipulationErrorType::AlreadyInSimul => write!(fmt, "You already joined the simul"),
+            ManipulationErrorType::NotInSimul => write!(fmt, "You aren't in the simul"),
+            ManipulationErrorType::NotSimulHost(input) => write!(
+                fmt,
+                "Only the simul's host, <@{}>, can do that",
+                input
+            ),
+            ManipulationErrorType::NoChallengers => write!(
+                fmt,
+                "Nobody has joined the simul yet. Members join with !simul join"
+            ),
+            ManipulationErrorType::AlreadyNominated(input) => write!(fmt, "Game {} is already nominated", input),
+            ManipulationErrorType::NotNominated(input) => write!(
+                fmt,
+                "Game {} isn't nominated for game of the week. !gotw shows the nominated games",
+                input
+            ),
+            ManipulationErrorType::NoMoves(input) => write!(
+                fmt,
+                "Game {} was played over the board, so the bot doesn't have its moves",
+                input
+            ),
+            ManipulationErrorType::NoDrawOffers => write!(
+                fmt,
+                "Draws can only be offered to another member"
+            ),
+            ManipulationErrorType::TakebacksNotAllowed => write!(
+                fmt,
+                "Moves can't be taken back in rated games. Challenge with casual to allow takebacks"
+            ),
+            ManipulationErrorType::NothingToTakeBack => write!(fmt, "You have no move to take back"),
+            ManipulationErrorType::InvalidLoanDays(days) => write!(
+                fmt,
+                "Books can be checked out for at most {} days, not {}",
+                MAX_LOAN_DAYS, days
+            ),
+            ManipulationErrorType::InvalidExtensionDays(days) => write!(
+                fmt,
+                "Extensions have to be between 1 and {} days, not {}",
+                MAX_EXTENSION_DAYS, days
+            ),
+            ManipulationErrorType::TournamentFull(input) => write!(
+                fmt,
+                "Tournament {} is full, it can have at most {} players",
+                input,
+                crate::tournaments::MAX_PLAYERS
+            ),
         }
     }
 }
@@ -103,10 +806,79 @@ pub enum ManipulationErrorType {
     OutstandingBooksNonReturned(Vec<CheckoutUuid>),
     UnknownBook(String),
     AlreadyAdded(String),
+    UnknownWish(String),
+    //Id of the existing wishlist entry
+    AlreadyWished(String),
+    UnknownUser(String),
+    //Name of the existing user
+    AlreadyRegistered(String),
+    SameUser,
+    HasActiveCheckouts,
+    Suspended,
+    NoCopiesAvailable(String),
+    UnknownCheckout(String),
+    NotReading(String),
+    UnknownExtension(String),
+    UnknownCopy(String),
+    //Discord id of the member nobody has a game with
+    NoGameWith(String),
+    NoGames,
+    UnknownGame(String),
+    //Discord id of the member who has more than one game going
+    SeveralGames(String),
+    //Discord id of the member already playing
+    AlreadyPlaying(String),
+    GameNotInProgress(String),
+    NotYourTurn,
+    IllegalMove(String),
+    CantPlayYourself,
+    UnknownTournament(String),
+    //Id of the tournament in each of these
+    RegistrationClosed(String),
+    AlreadyJoined(String),
+    NotJoined(String),
+    TournamentAlreadyStarted(String),
+    NotEnoughPlayers(String),
+    TournamentNotRunning(String),
+    NeedsWinner(String),
+    CheckInNotOpen(String),
+    CheckInClosed(String),
+    ResultsFromGames(String),
+    //Id of the tournament and the board asked for in each of these
+    UnknownBoard(String, String),
+    NotYourBoard(String, String),
+    ResultAlreadyEntered(String, String),
+    NoClaim(String, String),
+    CantAnswerClaim(String, String),
+    UnknownReport(String),
+    UnknownArchivedGame(String),
+    //Id of the reported result in each of these
+    ReportSettled(String),
+    CantAnswerReport(String),
+    //Discord id of the member reporting
+    NotYourGame(String),
+    NoVoteGame,
+    VoteGameInProgress,
+    NoSimul,
+    //Discord id of the host in each of these
+    SimulInProgress(String),
+    NotSimulHost(String),
+    SimulSignUpClosed,
+    AlreadyInSimul,
+    NotInSimul,
+    NoChallengers,
+    //Id of the archived game in each of these
+    AlreadyNominated(String),
+    NotNominated(String),
+    NoMoves(String),
+    NoDrawOffers,
+    TakebacksNotAllowed,
+    NothingToTakeBack,
+    InvalidLoanDays(u32),
+    InvalidExtensionDays(u32),
+    TournamentFull(String),
 }
 
-const LIBRARY_DB_NAME: &str = "library-db.bin";
-
 #[derive(Debug)]
 pub enum UuidError {
     InvalidEncoding,
@@ -114,6 +886,8 @@ pub enum UuidError {
     MismatchIsUserUuid,
     MismatchIsBookUuid,
     MismatchIsCheckoutUuid,
+    MismatchIsWishUuid,
+    MismatchIsCopyUuid,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -121,6 +895,8 @@ pub enum UuidType {
     User,
     Book,
     Checkout,
+    Wish,
+    Copy,
 }
 
 impl Database {
@@ -128,105 +904,1939 @@ impl Database {
         Database {
             books: IndexMap::new(),
             checkouts: IndexMap::new(),
+            archived_checkouts: IndexMap::new(),
             users: IndexMap::new(),
+            wishlist: IndexMap::new(),
+            extension_requests: IndexMap::new(),
+            audit_log: Vec::new(),
+            escalation_policy: default_escalation_policy(),
+            maintenance: false,
+            digest_schedule: WeeklySchedule::new(chrono::Weekday::Sun, 18, 0),
+            last_digest: None,
+            config: GuildConfig::default(),
+            announcements: IndexMap::new(),
+            games: IndexMap::new(),
+            puzzle_ratings: IndexMap::new(),
+            club_ratings: IndexMap::new(),
+            tournaments: IndexMap::new(),
+            lichess_ratings: IndexMap::new(),
+            otb_games: IndexMap::new(),
+            archive: IndexMap::new(),
+            vote_game: None,
+            simul: None,
+            game_of_the_week: GameOfTheWeek::default(),
+            puzzle_streaks: IndexMap::new(),
+            rating_history: IndexMap::new(),
+            lichess_team: None,
+            lichess_relays: Vec::new(),
+            lichess_live: IndexMap::new(),
+            author_index: IndexMap::new(),
+            title_index: Vec::new(),
+            active_checkouts: IndexMap::new(),
+            guild: None,
+            audit_mirrored: 0,
         }
     }
 
-    pub async fn load() -> Option<Database> {
-        let task = tokio::fs::read(LIBRARY_DB_NAME).await;
-        match task {
-            Ok(data) => {
-                let result: Result<Database, _> = bincode::deserialize(&data);
-
-                //We want to panic on failure
-                let db = result.unwrap();
-                println!("Loaded library: {:?} from disk successfully", db);
+    pub async fn load(guild: Option<u64>) -> Option<Database> {
+        match crate::storage::backend().load(guild).await {
+            Ok(Some(mut db)) => {
+                db.guild = guild;
+                db.rebuild_indices();
+                //Libraries saved before the archive was added have games that finished without
+                //being archived
+                if db.archive.is_empty() {
+                    db.archive_finished_games();
+                }
+                db.tag_openings();
+                db.audit_mirrored = db.audit_log.len();
+                //An encrypted library only logs how big it is
+                if crate::crypto::enabled() {
+                    println!(
+                        "Loaded library for guild {:?} successfully: {} books, {} users, {} checkouts",
+                        guild,
+                        db.books.len(),
+                        db.users.len(),
+                        db.checkouts.len()
+                    );
+                } else {
+                    println!("Loaded library: {:?} successfully", db);
+                }
                 Some(db)
             }
-            Err(err) => {
-                println!("Failed to load library file: {:?}", err);
-                None
-            }
+            Ok(None) => None,
+            //We want to panic on failure
+            Err(err) => panic!("Failed to load library for guild {:?}: {}", guild, err),
         }
     }
 
-    pub async fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let data: Vec<u8> = bincode::serialize(self)?;
-        tokio::fs::write(LIBRARY_DB_NAME, data).await?;
-
-        println!("Saved library database successfully");
-        Ok(())
+    //Reads back the json written by try_save when saving fails
+    pub fn from_json(json: &[u8]) -> Result<Database, serde_json::Error> {
+        let mut db: Database = serde_json::from_slice(json)?;
+        db.rebuild_indices();
+        db.audit_mirrored = db.audit_log.len();
+        Ok(db)
     }
 
-    pub async fn try_save(&self) {
-        match self.save().await {
-            Ok(_) => {}
-            Err(err) => {
-                println!("An error occured while trying to save thi library database!");
-                println!("{:?}", err);
-                println!("Dumping database json to stdout:");
-                let json = serde_json::to_string(&self).unwrap();
-                println!("{}", json);
-
-                let mut temp_file = std::env::temp_dir();
-                let num: u32 = rand::thread_rng().gen();
-                temp_file.push(format!("Chess-bot-DB-dump-{:x}.json", num));
-
-                println!("Also writing to temp file {:?}", temp_file.to_str());
-                match tokio::fs::write(temp_file, json).await {
-                    Err(err) => println!("Failed to save backup json!: {}", err),
-                    Ok(_) => {}
-                }
+    //Looks for records that point at things which don't exist. Returns a description of each
+    //problem found
+    pub fn check_consistency(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (uuid, book) in &self.books {
+            if *uuid != book.uuid {
+                problems.push(format!(
+                    "Book {} is stored under id {}",
+                    Database::encode_uuid(book.uuid),
+                    Database::encode_uuid(*uuid)
+                ));
             }
         }
-    }
-
-    pub fn add_book(&mut self, book: Book) -> Result<(), ManipulationError> {
-        if self.books.contains_key(&book.uuid) {
-            return Err(ManipulationError::new(ManipulationErrorType::AlreadyAdded(
-                Database::encode_uuid(book.uuid),
-            )));
-        }
-        for existing_book in &self.books {
-            if utils::cmp_ignore_case_ascii(&existing_book.1.name, &book.name)
-                && utils::cmp_ignore_case_ascii(&existing_book.1.author, &book.author)
-            {
-                return Err(ManipulationError::new(ManipulationErrorType::AlreadyAdded(
-                    book.name,
-                )));
+        for (uuid, user) in &self.users {
+            if *uuid != user.uuid {
+                problems.push(format!(
+                    "User {} is stored under id {}",
+                    Database::encode_uuid(user.uuid),
+                    Database::encode_uuid(*uuid)
+                ));
             }
         }
-        self.books.insert(book.uuid, book);
-
-        Ok(())
-    }
-
-    pub fn remove_book(&mut self, uuid: BookUuid) -> Result<Book, ManipulationError> {
-        for i in 0..self.checkouts.len() {
-            if self.checkouts[i].book == uuid {
-                //The book we are trying to remove is un accounted for
-                //Get the list of
-
-                let mut error_books: Vec<BookUuid> = Vec::new();
-                for j in 0..self.checkouts.len() {
-                    if self.checkouts[j].book == uuid {
-                        error_books.push(self.checkouts[j].uuid);
-                    }
-                }
-
-                return Err(ManipulationError::new(
-                    ManipulationErrorType::OutstandingBooksNonReturned(error_books),
+        let all_checkouts = self.checkouts.iter().chain(self.archived_checkouts.iter());
+        for (uuid, checkout) in all_checkouts {
+            if *uuid != checkout.uuid {
+                problems.push(format!(
+                    "Checkout {} is stored under id {}",
+                    Database::encode_uuid(checkout.uuid),
+                    Database::encode_uuid(*uuid)
+                ));
+            }
+            if !self.users.contains_key(&checkout.rentee) {
+                problems.push(format!(
+                    "Checkout {} belongs to unknown user {}",
+                    Database::encode_uuid(checkout.uuid),
+                    Database::encode_uuid(checkout.rentee)
                 ));
             }
         }
+        for checkout in self.checkouts.values() {
+            //Finished checkouts of removed books are expected, but outstanding ones aren't
+            if checkout.status != CheckoutStatus::DONE && !self.books.contains_key(&checkout.book) {
+                problems.push(format!(
+                    "Outstanding checkout {} is for unknown book {}",
+                    Database::encode_uuid(checkout.uuid),
+                    Database::encode_uuid(checkout.book)
+                ));
+            }
+        }
+        for checkout in self.archived_checkouts.values() {
+            if checkout.status != CheckoutStatus::DONE {
+                problems.push(format!(
+                    "Archived checkout {} was never completed",
+                    Database::encode_uuid(checkout.uuid)
+                ));
+            }
+        }
+        for request in self.extension_requests.values() {
+            if self.checkout(request.checkout).is_none() {
+                problems.push(format!(
+                    "Extension request {} is for unknown checkout {}",
+                    Database::encode_uuid(request.uuid),
+                    Database::encode_uuid(request.checkout)
+                ));
+            }
+        }
+
+        for checkout in self.checkouts.values() {
+            let copy = match checkout.copy {
+                Some(copy) => copy,
+                None => continue,
+            };
+            let copy_exists = self.books.get(&checkout.book).map_or(false, |book| {
+                book.copies.iter().any(|other| other.uuid == copy)
+            });
+            if !copy_exists {
+                problems.push(format!(
+                    "Checkout {} reserved unknown copy {}",
+                    Database::encode_uuid(checkout.uuid),
+                    Database::encode_uuid(copy)
+                ));
+            }
+        }
+
+        //Counted from the checkouts themselves rather than the index, which is what's being
+        //checked
+        let mut outstanding: IndexMap<BookUuid, u32> = IndexMap::new();
+        for checkout in self.checkouts.values() {
+            if checkout.status != CheckoutStatus::DONE {
+                *outstanding.entry(checkout.book).or_insert(0) += 1;
+            }
+        }
+        for (uuid, count) in outstanding {
+            if let Some(book) = self.books.get(&uuid) {
+                if count > book.quantity {
+                    problems.push(format!(
+                        "\"{}\" has {} copies out but the library only owns {}",
+                        book.name, count, book.quantity
+                    ));
+                }
+            }
+        }
+
+        //Ids are decoded without knowing what they are for, so they have to be unique across
+        //every kind of record
+        let mut seen: IndexMap<u32, &str> = IndexMap::new();
+        let mut ids: Vec<(u32, &str)> = Vec::new();
+        ids.extend(self.books.values().map(|book| (book.uuid, "book")));
+        ids.extend(self.users.values().map(|user| (user.uuid, "user")));
+        ids.extend(
+            self.checkouts
+                .values()
+                .map(|checkout| (checkout.uuid, "checkout")),
+        );
+        ids.extend(
+            self.archived_checkouts
+                .values()
+                .map(|checkout| (checkout.uuid, "archived checkout")),
+        );
+        ids.extend(self.wishlist.values().map(|wish| (wish.uuid, "wish")));
+        ids.extend(
+            self.extension_requests
+                .values()
+                .map(|request| (request.uuid, "extension request")),
+        );
+        ids.extend(
+            self.announcements
+                .values()
+                .map(|announcement| (announcement.uuid, "announcement")),
+        );
+        ids.extend(self.games.values().map(|game| (game.uuid, "game")));
+        ids.extend(
+            self.tournaments
+                .values()
+                .map(|tournament| (tournament.uuid, "tournament")),
+        );
+        ids.extend(
+            self.otb_games
+                .values()
+                .map(|game| (game.uuid, "over the board game")),
+        );
+        ids.extend(
+            self.archive
+                .values()
+                .map(|game| (game.uuid, "archived game")),
+        );
+        for book in self.books.values() {
+            ids.extend(book.copies.iter().map(|copy| (copy.uuid, "copy")));
+        }
+        for (uuid, kind) in ids {
+            if let Some(other) = seen.insert(uuid, kind) {
+                problems.push(format!(
+                    "Id {} is used by both a {} and a {}",
+                    Database::encode_uuid(uuid),
+                    other,
+                    kind
+                ));
+            }
+        }
+
+        let mut discord_ids: IndexMap<&str, UserUuid> = IndexMap::new();
+        for user in self.users.values() {
+            if let Some(other) = discord_ids.insert(&user.discord_id, user.uuid) {
+                problems.push(format!(
+                    "Users {} and {} have the same discord account. Use !library merge-users",
+                    Database::encode_uuid(other),
+                    Database::encode_uuid(user.uuid)
+                ));
+            }
+        }
+
+        problems
+    }
+
+    //Fixes the problems found by check_consistency that have an obvious fix. Returns a
+    //description of each repair made. Everything else needs an officer to look at it
+    pub fn repair_consistency(&mut self) -> Vec<String> {
+        let mut repairs = Vec::new();
+
+        //Records stored under the wrong key are moved to the right one, unless that would
+        //overwrite something
+        let misplaced: Vec<BookUuid> = self
+            .books
+            .iter()
+            .filter(|(uuid, book)| **uuid != book.uuid && !self.books.contains_key(&book.uuid))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in misplaced {
+            if let Some(book) = self.books.remove(&uuid) {
+                repairs.push(format!("Moved book {} to its own id", book.name));
+                self.books.insert(book.uuid, book);
+            }
+        }
+        let misplaced: Vec<UserUuid> = self
+            .users
+            .iter()
+            .filter(|(uuid, user)| **uuid != user.uuid && !self.users.contains_key(&user.uuid))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in misplaced {
+            if let Some(user) = self.users.remove(&uuid) {
+                repairs.push(format!("Moved user {} to their own id", user.read_name));
+                self.users.insert(user.uuid, user);
+            }
+        }
+        for checkouts in vec![&mut self.checkouts, &mut self.archived_checkouts] {
+            let misplaced: Vec<CheckoutUuid> = checkouts
+                .iter()
+                .filter(|(uuid, checkout)| {
+                    **uuid != checkout.uuid && !checkouts.contains_key(&checkout.uuid)
+                })
+                .map(|(uuid, _)| *uuid)
+                .collect();
+            for uuid in misplaced {
+                if let Some(checkout) = checkouts.remove(&uuid) {
+                    repairs.push(format!(
+                        "Moved checkout {} to its own id",
+                        Database::encode_uuid(checkout.uuid)
+                    ));
+                    checkouts.insert(checkout.uuid, checkout);
+                }
+            }
+        }
+
+        //Closed checkouts are archived when the indices are rebuilt below
+        let books = &self.books;
+        for checkout in self.checkouts.values_mut() {
+            let book = books.get(&checkout.book);
+            if checkout.status != CheckoutStatus::DONE && book.is_none() {
+                checkout.status = CheckoutStatus::DONE;
+                repairs.push(format!(
+                    "Closed checkout {} of a book that no longer exists",
+                    Database::encode_uuid(checkout.uuid)
+                ));
+            }
+            if let Some(copy) = checkout.copy {
+                if !book.map_or(false, |book| {
+                    book.copies.iter().any(|other| other.uuid == copy)
+                }) {
+                    checkout.copy = None;
+                    repairs.push(format!(
+                        "Dropped the unknown copy reserved by checkout {}",
+                        Database::encode_uuid(checkout.uuid)
+                    ));
+                }
+            }
+        }
+
+        let checkouts = &self.checkouts;
+        let archived_checkouts = &self.archived_checkouts;
+        let before = self.extension_requests.len();
+        self.extension_requests.retain(|_, request| {
+            checkouts.contains_key(&request.checkout)
+                || archived_checkouts.contains_key(&request.checkout)
+        });
+        let removed = before - self.extension_requests.len();
+        if removed > 0 {
+            repairs.push(format!(
+                "Removed {} extension request(s) for checkouts that no longer exist",
+                removed
+            ));
+        }
+
+        self.rebuild_indices();
+        repairs
+    }
+
+    //Makes sure a change that was just made survives a crash, usually by journaling it. Returns
+    //whether the library now has changes that haven't been saved in full
+    pub async fn persist_change(&self) -> bool {
+        match crate::storage::backend().journal(self).await {
+            Ok(changed) => changed,
+            Err(err) => {
+                println!("Failed to journal library change: {:?}", err);
+                true
+            }
+        }
+    }
+
+    pub async fn save(&self) -> Result<(), crate::storage::StorageError> {
+        crate::storage::backend().save(self).await
+    }
+
+    pub async fn try_save(&self) {
+        match self.save().await {
+            Ok(_) => {}
+            Err(err) => {
+                println!("An error occured while trying to save thi library database!");
+                println!("{:?}", err);
+                let json = serde_json::to_string(&self).unwrap();
+                //Member names and ids shouldn't end up in the logs when the library is encrypted
+                let dump = if crate::crypto::enabled() {
+                    match crate::crypto::encrypt(json.into_bytes()) {
+                        Ok(dump) => dump,
+                        Err(err) => {
+                            println!("Failed to encrypt backup json!: {}", err);
+                            return;
+                        }
+                    }
+                } else {
+                    println!("Dumping database json to stdout:");
+                    println!("{}", json);
+                    json.into_bytes()
+                };
+
+                let mut temp_file = std::env::temp_dir();
+                let num: u32 = rand::thread_rng().gen();
+                temp_file.push(format!("Chess-bot-DB-dump-{:x}.json", num));
+
+                println!("Writing dump to temp file {:?}", temp_file.to_str());
+                match tokio::fs::write(temp_file, dump).await {
+                    Err(err) => println!("Failed to save backup json!: {}", err),
+                    Ok(_) => {}
+                }
+            }
+        }
+    }
+
+    pub fn add_book(&mut self, book: Book) -> Result<(), ManipulationError> {
+        if self.books.contains_key(&book.uuid) {
+            return Err(ManipulationError::new(ManipulationErrorType::AlreadyAdded(
+                Database::encode_uuid(book.uuid),
+            )));
+        }
+        for existing_book in &self.books {
+            if utils::cmp_ignore_case_ascii(&existing_book.1.name, &book.name)
+                && utils::cmp_ignore_case_ascii(&existing_book.1.author, &book.author)
+            {
+                return Err(ManipulationError::new(ManipulationErrorType::AlreadyAdded(
+                    book.name,
+                )));
+            }
+        }
+        self.author_index
+            .entry(normalize_author(&book.author))
+            .or_insert_with(Vec::new)
+            .push(book.uuid);
+        let title = utils::normalize_title(&book.name);
+        let position = self
+            .title_index
+            .partition_point(|(other, _)| *other <= title);
+        self.title_index.insert(position, (title, book.uuid));
+        self.books.insert(book.uuid, book);
+
+        Ok(())
+    }
+
+    //Books to suggest while `input` is being typed, best first: titles starting with it, then
+    //titles with a word starting with it, then titles containing its letters in order
+    pub fn suggest_books(&self, input: &str, limit: usize) -> Vec<&Book> {
+        let input = utils::normalize_title(input);
+        let start = self
+            .title_index
+            .partition_point(|(title, _)| *title < input);
+        let mut uuids: Vec<BookUuid> = self.title_index[start..]
+            .iter()
+            .take_while(|(title, _)| title.starts_with(&input))
+            .take(limit)
+            .map(|(_, uuid)| *uuid)
+            .collect();
+
+        let word_matches = self
+            .title_index
+            .iter()
+            .filter(|(title, _)| title.split(' ').any(|word| word.starts_with(&input)));
+        let fuzzy_matches = self
+            .title_index
+            .iter()
+            .filter(|(title, _)| utils::is_subsequence(&input, title));
+        for (_, uuid) in word_matches.chain(fuzzy_matches) {
+            if uuids.len() >= limit {
+                break;
+            }
+            if !uuids.contains(uuid) {
+                uuids.push(*uuid);
+            }
+        }
+        uuids.iter().map(|uuid| &self.books[uuid]).collect()
+    }
+
+    //Books that `input` could be referring to when it isn't an exact title: titles containing it
+    //once case and punctuation are ignored, or within a few typos of it
+    pub fn books_matching(&self, input: &str) -> Vec<&Book> {
+        let input = utils::normalize_title(input);
+        if input.is_empty() {
+            return Vec::new();
+        }
+        let allowed_distance = std::cmp::max(2, input.len() / 8);
+
+        self.books
+            .values()
+            .filter(|book| {
+                let name = utils::normalize_title(&book.name);
+                name.contains(&input) || utils::edit_distance(&input, &name) <= allowed_distance
+            })
+            .collect()
+    }
+
+    //Looks for a book that is probably the same as the one described by `name` and `author` even
+    //though it isn't an exact match. Titles and authors are compared after normalizing case,
+    //whitespace, and punctuation and may differ by a few typos
+    pub fn find_similar_book(&self, name: &str, author: &str) -> Option<&Book> {
+        let name = utils::normalize_title(name);
+        let author = utils::normalize_title(author);
+
+        //Allow roughly one typo for every 8 characters, but at least 2
+        let allowed_name_distance = std::cmp::max(2, name.len() / 8);
+        let allowed_author_distance = std::cmp::max(2, author.len() / 8);
+
+        self.books.values().find(|book| {
+            let book_name = utils::normalize_title(&book.name);
+            let book_author = utils::normalize_title(&book.author);
+
+            utils::edit_distance(&name, &book_name) <= allowed_name_distance
+                && (utils::edit_distance(&author, &book_author) <= allowed_author_distance
+                    || book_author.split(' ').any(|word| word == author)
+                    || author.split(' ').any(|word| word == book_author))
+        })
+    }
+
+    pub fn add_wish(&mut self, wish: WishlistEntry) -> Result<(), ManipulationError> {
+        for existing in self.wishlist.values() {
+            if utils::normalize_title(&existing.name) == utils::normalize_title(&wish.name) {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::AlreadyWished(Database::encode_uuid(existing.uuid)),
+                ));
+            }
+        }
+        if let Some(book) = self.find_similar_book(&wish.name, &wish.author) {
+            return Err(ManipulationError::new(ManipulationErrorType::AlreadyAdded(
+                book.name.clone(),
+            )));
+        }
+        self.wishlist.insert(wish.uuid, wish);
+
+        Ok(())
+    }
+
+    //Toggles `discord_id`'s vote on a wishlist entry. Returns true if the vote was added
+    pub fn toggle_wish_vote(
+        &mut self,
+        uuid: WishUuid,
+        discord_id: String,
+    ) -> Result<bool, ManipulationError> {
+        let wish = match self.wishlist.get_mut(&uuid) {
+            Some(wish) => wish,
+            None => {
+                return Err(ManipulationError::new(ManipulationErrorType::UnknownWish(
+                    Database::encode_uuid(uuid),
+                )))
+            }
+        };
+        if wish.votes.contains(&discord_id) {
+            wish.votes.retain(|vote| *vote != discord_id);
+            Ok(false)
+        } else {
+            wish.votes.push(discord_id);
+            Ok(true)
+        }
+    }
+
+    //Moves a wishlist entry into the catalog once the club has bought it
+    pub fn fulfill_wish(
+        &mut self,
+        uuid: WishUuid,
+        quantity: u32,
+    ) -> Result<BookUuid, ManipulationError> {
+        let wish = match self.wishlist.get(&uuid) {
+            Some(wish) => wish,
+            None => {
+                return Err(ManipulationError::new(ManipulationErrorType::UnknownWish(
+                    Database::encode_uuid(uuid),
+                )))
+            }
+        };
+        let book = Book::new(
+            self.new_book_uuid(),
+            wish.name.clone(),
+            wish.author.clone(),
+            quantity,
+        );
+        let book_uuid = book.uuid;
+        self.add_book(book)?;
+        self.wishlist.shift_remove(&uuid);
+
+        Ok(book_uuid)
+    }
+
+    //All books that are part of `series`, ordered by volume
+    pub fn books_in_series(&self, series: &str) -> Vec<&Book> {
+        let mut books: Vec<&Book> = self
+            .books
+            .values()
+            .filter(|book| match &book.series {
+                Some(name) => utils::cmp_ignore_case_ascii(name, series),
+                None => false,
+            })
+            .collect();
+        books.sort_by_key(|book| book.volume);
+        books
+    }
+
+    fn rebuild_indices(&mut self) {
+        self.title_index = self
+            .books
+            .values()
+            .map(|book| (utils::normalize_title(&book.name), book.uuid))
+            .collect();
+        self.title_index.sort();
+
+        self.author_index.clear();
+        for book in self.books.values() {
+            self.author_index
+                .entry(normalize_author(&book.author))
+                .or_insert_with(Vec::new)
+                .push(book.uuid);
+        }
+
+        //Libraries saved before completed checkouts were archived still have them mixed in with
+        //the live ones
+        let done: Vec<CheckoutUuid> = self
+            .checkouts
+            .values()
+            .filter(|checkout| checkout.status == CheckoutStatus::DONE)
+            .map(|checkout| checkout.uuid)
+            .collect();
+        for uuid in done {
+            if let Some(checkout) = self.checkouts.shift_remove(&uuid) {
+                self.archived_checkouts.insert(uuid, checkout);
+            }
+        }
+
+        self.active_checkouts.clear();
+        for checkout in self.checkouts.values() {
+            self.active_checkouts
+                .entry(checkout.book)
+                .or_insert_with(Vec::new)
+                .push(checkout.uuid);
+        }
+    }
+
+    pub fn add_checkout(&mut self, checkout: CheckoutInstance) {
+        if checkout.status == CheckoutStatus::DONE {
+            self.archived_checkouts.insert(checkout.uuid, checkout);
+            return;
+        }
+        self.active_checkouts
+            .entry(checkout.book)
+            .or_insert_with(Vec::new)
+            .push(checkout.uuid);
+        self.checkouts.insert(checkout.uuid, checkout);
+    }
+
+    //Moves a checkout to a new stage. Checkouts should only be updated through here so that the
+    //active checkout index stays correct and completed checkouts get archived
+    pub fn set_checkout_status(&mut self, uuid: CheckoutUuid, status: CheckoutStatus) {
+        let checkout = match self.checkouts.get_mut(&uuid) {
+            Some(checkout) => checkout,
+            None => return,
+        };
+        checkout.status = status;
+
+        let active = self
+            .active_checkouts
+            .entry(checkout.book)
+            .or_insert_with(Vec::new);
+        active.retain(|other| *other != uuid);
+        if status != CheckoutStatus::DONE {
+            active.push(uuid);
+            return;
+        }
+
+        if let Some(checkout) = self.checkouts.shift_remove(&uuid) {
+            self.archived_checkouts.insert(uuid, checkout);
+        }
+    }
+
+    //Looks a checkout up whether it is still going or has been archived
+    pub fn checkout(&self, uuid: CheckoutUuid) -> Option<&CheckoutInstance> {
+        self.checkouts
+            .get(&uuid)
+            .or_else(|| self.archived_checkouts.get(&uuid))
+    }
+
+    //Every checkout ever made, completed ones first
+    pub fn all_checkouts(&self) -> impl Iterator<Item = &CheckoutInstance> {
+        self.archived_checkouts
+            .values()
+            .chain(self.checkouts.values())
+    }
+
+    //Checkouts of `book` that haven't been completed yet
+    pub fn active_checkouts_of(&self, book: BookUuid) -> Vec<&CheckoutInstance> {
+        match self.active_checkouts.get(&book) {
+            Some(uuids) => uuids.iter().map(|uuid| &self.checkouts[uuid]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    //Creates a checkout for each of `books` in the PreTransact stage. Books paired with a copy
+    //reserve that specific copy. Nothing is created unless every book can be checked out
+    pub fn begin_checkout(
+        &mut self,
+        rentee: UserUuid,
+        books: &[(BookUuid, Option<CopyUuid>)],
+    ) -> Result<Vec<CheckoutUuid>, ManipulationError> {
+        let user = match self.users.get(&rentee) {
+            Some(user) => user,
+            None => {
+                return Err(ManipulationError::new(ManipulationErrorType::UnknownUser(
+                    Database::encode_uuid(rentee),
+                )))
+            }
+        };
+        if user.suspended {
+            return Err(ManipulationError::new(ManipulationErrorType::Suspended));
+        }
+
+        for (uuid, copy) in books {
+            let book = match self.books.get(uuid) {
+                Some(book) => book,
+                None => {
+                    return Err(ManipulationError::new(ManipulationErrorType::UnknownBook(
+                        Database::encode_uuid(*uuid),
+                    )))
+                }
+            };
+            //Checking out two copies of the same book at once needs two available copies
+            let wanted = books.iter().filter(|(other, _)| other == uuid).count() as u32;
+            if self.available_copies(book) < wanted {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::NoCopiesAvailable(book.name.clone()),
+                ));
+            }
+            if let Some(copy) = copy {
+                let edition = match book.copies.iter().find(|other| other.uuid == *copy) {
+                    Some(book_copy) => &book_copy.edition,
+                    None => {
+                        return Err(ManipulationError::new(ManipulationErrorType::UnknownCopy(
+                            Database::encode_uuid(*copy),
+                        )))
+                    }
+                };
+                let taken = self
+                    .active_checkouts_of(*uuid)
+                    .iter()
+                    .any(|checkout| checkout.copy == Some(*copy))
+                    || books
+                        .iter()
+                        .filter(|(_, other)| *other == Some(*copy))
+                        .count()
+                        > 1;
+                if taken {
+                    return Err(ManipulationError::new(
+                        ManipulationErrorType::NoCopiesAvailable(format!(
+                            "{} ({})",
+                            book.name, edition
+                        )),
+                    ));
+                }
+            }
+        }
+
+        let mut uuids = Vec::new();
+        for (book, copy) in books {
+            let mut checkout = CheckoutInstance::new(self.new_checkout_uuid(), rentee, *book);
+            checkout.copy = *copy;
+            let uuid = checkout.uuid;
+            uuids.push(uuid);
+            self.add_checkout(checkout);
+            self.audit(
+                self.users[&rentee].discord_id.clone(),
+                format!(
+                    "Checked out \"{}\" ({})",
+                    self.books[book].name,
+                    Database::encode_uuid(uuid)
+                ),
+            );
+        }
+        Ok(uuids)
+    }
+
+    //Starts tracking an individual copy of `book`. If every copy is already tracked the book's
+    //quantity goes up, since this must be a new copy
+    pub fn add_copy(
+        &mut self,
+        book: BookUuid,
+        edition: String,
+    ) -> Result<CopyUuid, ManipulationError> {
+        let uuid = self.new_copy_uuid();
+        let book = match self.books.get_mut(&book) {
+            Some(book) => book,
+            None => {
+                return Err(ManipulationError::new(ManipulationErrorType::UnknownBook(
+                    Database::encode_uuid(book),
+                )))
+            }
+        };
+        book.copies.push(BookCopy::new(uuid, edition));
+        if (book.copies.len() as u32) > book.quantity {
+            book.quantity = book.copies.len() as u32;
+        }
+        Ok(uuid)
+    }
+
+    pub fn find_copy(&self, uuid: CopyUuid) -> Option<(&Book, &BookCopy)> {
+        self.books.values().find_map(|book| {
+            book.copies
+                .iter()
+                .find(|copy| copy.uuid == uuid)
+                .map(|copy| (book, copy))
+        })
+    }
+
+    //Whether a tracked copy is reserved or checked out by someone
+    pub fn is_copy_taken(&self, book: BookUuid, copy: CopyUuid) -> bool {
+        self.active_checkouts_of(book)
+            .iter()
+            .any(|checkout| checkout.copy == Some(copy))
+    }
+
+    //Whether `message_id` asks officers to hand out or take back books that are still waiting
+    pub fn awaits_approval(&self, message_id: u64) -> bool {
+        self.checkouts
+            .values()
+            .any(|checkout| match checkout.status {
+                CheckoutStatus::PreTransact => {
+                    checkout.approval_message.map(|m| m.message) == Some(message_id)
+                }
+                CheckoutStatus::ReturnVerifyNeeded => {
+                    checkout.return_message.map(|m| m.message) == Some(message_id)
+                }
+                _ => false,
+            })
+    }
+
+    //Called when an officer reacts to a checkout approval message. Starts the rental timer for
+    //every book linked to the message that hasn't been handed out yet and returns them
+    pub fn approve_checkouts(
+        &mut self,
+        message_id: u64,
+        officer_discord_id: &str,
+        now: TimeType,
+    ) -> Vec<CheckoutUuid> {
+        let pending: Vec<CheckoutUuid> = self
+            .checkouts
+            .values()
+            .filter(|checkout| checkout.status == CheckoutStatus::PreTransact)
+            .filter(|checkout| checkout.approval_message.map(|m| m.message) == Some(message_id))
+            .map(|checkout| checkout.uuid)
+            .collect();
+
+        for uuid in &pending {
+            let checkout = self.checkouts.get_mut(uuid).unwrap();
+            checkout.due_date = self
+                .books
+                .get(&checkout.book)
+                .and_then(|book| book.due_date_from(now));
+            checkout.checkout_approval =
+                Some(OfficerApproval::new(officer_discord_id.to_owned(), now));
+            self.set_checkout_status(*uuid, CheckoutStatus::Reading);
+            self.audit(
+                officer_discord_id.to_owned(),
+                format!(
+                    "Approved the handout of checkout {}",
+                    Database::encode_uuid(*uuid)
+                ),
+            );
+        }
+        pending
+    }
+
+    //The rentee's side of returning a book. Moves the checkout to ReturnVerifyNeeded so that an
+    //officer can confirm they got it back
+    pub fn request_return(
+        &mut self,
+        uuid: CheckoutUuid,
+        rentee: UserUuid,
+    ) -> Result<(), ManipulationError> {
+        let checkout = match self.checkout(uuid) {
+            Some(checkout) if checkout.rentee == rentee => checkout,
+            _ => {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::UnknownCheckout(Database::encode_uuid(uuid)),
+                ))
+            }
+        };
+        if checkout.status != CheckoutStatus::Reading {
+            return Err(ManipulationError::new(ManipulationErrorType::NotReading(
+                Database::encode_uuid(uuid),
+            )));
+        }
+        self.set_checkout_status(uuid, CheckoutStatus::ReturnVerifyNeeded);
+        self.audit(
+            self.users[&rentee].discord_id.clone(),
+            format!("Returned checkout {}", Database::encode_uuid(uuid)),
+        );
+        Ok(())
+    }
+
+    //Called when an officer reacts to a return message. Completes every checkout linked to the
+    //message that is waiting on verification and returns them
+    pub fn verify_returns(
+        &mut self,
+        message_id: u64,
+        officer_discord_id: &str,
+        now: TimeType,
+    ) -> Vec<CheckoutUuid> {
+        let pending: Vec<CheckoutUuid> = self
+            .checkouts
+            .values()
+            .filter(|checkout| checkout.status == CheckoutStatus::ReturnVerifyNeeded)
+            .filter(|checkout| checkout.return_message.map(|m| m.message) == Some(message_id))
+            .map(|checkout| checkout.uuid)
+            .collect();
+
+        for uuid in &pending {
+            let checkout = self.checkouts.get_mut(uuid).unwrap();
+            checkout.checkin_approval =
+                Some(OfficerApproval::new(officer_discord_id.to_owned(), now));
+            self.set_checkout_status(*uuid, CheckoutStatus::DONE);
+            self.audit(
+                officer_discord_id.to_owned(),
+                format!(
+                    "Confirmed the return of checkout {}",
+                    Database::encode_uuid(*uuid)
+                ),
+            );
+        }
+        pending
+    }
+
+    //Marks the announcements that are due at `now` as posted and returns them. Announcements
+    //that were missed by more than an hour, for example because the bot was offline, are skipped
+    //until next time
+    pub fn due_announcements(&mut self, now: TimeType) -> Vec<(u64, String)> {
+        let mut due = Vec::new();
+        for announcement in self.announcements.values_mut() {
+            let slot = match announcement.schedule.last_slot(now) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let already_posted = match announcement.last_posted {
+                Some(last) => last >= slot,
+                None => false,
+            };
+            if already_posted || now - slot > chrono::Duration::hours(1) {
+                continue;
+            }
+            announcement.last_posted = Some(now);
+            due.push((announcement.channel, announcement.message.clone()));
+        }
+        due
+    }
+
+    //Where messages of the given kind go. Overdue reports go to the library log unless they have a
+    //channel of their own, the game of the week goes with the digest, and the home library still
+    //uses the channels from the environment for kinds that haven't been set
+    pub fn channel(&self, kind: ChannelKind) -> Option<u64> {
+        if let Some(channel) = self.config.channels.get(&kind) {
+            return Some(*channel);
+        }
+        if kind == ChannelKind::Overdue {
+            if let Some(channel) = self.config.channels.get(&ChannelKind::LibraryLog) {
+                return Some(*channel);
+            }
+        }
+        if kind == ChannelKind::GameOfTheWeek {
+            return self.channel(ChannelKind::Digest);
+        }
+        if self.guild.is_some() {
+            return None;
+        }
+        std::env::var(kind.env_var()?).ok()?.parse::<u64>().ok()
+    }
+
+    pub fn audit(&mut self, actor: String, description: String) {
+        self.audit_log.push(AuditEntry::new(actor, description));
+    }
+
+    //Updates the players' club ratings after game `uuid` ended, if it was rated. Returns how their
+    //ratings changed
+    pub fn rate_game(&mut self, uuid: GameUuid) -> Option<[RatingChange; 2]> {
+        let game = self.games.get(&uuid)?;
+        if !game.is_rated() {
+            return None;
+        }
+        let white_score = game.status.white_score()?;
+        let (white, black) = (game.white.clone(), game.black.clone());
+        Some(self.rate(&white, &black, white_score))
+    }
+
+    //Updates the club ratings of `white` and `black` after a game white scored `white_score` in,
+    //and adds their new ratings to their rating history
+    fn rate(&mut self, white: &str, black: &str, white_score: f64) -> [RatingChange; 2] {
+        let k_factor = self
+            .config
+            .k_factor
+            .unwrap_or(crate::ratings::DEFAULT_K_FACTOR);
+        let changes = crate::ratings::rate(
+            &mut self.club_ratings,
+            self.config.rating_system,
+            k_factor,
+            white,
+            black,
+            white_score,
+        );
+        let taken = chrono::Local::now();
+        for change in &changes {
+            self.rating_history
+                .entry(change.member.clone())
+                .or_default()
+                .push(RatingSnapshot {
+                    taken,
+                    rating: change.rating.rating,
+                });
+        }
+        changes
+    }
+
+    pub fn find_otb_game(&self, input: &str) -> Result<OtbUuid, ManipulationError> {
+        self.decode_raw_uuid(input)
+            .filter(|uuid| self.otb_games.contains_key(uuid))
+            .ok_or_else(|| {
+                ManipulationError::new(ManipulationErrorType::UnknownReport(input.to_owned()))
+            })
+    }
+
+    //Records a game played over the board that `reporter` says ended in `outcome`. Results
+    //reported by officers count straight away and come with the rating changes, others wait for
+    //the opponent to confirm them
+    pub fn report_otb_game(
+        &mut self,
+        white: String,
+        black: String,
+        outcome: Outcome,
+        reporter: String,
+        officer: bool,
+    ) -> Result<(OtbUuid, Option<[RatingChange; 2]>), ManipulationError> {
+        if white == black {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CantPlayYourself,
+            ));
+        }
+        let uuid = self.new_otb_uuid();
+        let game = OtbGame::new(uuid, white, black, outcome, reporter.clone());
+        if !officer && !game.has_player(&reporter) {
+            return Err(ManipulationError::new(ManipulationErrorType::NotYourGame(
+                reporter,
+            )));
+        }
+        self.otb_games.insert(uuid, game);
+        if officer {
+            return self
+                .settle_otb_game(uuid, Some(outcome))
+                .map(|changes| (uuid, changes));
+        }
+        Ok((uuid, None))
+    }
+
+    //The reporter's opponent confirming or disputing reported result `uuid`. Returns the rating
+    //changes once it is confirmed
+    pub fn answer_otb_game(
+        &mut self,
+        uuid: OtbUuid,
+        player: &str,
+        confirm: bool,
+    ) -> Result<Option<[RatingChange; 2]>, ManipulationError> {
+        let game = self.otb_games.get_mut(&uuid).ok_or_else(|| {
+            ManipulationError::new(ManipulationErrorType::UnknownReport(Database::encode_uuid(
+                uuid,
+            )))
+        })?;
+        if game.status != OtbStatus::Reported {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::ReportSettled(Database::encode_uuid(uuid)),
+            ));
+        }
+        if game.confirmer() != player {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CantAnswerReport(Database::encode_uuid(uuid)),
+            ));
+        }
+        if confirm {
+            let outcome = game.outcome;
+            self.settle_otb_game(uuid, Some(outcome))
+        } else {
+            game.status = OtbStatus::Disputed;
+            Ok(None)
+        }
+    }
+
+    //An officer settling reported result `uuid` with `outcome`, or throwing it out with None.
+    //Returns the rating changes if it counts
+    pub fn settle_otb_game(
+        &mut self,
+        uuid: OtbUuid,
+        outcome: Option<Outcome>,
+    ) -> Result<Option<[RatingChange; 2]>, ManipulationError> {
+        let game = self.otb_games.get_mut(&uuid).ok_or_else(|| {
+            ManipulationError::new(ManipulationErrorType::UnknownReport(Database::encode_uuid(
+                uuid,
+            )))
+        })?;
+        if matches!(game.status, OtbStatus::Confirmed | OtbStatus::Voided) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::ReportSettled(Database::encode_uuid(uuid)),
+            ));
+        }
+        let outcome = match outcome {
+            Some(outcome) => outcome,
+            None => {
+                game.status = OtbStatus::Voided;
+                return Ok(None);
+            }
+        };
+        game.outcome = outcome;
+        game.status = OtbStatus::Confirmed;
+        let (white, black) = (game.white.clone(), game.black.clone());
+        self.archive_otb_game(uuid);
+        Ok(Some(self.rate(&white, &black, outcome.white_score())))
+    }
+
+    pub fn find_archived_game(&self, input: &str) -> Result<ArchiveUuid, ManipulationError> {
+        self.decode_raw_uuid(input)
+            .filter(|uuid| self.archive.contains_key(uuid))
+            .ok_or_else(|| {
+                ManipulationError::new(ManipulationErrorType::UnknownArchivedGame(input.to_owned()))
+            })
+    }
+
+    //Adds a finished game between `players`, white first, to the archive. A game that was already
+    //archived from the same source is updated instead, for results officers corrected. Returns its
+    //id in the archive
+    pub fn archive_game(
+        &mut self,
+        (white, black): (String, String),
+        played: TimeType,
+        event: Option<String>,
+        outcome: Outcome,
+        pgn: Option<String>,
+        source: GameSource,
+    ) -> ArchiveUuid {
+        if let Some(game) = self.archive.values_mut().find(|game| game.source == source) {
+            game.outcome = outcome;
+            return game.uuid;
+        }
+        let uuid = self.new_archive_uuid();
+        let mut game = ArchivedGame::new(uuid, white, black, played, event, outcome, pgn, source);
+        game.opening = game
+            .pgn
+            .as_deref()
+            .and_then(crate::openings::of_pgn)
+            .map(|opening| opening.to_string());
+        self.archive.insert(uuid, game);
+        uuid
+    }
+
+    //Archives game `uuid` played with the bot, if it was between two members and has ended
+    pub fn archive_discord_game(&mut self, uuid: GameUuid) {
+        let game = match self.games.get(&uuid) {
+            Some(game) if game.is_rated() => game,
+            _ => return,
+        };
+        let outcome = match game.status {
+            GameStatus::WhiteWon => Outcome::WhiteWon,
+            GameStatus::BlackWon => Outcome::BlackWon,
+            GameStatus::Drawn => Outcome::Draw,
+            _ => return,
+        };
+        let (white, black) = (game.white.clone(), game.black.clone());
+        let played = game.finished.unwrap_or(game.started);
+        let pgn = game.pgn.clone();
+        self.archive_game(
+            (white, black),
+            played,
+            None,
+            outcome,
+            pgn,
+            GameSource::Discord(uuid),
+        );
+    }
+
+    //Archives over the board result `uuid`, if it was confirmed
+    fn archive_otb_game(&mut self, uuid: OtbUuid) {
+        let game = match self.otb_games.get(&uuid) {
+            Some(game) if game.status == OtbStatus::Confirmed => game.clone(),
+            _ => return,
+        };
+        self.archive_game(
+            (game.white, game.black),
+            game.reported,
+            None,
+            game.outcome,
+            None,
+            GameSource::OverTheBoard(uuid),
+        );
+    }
+
+    //Archives the game on `board` of `round` of tournament `uuid`, if it has a result. Byes
+    //aren't games, so they aren't archived
+    pub fn archive_tournament_game(&mut self, uuid: TournamentUuid, round: usize, board: usize) {
+        //Arena games are archived like any other !chess game when they end
+        let tournament = match self.tournaments.get(&uuid) {
+            Some(tournament) if !tournament.is_arena() => tournament,
+            _ => return,
+        };
+        let pairing = match round
+            .checked_sub(1)
+            .and_then(|round| tournament.rounds.get(round))
+            .and_then(|round| round.pairings.get(board.checked_sub(1)?))
+        {
+            Some(pairing) => pairing,
+            None => return,
+        };
+        let (black, outcome) = match (&pairing.black, pairing.result) {
+            (Some(black), Some(outcome)) => (black.clone(), outcome),
+            _ => return,
+        };
+        let white = pairing.white.clone();
+        let event = Some(tournament.name.clone());
+        self.archive_game(
+            (white, black),
+            chrono::Local::now(),
+            event,
+            outcome,
+            None,
+            GameSource::Tournament(uuid, round, board),
+        );
+    }
+
+    //Archives the games that finished before the archive was added
+    pub fn archive_finished_games(&mut self) {
+        let games: Vec<GameUuid> = self.games.keys().copied().collect();
+        for uuid in games {
+            self.archive_discord_game(uuid);
+        }
+        let otb_games: Vec<OtbUuid> = self.otb_games.keys().copied().collect();
+        for uuid in otb_games {
+            self.archive_otb_game(uuid);
+        }
+        let mut boards = Vec::new();
+        for tournament in self.tournaments.values() {
+            for (round, games) in tournament.rounds.iter().enumerate() {
+                for board in 0..games.pairings.len() {
+                    boards.push((tournament.uuid, round + 1, board + 1));
+                }
+            }
+        }
+        for (uuid, round, board) in boards {
+            self.archive_tournament_game(uuid, round, board);
+        }
+        //Oldest first, like games archived as they finish
+        self.archive.sort_by(|_, a, _, b| a.played.cmp(&b.played));
+    }
+
+    //The archived games `member` played, newest first
+    pub fn history(&self, member: &str) -> Vec<&ArchivedGame> {
+        self.archive
+            .values()
+            .rev()
+            .filter(|game| game.has_player(member))
+            .collect()
+    }
+
+    //Archived games played in the openings matching `query`, as ArchivedGame::in_opening, newest
+    //first
+    pub fn games_in_opening(&self, query: &str) -> Vec<&ArchivedGame> {
+        self.archive
+            .values()
+            .rev()
+            .filter(|game| game.in_opening(query))
+            .collect()
+    }
+
+    //Names the openings of archived games whose moves are known but that were archived before
+    //openings were
+    pub fn tag_openings(&mut self) {
+        for game in self.archive.values_mut() {
+            if game.opening.is_none() {
+                game.opening = game
+                    .pgn
+                    .as_deref()
+                    .and_then(crate::openings::of_pgn)
+                    .map(|opening| opening.to_string());
+            }
+        }
+    }
+
+    //The archived games between `member` and `opponent`, newest first
+    pub fn games_between(&self, member: &str, opponent: &str) -> Vec<&ArchivedGame> {
+        self.archive
+            .values()
+            .rev()
+            .filter(|game| game.has_player(member) && game.has_player(opponent))
+            .collect()
+    }
+
+    //How `member` has done against `opponent` in the archived games between them, as wins,
+    //draws and losses
+    pub fn head_to_head(&self, member: &str, opponent: &str) -> (u32, u32, u32) {
+        let scores: Vec<f64> = self
+            .games_between(member, opponent)
+            .iter()
+            .map(|game| game.score_of(member))
+            .collect();
+        let wins = scores.iter().filter(|score| **score > 0.5).count() as u32;
+        let losses = scores.iter().filter(|score| **score < 0.5).count() as u32;
+        (wins, scores.len() as u32 - wins - losses, losses)
+    }
+
+    //The head to head record of `member` against `opponent`, for messages
+    pub fn head_to_head_text(&self, member: &str, opponent: &str) -> String {
+        let (wins, draws, losses) = self.head_to_head(member, opponent);
+        format!(
+            "Head to head: <@{}> has won {}, drawn {} and lost {} against <@{}>",
+            member, wins, draws, losses, opponent
+        )
+    }
+
+    //The unfinished game `player` has with `opponent` that `wanted` accepts. Without an opponent,
+    //the only such game `player` has
+    pub fn find_game(
+        &self,
+        player: &str,
+        opponent: Option<&str>,
+        wanted: impl Fn(&Game) -> bool,
+    ) -> Result<GameUuid, ManipulationError> {
+        let mut games = self.games.values().filter(|game| {
+            !game.status.is_over()
+                && game.has_player(player)
+                && opponent.map_or(true, |opponent| game.opponent_of(player) == opponent)
+                && wanted(game)
+        });
+        match (games.next(), games.next(), opponent) {
+            (Some(game), None, _) => Ok(game.uuid),
+            (Some(_), Some(_), _) => Err(ManipulationError::new(
+                ManipulationErrorType::SeveralGames(player.to_owned()),
+            )),
+            (None, _, Some(opponent)) => Err(ManipulationError::new(
+                ManipulationErrorType::NoGameWith(opponent.to_owned()),
+            )),
+            (None, _, None) => Err(ManipulationError::new(ManipulationErrorType::NoGames)),
+        }
+    }
+
+    //The tournament with the id `input`
+    pub fn find_tournament(&self, input: &str) -> Result<TournamentUuid, ManipulationError> {
+        self.decode_raw_uuid(input)
+            .filter(|uuid| self.tournaments.contains_key(uuid))
+            .ok_or_else(|| {
+                ManipulationError::new(ManipulationErrorType::UnknownTournament(input.to_owned()))
+            })
+    }
+
+    //The audit entries added since the last call, which still have to be posted to the audit
+    //channel
+    pub fn take_unmirrored_audit(&mut self) -> &[AuditEntry] {
+        let start = self.audit_mirrored.min(self.audit_log.len());
+        self.audit_mirrored = self.audit_log.len();
+        &self.audit_log[start..]
+    }
+
+    //A rentee asking for `days` more with a book they are reading
+    pub fn request_extension(
+        &mut self,
+        checkout: CheckoutUuid,
+        rentee: UserUuid,
+        days: u32,
+        reason: String,
+    ) -> Result<ExtensionUuid, ManipulationError> {
+        if !(1..=MAX_EXTENSION_DAYS).contains(&days) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::InvalidExtensionDays(days),
+            ));
+        }
+        match self.checkout(checkout) {
+            Some(instance) if instance.rentee == rentee => {
+                if instance.status != CheckoutStatus::Reading {
+                    return Err(ManipulationError::new(ManipulationErrorType::NotReading(
+                        Database::encode_uuid(checkout),
+                    )));
+                }
+            }
+            _ => {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::UnknownCheckout(Database::encode_uuid(checkout)),
+                ))
+            }
+        }
+
+        let request = ExtensionRequest {
+            uuid: self.new_extension_uuid(),
+            checkout,
+            days,
+            reason,
+            requested: chrono::Local::now(),
+            status: ExtensionStatus::Pending,
+            decision: None,
+        };
+        let uuid = request.uuid;
+        self.audit(
+            self.users[&rentee].discord_id.clone(),
+            format!(
+                "Requested a {} day extension for checkout {}: {}",
+                days,
+                Database::encode_uuid(checkout),
+                request.reason
+            ),
+        );
+        self.extension_requests.insert(uuid, request);
+        Ok(uuid)
+    }
+
+    //Records an officer's decision on an extension request, pushing back the due date if it was
+    //approved. Fails if the request was already decided
+    pub fn decide_extension(
+        &mut self,
+        uuid: ExtensionUuid,
+        approve: bool,
+        officer_discord_id: &str,
+    ) -> Result<(), ManipulationError> {
+        let request = match self.extension_requests.get_mut(&uuid) {
+            Some(request) if request.status == ExtensionStatus::Pending => request,
+            _ => {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::UnknownExtension(Database::encode_uuid(uuid)),
+                ))
+            }
+        };
+        let checkout_uuid = request.checkout;
+        let days = request.days;
+        //Requests made before extensions were capped can ask for more days than a date can hold
+        let due_date = match self.checkouts.get(&checkout_uuid).and_then(|c| c.due_date) {
+            Some(due_date) if approve => {
+                let due_date = due_date.checked_add_signed(chrono::Duration::days(days as i64));
+                match due_date {
+                    Some(due_date) => Some(due_date),
+                    None => {
+                        return Err(ManipulationError::new(
+                            ManipulationErrorType::InvalidExtensionDays(days),
+                        ))
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let now = chrono::Local::now();
+        request.status = if approve {
+            ExtensionStatus::Approved
+        } else {
+            ExtensionStatus::Denied
+        };
+        request.decision = Some(OfficerApproval::new(officer_discord_id.to_owned(), now));
+        if approve {
+            if let Some(checkout) = self.checkouts.get_mut(&checkout_uuid) {
+                if due_date.is_some() {
+                    checkout.due_date = due_date;
+                }
+                //Give the escalation policy a fresh start from the new due date
+                checkout.escalation_level = 0;
+            }
+        }
+        self.audit(
+            officer_discord_id.to_owned(),
+            format!(
+                "{} the {} day extension for checkout {}",
+                if approve { "Approved" } else { "Denied" },
+                days,
+                Database::encode_uuid(checkout_uuid)
+            ),
+        );
+        Ok(())
+    }
+
+    //Creates a user record for a discord account. Each account can only be registered once
+    pub fn register_user(
+        &mut self,
+        discord_id: String,
+        read_name: String,
+    ) -> Result<UserUuid, ManipulationError> {
+        if let Some(existing) = self.find_user_by_discord_id(&discord_id) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::AlreadyRegistered(existing.read_name.clone()),
+            ));
+        }
+        let user = User::new(discord_id, read_name, self.new_user_uuid());
+        let uuid = user.uuid;
+        self.users.insert(uuid, user);
+        Ok(uuid)
+    }
+
+    //Returns the user for a discord account, registering them under `read_name` if they don't have
+    //a record yet. The bool is true if a new user was created
+    pub fn find_or_register_user(
+        &mut self,
+        discord_id: String,
+        read_name: String,
+    ) -> (UserUuid, bool) {
+        match self.find_user_by_discord_id(&discord_id) {
+            Some(user) => (user.uuid, false),
+            None => (self.register_user(discord_id, read_name).unwrap(), true),
+        }
+    }
+
+    //Folds `duplicate` into `survivor`: everything that referenced the duplicate user now points at
+    //the survivor and the duplicate record is removed, along with games the two played each other
+    pub fn merge_users(
+        &mut self,
+        survivor: UserUuid,
+        duplicate: UserUuid,
+    ) -> Result<(), ManipulationError> {
+        if survivor == duplicate {
+            return Err(ManipulationError::new(ManipulationErrorType::SameUser));
+        }
+        if !self.users.contains_key(&survivor) {
+            return Err(ManipulationError::new(ManipulationErrorType::UnknownUser(
+                Database::encode_uuid(survivor),
+            )));
+        }
+        let duplicate_user = match self.users.shift_remove(&duplicate) {
+            Some(user) => user,
+            None => {
+                return Err(ManipulationError::new(ManipulationErrorType::UnknownUser(
+                    Database::encode_uuid(duplicate),
+                )))
+            }
+        };
+
+        let survivor_user = self.users.get_mut(&survivor).unwrap();
+        survivor_user.suspended |= duplicate_user.suspended;
+        survivor_user.registered =
+            std::cmp::min(survivor_user.registered, duplicate_user.registered);
+        //Linked accounts are kept unless the survivor already has its own
+        let took_lichess = survivor_user.lichess.is_none() && duplicate_user.lichess.is_some();
+        if took_lichess {
+            survivor_user.lichess = duplicate_user.lichess.clone();
+        }
+        if survivor_user.chesscom.is_none() {
+            survivor_user.chesscom = duplicate_user.chesscom.clone();
+        }
+        let survivor_discord_id = survivor_user.discord_id.clone();
+        let duplicate_discord_id = duplicate_user.discord_id.as_str();
+
+        let all_checkouts = self
+            .checkouts
+            .values_mut()
+            .chain(self.archived_checkouts.values_mut());
+        for checkout in all_checkouts {
+            if checkout.rentee == duplicate {
+                checkout.rentee = survivor;
+            }
+        }
+
+        //The wishlist goes by discord id rather than user
+        for wish in self.wishlist.values_mut() {
+            if wish.suggested_by == duplicate_user.discord_id {
+                wish.suggested_by = survivor_discord_id.clone();
+            }
+            let had_vote = wish.votes.contains(&duplicate_user.discord_id);
+            wish.votes.retain(|vote| *vote != duplicate_user.discord_id);
+            if had_vote && !wish.votes.contains(&survivor_discord_id) {
+                wish.votes.push(survivor_discord_id.clone());
+            }
+        }
+
+        //So do ratings and games
+        if let Some(rating) = self.puzzle_ratings.shift_remove(duplicate_discord_id) {
+            self.puzzle_ratings
+                .entry(survivor_discord_id.clone())
+                .or_default()
+                .merge(rating);
+        }
+        if let Some(streak) = self.puzzle_streaks.shift_remove(duplicate_discord_id) {
+            self.puzzle_streaks
+                .entry(survivor_discord_id.clone())
+                .or_default()
+                .merge(streak);
+        }
+        //Lichess snapshots and live posts follow the Lichess account the survivor ends up with
+        if let Some(snapshots) = self.lichess_ratings.shift_remove(duplicate_discord_id) {
+            if took_lichess {
+                self.lichess_ratings
+                    .insert(survivor_discord_id.clone(), snapshots);
+            }
+        }
+        if let Some(watch) = self.lichess_live.shift_remove(duplicate_discord_id) {
+            if took_lichess {
+                self.lichess_live.insert(survivor_discord_id.clone(), watch);
+            }
+        }
+        if let Some(rating) = self.club_ratings.shift_remove(&duplicate_user.discord_id) {
+            self.club_ratings
+                .entry(survivor_discord_id.clone())
+                .or_default()
+                .merge(rating);
+        }
+        if let Some(mut snapshots) = self.rating_history.shift_remove(&duplicate_user.discord_id) {
+            let history = self
+                .rating_history
+                .entry(survivor_discord_id.clone())
+                .or_default();
+            history.append(&mut snapshots);
+            history.sort_by_key(|snapshot| snapshot.taken);
+        }
+        //Games the two accounts played against each other would become games against themselves,
+        //which look like analysis boards, so they go. Ratings keep what those games did to them,
+        //as undoing it would mean replaying every rated game played since
+        let (a, b) = (duplicate_discord_id, survivor_discord_id.as_str());
+        self.games
+            .retain(|_, game| !(game.has_player(a) && game.has_player(b)));
+        self.otb_games
+            .retain(|_, game| !(game.has_player(a) && game.has_player(b)));
+        self.archive
+            .retain(|_, game| !(game.has_player(a) && game.has_player(b)));
+        for game in self.games.values_mut() {
+            game.replace_player(&duplicate_user.discord_id, &survivor_discord_id);
+        }
+        for game in self.otb_games.values_mut() {
+            game.replace_player(&duplicate_user.discord_id, &survivor_discord_id);
+        }
+        for game in self.archive.values_mut() {
+            game.replace_player(&duplicate_user.discord_id, &survivor_discord_id);
+        }
+        for tournament in self.tournaments.values_mut() {
+            tournament.replace_player(&duplicate_user.discord_id, &survivor_discord_id);
+        }
+
+        Ok(())
+    }
+
+    //Removes everything that identifies the member with `discord_id` while keeping the records
+    //that aggregate stats are built from. If they registered, their user record stays (so checkouts
+    //still count towards totals) but loses its name, discord id and linked accounts. Games, ratings,
+    //tournaments and the like are kept by discord id whether or not they registered, so anything
+    //that mentions it is anonymized and what is only about them, like their ratings, goes. `names`
+    //are what saved PGNs may call them. Anything new kept per member has to be handled here.
+    //Refused while they still have books out
+    pub fn forget_user(
+        &mut self,
+        discord_id: &str,
+        names: &[String],
+    ) -> Result<(), ManipulationError> {
+        let former_name = "Former member";
+        let mut names = names.to_vec();
+        //Their extension reasons, which are quoted in the audit log
+        let mut reasons = Vec::new();
+        let anonymous_id = match self
+            .find_user_by_discord_id(discord_id)
+            .map(|user| user.uuid)
+        {
+            Some(user) => {
+                if !self.active_checkouts_of_user(user).is_empty() {
+                    return Err(ManipulationError::new(
+                        ManipulationErrorType::HasActiveCheckouts,
+                    ));
+                }
+                let anonymous_id = format!("forgotten-{}", Database::encode_uuid(user));
+                let record = self.users.get_mut(&user).unwrap();
+                record.discord_id = anonymous_id.clone();
+                names.push(std::mem::replace(
+                    &mut record.read_name,
+                    former_name.to_owned(),
+                ));
+                record.lichess = None;
+                record.chesscom = None;
+
+                for request in self.extension_requests.values_mut() {
+                    let rentee = self
+                        .checkouts
+                        .get(&request.checkout)
+                        .or_else(|| self.archived_checkouts.get(&request.checkout))
+                        .map(|c| c.rentee);
+                    if rentee == Some(user) {
+                        let reason = std::mem::replace(&mut request.reason, "[removed]".to_owned());
+                        reasons.push(reason);
+                    }
+                }
+                anonymous_id
+            }
+            None => format!("forgotten-{}", Database::encode_uuid(self.new_raw_uuid())),
+        };
+
+        for wish in self.wishlist.values_mut() {
+            if wish.suggested_by == discord_id {
+                wish.suggested_by = anonymous_id.clone();
+            }
+            for vote in wish.votes.iter_mut() {
+                if *vote == discord_id {
+                    *vote = anonymous_id.clone();
+                }
+            }
+        }
+        //Entries name them as the actor or mention them, like grants, and quote their extension
+        //reasons
+        for entry in self.audit_log.iter_mut() {
+            entry.description = entry.description.replace(discord_id, &anonymous_id);
+            if entry.actor != discord_id {
+                continue;
+            }
+            entry.actor = anonymous_id.clone();
+            for reason in reasons.iter().filter(|reason| !reason.is_empty()) {
+                entry.description = entry.description.replace(reason.as_str(), "[removed]");
+            }
+        }
+        //Permission tiers granted to them in person
+        if let Ok(id) = discord_id.parse::<u64>() {
+            self.config.member_tiers.shift_remove(&id);
+        }
+        for announcement in self.announcements.values_mut() {
+            if announcement.created_by == discord_id {
+                announcement.created_by = anonymous_id.clone();
+            }
+        }
+        //Games stay, for their opponents' records
+        for game in self.games.values_mut() {
+            game.replace_player(discord_id, &anonymous_id);
+            //Saved PGNs have the names players had when the game ended
+            if !game.has_player(&anonymous_id) {
+                continue;
+            }
+            if let Some(pgn) = &mut game.pgn {
+                for name in &names {
+                    *pgn = crate::games::rename_in_pgn(pgn, name, former_name);
+                }
+            }
+        }
+        for game in self.otb_games.values_mut() {
+            game.replace_player(discord_id, &anonymous_id);
+        }
+        for game in self.archive.values_mut() {
+            game.replace_player(discord_id, &anonymous_id);
+            if !game.has_player(&anonymous_id) {
+                continue;
+            }
+            if let Some(pgn) = &mut game.pgn {
+                for name in &names {
+                    *pgn = crate::games::rename_in_pgn(pgn, name, former_name);
+                }
+            }
+        }
+        self.puzzle_ratings.shift_remove(discord_id);
+        self.club_ratings.shift_remove(discord_id);
+        for tournament in self.tournaments.values_mut() {
+            tournament.replace_player(discord_id, &anonymous_id);
+        }
+        self.lichess_ratings.shift_remove(discord_id);
+        if let Some(game) = &mut self.vote_game {
+            if game.started_by == discord_id {
+                game.started_by = anonymous_id.clone();
+            }
+            if let Some(vote) = game.votes.shift_remove(discord_id) {
+                game.votes.insert(anonymous_id.clone(), vote);
+            }
+        }
+        if let Some(simul) = &mut self.simul {
+            for id in std::iter::once(&mut simul.host).chain(simul.challengers.iter_mut()) {
+                if *id == discord_id {
+                    *id = anonymous_id.clone();
+                }
+            }
+        }
+        let gotw = &mut self.game_of_the_week;
+        for nominator in gotw.nominations.values_mut() {
+            if *nominator == discord_id {
+                *nominator = anonymous_id.clone();
+            }
+        }
+        if let Some(vote) = gotw.votes.shift_remove(discord_id) {
+            gotw.votes.insert(anonymous_id.clone(), vote);
+        }
+        self.puzzle_streaks.shift_remove(discord_id);
+        self.rating_history.shift_remove(discord_id);
+        for relay in self.lichess_relays.iter_mut() {
+            if relay.started_by == discord_id {
+                relay.started_by = anonymous_id.clone();
+            }
+        }
+        self.lichess_live.shift_remove(discord_id);
+        Ok(())
+    }
+
+    pub fn find_user_by_discord_id(&self, discord_id: &str) -> Option<&User> {
+        self.users
+            .values()
+            .find(|user| user.discord_id == discord_id)
+    }
+
+    //Checkouts `user` has started that haven't been completed yet, soonest due first
+    pub fn active_checkouts_of_user(&self, user: UserUuid) -> Vec<&CheckoutInstance> {
+        let mut checkouts: Vec<&CheckoutInstance> = self
+            .active_checkouts
+            .values()
+            .flatten()
+            .map(|uuid| &self.checkouts[uuid])
+            .filter(|checkout| checkout.rentee == user)
+            .collect();
+        checkouts.sort_by_key(|checkout| checkout.due_date);
+        checkouts
+    }
+
+    //Every checkout `user` has ever made, newest first. Checkouts don't store when they were created
+    //so this goes by insertion order, with ones that are still going before completed ones
+    pub fn checkouts_of_user(&self, user: UserUuid) -> Vec<&CheckoutInstance> {
+        self.checkouts
+            .values()
+            .rev()
+            .chain(self.archived_checkouts.values().rev())
+            .filter(|checkout| checkout.rentee == user)
+            .collect()
+    }
+
+    //Number of books `user` has finished reading and returned
+    pub fn books_read(&self, user: UserUuid) -> usize {
+        self.archived_checkouts
+            .values()
+            .filter(|checkout| checkout.rentee == user)
+            .count()
+    }
+
+    //How many times `book` has ever been checked out
+    pub fn checkout_count(&self, book: BookUuid) -> usize {
+        self.all_checkouts()
+            .filter(|checkout| checkout.book == book)
+            .count()
+    }
+
+    //Number of copies of `book` that are on the shelf right now
+    pub fn available_copies(&self, book: &Book) -> u32 {
+        let out = self
+            .active_checkouts
+            .get(&book.uuid)
+            .map_or(0, |uuids| uuids.len());
+        book.quantity.saturating_sub(out as u32)
+    }
+
+    //The soonest time a checked out copy of `book` is due back, if any copies have a due date
+    pub fn next_due_date(&self, book: BookUuid) -> Option<TimeType> {
+        self.active_checkouts_of(book)
+            .iter()
+            .filter_map(|checkout| checkout.due_date)
+            .min()
+    }
+
+    //Every author in the library along with the books they wrote. The name shown is the one used
+    //by the first book added for that author
+    pub fn authors(&self) -> Vec<(&str, Vec<&Book>)> {
+        self.author_index
+            .values()
+            .map(|uuids| {
+                let books: Vec<&Book> = uuids.iter().map(|uuid| &self.books[uuid]).collect();
+                (books[0].author.as_str(), books)
+            })
+            .collect()
+    }
+
+    //Finds the books by an author. An exact (case insensitive) match on the full name is
+    //preferred, otherwise any author with a name containing `query` as a word matches, so that
+    //"Silman" finds "Jeremy Silman"
+    pub fn books_by_author(&self, query: &str) -> Vec<&Book> {
+        let query = normalize_author(query);
+        let uuids: Vec<BookUuid> = match self.author_index.get(&query) {
+            Some(uuids) => uuids.clone(),
+            None => self
+                .author_index
+                .iter()
+                .filter(|(author, _)| author.split(' ').any(|word| word == query))
+                .flat_map(|(_, uuids)| uuids.iter().cloned())
+                .collect(),
+        };
+        uuids.iter().map(|uuid| &self.books[uuid]).collect()
+    }
+
+    pub fn remove_book(&mut self, uuid: BookUuid) -> Result<Book, ManipulationError> {
+        //Completed checkouts are archived, so anything still in checkouts means the book we are
+        //trying to remove is un accounted for
+        let outstanding: Vec<CheckoutUuid> = self
+            .checkouts
+            .values()
+            .filter(|checkout| checkout.book == uuid)
+            .map(|checkout| checkout.uuid)
+            .collect();
+        if !outstanding.is_empty() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::OutstandingBooksNonReturned(outstanding),
+            ));
+        }
 
         let opt_book = self.books.remove(&uuid);
         match opt_book {
             None => Err(ManipulationError::new(ManipulationErrorType::UnknownBook(
                 Database::encode_uuid(uuid),
             ))),
-            Some(book) => Ok(book),
+            Some(book) => {
+                self.title_index.retain(|(_, other)| *other != uuid);
+                let key = normalize_author(&book.author);
+                if let Some(uuids) = self.author_index.get_mut(&key) {
+                    uuids.retain(|other| *other != uuid);
+                    if uuids.is_empty() {
+                        self.author_index.shift_remove(&key);
+                    }
+                }
+                Ok(book)
+            }
+        }
+    }
+
+    //Adds or replaces the step of the escalation policy that triggers at `days_overdue`
+    pub fn set_escalation_step(&mut self, step: EscalationStep) {
+        self.escalation_policy
+            .retain(|existing| existing.days_overdue != step.days_overdue);
+        self.escalation_policy.push(step);
+        self.escalation_policy.sort_by_key(|step| step.days_overdue);
+    }
+
+    pub fn remove_escalation_step(&mut self, days_overdue: u32) -> bool {
+        let len = self.escalation_policy.len();
+        self.escalation_policy
+            .retain(|existing| existing.days_overdue != days_overdue);
+        len != self.escalation_policy.len()
+    }
+
+    //Walks every checkout that is past its due date and returns the escalation steps that have
+    //become due since the last time this was called. Suspensions are applied right away, the
+    //caller is responsible for sending any messages
+    pub fn evaluate_escalations(&mut self, now: TimeType) -> Vec<PendingEscalation> {
+        let mut pending = Vec::new();
+        for checkout in self.checkouts.values_mut() {
+            if checkout.status != CheckoutStatus::Reading {
+                continue;
+            }
+            let due_date = match checkout.due_date {
+                Some(date) if date < now => date,
+                _ => continue,
+            };
+            let days_overdue = (now - due_date).num_days();
+
+            while let Some(step) = self.escalation_policy.get(checkout.escalation_level) {
+                if (step.days_overdue as i64) > days_overdue {
+                    break;
+                }
+                checkout.escalation_level += 1;
+
+                if step.action == EscalationAction::SuspendBorrowing {
+                    if let Some(user) = self.users.get_mut(&checkout.rentee) {
+                        user.suspended = true;
+                    }
+                }
+                pending.push(PendingEscalation {
+                    checkout: checkout.uuid,
+                    rentee: checkout.rentee,
+                    book: checkout.book,
+                    days_overdue,
+                    action: step.action,
+                });
+            }
         }
+        pending
     }
 
     fn new_raw_uuid(&self) -> u32 {
@@ -243,6 +2853,15 @@ impl Database {
             if !self.users.contains_key(&uuid)
                 && !self.books.contains_key(&uuid)
                 && !self.checkouts.contains_key(&uuid)
+                && !self.archived_checkouts.contains_key(&uuid)
+                && !self.wishlist.contains_key(&uuid)
+                && !self.extension_requests.contains_key(&uuid)
+                && !self.announcements.contains_key(&uuid)
+                && !self.games.contains_key(&uuid)
+                && !self.tournaments.contains_key(&uuid)
+                && !self.otb_games.contains_key(&uuid)
+                && !self.archive.contains_key(&uuid)
+                && self.find_copy(uuid).is_none()
             {
                 return uuid;
             }
@@ -261,6 +2880,51 @@ impl Database {
         self.new_raw_uuid()
     }
 
+    pub fn new_wish_uuid(&self) -> WishUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_extension_uuid(&self) -> ExtensionUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_copy_uuid(&self) -> CopyUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_announcement_uuid(&self) -> AnnouncementUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_game_uuid(&self) -> GameUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_tournament_uuid(&self) -> TournamentUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_otb_uuid(&self) -> OtbUuid {
+        self.new_raw_uuid()
+    }
+
+    pub fn new_archive_uuid(&self) -> ArchiveUuid {
+        self.new_raw_uuid()
+    }
+
+    //Decodes an id without checking what kind of object it refers to
+    pub fn decode_raw_uuid(&self, uuid: &str) -> Option<u32> {
+        let mut decoded = [0; 4];
+        match data_encoding::BASE32_NOPAD.decode_len(uuid.len()) {
+            Ok(len) if len == decoded.len() => {}
+            _ => return None,
+        }
+        data_encoding::BASE32_NOPAD
+            .decode_mut(uuid.as_bytes(), &mut decoded)
+            .ok()?;
+        Some(u32::from_be_bytes(decoded))
+    }
+
     fn decode_raw(&self, uuid: &str) -> Result<(u32, UuidType), UuidError> {
         let len_needed = match data_encoding::BASE32_NOPAD.decode_len(uuid.len()) {
             Err(_) => return Err(UuidError::InvalidEncoding),
@@ -280,8 +2944,12 @@ impl Database {
                 UuidType::User
             } else if self.books.contains_key(&result) {
                 UuidType::Book
-            } else if self.checkouts.contains_key(&result) {
+            } else if self.checkout(result).is_some() {
                 UuidType::Checkout
+            } else if self.wishlist.contains_key(&result) {
+                UuidType::Wish
+            } else if self.find_copy(result).is_some() {
+                UuidType::Copy
             } else {
                 return Err(UuidError::NotFound);
             }
@@ -294,6 +2962,8 @@ impl Database {
             UuidType::User => UuidError::MismatchIsUserUuid,
             UuidType::Book => UuidError::MismatchIsBookUuid,
             UuidType::Checkout => UuidError::MismatchIsCheckoutUuid,
+            UuidType::Wish => UuidError::MismatchIsWishUuid,
+            UuidType::Copy => UuidError::MismatchIsCopyUuid,
             _ => unreachable!(),
         }
     }
@@ -325,6 +2995,24 @@ impl Database {
         }
     }
 
+    pub fn decode_wish_uuid(&self, uuid: &str) -> Result<WishUuid, UuidError> {
+        let (decoded, uuid_type) = self.decode_raw(uuid)?;
+        if uuid_type != UuidType::Wish {
+            Err(Database::uuid_type_to_mismatch_error(uuid_type))
+        } else {
+            Ok(decoded)
+        }
+    }
+
+    pub fn decode_copy_uuid(&self, uuid: &str) -> Result<CopyUuid, UuidError> {
+        let (decoded, uuid_type) = self.decode_raw(uuid)?;
+        if uuid_type != UuidType::Copy {
+            Err(Database::uuid_type_to_mismatch_error(uuid_type))
+        } else {
+            Ok(decoded)
+        }
+    }
+
     pub fn encode_uuid(uuid: u32) -> String {
         let bytes: [u8; 4] = uuid.to_be_bytes();
         data_encoding::BASE32_NOPAD.encode(&bytes[0..4])
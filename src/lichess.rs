@@ -0,0 +1,370 @@
+use once_cell::sync::Lazy;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serenity::prelude::RwLock;
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::guilds::Libraries;
+use crate::library::{Database, TimeType};
+
+//Members link their Lichess accounts with Lichess' OAuth, so that the bot knows an account really
+//is theirs before trusting it for ratings, games and teams. Lichess normally sends people back to a
+//web page after they approve, but the bot doesn't run a web server, so linking works like signing
+//in on a TV: !lichess link DMs the member an authorization link, Lichess sends them on to an
+//address that won't load, and they paste that address back with !lichess link <address>. The
+//code in it is traded for a token, which is only used to read the account's username and is then
+//revoked. PKCE means no client secret is needed
+
+type LichessError = Box<dyn std::error::Error + Send + Sync>;
+
+const DEFAULT_URL: &str = "https://lichess.org";
+//Lichess doesn't need apps to be registered, any id works
+const DEFAULT_CLIENT_ID: &str = "chess-club-bot";
+const DEFAULT_REDIRECT_URI: &str = "http://localhost/lichess-link";
+//Links that weren't finished are forgotten after this long
+const LINK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const VERIFIER_LENGTH: usize = 64;
+//How often linked members' Lichess ratings are fetched again
+const RATING_SYNC_SECS: u64 = 6 * 60 * 60;
+//Lichess answers questions about at most this many users at once
+const USERS_PER_REQUEST: usize = 300;
+//Snapshots kept per member. Older ones are dropped
+const MAX_SNAPSHOTS: usize = 365;
+
+//Overridable with LICHESS_URL, for testing against a local Lichess
+pub fn base_url() -> String {
+    env::var("LICHESS_URL").unwrap_or_else(|_| DEFAULT_URL.to_owned())
+}
+
+fn client_id() -> String {
+    env::var("LICHESS_CLIENT_ID").unwrap_or_else(|_| DEFAULT_CLIENT_ID.to_owned())
+}
+
+fn redirect_uri() -> String {
+    env::var("LICHESS_REDIRECT_URI").unwrap_or_else(|_| DEFAULT_REDIRECT_URI.to_owned())
+}
+
+struct PendingLink {
+    //Guild the member started linking from, whose User record gets the account
+    guild: Option<u64>,
+    verifier: String,
+    state: String,
+    started: Instant,
+}
+
+//By discord id
+static PENDING: Lazy<Mutex<HashMap<u64, PendingLink>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+//Starts linking for `member`, replacing a link they already started. Returns the address to
+//approve the link at
+pub fn start(member: u64, guild: Option<u64>) -> Result<String, LichessError> {
+    let verifier = random_string(VERIFIER_LENGTH);
+    let state = random_string(16);
+    let challenge = data_encoding::BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()));
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/oauth", base_url()),
+        &[
+            ("response_type", "code"),
+            ("client_id", &client_id()),
+            ("redirect_uri", &redirect_uri()),
+            ("code_challenge_method", "S256"),
+            ("code_challenge", &challenge),
+            ("state", &state),
+        ],
+    )?;
+    PENDING.lock().unwrap().insert(
+        member,
+        PendingLink {
+            guild,
+            verifier,
+            state,
+            started: Instant::now(),
+        },
+    );
+    Ok(url.to_string())
+}
+
+//Called from flows.rs
+pub fn forget_expired() {
+    PENDING
+        .lock()
+        .unwrap()
+        .retain(|_, link| link.started.elapsed() < LINK_TIMEOUT);
+}
+
+//Finishes the link `member` started with what they pasted back: the address Lichess sent them to,
+//or just the code from it. Returns the guild they started from and their Lichess username
+pub async fn finish(member: u64, pasted: &str) -> Result<(Option<u64>, String), LichessError> {
+    let link =
+        PENDING.lock().unwrap().remove(&member).ok_or(
+            "You haven't started linking, or it took too long. Use !lichess link to start",
+        )?;
+
+    let code = match reqwest::Url::parse(pasted) {
+        Ok(url) => {
+            let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+            if let Some(error) = query.get("error") {
+                return Err(
+                    format!("Lichess said {}. Use !lichess link to try again", error).into(),
+                );
+            }
+            if query.get("state") != Some(&link.state) {
+                return Err(
+                    "That address is from a different link. Use !lichess link to start again"
+                        .into(),
+                );
+            }
+            query.get("code").cloned().ok_or(
+                "That address has no code in it. Paste the whole address Lichess sent you to",
+            )?
+        }
+        Err(_) => pasted.to_owned(),
+    };
+
+    let client = reqwest::Client::new();
+    let base = base_url();
+    let response = client
+        .post(format!("{}/api/token", base))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("code_verifier", &link.verifier),
+            ("redirect_uri", &redirect_uri()),
+            ("client_id", &client_id()),
+        ])
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err("Lichess didn't accept that code. Use !lichess link to try again".into());
+    }
+    let body: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+    let token = body["access_token"]
+        .as_str()
+        .ok_or("Lichess didn't send a token")?
+        .to_owned();
+
+    let account = client
+        .get(format!("{}/api/account", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    //The token isn't needed any more, whatever happened
+    let revoked = client
+        .delete(format!("{}/api/token", base))
+        .bearer_auth(&token)
+        .send()
+        .await;
+    if let Err(err) = revoked {
+        println!("Failed to revoke a Lichess token: {:?}", err);
+    }
+
+    let account: serde_json::Value = serde_json::from_str(&account?.text().await?)?;
+    let username = account["username"]
+        .as_str()
+        .ok_or("Lichess didn't say who the account belongs to")?;
+    Ok((link.guild, username.to_owned()))
+}
+
+//One of a member's Lichess ratings
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerfRating {
+    pub rating: u32,
+    //Lichess hasn't seen enough games to trust the rating yet
+    pub provisional: bool,
+}
+
+impl std::fmt::Display for PerfRating {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let provisional = if self.provisional { "?" } else { "" };
+        write!(fmt, "{}{}", self.rating, provisional)
+    }
+}
+
+//A member's Lichess ratings as they were when fetched. None for the time controls they haven't
+//played
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LichessRatings {
+    pub taken: TimeType,
+    pub blitz: Option<PerfRating>,
+    pub rapid: Option<PerfRating>,
+    pub classical: Option<PerfRating>,
+}
+
+impl LichessRatings {
+    fn same_as(&self, other: &LichessRatings) -> bool {
+        self.blitz == other.blitz && self.rapid == other.rapid && self.classical == other.classical
+    }
+
+    //Blitz 1850 · Rapid 1900?, leaving out the time controls that haven't been played
+    pub fn describe(&self) -> Option<String> {
+        let ratings: Vec<String> = [
+            ("Blitz", self.blitz),
+            ("Rapid", self.rapid),
+            ("Classical", self.classical),
+        ]
+        .iter()
+        .filter_map(|(name, rating)| rating.map(|rating| format!("{} {}", name, rating)))
+        .collect();
+        if ratings.is_empty() {
+            None
+        } else {
+            Some(ratings.join(" · "))
+        }
+    }
+}
+
+fn perf(user: &serde_json::Value, name: &str) -> Option<PerfRating> {
+    let perf = &user["perfs"][name];
+    //Lichess lists time controls that were never played with no games
+    if perf["games"].as_u64() == Some(0) {
+        return None;
+    }
+    Some(PerfRating {
+        rating: perf["rating"].as_u64()? as u32,
+        provisional: perf["prov"].as_bool().unwrap_or(false),
+    })
+}
+
+//Fetches the ratings of `usernames`, by lowercased username. Accounts Lichess doesn't know about
+//any more are left out
+async fn fetch_ratings(
+    usernames: &[String],
+) -> Result<HashMap<String, LichessRatings>, LichessError> {
+    let client = reqwest::Client::new();
+    let now = chrono::Local::now();
+    let mut ratings = HashMap::new();
+    for chunk in usernames.chunks(USERS_PER_REQUEST) {
+        let response = client
+            .post(format!("{}/api/users", base_url()))
+            .body(chunk.join(","))
+            .send()
+            .await?
+            .error_for_status()?;
+        let users: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        for user in users.as_array().into_iter().flatten() {
+            let username = match user["username"].as_str() {
+                Some(username) => username.to_lowercase(),
+                None => continue,
+            };
+            ratings.insert(
+                username,
+                LichessRatings {
+                    taken: now,
+                    blitz: perf(user, "blitz"),
+                    rapid: perf(user, "rapid"),
+                    classical: perf(user, "classical"),
+                },
+            );
+        }
+    }
+    Ok(ratings)
+}
+
+//Fetches the Lichess ratings of every member of the library who has linked an account, and
+//records a snapshot for each of them whose ratings changed
+pub async fn sync_ratings(library_arc: &RwLock<Database>) -> Result<(), LichessError> {
+    let linked: Vec<(String, String)> = library_arc
+        .read()
+        .await
+        .users
+        .values()
+        .filter_map(|user| Some((user.discord_id.clone(), user.lichess.clone()?)))
+        .collect();
+    if linked.is_empty() {
+        return Ok(());
+    }
+    let usernames: Vec<String> = linked
+        .iter()
+        .map(|(_, username)| username.clone())
+        .collect();
+    let fetched = fetch_ratings(&usernames).await?;
+
+    let mut library = library_arc.write().await;
+    for (member, username) in linked {
+        let ratings = match fetched.get(&username.to_lowercase()) {
+            Some(ratings) => ratings.clone(),
+            None => continue,
+        };
+        let snapshots = library.lichess_ratings.entry(member).or_default();
+        //While nothing changes, the snapshot from when the ratings last changed is kept and the one
+        //after it is moved along to now
+        let unchanged = snapshots.len() >= 2
+            && snapshots[snapshots.len() - 2..]
+                .iter()
+                .all(|snapshot| snapshot.same_as(&ratings));
+        if unchanged {
+            *snapshots.last_mut().unwrap() = ratings;
+        } else {
+            snapshots.push(ratings);
+        }
+        if snapshots.len() > MAX_SNAPSHOTS {
+            let extra = snapshots.len() - MAX_SNAPSHOTS;
+            snapshots.drain(..extra);
+        }
+    }
+    crate::autosave::persist_change(&library).await;
+    Ok(())
+}
+
+//Background task that keeps linked members' Lichess ratings up to date
+pub async fn rating_sync_task(libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(RATING_SYNC_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            if let Err(err) = sync_ratings(&library_arc).await {
+                println!(
+                    "Failed to sync Lichess ratings for guild {:?}: {:?}",
+                    guild, err
+                );
+            }
+        }
+    }
+}
+
+//Lichess game ids are this long. Addresses of games seen from black's side, or of a player's
+//view of the game, add more after it
+const GAME_ID_LENGTH: usize = 8;
+
+//The id of the game at a Lichess address like https://lichess.org/abcdEFGH/black, or the id
+//itself
+pub fn game_id(input: &str) -> Option<String> {
+    let path = match reqwest::Url::parse(input) {
+        Ok(url) => url.path_segments()?.next()?.to_owned(),
+        Err(_) => input.to_owned(),
+    };
+    let id: String = path.chars().take(GAME_ID_LENGTH).collect();
+    if id.len() == GAME_ID_LENGTH && id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+//The PGN of Lichess game `id`, or None if there is no such game
+pub async fn export_game(id: &str) -> Result<Option<String>, LichessError> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/game/export/{}", base_url(), id))
+        .query(&[("clocks", "false"), ("evals", "false")])
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.text().await?))
+}
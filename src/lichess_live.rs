@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use serenity::{http::Http, model::id::ChannelId};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library::{ChannelKind, TimeType};
+
+//Members who linked their Lichess account can let the club know when they are playing there. Once
+//they turn it on with !lichess live on, the live task asks Lichess every few minutes which of them
+//are in a game, and posts a link to watch each new game in the lichess-live channel, which admins
+//set with !config channel. Every game is posted once, and each member at most once every
+//COOLDOWN_MINUTES, so that a run of bullet games doesn't flood the channel
+
+type LiveError = Box<dyn std::error::Error + Send + Sync>;
+
+//How often the live task asks Lichess who is playing
+const LIVE_CHECK_SECS: u64 = 2 * 60;
+const COOLDOWN_MINUTES: i64 = 30;
+//Lichess answers status questions about at most this many users at once
+const USERS_PER_REQUEST: usize = 100;
+
+//A member who turned live notifications on
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LiveWatch {
+    //Lichess id of the last game posted about
+    pub last_game: Option<String>,
+    pub last_posted: Option<TimeType>,
+}
+
+impl LiveWatch {
+    fn cooling_down(&self, now: TimeType) -> bool {
+        self.last_posted.map_or(false, |posted| {
+            now - posted < chrono::Duration::minutes(COOLDOWN_MINUTES)
+        })
+    }
+}
+
+//The games `usernames` are playing, by lowercased username. Those who aren't playing are left out
+async fn playing(usernames: &[String]) -> Result<HashMap<String, String>, LiveError> {
+    let client = reqwest::Client::new();
+    let mut games = HashMap::new();
+    for chunk in usernames.chunks(USERS_PER_REQUEST) {
+        let response = client
+            .get(format!("{}/api/users/status", crate::lichess::base_url()))
+            .query(&[("ids", chunk.join(",")), ("withGameIds", "true".to_owned())])
+            .send()
+            .await?
+            .error_for_status()?;
+        let users: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        for user in users.as_array().into_iter().flatten() {
+            if let (Some(id), Some(game)) = (user["id"].as_str(), user["playingId"].as_str()) {
+                games.insert(id.to_lowercase(), game.to_owned());
+            }
+        }
+    }
+    Ok(games)
+}
+
+//What a post says about a game: "a rated blitz game against DrNykterstein"
+async fn describe_game(game: &str, username: &str) -> Result<String, LiveError> {
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/game/export/{}",
+            crate::lichess::base_url(),
+            game
+        ))
+        .query(&[("moves", "false"), ("clocks", "false"), ("evals", "false")])
+        .header("Accept", "application/json")
+        .send()
+        .await?
+        .error_for_status()?;
+    let json: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+    let rated = if json["rated"].as_bool().unwrap_or(false) {
+        "rated"
+    } else {
+        "casual"
+    };
+    let speed = json["speed"].as_str().unwrap_or("chess");
+    let player_name = |colour: &str| {
+        let player = &json["players"][colour];
+        match player["aiLevel"].as_u64() {
+            Some(level) => Some(format!("Stockfish level {}", level)),
+            None => player["user"]["name"].as_str().map(str::to_owned),
+        }
+    };
+    let (white, black) = (player_name("white"), player_name("black"));
+    let opponent = if white
+        .as_deref()
+        .map_or(false, |white| white.eq_ignore_ascii_case(username))
+    {
+        black
+    } else {
+        white
+    };
+    Ok(match opponent {
+        Some(opponent) => format!("a {} {} game against {}", rated, speed, opponent),
+        None => format!("a {} {} game", rated, speed),
+    })
+}
+
+//Background task that posts the Lichess games of members who turned live notifications on
+pub async fn live_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(LIVE_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            let now = chrono::Local::now();
+            //Discord id and Lichess username of each member who could be posted about now
+            let (channel, watched) = {
+                let library = library_arc.read().await;
+                let channel = match library.channel(ChannelKind::LichessLive) {
+                    Some(channel) if !library.lichess_live.is_empty() => ChannelId(channel),
+                    _ => continue,
+                };
+                let watched: Vec<(String, String)> = library
+                    .users
+                    .values()
+                    .filter_map(|user| {
+                        let watch = library.lichess_live.get(&user.discord_id)?;
+                        if watch.cooling_down(now) {
+                            return None;
+                        }
+                        Some((user.discord_id.clone(), user.lichess.clone()?))
+                    })
+                    .collect();
+                (channel, watched)
+            };
+            if watched.is_empty() {
+                continue;
+            }
+
+            let usernames: Vec<String> = watched
+                .iter()
+                .map(|(_, username)| username.clone())
+                .collect();
+            let games = match playing(&usernames).await {
+                Ok(games) => games,
+                Err(err) => {
+                    println!(
+                        "Failed to ask Lichess who is playing for guild {:?}: {:?}",
+                        guild, err
+                    );
+                    continue;
+                }
+            };
+
+            //Members playing each other are both noted, but the game is only posted once
+            let mut posted_games = HashSet::new();
+            let mut noted = Vec::new();
+            for (member, username) in watched {
+                let game = match games.get(&username.to_lowercase()) {
+                    Some(game) => game.clone(),
+                    None => continue,
+                };
+                let already = library_arc
+                    .read()
+                    .await
+                    .lichess_live
+                    .get(&member)
+                    .map_or(true, |watch| {
+                        watch.last_game.as_deref() == Some(game.as_str())
+                    });
+                if already {
+                    continue;
+                }
+                if posted_games.insert(game.clone()) {
+                    let what = match describe_game(&game, &username).await {
+                        Ok(what) => what,
+                        Err(err) => {
+                            println!("Failed to look up Lichess game {}: {:?}", game, err);
+                            "a game".to_owned()
+                        }
+                    };
+                    let text = format!(
+                        "<@{}> ({}) is playing {} on Lichess. Watch at {}/{}",
+                        member,
+                        username,
+                        what,
+                        crate::lichess::base_url(),
+                        game
+                    );
+                    let result = channel
+                        .send_message(&http, |m| {
+                            m.content(text).allowed_mentions(|a| a.empty_parse())
+                        })
+                        .await;
+                    if let Err(err) = result {
+                        println!(
+                            "Failed to post a live Lichess game for guild {:?}: {:?}",
+                            guild, err
+                        );
+                        continue;
+                    }
+                }
+                noted.push((member, game));
+            }
+            if noted.is_empty() {
+                continue;
+            }
+
+            let mut library = library_arc.write().await;
+            for (member, game) in noted {
+                //Members may have turned notifications off meanwhile
+                if let Some(watch) = library.lichess_live.get_mut(&member) {
+                    watch.last_game = Some(game);
+                    watch.last_posted = Some(now);
+                }
+            }
+            crate::autosave::persist_change(&library).await;
+        }
+    }
+}
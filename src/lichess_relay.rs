@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+use serenity::{builder::CreateEmbed, http::Http, model::id::ChannelId};
+
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library::{Database, TimeType};
+
+//Club events held on Lichess, arenas or Swiss tournaments, can be followed from Discord. Officers
+//start a relay with !lichess relay <tournament address> in the channel the standings should go to,
+//and every minute the relay task fetches the tournament from the Lichess API and edits one
+//standings embed there with the leaders. Once the tournament is over the final standings are left
+//in place, the winner is announced and the relay stops. Relays are saved with the library, so they
+//carry on after a restart
+
+type RelayError = Box<dyn std::error::Error + Send + Sync>;
+
+//How often the relay task fetches the tournaments being relayed
+const RELAY_SECS: u64 = 60;
+//Players shown in the standings
+const TOP_PLAYERS: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayKind {
+    Arena,
+    Swiss,
+}
+
+impl RelayKind {
+    //Where tournaments of the kind live on Lichess, in their addresses and in the API
+    fn path(self) -> &'static str {
+        match self {
+            RelayKind::Arena => "tournament",
+            RelayKind::Swiss => "swiss",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LichessRelay {
+    pub kind: RelayKind,
+    //The tournament's id on Lichess
+    pub id: String,
+    pub channel: u64,
+    //The standings embed, which is edited with every update
+    pub message: u64,
+    //Discord id of the officer who started the relay
+    pub started_by: String,
+    pub started: TimeType,
+}
+
+impl LichessRelay {
+    pub fn url(&self) -> String {
+        tournament_url(self.kind, &self.id)
+    }
+}
+
+fn tournament_url(kind: RelayKind, id: &str) -> String {
+    format!("{}/{}/{}", crate::lichess::base_url(), kind.path(), id)
+}
+
+//A tournament's address, like https://lichess.org/tournament/Xy12AbCd or
+//https://lichess.org/swiss/Xy12AbCd, split into its kind and id
+pub fn parse_url(input: &str) -> Option<(RelayKind, String)> {
+    let (kind, rest) = [RelayKind::Arena, RelayKind::Swiss]
+        .iter()
+        .find_map(|kind| {
+            let marker = format!("/{}/", kind.path());
+            let start = input.find(&marker)?;
+            Some((*kind, &input[start + marker.len()..]))
+        })?;
+    let id = rest.split(|c| c == '/' || c == '?' || c == '#').next()?;
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some((kind, id.to_owned()))
+    } else {
+        None
+    }
+}
+
+pub struct Standing {
+    pub rank: u64,
+    pub username: String,
+    pub rating: Option<u64>,
+    //Points in arenas, which are whole, or in Swiss tournaments, which can be halves
+    pub score: f64,
+}
+
+//Where a tournament stands, as Lichess sees it
+pub struct Standings {
+    pub name: String,
+    //Like "Round 3 of 7" or "12 minutes left"
+    pub status: String,
+    pub finished: bool,
+    pub players: u64,
+    //The leaders, best first
+    pub top: Vec<Standing>,
+}
+
+impl Standings {
+    pub fn winner(&self) -> Option<&Standing> {
+        self.top.first().filter(|_| self.finished)
+    }
+}
+
+async fn get(url: String) -> Result<String, RelayError> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+//Fetches tournament `id` and its leaders from Lichess
+pub async fn fetch(kind: RelayKind, id: &str) -> Result<Standings, RelayError> {
+    let api = format!("{}/api/{}/{}", crate::lichess::base_url(), kind.path(), id);
+    let info: serde_json::Value = serde_json::from_str(&get(api.clone()).await?)?;
+    let minutes = |seconds: &serde_json::Value| (seconds.as_u64().unwrap_or(0) + 59) / 60;
+    let (name, status, finished) = match kind {
+        RelayKind::Arena => {
+            let finished = info["isFinished"].as_bool().unwrap_or(false);
+            let status = if finished {
+                "Finished".to_owned()
+            } else if info["isStarted"].as_bool().unwrap_or(false) {
+                format!("{} minutes left", minutes(&info["secondsToFinish"]))
+            } else {
+                format!("Starts in {} minutes", minutes(&info["secondsToStart"]))
+            };
+            (info["fullName"].as_str(), status, finished)
+        }
+        RelayKind::Swiss => {
+            let (status, finished) = match info["status"].as_str() {
+                Some("finished") => ("Finished".to_owned(), true),
+                Some("started") => (
+                    format!(
+                        "Round {} of {}",
+                        info["round"].as_u64().unwrap_or(0),
+                        info["nbRounds"].as_u64().unwrap_or(0)
+                    ),
+                    false,
+                ),
+                _ => ("Not started yet".to_owned(), false),
+            };
+            (info["name"].as_str(), status, finished)
+        }
+    };
+
+    //Results come as a line of json per player, best first
+    let results = get(format!("{}/results?nb={}", api, TOP_PLAYERS)).await?;
+    let mut top = Vec::new();
+    for line in results.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let player: serde_json::Value = serde_json::from_str(line)?;
+        let score = match kind {
+            RelayKind::Arena => player["score"].as_f64(),
+            RelayKind::Swiss => player["points"].as_f64(),
+        };
+        top.push(Standing {
+            rank: player["rank"].as_u64().unwrap_or(top.len() as u64 + 1),
+            username: player["username"].as_str().unwrap_or("?").to_owned(),
+            rating: player["rating"].as_u64(),
+            score: score.unwrap_or(0.0),
+        });
+    }
+
+    Ok(Standings {
+        name: name.unwrap_or(id).to_owned(),
+        status,
+        finished,
+        players: info["nbPlayers"].as_u64().unwrap_or(0),
+        top,
+    })
+}
+
+fn standings_embed<'a>(
+    e: &'a mut CreateEmbed,
+    standings: &Standings,
+    url: &str,
+) -> &'a mut CreateEmbed {
+    let lines: Vec<String> = standings
+        .top
+        .iter()
+        .map(|standing| {
+            let rating = standing
+                .rating
+                .map_or_else(String::new, |rating| format!(" ({})", rating));
+            format!(
+                "{}. **{}**{}: {}",
+                standing.rank, standing.username, rating, standing.score
+            )
+        })
+        .collect();
+    let description = if lines.is_empty() {
+        "Nobody has joined yet".to_owned()
+    } else {
+        lines.join("\n")
+    };
+    e.colour(crate::response::Tone::Info.colour())
+        .title(&standings.name)
+        .url(url)
+        .description(description)
+        .footer(|f| {
+            f.text(format!(
+                "{} · {} players · updated {}",
+                standings.status,
+                standings.players,
+                chrono::Local::now().format("%H:%M")
+            ))
+        })
+}
+
+//Posts the standings of tournament `id` in `channel` for a new relay. Returns the message, which
+//later updates edit
+pub async fn post(
+    http: &Http,
+    channel: ChannelId,
+    kind: RelayKind,
+    id: &str,
+    standings: &Standings,
+) -> serenity::Result<u64> {
+    let url = tournament_url(kind, id);
+    let message = channel
+        .send_message(http, |m| m.embed(|e| standings_embed(e, standings, &url)))
+        .await?;
+    Ok(message.id.0)
+}
+
+//Fetches the tournament of `relay` and edits its standings embed. Returns whether it is over
+async fn update(http: &Http, relay: &LichessRelay) -> Result<bool, RelayError> {
+    let standings = fetch(relay.kind, &relay.id).await?;
+    let url = relay.url();
+    let channel = ChannelId(relay.channel);
+    channel
+        .edit_message(http, relay.message, |m| {
+            m.embed(|e| standings_embed(e, &standings, &url))
+        })
+        .await?;
+    if standings.finished {
+        let text = match standings.winner() {
+            Some(winner) => format!(
+                "**{}** is over, congratulations to {} for winning with {} points! Final standings: {}",
+                standings.name, winner.username, winner.score, url
+            ),
+            None => format!("**{}** is over. Final standings: {}", standings.name, url),
+        };
+        channel.say(http, text).await?;
+    }
+    Ok(standings.finished)
+}
+
+//Background task that keeps the standings of the tournaments being relayed up to date
+pub async fn relay_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(RELAY_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            let relays = library_arc.read().await.lichess_relays.clone();
+            if relays.is_empty() {
+                continue;
+            }
+
+            //The library isn't held while Lichess is asked
+            let mut over = Vec::new();
+            for relay in relays {
+                match update(&http, &relay).await {
+                    Ok(true) => over.push(relay),
+                    Ok(false) => {}
+                    Err(err) => println!(
+                        "Failed to relay Lichess tournament {} for guild {:?}: {:?}",
+                        relay.id, guild, err
+                    ),
+                }
+            }
+            if over.is_empty() {
+                continue;
+            }
+            let mut library = library_arc.write().await;
+            library.lichess_relays.retain(|relay| {
+                !over
+                    .iter()
+                    .any(|done| done.id == relay.id && done.channel == relay.channel)
+            });
+            crate::autosave::persist_change(&library).await;
+        }
+    }
+}
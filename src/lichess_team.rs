@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId},
+    prelude::RwLock,
+};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library::{ChannelKind, Database, TimeType};
+
+//Clubs with a Lichess team can keep it in step with the server. Admins pick the team and a role
+//with !lichess team, and every few hours the team sync task fetches the team's members from
+//Lichess. Members who linked their Lichess account with !lichess link, so that the bot knows it
+//really is theirs, are given the role while they are in the team and lose it once they leave it or
+//unlink. Team members nobody in the server has linked are listed for officers in the library-log
+//channel, each of them once, so that they can be asked to link
+
+type TeamError = Box<dyn std::error::Error + Send + Sync>;
+
+//How often the team sync task fetches the teams' members
+const TEAM_SYNC_SECS: u64 = 6 * 60 * 60;
+//Lists of usernames stop after this many, so that big teams fit in a message
+const MAX_LISTED: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LichessTeam {
+    //The team's id, as in its address on Lichess
+    pub id: String,
+    //Role given to the linked members of the team
+    pub role: u64,
+    //Discord ids of the members the bot gave the role to. Only they have it taken away again
+    pub granted: Vec<String>,
+    //Lichess usernames of the team's members that nobody has linked, as of the last sync
+    pub unlinked: Vec<String>,
+    pub last_sync: Option<TimeType>,
+}
+
+impl LichessTeam {
+    pub fn new(id: String, role: u64) -> Self {
+        LichessTeam {
+            id,
+            role,
+            granted: Vec::new(),
+            unlinked: Vec::new(),
+            last_sync: None,
+        }
+    }
+
+    //The team, its role and the members who haven't linked their accounts, for !lichess team
+    pub fn describe(&self) -> String {
+        let synced = match self.last_sync {
+            Some(last_sync) => format!("last synced {}", last_sync.format("%b %-d at %H:%M")),
+            None => "not synced yet".to_owned(),
+        };
+        let mut text = format!(
+            "Lichess team {}, whose linked members get <@&{}>. {} member(s) have it, {}",
+            self.id,
+            self.role,
+            self.granted.len(),
+            synced
+        );
+        if self.unlinked.is_empty() {
+            text.push_str("\nEvery member of the team has linked their Lichess account");
+        } else {
+            text.push_str(&format!(
+                "\nThese members of the team haven't linked their Lichess account: {}",
+                list(&self.unlinked)
+            ));
+        }
+        text
+    }
+}
+
+//`names` joined with commas, leaving out all but the first MAX_LISTED
+pub fn list(names: &[String]) -> String {
+    let mut text = names
+        .iter()
+        .take(MAX_LISTED)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > MAX_LISTED {
+        text.push_str(&format!(" and {} more", names.len() - MAX_LISTED));
+    }
+    text
+}
+
+//What a sync changed
+pub struct TeamSync {
+    pub granted: usize,
+    pub removed: usize,
+    //Role changes Discord refused, usually because the bot may not manage the role
+    pub failed: usize,
+    //Team members nobody has linked who weren't unlinked at the last sync
+    pub new_unlinked: Vec<String>,
+}
+
+//The team id in a team's address, like https://lichess.org/team/my-club, or the id itself
+pub fn parse_team(input: &str) -> Option<String> {
+    let id = match input.find("/team/") {
+        Some(start) => &input[start + "/team/".len()..],
+        None => input,
+    };
+    let id = id.split(|c| c == '/' || c == '?').next()?;
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Some(id.to_lowercase())
+    } else {
+        None
+    }
+}
+
+//The lowercased usernames of team `id`'s members. Lichess sends them a line of json each
+async fn fetch_members(id: &str) -> Result<Vec<String>, TeamError> {
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/api/team/{}/users",
+            crate::lichess::base_url(),
+            id
+        ))
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut members = Vec::new();
+    for line in response.text().await?.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let user: serde_json::Value = serde_json::from_str(line)?;
+        if let Some(name) = user["id"].as_str().or_else(|| user["username"].as_str()) {
+            members.push(name.to_lowercase());
+        }
+    }
+    Ok(members)
+}
+
+//Fetches the members of the library's Lichess team and gives or takes away its role in `guild`
+//to match. Returns None when no team is set
+pub async fn sync(
+    http: &Http,
+    guild: GuildId,
+    library_arc: &RwLock<Database>,
+) -> Result<Option<TeamSync>, TeamError> {
+    let team = match library_arc.read().await.lichess_team.clone() {
+        Some(team) => team,
+        None => return Ok(None),
+    };
+    let members = fetch_members(&team.id).await?;
+
+    //Lowercased Lichess username -> discord id of whoever linked it
+    let linked: HashMap<String, String> = library_arc
+        .read()
+        .await
+        .users
+        .values()
+        .filter_map(|user| {
+            let lichess = user.lichess.as_ref()?;
+            Some((lichess.to_lowercase(), user.discord_id.clone()))
+        })
+        .collect();
+    let in_team: Vec<String> = members
+        .iter()
+        .filter_map(|member| linked.get(member).cloned())
+        .collect();
+    let unlinked: Vec<String> = members
+        .iter()
+        .filter(|member| !linked.contains_key(*member))
+        .cloned()
+        .collect();
+
+    //The library isn't held while Discord changes the roles
+    let mut granted = team.granted.clone();
+    let mut result = TeamSync {
+        granted: 0,
+        removed: 0,
+        failed: 0,
+        new_unlinked: unlinked
+            .iter()
+            .filter(|member| !team.unlinked.contains(member))
+            .cloned()
+            .collect(),
+    };
+    for member in &in_team {
+        if granted.contains(member) {
+            continue;
+        }
+        let id = match member.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        match http.add_member_role(guild.0, id, team.role).await {
+            Ok(()) => {
+                granted.push(member.clone());
+                result.granted += 1;
+            }
+            Err(err) => {
+                println!(
+                    "Failed to give Lichess team member {} their role in guild {:?}: {:?}",
+                    member, guild, err
+                );
+                result.failed += 1;
+            }
+        }
+    }
+    let left: Vec<String> = granted
+        .iter()
+        .filter(|member| !in_team.contains(member))
+        .cloned()
+        .collect();
+    for member in left {
+        let id = match member.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        match http.remove_member_role(guild.0, id, team.role).await {
+            Ok(()) => {
+                granted.retain(|granted| *granted != member);
+                result.removed += 1;
+            }
+            Err(err) => {
+                println!(
+                    "Failed to take the Lichess team role from {} in guild {:?}: {:?}",
+                    member, guild, err
+                );
+                result.failed += 1;
+            }
+        }
+    }
+
+    let mut library = library_arc.write().await;
+    match &mut library.lichess_team {
+        //The team may have been changed while Discord was busy
+        Some(current) if current.id == team.id && current.role == team.role => {
+            current.granted = granted;
+            current.unlinked = unlinked;
+            current.last_sync = Some(chrono::Local::now());
+        }
+        _ => return Ok(Some(result)),
+    }
+    crate::autosave::persist_change(&library).await;
+    Ok(Some(result))
+}
+
+//Background task that keeps each guild's Lichess team role up to date and tells officers about team
+//members nobody has linked
+pub async fn team_sync_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(TEAM_SYNC_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            let guild = match guild {
+                Some(guild) => guild,
+                None => continue,
+            };
+            let result = match sync(&http, guild, &library_arc).await {
+                Ok(Some(result)) => result,
+                Ok(None) => continue,
+                Err(err) => {
+                    println!(
+                        "Failed to sync the Lichess team of guild {:?}: {:?}",
+                        guild, err
+                    );
+                    continue;
+                }
+            };
+            if result.new_unlinked.is_empty() {
+                continue;
+            }
+            let (team, channel) = {
+                let library = library_arc.read().await;
+                let team = match &library.lichess_team {
+                    Some(team) => team.id.clone(),
+                    None => continue,
+                };
+                (team, library.channel(ChannelKind::LibraryLog))
+            };
+            let channel = match channel {
+                Some(channel) => ChannelId(channel),
+                None => {
+                    println!(
+                        "No library-log channel set for guild {:?}, can't report unlinked Lichess team members",
+                        guild
+                    );
+                    continue;
+                }
+            };
+            let text = format!(
+                "These members of the Lichess team {} haven't linked their Lichess account here: {}\nThey get the team role once they link it with !lichess link",
+                team,
+                list(&result.new_unlinked)
+            );
+            if let Err(err) = channel.say(&http, text).await {
+                println!(
+                    "Failed to report unlinked Lichess team members for guild {:?}: {:?}",
+                    guild, err
+                );
+            }
+        }
+    }
+}
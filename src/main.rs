@@ -5,15 +5,21 @@ use serenity::{
     framework::standard::{
         help_commands,
         macros::{check, command, group, help, hook},
-        Args, CommandGroup, CommandOptions, CommandResult, DispatchError, HelpOptions, Reason,
-        StandardFramework,
+        Args, CommandError, CommandGroup, CommandOptions, CommandResult, DispatchError,
+        HelpOptions, Reason, StandardFramework,
     },
     http::Http,
     model::{
-        channel::{Channel, Message},
-        gateway::Ready,
-        id::UserId,
+        channel::{Channel, Message, Reaction, ReactionType},
+        gateway::{GatewayIntents, Ready},
+        guild::{Guild, Member},
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            Interaction, InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
         permissions::Permissions,
+        user::User,
     },
 };
 
@@ -26,23 +32,268 @@ use std::sync::Arc;
 
 use signal_hook::iterator::Signals;
 
+use games::{BoardStyle, Game, GameStatus};
+use permissions::{is_officer, Tier, ADMIN_CHECK, OFFICER_CHECK};
+
+mod announcements;
+mod archive;
+mod arena;
+mod audit_feed;
+mod autosave;
+mod backup;
+mod blunders;
+mod board_image;
+mod broadcast;
+mod chesscom;
+mod clocks;
+mod cooldowns;
+mod correspondence;
+mod crypto;
+mod digest;
+mod engine;
+mod error_report;
+mod flows;
+mod game_of_the_week;
+mod games;
+mod guilds;
+mod journal;
+mod label;
+mod leaderboard;
 mod library;
+mod lichess;
+mod lichess_live;
+mod lichess_relay;
+mod lichess_team;
+mod migrations;
+mod openings;
+mod otb;
+mod permissions;
+mod pgn;
+mod picker;
+mod puzzles;
+mod rating_graph;
+mod ratings;
+mod reminders;
+mod replay;
+mod response;
+mod rules;
+mod simul;
+mod slash;
+mod sqlite;
+mod storage;
+mod tablebase;
+mod threads;
+mod tournaments;
 mod utils;
+mod vote_chess;
+mod watchdog;
+mod welcome;
 
 #[macro_use]
 extern crate derive_new;
 
 #[group]
-#[commands(check)]
+#[commands(
+    check,
+    profile,
+    leaderboard_command,
+    head_to_head_command,
+    report_result,
+    settle_result
+)]
 struct General;
 
+#[group]
+#[prefix = "admin"]
+#[owners_only]
+#[description = "Commands for the bot's owners to manage the bot itself"]
+#[commands(maintenance, restore, fsck, snapshot, backup_status, import_puzzles)]
+struct Admin;
+
+#[group]
+#[prefix = "config"]
+#[only_in(guilds)]
+#[checks(Admin)]
+#[description = "Commands for server admins to change how the bot behaves in their server"]
+#[commands(
+    prefix,
+    permissions,
+    grant,
+    revoke,
+    channel,
+    welcome,
+    board_style,
+    k_factor,
+    rating_system
+)]
+struct Config;
+
+#[group]
+#[prefix = "announce"]
+#[only_in(guilds)]
+#[checks(Officer)]
+#[description = "Commands for officers to post club notices on a schedule"]
+#[commands(schedule_announcement, list_announcements, cancel_announcement)]
+struct Announce;
+
+#[group]
+#[prefix = "chess"]
+#[only_in(guilds)]
+#[description = "Commands to play chess against other members. Opponents only have to be mentioned when you have more than one game going"]
+#[commands(
+    challenge,
+    accept,
+    decline,
+    move_command,
+    moves,
+    board,
+    show,
+    fen,
+    pgn,
+    replay_command,
+    analyze,
+    eval,
+    blundercheck,
+    play_bot,
+    hint,
+    puzzle,
+    daily,
+    solve,
+    resign,
+    draw,
+    takeback,
+    broadcast_command
+)]
+struct Chess;
+
+#[group]
+#[prefix = "tournament"]
+#[only_in(guilds)]
+#[description = "Commands to run club tournaments. Officers create and start them and enter results, members join them"]
+#[commands(
+    create_tournament,
+    join_tournament,
+    leave_tournament,
+    open_check_in,
+    check_in,
+    start_tournament,
+    tournament_result,
+    tournament_pairings,
+    tournament_standings,
+    list_tournaments
+)]
+struct Tournament;
+
+#[group]
+#[prefix = "lichess"]
+#[description = "Commands to link your Lichess account, which also work in DMs with the bot, and to keep the club's Lichess team and tournaments in step with the server"]
+#[commands(
+    lichess_link,
+    lichess_unlink,
+    lichess_live_command,
+    lichess_team_command,
+    lichess_relay_command
+)]
+struct Lichess;
+
+#[group]
+#[prefix = "chesscom"]
+#[description = "Commands to look up chess.com players and link your chess.com account. !chesscom <username> shows a player's stats"]
+#[default_command(chesscom_stats)]
+#[commands(chesscom_link, chesscom_unlink)]
+struct Chesscom;
+
+#[group]
+#[prefix = "archive"]
+#[only_in(guilds)]
+#[description = "Commands to look through the archive of games members have played against each other, with the bot, over the board, in tournaments or on Lichess"]
+#[commands(archive_history, archive_show, archive_opening, archive_lichess)]
+struct Archive;
+
+#[group]
+#[prefix = "vote"]
+#[only_in(guilds)]
+#[description = "Vote chess, where the server plays the engine a move a day. !vote <move> votes for the server's next move"]
+#[default_command(vote_for)]
+#[commands(vote_start, vote_show, vote_stop)]
+struct Vote;
+
+#[group]
+#[prefix = "simul"]
+#[only_in(guilds)]
+#[description = "Simultaneous exhibitions, where the host plays everyone who joins at once. The games are played with !chess move, each in its own thread"]
+#[commands(
+    simul_host,
+    simul_join,
+    simul_leave,
+    simul_start,
+    simul_queue,
+    simul_show,
+    simul_cancel
+)]
+struct Simul;
+
+#[group]
+#[prefix = "gotw"]
+#[only_in(guilds)]
+#[description = "Game of the week. Officers nominate games from the archive, members vote, and the winner is posted and pinned each week. !gotw shows the nominated games"]
+#[default_command(gotw_show)]
+#[commands(gotw_nominate, gotw_vote)]
+struct Gotw;
+
+#[group]
+#[prefix = "puzzle"]
+#[only_in(guilds)]
+#[description = "Tactics training. Pick a theme and rating range and get puzzles one after another, solving each with !chess solve"]
+#[commands(puzzle_train, puzzle_stop, puzzle_themes)]
+struct Puzzle;
+
+#[group]
+#[prefix = "rating"]
+#[description = "Club ratings, from the games members play each other. !rating [@member] shows a member's rating"]
+#[default_command(rating)]
+#[commands(rating_graph)]
+struct Rating;
+
 #[group]
 // Sets a single prefix for this group.
 // So one has to call commands in this group
 // via `!library XXX` instead of just `! XXX`.
 #[prefix = "library"]
 #[description = "Commands to query, checkout, or update information about books owned by this chess club"]
-#[commands(list, checkout, return_command, add, remove, set_quantity)]
+#[commands(
+    list,
+    checkout,
+    checkout_for,
+    return_command,
+    add,
+    remove,
+    set_quantity,
+    set_loan_days,
+    escalation_policy,
+    set_escalation,
+    remove_escalation,
+    unsuspend,
+    digest,
+    digest_schedule,
+    label,
+    authors,
+    series,
+    set_series,
+    wish,
+    wishlist,
+    upvote,
+    purchased,
+    mine,
+    extend,
+    reserve,
+    copies,
+    add_copy,
+    register,
+    user_info,
+    merge_users,
+    forget_me
+)]
 struct Library;
 
 // The framework provides two built-in help commands for you to use.
@@ -87,44 +338,395 @@ struct Handler;
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
+        slash::register_commands(&ctx).await;
+    }
+
+    //Load every guild's library up front so that background tasks like overdue reminders cover
+    //guilds that haven't used a command since the bot started
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: bool) {
+        library_for(&ctx, Some(guild.id)).await;
+        flows::resume_approvals(&ctx, guild.id).await;
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, guild_id: GuildId, new_member: Member) {
+        welcome::greet(&ctx, guild_id, &new_member).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::MessageComponent(component) => self.handle_component(ctx, component).await,
+            Interaction::ApplicationCommand(command) => slash::handle_command(&ctx, command).await,
+            Interaction::Autocomplete(autocomplete) => {
+                slash::handle_autocomplete(&ctx, autocomplete).await
+            }
+            _ => {}
+        }
+    }
+
+    //Officers approve handouts and returns by reacting to the bot's messages
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        if reaction.emoji != ReactionType::Unicode(APPROVE_EMOJI.to_owned()) {
+            return;
+        }
+        let user_id = match reaction.user_id {
+            Some(id) => id,
+            None => return,
+        };
+        if user_id == ctx.cache.current_user_id().await
+            || !is_officer(&ctx, reaction.guild_id, user_id).await
+        {
+            return;
+        }
+
+        approve_by_reaction(
+            &ctx,
+            reaction.guild_id,
+            reaction.channel_id,
+            reaction.message_id,
+            user_id,
+        )
+        .await;
+    }
+}
+
+impl Handler {
+    //Buttons on extension requests have custom ids of the form extend-approve:<id> or
+    //extend-deny:<id>. Book pickers are handled in picker.rs, the register button on welcome
+    //messages in welcome.rs, the buttons under chess replays in replay.rs and the ones under reported
+    //over the board results in otb.rs
+    async fn handle_component(&self, ctx: Context, component: MessageComponentInteraction) {
+        let (action, id) = match component.data.custom_id.split_once(':') {
+            Some(parts) => parts,
+            None => return,
+        };
+        let approve = match action {
+            "extend-approve" => true,
+            "extend-deny" => false,
+            "pick" => return picker::handle_pick(&ctx, &component, id).await,
+            "welcome-register" => return welcome::handle_register(&ctx, &component, id).await,
+            "replay" => return replay::handle_step(&ctx, &component, id).await,
+            "leaderboard" => return leaderboard::handle_page(&ctx, &component, id).await,
+            "tournament-check-in" => {
+                return tournaments::handle_check_in(&ctx, &component, id).await
+            }
+            "tournament-result" => {
+                return tournaments::handle_result_answer(&ctx, &component, id).await
+            }
+            "otb-result" => return otb::handle_answer(&ctx, &component, id).await,
+            _ => return,
+        };
+
+        if !is_officer(&ctx, component.guild_id, component.user.id).await {
+            let _ = component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content("Only officers can decide extension requests")
+                                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                        })
+                })
+                .await;
+            return;
+        }
+
+        let result = {
+            let library_arc = library_for(&ctx, component.guild_id).await;
+            let mut library = library_arc.write().await;
+
+            if library.maintenance {
+                drop(library);
+                //The buttons stay so the request can be decided once maintenance is over
+                let _ = component
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|d| {
+                                d.content(MAINTENANCE_MESSAGE).flags(
+                                    InteractionApplicationCommandCallbackDataFlags::EPHEMERAL,
+                                )
+                            })
+                    })
+                    .await;
+                return;
+            }
+            match library.decode_raw_uuid(id) {
+                Some(uuid) => library
+                    .decide_extension(uuid, approve, &component.user.id.to_string())
+                    .map(|_| {
+                        let request = &library.extension_requests[&uuid];
+                        //Decided requests can be for checkouts that have since been returned
+                        let checkout = library
+                            .checkout(request.checkout)
+                            .expect("extension requests always point at a checkout");
+                        let mut text = format!(
+                            "{} {} the {} day extension for *{}* ({})",
+                            component.user.name,
+                            if approve { "approved" } else { "denied" },
+                            request.days,
+                            checkout_book_name(&library, checkout),
+                            library::Database::encode_uuid(checkout.uuid)
+                        );
+                        if let (true, Some(due_date)) = (approve, checkout.due_date) {
+                            let _ = write!(text, ". New due date: {}", due_date.format("%b %-d"));
+                        }
+                        text
+                    }),
+                None => Err(library::ManipulationError::new(
+                    library::ManipulationErrorType::UnknownExtension(id.to_owned()),
+                )),
+            }
+        };
+        save_after_change(&ctx, component.guild_id).await;
+
+        let text = match result {
+            Ok(text) => text,
+            Err(err) => err.to_string(),
+        };
+        //Replace the request with the decision and remove the buttons so it can't be decided twice
+        let response = component
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| d.content(text).components(|c| c))
+            })
+            .await;
+        if let Err(err) = response {
+            println!("Failed to respond to extension decision: {:?}", err);
+        }
+    }
+}
+
+//Officers react with this to approve checkouts and returns
+const APPROVE_EMOJI: &str = "👍";
+
+//Where approval requests and other messages for officers go. Falls back to the channel the
+//command was used in when the guild hasn't set a library-log channel
+async fn officers_channel(ctx: &Context, invocation: &Invocation) -> ChannelId {
+    let library_arc = library_for(ctx, invocation.guild).await;
+    let channel = library_arc
+        .read()
+        .await
+        .channel(library::ChannelKind::LibraryLog);
+    channel.map(ChannelId).unwrap_or(invocation.channel)
+}
+
+//Carries out what an officer's reaction to `message` approves: handing out the books of a
+//checkout or confirming they were returned. In maintenance mode nothing is approved, and the
+//officer can react again once it is over
+async fn approve_by_reaction(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    channel: ChannelId,
+    message: MessageId,
+    officer_id: UserId,
+) {
+    let paused = {
+        let library_arc = library_for(ctx, guild).await;
+        let library = library_arc.read().await;
+        library
+            .maintenance
+            .then(|| library.awaits_approval(message.0))
+    };
+    match paused {
+        Some(true) => {
+            if let Err(err) = channel.say(ctx, MAINTENANCE_MESSAGE).await {
+                println!("Failed to refuse officer approval: {:?}", err);
+            }
+            return;
+        }
+        //Reactions to anything but an approval request are ignored without a word
+        Some(false) => return,
+        None => {}
+    }
+
+    //Each confirmation goes to the thread of the checkout it is about, grouped so that books
+    //handed out together are confirmed in one message
+    let updates = {
+        let library_arc = library_for(ctx, guild).await;
+        let mut library = library_arc.write().await;
+
+        let now = chrono::Local::now();
+        let officer = officer_id.to_string();
+        let approved = library.approve_checkouts(message.0, &officer, now);
+        let returned = library.verify_returns(message.0, &officer, now);
+
+        let mut updates: Vec<(Option<library::MessageRef>, String)> = Vec::new();
+        let mut add_update = |approval_message, line: String| match updates.last_mut() {
+            Some((last, text)) if *last == approval_message => {
+                text.push('\n');
+                text.push_str(&line);
+            }
+            _ => updates.push((approval_message, line)),
+        };
+        for uuid in approved {
+            let checkout = &library.checkouts[&uuid];
+            let mut line = format!(
+                "Handed out *{}* ({})",
+                checkout_book_name(&library, checkout),
+                library::Database::encode_uuid(uuid)
+            );
+            if let Some(due_date) = checkout.due_date {
+                let _ = write!(line, ", due back {}", due_date.format("%b %-d"));
+            }
+            add_update(checkout.approval_message, line);
+        }
+        for uuid in returned {
+            let checkout = &library.archived_checkouts[&uuid];
+            let line = format!(
+                "Confirmed the return of *{}* ({})",
+                checkout_book_name(&library, checkout),
+                library::Database::encode_uuid(uuid)
+            );
+            add_update(checkout.approval_message, line);
+        }
+        updates
+    };
+    save_after_change(ctx, guild).await;
+
+    //Checkouts without a thread are confirmed where the officer reacted
+    let mut unposted = String::new();
+    for (approval_message, text) in updates {
+        let posted = guild.is_some()
+            && threads::post(
+                ctx,
+                approval_message,
+                &format!("{}\nConfirmed by <@{}>", text, officer_id),
+            )
+            .await;
+        if !posted {
+            let _ = write!(unposted, "\n{}", text);
+        }
+    }
+    if !unposted.is_empty() {
+        if let Err(err) = channel.say(ctx, unposted.trim_start()).await {
+            println!("Failed to confirm officer approval: {:?}", err);
+        }
+    }
+}
+
+fn checkout_book_name<'a>(
+    library: &'a library::Database,
+    checkout: &library::CheckoutInstance,
+) -> &'a str {
+    match library.books.get(&checkout.book) {
+        Some(book) => book.name.as_str(),
+        None => "<removed book>",
     }
 }
 
 #[hook]
-async fn before(_ctx: &Context, msg: &Message, command_name: &str) -> bool {
+async fn before(ctx: &Context, msg: &Message, command_name: &str) -> bool {
     println!(
         "Got command '{}' by user '{}'",
         command_name, msg.author.name
     );
+    if let Some(watchdog) = ctx.data.read().await.get::<watchdog::WatchdogData>() {
+        watchdog.set_command(msg.id, command_name);
+    }
 
     true
 }
 
+//Journals (or with SQLite saves) whatever the last command or event changed, lets the autosave
+//task know there is something new to save and posts any new audit entries to the audit channel
+async fn save_after_change(ctx: &Context, guild: Option<GuildId>) {
+    let library_arc = library_for(ctx, guild).await;
+    let changed = library_arc.read().await.persist_change().await;
+
+    if changed {
+        if let Some(autosave) = ctx.data.read().await.get::<autosave::AutosaveData>() {
+            autosave.record_change(guild);
+        }
+    }
+    audit_feed::mirror(ctx, guild).await;
+}
+
 #[hook]
 async fn after(ctx: &Context, msg: &Message, command_name: &str, command_result: CommandResult) {
+    save_after_change(ctx, guild_of(ctx, msg).await).await;
     match command_result {
         Ok(()) => println!("Processed command '{}'", command_name),
         Err(why) => {
             println!("Command '{}' returned error {:?}", command_name, why);
-            msg.reply(ctx, format!("Error: {}", why)).await;
+            let _ = response::error(ctx, msg, format!("Error: {}", why)).await;
+            error_report::report(ctx, msg, command_name, &why).await;
+        }
+    }
+}
+
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
+    match error {
+        DispatchError::CheckFailed(_, Reason::User(reason)) => {
+            let _ = response::error(ctx, msg, reason).await;
+        }
+        //Only answer the first try, so that spamming the command doesn't spam replies too
+        DispatchError::Ratelimited(info) => {
+            if info.is_first_try {
+                let _ = response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "Slow down a little! Try again in {}s",
+                        info.as_secs().max(1)
+                    ),
+                )
+                .await;
+            }
         }
+        DispatchError::LackingRole => {
+            let _ = response::error(
+                ctx,
+                msg,
+                "You don't have the role needed to use this command",
+            )
+            .await;
+        }
+        _ => println!("Dispatch error: {:?}", error),
+    }
+}
+
+//Refuses commands that change the library while the bot is in maintenance mode
+#[check]
+#[name = "Writable"]
+async fn writable_check(
+    ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    _options: &CommandOptions,
+) -> Result<(), Reason> {
+    let library_arc = library_for(ctx, guild_of(ctx, msg).await).await;
+    let library = library_arc.read().await;
+
+    if library.maintenance {
+        Err(Reason::User(MAINTENANCE_MESSAGE.to_owned()))
+    } else {
+        Ok(())
     }
 }
 
+const MAINTENANCE_MESSAGE: &str = "🚧 The library is in maintenance mode right now, so changes are paused. You can still browse with !library list. Please try again later!";
+
+//Commands start with the prefix the guild picked with !config prefix, or ! if it never did
+#[hook]
+async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    let library_arc = library_for(ctx, guild_of(ctx, msg).await).await;
+    let library = library_arc.read().await;
+    Some(library.config.prefix().to_owned())
+}
+
 #[hook]
 async fn unknown_command(ctx: &Context, msg: &Message, unknown_command_name: &str) {
     println!("Could not find command named '{}'", unknown_command_name);
-    let _ = msg
-        .reply(
-            ctx,
-            format!(
-                "Unknown command \"{}\". Try !help for a list of available commands",
-                unknown_command_name
-            ),
-        )
-        .await;
+    let _ = response::error(
+        ctx,
+        msg,
+        format!(
+            "Unknown command \"{}\". Try !help for a list of available commands",
+            unknown_command_name
+        ),
+    )
+    .await;
 }
 
 #[hook]
@@ -132,8 +734,12 @@ async fn normal_message(_ctx: &Context, msg: &Message) {
     println!("Message is not a command '{}'", msg.content);
 }
 
-async fn init() -> Result<(library::Database, Client), Box<dyn std::error::Error>> {
-    let prev_db = library::Database::load().await;
+async fn init(
+) -> Result<(library::Database, Client, Arc<watchdog::Watchdog>), Box<dyn std::error::Error>> {
+    let prev_db = library::Database::load(None).await;
+    if guilds::home_guild().is_none() {
+        println!("HOME_GUILD_ID not set. Every guild gets a library of its own and library-db.bin will only be used for commands sent in DMs");
+    }
 
     // Login with a bot token from the environment
     let token = env::var("DISCORD_TOKEN")?;
@@ -157,19 +763,50 @@ async fn init() -> Result<(library::Database, Client), Box<dyn std::error::Error
         Err(why) => panic!("Could not access application info: {:?}", why),
     };
 
+    let watchdog = Arc::new(watchdog::Watchdog::new(owners.clone()));
+
     let framework = StandardFramework::new()
-        .configure(|c| c.on_mention(Some(bot_id)).owners(owners).prefix("!"))
+        //No static prefix, so that a guild that picked its own prefix doesn't also answer to !
+        .configure(|c| {
+            c.on_mention(Some(bot_id))
+                .owners(owners)
+                //Members can check on their loans in DMs without typing the prefix
+                .no_dm_prefix(true)
+                .prefixes(Vec::<String>::new())
+                .dynamic_prefix(guild_prefix)
+        })
         .before(before)
         .after(after)
         .unrecognised_command(unknown_command)
+        .on_dispatch_error(dispatch_error)
         .normal_message(normal_message)
         .help(&MY_HELP)
         .group(&GENERAL_GROUP)
-        .group(&LIBRARY_GROUP);
+        .group(&LIBRARY_GROUP)
+        .group(&ADMIN_GROUP)
+        .group(&CONFIG_GROUP)
+        .group(&ANNOUNCE_GROUP)
+        .group(&CHESS_GROUP)
+        .group(&TOURNAMENT_GROUP)
+        .group(&LICHESS_GROUP)
+        .group(&CHESSCOM_GROUP)
+        .group(&ARCHIVE_GROUP)
+        .group(&VOTE_GROUP)
+        .group(&SIMUL_GROUP)
+        .group(&GOTW_GROUP)
+        .group(&PUZZLE_GROUP)
+        .group(&RATING_GROUP);
+    let framework = cooldowns::add_buckets(framework).await;
 
+    //Members joining is a privileged event, which has to be turned on for the bot in the developer
+    //portal too
     let client = Client::builder(token)
+        .intents(GatewayIntents::non_privileged() | GatewayIntents::GUILD_MEMBERS)
         .event_handler(Handler)
-        .framework(framework)
+        .framework(watchdog::WatchdogFramework::new(
+            framework,
+            watchdog.clone(),
+        ))
         .await?;
 
     //Assign the database if we make it this far because this is how we tell if if
@@ -178,13 +815,86 @@ async fn init() -> Result<(library::Database, Client), Box<dyn std::error::Error
         Some(lib) => lib,
         None => library::Database::new(),
     };
-    Ok((database, client))
+    Ok((database, client, watchdog))
+}
+
+//Replies to `msg`, along with the menu if the reply has one
+async fn send_reply(ctx: &Context, msg: &Message, reply: picker::Reply) -> serenity::Result<()> {
+    if reply.picker.is_none() {
+        response::success(ctx, msg, reply.content).await?;
+        return Ok(());
+    }
+    let sent = msg
+        .channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| {
+                    e.colour(response::Tone::Info.colour())
+                        .description(&reply.content)
+                })
+                .components(|c| reply.components(c))
+        })
+        .await?;
+    reply.track(&sent);
+    Ok(())
 }
 
+//Asks the author of `msg` to confirm something by reacting to the bot's reply. Returns false if
+//they don't react within CONFIRM_TIMEOUT_SECS
+async fn confirm(ctx: &Context, msg: &Message, prompt: String) -> serenity::Result<bool> {
+    let prompt = format!("{} (within {} seconds)", prompt, CONFIRM_TIMEOUT_SECS);
+    let prompt_msg = msg
+        .channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| e.colour(response::Tone::Info.colour()).description(prompt))
+        })
+        .await?;
+    prompt_msg.react(ctx, '✅').await?;
+
+    let confirmation = prompt_msg
+        .await_reaction(ctx)
+        .author_id(msg.author.id)
+        .filter(|reaction| reaction.emoji == ReactionType::Unicode("✅".to_owned()))
+        .timeout(std::time::Duration::from_secs(CONFIRM_TIMEOUT_SECS))
+        .await;
+
+    if confirmation.is_none() {
+        //Take the bot's reaction off so the prompt doesn't look like it can still be answered
+        let _ = prompt_msg.delete_reaction(ctx, None, '✅').await;
+        response::error(ctx, msg, "Not confirmed, cancelling").await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+//Kept under the watchdog's default timeout so that waiting on a confirmation doesn't look like
+//a stuck command
+const CONFIRM_TIMEOUT_SECS: u64 = 20;
+
 struct LibraryData;
 
 impl TypeMapKey for LibraryData {
-    type Value = Arc<RwLock<library::Database>>;
+    type Value = Arc<guilds::Libraries>;
+}
+
+//The guild a private query is about. In DMs that is the guild the author is registered in
+async fn guild_of(ctx: &Context, msg: &Message) -> Option<GuildId> {
+    match msg.guild_id {
+        Some(guild) => Some(guild),
+        None => dm_guild(ctx, msg.author.id).await,
+    }
+}
+
+async fn dm_guild(ctx: &Context, user: UserId) -> Option<GuildId> {
+    let libraries = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+    libraries.guild_for_dm(user).await
+}
+
+//The library belonging to the guild a command or event came from
+async fn library_for(ctx: &Context, guild: Option<GuildId>) -> Arc<RwLock<library::Database>> {
+    let libraries = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+    libraries.get(guild).await
 }
 
 fn main() {
@@ -193,20 +903,93 @@ fn main() {
     let init_result = rt.block_on(init());
 
     match init_result {
-        Ok((tmp_database, bad_client)) => {
+        Ok((tmp_database, bad_client, watchdog)) => {
             //Leaking is ok because the program will exit when the future returns and there is no
             //other way to easily get 'static
             let client = Box::leak(Box::new(bad_client));
 
             //We need to store an arc to library after adding it to context so that we can access
             //it in commands and in this scope when we need to save during shutdown
-            let library_arc = {
+            let autosave = autosave::handle();
+            let backups = Arc::new(backup::Backups::new());
+            let libraries = {
                 let mut data = rt.block_on(async { client.data.write().await });
-                let library = Arc::new(RwLock::new(tmp_database));
-                data.insert::<LibraryData>(library.clone());
-                library
+                let libraries = Arc::new(guilds::Libraries::new(tmp_database));
+                data.insert::<LibraryData>(libraries.clone());
+                data.insert::<watchdog::WatchdogData>(watchdog.clone());
+                data.insert::<autosave::AutosaveData>(autosave.clone());
+                data.insert::<backup::BackupData>(backups.clone());
+                libraries
             };
 
+            rt.spawn(backup::backup_task(backups, libraries.clone()));
+
+            rt.spawn(autosave::autosave_task(autosave, libraries.clone()));
+
+            rt.spawn(digest::digest_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+            rt.spawn(watchdog::watchdog_task(
+                watchdog,
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(reminders::reminder_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(announcements::announcement_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(flows::flow_task(client.cache_and_http.http.clone()));
+
+            rt.spawn(lichess::rating_sync_task(libraries.clone()));
+
+            rt.spawn(lichess_team::team_sync_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(lichess_relay::relay_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(lichess_live::live_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(vote_chess::vote_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(correspondence::clock_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(clocks::flag_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(game_of_the_week::game_of_the_week_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
+            rt.spawn(arena::arena_task(
+                client.cache_and_http.http.clone(),
+                libraries.clone(),
+            ));
+
             let client_future = client.start();
             let client_join = rt.spawn(client_future);
 
@@ -222,9 +1005,11 @@ fn main() {
             });
 
             rt.block_on(async {
-                let library = library_arc.read().await;
+                for (_, library_arc) in libraries.all().await {
+                    let library = library_arc.read().await;
 
-                library::Database::try_save(&library).await;
+                    library::Database::try_save(&library).await;
+                }
             });
         }
         Err(err) => {
@@ -233,82 +1018,269 @@ fn main() {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BookSort {
+    Title,
+    Author,
+    Added,
+    Popularity,
+}
+
+impl BookSort {
+    fn parse(input: &str) -> Option<BookSort> {
+        match input.to_ascii_lowercase().as_str() {
+            "title" => Some(BookSort::Title),
+            "author" => Some(BookSort::Author),
+            "added" => Some(BookSort::Added),
+            "popularity" => Some(BookSort::Popularity),
+            _ => None,
+        }
+    }
+}
+
+//Who used a command and where. Commands that can be used both with the ! prefix and as slash
+//commands are written against this instead of the message
+#[derive(Clone)]
+struct Invocation {
+    guild: Option<GuildId>,
+    channel: ChannelId,
+    author: User,
+    //Their nickname in the guild, or their username if they don't have one
+    display_name: String,
+}
+
+impl Invocation {
+    fn from_message(msg: &Message) -> Invocation {
+        let nick = msg.member.as_ref().and_then(|member| member.nick.clone());
+        Invocation {
+            guild: msg.guild_id,
+            channel: msg.channel_id,
+            author: msg.author.clone(),
+            display_name: nick.unwrap_or_else(|| msg.author.name.clone()),
+        }
+    }
+}
+
 #[command]
+#[bucket = "listing"]
 #[description = "Lists the books in the library and other information such as author and availability"]
-async fn list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+#[usage = "[--author <name>] [--sort title|author|added|popularity] [--asc|--desc]"]
+#[example = "--author \"Silman\" --sort added --desc"]
+async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut author_filter: Option<String> = None;
+    let mut sort: Option<BookSort> = None;
+    let mut descending: Option<bool> = None;
+    while !args.is_empty() {
+        let flag: String = args.single::<String>()?;
+        match flag.as_str() {
+            "--author" => author_filter = Some(args.single_quoted::<String>()?),
+            "--sort" => {
+                let key: String = args.single::<String>()?;
+                sort = match BookSort::parse(&key) {
+                    Some(sort) => Some(sort),
+                    None => {
+                        response::error(
+                            ctx,
+                            msg,
+                            format!(
+                                "Unknown sort \"{}\". Expected title, author, added, or popularity",
+                                key
+                            ),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+            "--asc" => descending = Some(false),
+            "--desc" => descending = Some(true),
+            _ => {
+                response::error(ctx, msg, format!("Unknown option \"{}\"", flag)).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let guild = guild_of(ctx, msg).await;
+    let books = list_books(ctx, guild, author_filter, sort, descending).await?;
+    response::info(ctx, msg, books).await?;
+
+    Ok(())
+}
+
+async fn list_books(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    author_filter: Option<String>,
+    sort: Option<BookSort>,
+    descending: Option<bool>,
+) -> Result<String, CommandError> {
     let mut response = String::new();
     {
         //Acquire the data and clone the Arc to it
-        let library_arc = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+        let library_arc = library_for(ctx, guild).await;
 
         let library = library_arc.read().await;
 
-        write!(
-            response,
-            "The library contains {} book(s):",
-            library.books.len()
-        )?;
+        let mut books: Vec<&library::Book> = match &author_filter {
+            Some(author) => {
+                let books = library.books_by_author(author);
+                write!(
+                    response,
+                    "The library contains {} book(s) by {}:",
+                    books.len(),
+                    author
+                )?;
+                books
+            }
+            None => {
+                write!(
+                    response,
+                    "The library contains {} book(s):",
+                    library.books.len()
+                )?;
+                library.books.values().collect()
+            }
+        };
 
-        for (uuid, book) in &library.books {
-            write!(
-                response,
-                "\n  *{}* by {} - {}",
-                book.name,
-                book.author,
-                library::Database::encode_uuid(book.uuid)
-            )?;
-            if book.quantity > 1 {
-                write!(response, " | quantity {}", book.quantity)?;
+        if let Some(sort) = sort {
+            match sort {
+                BookSort::Title => books.sort_by_key(|book| book.name.to_lowercase()),
+                BookSort::Author => books.sort_by_key(|book| book.author.to_lowercase()),
+                BookSort::Added => books.sort_by_key(|book| book.added),
+                BookSort::Popularity => books.sort_by_key(|book| library.checkout_count(book.uuid)),
             }
+            //Most popular first unless asked otherwise, everything else A-Z/oldest first
+            if descending.unwrap_or(sort == BookSort::Popularity) {
+                books.reverse();
+            }
+        } else if descending == Some(true) {
+            books.reverse();
         }
+
+        write_book_listing(&mut response, &library, &books)?;
     }
 
-    msg.reply(ctx, response).await?;
+    Ok(response)
+}
+
+//Writes one line per book, with books that are part of a series grouped together under the
+//series name in volume order
+fn write_book_listing(
+    response: &mut String,
+    library: &library::Database,
+    books: &[&library::Book],
+) -> std::fmt::Result {
+    let mut written_series: Vec<&str> = Vec::new();
+    for book in books {
+        match &book.series {
+            None => write_book_line(response, library, book, "  ")?,
+            Some(series) => {
+                if written_series
+                    .iter()
+                    .any(|written| utils::cmp_ignore_case_ascii(written, series))
+                {
+                    continue;
+                }
+                written_series.push(series);
 
+                write!(response, "\n  **{}**", series)?;
+                for volume in library.books_in_series(series) {
+                    //When filtering, only show the volumes that matched
+                    if books.iter().any(|book| book.uuid == volume.uuid) {
+                        write_book_line(response, library, volume, "    ")?;
+                    }
+                }
+            }
+        }
+    }
     Ok(())
 }
 
-#[command]
-#[description = "Adds a new book to the library"]
-async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let book_name: String = args.single_quoted()?;
-    let book_author: String = args.single_quoted()?;
-
-    let library_arc = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+fn write_book_line(
+    response: &mut String,
+    library: &library::Database,
+    book: &library::Book,
+    indent: &str,
+) -> std::fmt::Result {
+    write!(response, "\n{}", indent)?;
+    if let Some(volume) = book.volume {
+        write!(response, "Vol. {}: ", volume)?;
+    }
+    write!(
+        response,
+        "*{}* by {} - {}",
+        book.name,
+        book.author,
+        library::Database::encode_uuid(book.uuid)
+    )?;
+    let available = library.available_copies(book);
+    if available > 0 {
+        write!(response, " | {}/{} available", available, book.quantity)?;
+    } else {
+        match library.next_due_date(book.uuid) {
+            Some(due_date) => write!(
+                response,
+                " | checked out, due back {}",
+                due_date.format("%b %-d")
+            )?,
+            None => write!(response, " | checked out")?,
+        }
+    }
+    if let Some(days) = book.loan_days {
+        write!(response, " | {} day loan", days)?;
+    }
+    Ok(())
+}
 
-    let mut library = library_arc.write().await;
+#[command]
+#[bucket = "listing"]
+#[description = "Lists the books in a series in volume order"]
+#[usage = "<series name>"]
+async fn series(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let series_name = args.rest().trim().trim_matches('"').to_owned();
 
-    let book = library::Book::new(library.new_book_uuid(), book_name.clone(), book_author, 1);
-    let book_uuid = book.uuid;
-    let result = library.add_book(book);
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, guild_of(ctx, msg).await).await;
 
-    if result.is_ok() {
-        msg.reply(
-            ctx,
-            format!(
-                "Added book \"{}\" successfully. ID={}",
-                book_name,
-                library::Database::encode_uuid(book_uuid)
-            ),
-        )
-        .await?;
+        let library = library_arc.read().await;
+        let books = library.books_in_series(&series_name);
+        if books.is_empty() {
+            response.push_str(&format!("No books in series \"{}\"", series_name));
+        } else {
+            write!(
+                response,
+                "\"{}\" has {} volume(s):",
+                series_name,
+                books.len()
+            )?;
+            for book in books {
+                write_book_line(&mut response, &library, book, "  ")?;
+            }
+        }
     }
 
-    //Having the last line be just "r" doesn't work because otherwise type inference thinks this
-    //function returns a ManipulationError and then the ? operators above fail because they return
-    //other error types.
-    let _ = result?;
+    response::info(ctx, msg, response).await?;
+
     Ok(())
 }
 
-#[command("set-quantity")]
-#[allowed_roles("Minor Pieces")]
-#[description = "Sets the quantity of a book in the library"]
-async fn set_quantity(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+#[command("set-series")]
+#[checks(Officer, Writable)]
+#[description = "Marks a book as a volume of a series. Use none as the series to remove it from its series"]
+#[usage = "<book> <series> [volume]"]
+#[example = "\"Build Up Your Chess 1\" \"Build Up Your Chess\" 1"]
+async fn set_series(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let book_input: String = args.single_quoted::<String>()?;
-    let new_quantity: u32 = args.single::<u32>()?;
+    let series_input: String = args.single_quoted::<String>()?;
+    let volume: Option<u32> = if args.is_empty() {
+        None
+    } else {
+        Some(args.single::<u32>()?)
+    };
 
-    let library_arc = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+    let library_arc = library_for(ctx, msg.guild_id).await;
 
     let mut library = library_arc.write().await;
 
@@ -319,18 +1291,20 @@ async fn set_quantity(ctx: &Context, msg: &Message, mut args: Args) -> CommandRe
         )),
 
         Some(book) => {
-            book.quantity = new_quantity;
-
-            msg.reply(
-                ctx,
+            let response = if series_input.eq_ignore_ascii_case("none") {
+                book.series = None;
+                book.volume = None;
+                format!("Book \"{}\" is no longer part of a series", &book.name)
+            } else {
+                book.series = Some(series_input);
+                book.volume = volume;
                 format!(
-                    "Book \"{}\" ({}) set to have {} copies",
+                    "Book \"{}\" is now part of the series \"{}\"",
                     &book.name,
-                    library::Database::encode_uuid(book.uuid),
-                    book.quantity,
-                ),
-            )
-            .await?;
+                    book.series.as_ref().unwrap()
+                )
+            };
+            response::success(ctx, msg, response).await?;
 
             Ok(())
         }
@@ -340,55 +1314,6638 @@ async fn set_quantity(ctx: &Context, msg: &Message, mut args: Args) -> CommandRe
 }
 
 #[command]
-#[description = "Removes a book from the library"]
-async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let book_input: String = args.single_quoted::<String>()?;
+#[bucket = "listing"]
+#[description = "Lists every author in the library and how many of their books we have"]
+async fn authors(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, guild_of(ctx, msg).await).await;
 
-    let library_arc = { ctx.data.read().await.get::<LibraryData>().unwrap().clone() };
+        let library = library_arc.read().await;
+        let authors = library.authors();
 
-    let mut library = library_arc.write().await;
+        write!(
+            response,
+            "The library has books by {} author(s):",
+            authors.len()
+        )?;
+        for (author, books) in authors {
+            write!(response, "\n  {} - {} book(s)", author, books.len())?;
+        }
+    }
 
-    let result = {
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Officer, Writable)]
+#[description = "Adds a new book to the library"]
+async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_name: String = args.single_quoted()?;
+    let book_author: String = args.single_quoted()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let similar = {
+        let library = library_arc.read().await;
+        library
+            .find_similar_book(&book_name, &book_author)
+            .map(|book| {
+                format!(
+                    "\"{}\" by {} ({})",
+                    book.name,
+                    book.author,
+                    library::Database::encode_uuid(book.uuid)
+                )
+            })
+    };
+    //Don't hold the lock while waiting on the user to confirm
+    if let Some(similar) = similar {
+        let prompt = format!("Did you mean {}? React with ✅ to add it anyway", similar);
+        if !confirm(ctx, msg, prompt).await? {
+            return Ok(());
+        }
+    }
+
+    let mut library = library_arc.write().await;
+
+    let book = library::Book::new(library.new_book_uuid(), book_name.clone(), book_author, 1);
+    let book_uuid = book.uuid;
+    let result = library.add_book(book);
+
+    if result.is_ok() {
+        library.audit(
+            msg.author.id.to_string(),
+            format!(
+                "Added the book \"{}\" ({})",
+                book_name,
+                library::Database::encode_uuid(book_uuid)
+            ),
+        );
+        response::success(
+            ctx,
+            msg,
+            format!(
+                "Added book \"{}\" successfully. ID={}",
+                book_name,
+                library::Database::encode_uuid(book_uuid)
+            ),
+        )
+        .await?;
+    }
+
+    //Having the last line be just "r" doesn't work because otherwise type inference thinks this
+    //function returns a ManipulationError and then the ? operators above fail because they return
+    //other error types.
+    let _ = result?;
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Suggests a book for the club to buy"]
+#[usage = "<title> <author>"]
+async fn wish(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_name: String = args.single_quoted()?;
+    let book_author: String = args.single_quoted()?;
+
+    let response = add_wish(ctx, &Invocation::from_message(msg), book_name, book_author).await?;
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+async fn add_wish(
+    ctx: &Context,
+    invocation: &Invocation,
+    book_name: String,
+    book_author: String,
+) -> Result<String, CommandError> {
+    let library_arc = library_for(ctx, invocation.guild).await;
+
+    let mut library = library_arc.write().await;
+
+    let discord_id = invocation.author.id.to_string();
+    let entry = library::WishlistEntry::new(
+        library.new_wish_uuid(),
+        book_name.clone(),
+        book_author,
+        discord_id.clone(),
+        vec![discord_id],
+    );
+    let wish_uuid = entry.uuid;
+    library.add_wish(entry)?;
+
+    Ok(format!(
+        "Added \"{}\" to the wishlist. ID={}",
+        book_name,
+        library::Database::encode_uuid(wish_uuid)
+    ))
+}
+
+#[command]
+#[bucket = "listing"]
+#[description = "Lists the books members would like the club to buy, most wanted first"]
+async fn wishlist(ctx: &Context, msg: &Message) -> CommandResult {
+    let response = wishlist_text(ctx, msg.guild_id).await?;
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+async fn wishlist_text(ctx: &Context, guild: Option<GuildId>) -> Result<String, CommandError> {
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, guild).await;
+
+        let library = library_arc.read().await;
+
+        let mut wishes: Vec<&library::WishlistEntry> = library.wishlist.values().collect();
+        wishes.sort_by(|a, b| b.votes.len().cmp(&a.votes.len()));
+
+        write!(
+            response,
+            "The wishlist has {} book(s). Use !library upvote <id> to vote for one:",
+            wishes.len()
+        )?;
+        for wish in wishes {
+            write!(
+                response,
+                "\n  *{}* by {} - {} | {} vote(s)",
+                wish.name,
+                wish.author,
+                library::Database::encode_uuid(wish.uuid),
+                wish.votes.len()
+            )?;
+        }
+    }
+
+    Ok(response)
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Votes for a book on the wishlist. Using it again removes your vote"]
+#[usage = "<wishlist id>"]
+async fn upvote(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let wish_input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let wish_uuid = match library.decode_wish_uuid(&wish_input) {
+        Ok(uuid) => uuid,
+        Err(err) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown wishlist entry \"{}\": {:?}", wish_input, err),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let added = library.toggle_wish_vote(wish_uuid, msg.author.id.to_string())?;
+    let wish = &library.wishlist[&wish_uuid];
+
+    let response = if added {
+        format!("Voted for \"{}\" ({} votes)", wish.name, wish.votes.len())
+    } else {
+        format!(
+            "Removed your vote for \"{}\" ({} votes)",
+            wish.name,
+            wish.votes.len()
+        )
+    };
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Officer, Writable)]
+#[description = "Moves a book from the wishlist into the library once the club has bought it"]
+#[usage = "<wishlist id> [quantity]"]
+async fn purchased(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let wish_input: String = args.single::<String>()?;
+    let quantity: u32 = if args.is_empty() {
+        1
+    } else {
+        args.single::<u32>()?
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let wish_uuid = match library.decode_wish_uuid(&wish_input) {
+        Ok(uuid) => uuid,
+        Err(err) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown wishlist entry \"{}\": {:?}", wish_input, err),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let book_uuid = library.fulfill_wish(wish_uuid, quantity)?;
+    let book = &library.books[&book_uuid];
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Added book \"{}\" to the library. ID={}",
+            book.name,
+            library::Database::encode_uuid(book_uuid)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("set-quantity")]
+#[checks(Officer, Writable)]
+#[description = "Sets the quantity of a book in the library"]
+async fn set_quantity(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+    let new_quantity: u32 = args.single::<u32>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let opt_book = library.get_book_from_input_mut(&book_input);
+    let result = match opt_book {
+        None => Err(library::ManipulationError::new(
+            library::ManipulationErrorType::UnknownBook(book_input),
+        )),
+
+        Some(book) => {
+            book.quantity = new_quantity;
+
+            response::success(
+                ctx,
+                msg,
+                format!(
+                    "Book \"{}\" ({}) set to have {} copies",
+                    &book.name,
+                    library::Database::encode_uuid(book.uuid),
+                    book.quantity,
+                ),
+            )
+            .await?;
+
+            Ok(())
+        }
+    };
+    let _ = result?;
+    Ok(())
+}
+
+#[command("set-loan-days")]
+#[checks(Officer, Writable)]
+#[description = "Sets how many days, up to a year, a book can be checked out for. Use 0 to go back to the default"]
+async fn set_loan_days(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+    let days: u32 = args.single::<u32>()?;
+    if days > library::MAX_LOAN_DAYS {
+        return Err(library::ManipulationError::new(
+            library::ManipulationErrorType::InvalidLoanDays(days),
+        )
+        .into());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let opt_book = library.get_book_from_input_mut(&book_input);
+    let result = match opt_book {
+        None => Err(library::ManipulationError::new(
+            library::ManipulationErrorType::UnknownBook(book_input),
+        )),
+
+        Some(book) => {
+            book.loan_days = if days == 0 { None } else { Some(days) };
+
+            response::success(
+                ctx,
+                msg,
+                format!(
+                    "Book \"{}\" ({}) can now be checked out for {} days",
+                    &book.name,
+                    library::Database::encode_uuid(book.uuid),
+                    book.loan_days(),
+                ),
+            )
+            .await?;
+
+            Ok(())
+        }
+    };
+    let _ = result?;
+    Ok(())
+}
+
+#[command("escalation-policy")]
+#[checks(Officer)]
+#[description = "Shows what happens when a book is overdue"]
+async fn escalation_policy(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+
+        let library = library_arc.read().await;
+
+        write!(response, "Overdue escalation policy:")?;
+        for step in &library.escalation_policy {
+            write!(
+                response,
+                "\n  {} day(s) overdue: {}",
+                step.days_overdue, step.action
+            )?;
+        }
+    }
+
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command("set-escalation")]
+#[checks(Officer, Writable)]
+#[description = "Sets what happens once a book is a number of days overdue. Actions are dm, officers, or suspend"]
+async fn set_escalation(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let days: u32 = args.single::<u32>()?;
+    let action_input: String = args.single::<String>()?;
+
+    let action = match library::EscalationAction::parse(&action_input) {
+        Some(action) => action,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown action \"{}\". Expected dm, officers, or suspend",
+                    action_input
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+    library.set_escalation_step(library::EscalationStep::new(days, action));
+
+    response::success(
+        ctx,
+        msg,
+        format!("Books {} day(s) overdue will now {}", days, action),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("remove-escalation")]
+#[checks(Officer, Writable)]
+#[description = "Removes the escalation step that happens a number of days after a book is overdue"]
+async fn remove_escalation(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let days: u32 = args.single::<u32>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+    if library.remove_escalation_step(days) {
+        response::success(
+            ctx,
+            msg,
+            format!("Removed the {} day escalation step", days),
+        )
+        .await?;
+    } else {
+        response::error(
+            ctx,
+            msg,
+            format!("There is no escalation step at {} day(s)", days),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[checks(Officer, Writable)]
+#[description = "Lifts a borrowing suspension put in place by the overdue escalation policy"]
+#[usage = "<@member|user id>"]
+async fn unsuspend(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_input: String = args.single::<String>()?;
+
+    let user_uuid = resolve_user(ctx, msg.guild_id, &user_input, false).await?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let user = library.users.get_mut(&user_uuid).unwrap();
+    user.suspended = false;
+
+    response::success(
+        ctx,
+        msg,
+        format!("{} can borrow books again", user.read_name),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows a summary of the past week's library activity"]
+async fn digest(ctx: &Context, msg: &Message) -> CommandResult {
+    let response = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+
+        let library = library_arc.read().await;
+        digest::build_digest(&library, chrono::Local::now())?
+    };
+
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command("digest-schedule")]
+#[checks(Officer, Writable)]
+#[description = "Sets when the weekly digest is posted to the library channel"]
+#[usage = "<day> <HH:MM>"]
+#[example = "sun 18:00"]
+async fn digest_schedule(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let day: String = args.single::<String>()?;
+    let time: String = args.single::<String>()?;
+    let schedule = match library::WeeklySchedule::parse(&day, &time) {
+        Ok(schedule) => schedule,
+        Err(err) => {
+            response::error(ctx, msg, err).await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+    library.digest_schedule = schedule;
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "The weekly digest will be posted every {}",
+            library.digest_schedule
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "lookup"]
+#[description = "Creates a printable QR code sticker for a book's ID"]
+async fn label(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+
+    let (name, id) = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+
+        let library = library_arc.read().await;
+        match library.get_book_from_input(&book_input) {
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownBook(book_input),
+            )),
+            Some(book) => Ok((book.name.clone(), library::Database::encode_uuid(book.uuid))),
+        }?
+    };
+
+    let png = label::render_label(&id)?;
+    let file_name = format!("{}.png", id);
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.content(format!("Label for \"{}\" ({})", name, id));
+            m.add_file((png.as_slice(), file_name.as_str()))
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Officer, Writable)]
+#[description = "Removes a book from the library"]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let result = {
         let opt_book = library.get_book_from_input_mut(&book_input);
         match opt_book {
             None => Err(library::ManipulationError::new(
                 library::ManipulationErrorType::UnknownBook(book_input),
             )),
 
-            Some(book) => Ok((book.name.clone(), book.uuid)),
+            Some(book) => Ok((book.name.clone(), book.uuid)),
+        }
+    };
+    let (name, uuid) = result?;
+    match library.remove_book(uuid) {
+        Ok(_) => {
+            library.audit(
+                msg.author.id.to_string(),
+                format!(
+                    "Removed the book \"{}\" ({})",
+                    &name,
+                    library::Database::encode_uuid(uuid)
+                ),
+            );
+            response::success(
+                ctx,
+                msg,
+                format!(
+                    "Book \"{}\" ({}) was removed",
+                    &name,
+                    library::Database::encode_uuid(uuid),
+                ),
+            )
+            .await?
+        }
+        Err(err) => Err(err)?,
+    };
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Registers you with the library so you can check out books. Officers can register someone else by mentioning them first"]
+#[usage = "[@member] <real name>"]
+#[example = "\"Magnus Carlsen\""]
+async fn register(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let target = match args.parse::<UserId>() {
+        Ok(user_id) => {
+            args.advance();
+            user_id
+        }
+        Err(_) => msg.author.id,
+    };
+    let read_name = args.rest().trim().trim_matches('"').to_owned();
+
+    let response = register_member(ctx, &Invocation::from_message(msg), target, read_name).await?;
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+//Registers `target` under `read_name`. Members can register themselves, officers can register
+//anyone
+async fn register_member(
+    ctx: &Context,
+    invocation: &Invocation,
+    target: UserId,
+    mut read_name: String,
+) -> Result<String, CommandError> {
+    let author = invocation.author.id;
+    if target != author && !is_officer(ctx, invocation.guild, author).await {
+        return Err("Only officers can register other members".into());
+    }
+    if read_name.is_empty() {
+        if target != author {
+            return Err("Please include the member's real name".into());
+        }
+        read_name = invocation.author.name.clone();
+    }
+
+    let library_arc = library_for(ctx, invocation.guild).await;
+
+    let mut library = library_arc.write().await;
+    let uuid = library.register_user(target.to_string(), read_name.clone())?;
+
+    Ok(format!(
+        "Registered {} with the library. ID={}",
+        read_name,
+        library::Database::encode_uuid(uuid)
+    ))
+}
+
+//Their nickname in the guild, or their username if they don't have one
+async fn display_name(ctx: &Context, guild: Option<GuildId>, user: &User) -> String {
+    let nick = match guild {
+        Some(guild) => user.nick_in(ctx, guild).await,
+        None => None,
+    };
+    nick.unwrap_or_else(|| user.name.clone())
+}
+
+//Works out which library user an officer means by `input`, which can be a mention of the member
+//or their library id. With `register`, members who aren't registered yet are registered under
+//their display name
+async fn resolve_user(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    input: &str,
+    register: bool,
+) -> Result<library::UserUuid, CommandError> {
+    let library_arc = library_for(ctx, guild).await;
+    let unknown = || {
+        library::ManipulationError::new(library::ManipulationErrorType::UnknownUser(
+            input.to_owned(),
+        ))
+    };
+
+    if let Ok(uuid) = library_arc.read().await.decode_user_uuid(input) {
+        return Ok(uuid);
+    }
+    let target = match input.parse::<UserId>() {
+        Ok(target) => target,
+        Err(_) => return Err(unknown().into()),
+    };
+    if let Some(user) = library_arc
+        .read()
+        .await
+        .find_user_by_discord_id(&target.to_string())
+    {
+        return Ok(user.uuid);
+    }
+    if !register {
+        return Err(unknown().into());
+    }
+
+    //Look the member up before locking the library, so commands aren't held up on discord
+    let user = target.to_user(ctx).await?;
+    let read_name = display_name(ctx, guild, &user).await;
+    let (uuid, _) = library_arc
+        .write()
+        .await
+        .find_or_register_user(target.to_string(), read_name);
+    Ok(uuid)
+}
+
+#[command("user-info")]
+#[checks(Officer)]
+#[description = "Shows an officer everything about a member: loans, overdue history, and suspension status"]
+#[usage = "<@member|user id>"]
+async fn user_info(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user_input: String = args.single::<String>()?;
+
+    let user_uuid = match resolve_user(ctx, msg.guild_id, &user_input, false).await {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            response::error(ctx, msg, "That member isn't registered with the library").await?;
+            return Ok(());
+        }
+    };
+
+    let response = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        member_summary(&library, user_uuid, msg.guild_id)?
+    };
+
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+//Everything an officer might want to know about a member: their loans, overdue history and
+//whether they are suspended
+fn member_summary(
+    library: &library::Database,
+    user_uuid: library::UserUuid,
+    guild: Option<GuildId>,
+) -> Result<String, std::fmt::Error> {
+    let guild = guild.map(|id| id.0);
+    let mut response = String::new();
+    let user = &library.users[&user_uuid];
+
+    write!(
+        response,
+        "**{}** ({}) - registered {}",
+        user.read_name,
+        library::Database::encode_uuid(user.uuid),
+        user.registered.format("%b %-d, %Y")
+    )?;
+    if user.suspended {
+        write!(
+            response,
+            "\n**Borrowing suspended.** Use !library unsuspend {} to lift it",
+            library::Database::encode_uuid(user.uuid)
+        )?;
+    }
+
+    let now = chrono::Local::now();
+    let active = library.active_checkouts_of_user(user.uuid);
+    write!(response, "\n\nActive checkouts ({}):", active.len())?;
+    for checkout in active {
+        write!(
+            response,
+            "\n  *{}* ({}) - {}",
+            checkout_book_name(library, checkout),
+            library::Database::encode_uuid(checkout.uuid),
+            checkout.status
+        )?;
+        if let Some(due_date) = checkout.due_date {
+            let overdue = if due_date < now { " **overdue**" } else { "" };
+            write!(response, " | due {}{}", due_date.format("%b %-d"), overdue)?;
+        }
+        if let Some(message) = checkout.approval_message {
+            write!(response, " | <{}>", message.link(guild))?;
+        }
+    }
+
+    //Books that were returned late, or still haven't been
+    let late: Vec<&library::CheckoutInstance> = library
+        .checkouts_of_user(user.uuid)
+        .into_iter()
+        .filter(
+            |checkout| match (checkout.due_date, &checkout.checkin_approval) {
+                (Some(due_date), Some(approval)) => approval.time > due_date,
+                (Some(due_date), None) => due_date < now,
+                _ => false,
+            },
+        )
+        .collect();
+    write!(response, "\n\nOverdue history ({}):", late.len())?;
+    for checkout in late {
+        let due_date = checkout.due_date.unwrap();
+        let returned = match &checkout.checkin_approval {
+            Some(approval) => format!(
+                "returned {} day(s) late",
+                (approval.time - due_date).num_days()
+            ),
+            None => format!("{} day(s) overdue", (now - due_date).num_days()),
+        };
+        write!(
+            response,
+            "\n  *{}* ({}) - {}",
+            checkout_book_name(library, checkout),
+            library::Database::encode_uuid(checkout.uuid),
+            returned
+        )?;
+        if let Some(message) = checkout.approval_message {
+            write!(response, " | <{}>", message.link(guild))?;
+        }
+    }
+
+    Ok(response)
+}
+
+#[command("merge-users")]
+#[checks(Officer, Writable)]
+#[description = "Merges a duplicate user record into another. Everything the duplicate had is moved to the user that is kept, except games the two played against each other, which are removed"]
+#[usage = "<@member|id to keep> <@member|duplicate id>"]
+async fn merge_users(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let survivor_input: String = args.single::<String>()?;
+    let duplicate_input: String = args.single::<String>()?;
+
+    let mut uuids = Vec::new();
+    for input in &[survivor_input, duplicate_input] {
+        uuids.push(resolve_user(ctx, msg.guild_id, input, false).await?);
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let duplicate_name = library.users[&uuids[1]].read_name.clone();
+    library.merge_users(uuids[0], uuids[1])?;
+    let survivor = &library.users[&uuids[0]];
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Merged {} ({}) into {} ({})",
+            duplicate_name,
+            library::Database::encode_uuid(uuids[1]),
+            survivor.read_name,
+            library::Database::encode_uuid(uuids[0])
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("forget-me")]
+#[checks(Writable)]
+#[description = "Deletes your name and discord account from the library's records. The club's overall stats are kept without your name on them"]
+async fn forget_me(ctx: &Context, msg: &Message) -> CommandResult {
+    let prompt = "This permanently removes your name and discord account from the library's records and can't be undone. React with ✅ to continue".to_owned();
+    if !confirm(ctx, msg, prompt).await? {
+        return Ok(());
+    }
+
+    //Saved PGNs name players the way player_name did when the game ended
+    let names = vec![
+        msg.author.name.clone(),
+        display_name(ctx, msg.guild_id, &msg.author).await,
+    ];
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    library.forget_user(&msg.author.id.to_string(), &names)?;
+
+    response::success(
+        ctx,
+        msg,
+        "Done. The library no longer has any records about you",
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "lookup"]
+#[description = "Shows the books you currently have checked out and the IDs you need to return them"]
+async fn mine(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = guild_of(ctx, msg).await;
+    let response = my_checkouts(ctx, guild, msg.author.id).await?;
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+async fn my_checkouts(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    user: UserId,
+) -> Result<String, CommandError> {
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, guild).await;
+
+        let library = library_arc.read().await;
+
+        let checkouts = match library.find_user_by_discord_id(&user.to_string()) {
+            Some(user) => library.active_checkouts_of_user(user.uuid),
+            None => Vec::new(),
+        };
+
+        if checkouts.is_empty() {
+            write!(response, "You don't have any books checked out")?;
+        } else {
+            write!(
+                response,
+                "You have {} book(s) checked out:",
+                checkouts.len()
+            )?;
+        }
+        let now = chrono::Local::now();
+        for checkout in checkouts {
+            let name = match library.books.get(&checkout.book) {
+                Some(book) => book.name.as_str(),
+                None => "<removed book>",
+            };
+            write!(
+                response,
+                "\n  *{}* - {} | ID: {}",
+                name,
+                checkout.status,
+                library::Database::encode_uuid(checkout.uuid)
+            )?;
+            if let Some(due_date) = checkout.due_date {
+                if due_date < now {
+                    write!(
+                        response,
+                        " | **overdue since {}**",
+                        due_date.format("%b %-d")
+                    )?;
+                } else {
+                    write!(response, " | due {}", due_date.format("%b %-d"))?;
+                }
+            }
+        }
+        write!(
+            response,
+            "\nUse !library return <ID> once you've given a book back to an officer"
+        )?;
+    }
+
+    Ok(response)
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Starts a checkout transaction for one or more books. Use this to checkout books in the library. Naming a series checks out every volume"]
+#[usage = "<book> [more books...]"]
+#[example = "\"My System\" \"Silman's Complete Endgame Course\""]
+async fn checkout(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut book_inputs: Vec<String> = Vec::new();
+    while !args.is_empty() {
+        book_inputs.push(args.single_quoted::<String>()?);
+    }
+    if book_inputs.is_empty() {
+        response::error(ctx, msg, "Which book(s) do you want to check out?").await?;
+        return Ok(());
+    }
+
+    let invocation = Invocation::from_message(msg);
+    let reply = checkout_books(ctx, &invocation, Vec::new(), book_inputs).await?;
+    send_reply(ctx, msg, reply).await?;
+
+    Ok(())
+}
+
+#[command("checkout-for")]
+#[checks(Officer, Writable)]
+#[description = "Starts a checkout on behalf of a member, registering them first if they haven't yet"]
+#[usage = "<@member> <book> [more books...]"]
+#[example = "@Magnus \"My System\""]
+async fn checkout_for(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let target: UserId = args.single::<UserId>()?;
+    let mut book_inputs: Vec<String> = Vec::new();
+    while !args.is_empty() {
+        book_inputs.push(args.single_quoted::<String>()?);
+    }
+    if book_inputs.is_empty() {
+        response::error(ctx, msg, "Which book(s) should they check out?").await?;
+        return Ok(());
+    }
+
+    resolve_user(ctx, msg.guild_id, &target.to_string(), true).await?;
+    let author = target.to_user(ctx).await?;
+    let invocation = Invocation {
+        guild: msg.guild_id,
+        channel: msg.channel_id,
+        display_name: display_name(ctx, msg.guild_id, &author).await,
+        author,
+    };
+    let reply = checkout_books(ctx, &invocation, Vec::new(), book_inputs).await?;
+    send_reply(ctx, msg, reply).await?;
+
+    Ok(())
+}
+
+//Looks up the books named by `inputs` and checks them out, on top of the `books` that were already
+//settled on. Naming a series picks every volume. If an input could mean several books, the member
+//is asked to pick one and this carries on once they do
+async fn checkout_books(
+    ctx: &Context,
+    invocation: &Invocation,
+    mut books: Vec<(library::BookUuid, Option<library::CopyUuid>)>,
+    inputs: Vec<String>,
+) -> Result<picker::Reply, CommandError> {
+    {
+        let library_arc = library_for(ctx, invocation.guild).await;
+        let library = library_arc.read().await;
+
+        let mut inputs = inputs.into_iter();
+        while let Some(input) = inputs.next() {
+            if let Some(book) = library.get_book_from_input(&input) {
+                books.push((book.uuid, None));
+                continue;
+            }
+            let volumes = library.books_in_series(&input);
+            if !volumes.is_empty() {
+                books.extend(volumes.iter().map(|book| (book.uuid, None)));
+                continue;
+            }
+
+            let candidates = library.books_matching(&input);
+            match candidates.len() {
+                0 => {
+                    return Err(library::ManipulationError::new(
+                        library::ManipulationErrorType::UnknownBook(input),
+                    )
+                    .into())
+                }
+                1 => books.push((candidates[0].uuid, None)),
+                _ => {
+                    return Ok(picker::ask(
+                        invocation,
+                        format!("Several books match \"{}\". Which one did you mean?", input),
+                        candidates.into_iter().map(picker::book_option).collect(),
+                        picker::PendingAction::Checkout {
+                            books,
+                            remaining: inputs.collect(),
+                        },
+                    ))
+                }
+            }
+        }
+    }
+
+    start_checkout(ctx, invocation, books)
+        .await
+        .map(picker::Reply::from)
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Reserves a specific copy of a book, like a particular edition, instead of whichever copy is free"]
+#[usage = "<book> <copy id or edition>"]
+#[example = "\"My System\" \"annotated 2nd edition\""]
+async fn reserve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+    let copy_input: String = args.single_quoted::<String>()?;
+
+    let invocation = Invocation::from_message(msg);
+    let reservation = find_copy_to_reserve(ctx, msg.guild_id, book_input, copy_input).await?;
+    let response = start_checkout(ctx, &invocation, vec![reservation]).await?;
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+//Looks up a copy of a book by its id or edition
+async fn find_copy_to_reserve(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    book_input: String,
+    copy_input: String,
+) -> Result<(library::BookUuid, Option<library::CopyUuid>), CommandError> {
+    let reservation = {
+        let library_arc = library_for(ctx, guild).await;
+        let library = library_arc.read().await;
+
+        let book = match library.get_book_from_input(&book_input) {
+            Some(book) => book,
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownBook(book_input),
+            ))?,
+        };
+        let copy = match library.decode_copy_uuid(&copy_input) {
+            Ok(uuid) => book.copies.iter().find(|copy| copy.uuid == uuid),
+            Err(_) => book
+                .copies
+                .iter()
+                .filter(|copy| utils::cmp_ignore_case_ascii(&copy.edition, &copy_input))
+                //Prefer a copy of the edition that nobody has
+                .min_by_key(|copy| library.is_copy_taken(book.uuid, copy.uuid)),
+        };
+        match copy {
+            Some(copy) => (book.uuid, Some(copy.uuid)),
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownCopy(copy_input),
+            ))?,
+        }
+    };
+
+    Ok(reservation)
+}
+
+//Creates checkouts for whoever used the command and posts the message officers react to once the
+//books have been handed over
+async fn start_checkout(
+    ctx: &Context,
+    invocation: &Invocation,
+    books: Vec<(library::BookUuid, Option<library::CopyUuid>)>,
+) -> Result<String, CommandError> {
+    let library_arc = library_for(ctx, invocation.guild).await;
+
+    //Members who haven't registered yet are registered under their display name
+    let display_name = invocation.display_name.clone();
+    let author = &invocation.author;
+
+    let (uuids, text, thread_name, registered) = {
+        let mut library = library_arc.write().await;
+
+        let (rentee, registered) =
+            library.find_or_register_user(author.id.to_string(), display_name.clone());
+
+        let uuids = library.begin_checkout(rentee, &books)?;
+
+        let mut text = format!(
+            "{} wants to check out {} book(s):",
+            author.name,
+            uuids.len()
+        );
+        for uuid in &uuids {
+            let checkout = &library.checkouts[uuid];
+            write!(
+                text,
+                "\n  *{}* ({})",
+                checkout_book_name(&library, checkout),
+                library::Database::encode_uuid(*uuid)
+            )?;
+            if let Some((_, copy)) = checkout.copy.and_then(|copy| library.find_copy(copy)) {
+                write!(
+                    text,
+                    " - reserved copy: {} ({})",
+                    copy.edition,
+                    library::Database::encode_uuid(copy.uuid)
+                )?;
+            }
+        }
+        write!(
+            text,
+            "\nOfficers: react with {} once the books have been handed over",
+            APPROVE_EMOJI
+        )?;
+        let book_names: Vec<&str> = uuids
+            .iter()
+            .map(|uuid| checkout_book_name(&library, &library.checkouts[uuid]))
+            .collect();
+        let thread_name = format!("{}: {}", author.name, book_names.join(", "));
+        (uuids, text, thread_name, registered)
+    };
+
+    let channel = officers_channel(ctx, invocation).await;
+    let approval_msg = channel.say(ctx, text).await?;
+    approval_msg
+        .react(ctx, ReactionType::Unicode(APPROVE_EMOJI.to_owned()))
+        .await?;
+    if invocation.guild.is_some() {
+        threads::open(ctx, &approval_msg, &thread_name, author.id).await;
+    }
+
+    {
+        let mut library = library_arc.write().await;
+        for uuid in &uuids {
+            if let Some(checkout) = library.checkouts.get_mut(uuid) {
+                checkout.approval_message = Some(library::MessageRef::new(
+                    approval_msg.channel_id.0,
+                    approval_msg.id.0,
+                ));
+            }
+        }
+    }
+
+    let mut response = String::new();
+    if registered {
+        write!(
+            response,
+            "Welcome! You've been registered with the library as {}. ",
+            display_name
+        )?;
+    }
+    response.push_str(
+        "Checkout started! Your rental begins once an officer hands you the book(s) and approves it",
+    );
+    Ok(response)
+}
+
+#[command]
+#[description = "Lists the individually tracked copies of a book and whether they are available"]
+#[usage = "<book>"]
+async fn copies(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+
+    let mut response = String::new();
+    {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+
+        let book = match library.get_book_from_input(&book_input) {
+            Some(book) => book,
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownBook(book_input),
+            ))?,
+        };
+        write!(
+            response,
+            "*{}* has {} copies, {} of which are tracked individually:",
+            book.name,
+            book.quantity,
+            book.copies.len()
+        )?;
+        for copy in &book.copies {
+            write!(
+                response,
+                "\n  {} - {} | {}",
+                copy.edition,
+                library::Database::encode_uuid(copy.uuid),
+                if library.is_copy_taken(book.uuid, copy.uuid) {
+                    "taken"
+                } else {
+                    "available"
+                }
+            )?;
+        }
+        write!(
+            response,
+            "\nUse !library reserve <book> <copy id or edition> to reserve one"
+        )?;
+    }
+
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command("add-copy")]
+#[checks(Officer, Writable)]
+#[description = "Starts tracking an individual copy of a book, like a particular edition, so members can reserve it"]
+#[usage = "<book> <edition>"]
+#[example = "\"My System\" \"annotated 2nd edition\""]
+async fn add_copy(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let book_input: String = args.single_quoted::<String>()?;
+    let edition: String = args.single_quoted::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+
+    let book_uuid = match library.get_book_from_input(&book_input) {
+        Some(book) => book.uuid,
+        None => Err(library::ManipulationError::new(
+            library::ManipulationErrorType::UnknownBook(book_input),
+        ))?,
+    };
+    let copy_uuid = library.add_copy(book_uuid, edition.clone())?;
+    let book = &library.books[&book_uuid];
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Now tracking the {} copy of \"{}\". ID={}. The library has {} copies",
+            edition,
+            book.name,
+            library::Database::encode_uuid(copy_uuid),
+            book.quantity
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Asks the officers for more time with a book you are reading"]
+#[usage = "<checkout id> <days> <reason>"]
+#[example = "ABCDEFG 7 I'm halfway through the endgame chapter"]
+async fn extend(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let checkout_input: String = args.single::<String>()?;
+    let days: u32 = args.single::<u32>()?;
+    let reason = args.rest().trim().to_owned();
+    if reason.is_empty() {
+        response::error(ctx, msg, "Please include a reason for the extension").await?;
+        return Ok(());
+    }
+
+    let invocation = Invocation::from_message(msg);
+    let response = ask_for_extension(ctx, &invocation, checkout_input, days, reason).await?;
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+//Sends an extension request to the officers with buttons to approve or deny it
+async fn ask_for_extension(
+    ctx: &Context,
+    invocation: &Invocation,
+    checkout_input: String,
+    days: u32,
+    reason: String,
+) -> Result<String, CommandError> {
+    let library_arc = library_for(ctx, invocation.guild).await;
+    let author = &invocation.author;
+
+    let (request_uuid, text) = {
+        let mut library = library_arc.write().await;
+
+        let rentee = match library.find_user_by_discord_id(&author.id.to_string()) {
+            Some(user) => user.uuid,
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownUser(author.name.clone()),
+            ))?,
+        };
+        let checkout_uuid = match library.decode_checkout_uuid(&checkout_input) {
+            Ok(uuid) => uuid,
+            Err(_) => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownCheckout(checkout_input),
+            ))?,
+        };
+        let request_uuid =
+            library.request_extension(checkout_uuid, rentee, days, reason.clone())?;
+
+        let checkout = &library.checkouts[&checkout_uuid];
+        let mut text = format!(
+            "{} is asking for {} more day(s) with *{}* ({})",
+            author.name,
+            days,
+            checkout_book_name(&library, checkout),
+            library::Database::encode_uuid(checkout_uuid)
+        );
+        if let Some(due_date) = checkout.due_date {
+            write!(text, ", currently due {}", due_date.format("%b %-d"))?;
+        }
+        write!(text, "\nReason: {}", reason)?;
+        (request_uuid, text)
+    };
+
+    let id = library::Database::encode_uuid(request_uuid);
+    let channel = officers_channel(ctx, invocation).await;
+    channel
+        .send_message(ctx, |m| {
+            m.content(text).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.style(ButtonStyle::Success)
+                            .label("Approve")
+                            .custom_id(format!("extend-approve:{}", id))
+                    })
+                    .create_button(|b| {
+                        b.style(ButtonStyle::Danger)
+                            .label("Deny")
+                            .custom_id(format!("extend-deny:{}", id))
+                    })
+                })
+            })
+        })
+        .await?;
+
+    Ok("Your extension request was sent to the officers".to_owned())
+}
+
+#[command("return")]
+#[checks(Writable)]
+#[description = "Used to indicate that you have returned one or more books to an officer. Use !library mine to see your checkout IDs"]
+#[usage = "<checkout id> [more checkout ids...]"]
+async fn return_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut checkout_inputs: Vec<String> = Vec::new();
+    while !args.is_empty() {
+        checkout_inputs.push(args.single::<String>()?);
+    }
+    if checkout_inputs.is_empty() {
+        response::error(
+            ctx,
+            msg,
+            "Which checkout(s) are you returning? Use !library mine to see them",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let invocation = Invocation::from_message(msg);
+    let reply = return_books(ctx, &invocation, Vec::new(), checkout_inputs).await?;
+    send_reply(ctx, msg, reply).await?;
+
+    Ok(())
+}
+
+//The rentee's side of returning books, on top of the `uuids` that were already settled on.
+//Checkouts can be given by id or by the name of the book. Posts the message officers react to
+//once they have the books back
+async fn return_books(
+    ctx: &Context,
+    invocation: &Invocation,
+    mut uuids: Vec<library::CheckoutUuid>,
+    checkout_inputs: Vec<String>,
+) -> Result<picker::Reply, CommandError> {
+    let library_arc = library_for(ctx, invocation.guild).await;
+    let author = &invocation.author;
+
+    let (uuids, text) = {
+        let mut library = library_arc.write().await;
+
+        let rentee = match library.find_user_by_discord_id(&author.id.to_string()) {
+            Some(user) => user.uuid,
+            None => Err(library::ManipulationError::new(
+                library::ManipulationErrorType::UnknownUser(author.name.clone()),
+            ))?,
+        };
+
+        let mut inputs = checkout_inputs.into_iter();
+        while let Some(input) = inputs.next() {
+            if let Ok(uuid) = library.decode_checkout_uuid(&input) {
+                uuids.push(uuid);
+                continue;
+            }
+
+            let books: Vec<library::BookUuid> = library
+                .books_matching(&input)
+                .iter()
+                .map(|book| book.uuid)
+                .collect();
+            let candidates: Vec<&library::CheckoutInstance> = library
+                .active_checkouts_of_user(rentee)
+                .into_iter()
+                .filter(|checkout| checkout.status == library::CheckoutStatus::Reading)
+                .filter(|checkout| {
+                    books.contains(&checkout.book) && !uuids.contains(&checkout.uuid)
+                })
+                .collect();
+            match candidates.len() {
+                0 => Err(library::ManipulationError::new(
+                    library::ManipulationErrorType::UnknownCheckout(input),
+                ))?,
+                1 => uuids.push(candidates[0].uuid),
+                _ => {
+                    let options = candidates
+                        .iter()
+                        .map(|checkout| {
+                            let mut description = library::Database::encode_uuid(checkout.uuid);
+                            if let Some(due_date) = checkout.due_date {
+                                let _ = write!(description, ", due {}", due_date.format("%b %-d"));
+                            }
+                            picker::PickOption::new(
+                                checkout_book_name(&library, checkout).to_owned(),
+                                description,
+                                checkout.uuid,
+                            )
+                        })
+                        .collect();
+                    return Ok(picker::ask(
+                        invocation,
+                        format!(
+                            "You have several books out that match \"{}\". Which one are you returning?",
+                            input
+                        ),
+                        options,
+                        picker::PendingAction::Return {
+                            checkouts: uuids,
+                            remaining: inputs.collect(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        //Nothing is returned until every input has been worked out
+        for uuid in &uuids {
+            library.request_return(*uuid, rentee)?;
+        }
+
+        let mut text = format!("{} returned:", author.name);
+        for uuid in &uuids {
+            let checkout = &library.checkouts[uuid];
+            write!(
+                text,
+                "\n  *{}* ({})",
+                checkout_book_name(&library, checkout),
+                library::Database::encode_uuid(*uuid)
+            )?;
+        }
+        write!(
+            text,
+            "\nOfficers: react with {} once you have the book(s) back",
+            APPROVE_EMOJI
+        )?;
+        (uuids, text)
+    };
+
+    let channel = officers_channel(ctx, invocation).await;
+    let return_msg = channel.say(ctx, text).await?;
+    return_msg
+        .react(ctx, ReactionType::Unicode(APPROVE_EMOJI.to_owned()))
+        .await?;
+
+    {
+        let mut library = library_arc.write().await;
+        for uuid in &uuids {
+            if let Some(checkout) = library.checkouts.get_mut(uuid) {
+                checkout.return_message = Some(library::MessageRef::new(
+                    return_msg.channel_id.0,
+                    return_msg.id.0,
+                ));
+            }
+        }
+    }
+
+    Ok(picker::Reply::from(
+        "Thanks! An officer will confirm the return shortly".to_owned(),
+    ))
+}
+
+//Longer prefixes would be a pain to type
+const MAX_PREFIX_LEN: usize = 5;
+
+#[command]
+#[description = "Changes the prefix commands start with in this server"]
+#[usage = "<new prefix>"]
+#[example = "?"]
+async fn prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let new_prefix: String = args.single::<String>()?;
+    if new_prefix.chars().count() > MAX_PREFIX_LEN {
+        response::error(
+            ctx,
+            msg,
+            format!("Prefixes can be at most {} characters long", MAX_PREFIX_LEN),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+
+    library.config.prefix = if new_prefix == library::DEFAULT_PREFIX {
+        None
+    } else {
+        Some(new_prefix.clone())
+    };
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Changed the command prefix to {}", new_prefix),
+    );
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Commands now start with {0}, for example {0}library list",
+            new_prefix
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows the permission tiers that were granted in this server"]
+async fn permissions(ctx: &Context, msg: &Message) -> CommandResult {
+    let config = library_for(ctx, msg.guild_id)
+        .await
+        .read()
+        .await
+        .config
+        .clone();
+
+    let mut response = String::from(
+        "The server owner and members with the Administrator permission are always admins",
+    );
+    if !config
+        .role_tiers
+        .values()
+        .any(|tier| *tier >= Tier::Officer)
+    {
+        write!(
+            response,
+            "\nNo role has been made officer, so the role named {} is",
+            library::DEFAULT_OFFICER_ROLE
+        )?;
+    }
+    for (role, tier) in &config.role_tiers {
+        write!(response, "\n<@&{}> - {}", role, tier)?;
+    }
+    for (user, tier) in &config.member_tiers {
+        write!(response, "\n<@{}> - {}", user, tier)?;
+    }
+
+    response::info(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+//Who a tier is granted to or revoked from
+enum GrantTarget {
+    Role(RoleId),
+    User(UserId),
+}
+
+impl GrantTarget {
+    fn parse(input: &str) -> Option<GrantTarget> {
+        if let Some(role) = serenity::utils::parse_role(input) {
+            Some(GrantTarget::Role(RoleId(role)))
+        } else {
+            serenity::utils::parse_username(input).map(|user| GrantTarget::User(UserId(user)))
+        }
+    }
+
+    //The grants of the target's kind, and the target's id in them
+    fn tiers<'a>(
+        &self,
+        config: &'a mut library::GuildConfig,
+    ) -> (&'a mut indexmap::IndexMap<u64, Tier>, u64) {
+        match self {
+            GrantTarget::Role(role) => (&mut config.role_tiers, role.0),
+            GrantTarget::User(user) => (&mut config.member_tiers, user.0),
+        }
+    }
+}
+
+impl std::fmt::Display for GrantTarget {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            GrantTarget::Role(role) => write!(fmt, "<@&{}>", role.0),
+            GrantTarget::User(user) => write!(fmt, "<@{}>", user.0),
+        }
+    }
+}
+
+#[command]
+#[description = "Makes a role or member an officer or admin of the library"]
+#[usage = "<officer|admin> <@role|@member>"]
+#[example = "officer @Librarians"]
+async fn grant(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let tier_input: String = args.single::<String>()?;
+    //Everyone is a member already
+    let tier = match Tier::parse(&tier_input) {
+        Some(tier) if tier > Tier::Member => tier,
+        _ => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown tier \"{}\". Expected officer or admin", tier_input),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let target_input: String = args.single::<String>()?;
+    let target = match GrantTarget::parse(&target_input) {
+        Some(target) => target,
+        None => {
+            response::error(ctx, msg, "Expected a role or member mention").await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let (tiers, id) = target.tiers(&mut library.config);
+    tiers.insert(id, tier);
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Granted {} to {}", tier, target),
+    );
+
+    response::success(ctx, msg, format!("{} is now a library {}", target, tier)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Takes back what was granted to a role or member with !config grant"]
+#[usage = "<@role|@member>"]
+#[example = "@Librarians"]
+async fn revoke(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let target_input: String = args.single::<String>()?;
+    let target = match GrantTarget::parse(&target_input) {
+        Some(target) => target,
+        None => {
+            response::error(ctx, msg, "Expected a role or member mention").await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let (tiers, id) = target.tiers(&mut library.config);
+    if tiers.shift_remove(&id).is_none() {
+        response::error(ctx, msg, format!("Nothing was granted to {}", target)).await?;
+        return Ok(());
+    }
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Revoked what was granted to {}", target),
+    );
+
+    response::success(ctx, msg, format!("Revoked what was granted to {}", target)).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Sets the channel the bot posts a kind of message in. With no arguments, shows the channels that are set"]
+#[usage = "[library-log|overdue|digest|audit|errors|broadcast|game-of-the-week|lichess-live] [#channel|none]"]
+#[example = "library-log #officers"]
+async fn channel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    if args.is_empty() {
+        let library = library_arc.read().await;
+        let mut response = String::from("Channels the bot posts in:");
+        for kind in &library::CHANNEL_KINDS {
+            match library.channel(*kind) {
+                Some(channel) => write!(response, "\n{}: <#{}>", kind.name(), channel)?,
+                None => write!(response, "\n{}: not set", kind.name())?,
+            }
+        }
+        response::info(ctx, msg, response).await?;
+        return Ok(());
+    }
+
+    let kind_input: String = args.single::<String>()?;
+    let kind = match library::ChannelKind::parse(&kind_input) {
+        Some(kind) => kind,
+        None => {
+            let names: Vec<&str> = library::CHANNEL_KINDS
+                .iter()
+                .map(|kind| kind.name())
+                .collect();
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown channel \"{}\". Expected one of {}",
+                    kind_input,
+                    names.join(", ")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let channel_input: String = args.single::<String>()?;
+    let channel = if channel_input == "none" {
+        None
+    } else {
+        Some(channel_input.parse::<ChannelId>()?)
+    };
+
+    let mut library = library_arc.write().await;
+    match channel {
+        Some(channel) => library.config.channels.insert(kind, channel.0),
+        None => library.config.channels.shift_remove(&kind),
+    };
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Set the {} channel to {}", kind.name(), channel_input),
+    );
+
+    let text = match channel {
+        Some(channel) => format!(
+            "{} messages will be posted in <#{}>",
+            kind.name(),
+            channel.0
+        ),
+        None => format!("{} channel cleared", kind.name()),
+    };
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("schedule")]
+#[checks(Writable)]
+#[description = "Posts a message every week at the given time, in this channel unless another one is given"]
+#[usage = "<message> every <day> <HH:MM> [#channel]"]
+#[example = "\"Club night starts in an hour!\" every friday 18:00"]
+async fn schedule_announcement(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let message: String = args.single_quoted::<String>()?;
+    if !args.single::<String>()?.eq_ignore_ascii_case("every") {
+        response::error(ctx, msg, "Expected every after the message").await?;
+        return Ok(());
+    }
+    let day: String = args.single::<String>()?;
+    let time: String = args.single::<String>()?;
+    let schedule = match library::WeeklySchedule::parse(&day, &time) {
+        Ok(schedule) => schedule,
+        Err(err) => {
+            response::error(ctx, msg, err).await?;
+            return Ok(());
+        }
+    };
+    let channel = if args.is_empty() {
+        msg.channel_id
+    } else {
+        args.single::<ChannelId>()?
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+
+    let uuid = library.new_announcement_uuid();
+    let text = format!(
+        "Will post in <#{}> every {}. ID={}",
+        channel.0,
+        schedule,
+        library::Database::encode_uuid(uuid)
+    );
+    let announcement = library::Announcement::new(
+        uuid,
+        channel.0,
+        message,
+        schedule,
+        msg.author.id.to_string(),
+    );
+    library.announcements.insert(uuid, announcement);
+
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[description = "Lists the scheduled announcements"]
+async fn list_announcements(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut response = String::new();
+    {
+        let library = library_arc.read().await;
+        if library.announcements.is_empty() {
+            response.push_str("No announcements are scheduled");
+        }
+        for announcement in library.announcements.values() {
+            write!(
+                response,
+                "\n{} - every {} in <#{}>: {}",
+                library::Database::encode_uuid(announcement.uuid),
+                announcement.schedule,
+                announcement.channel,
+                announcement.message
+            )?;
+        }
+    }
+
+    response::info(ctx, msg, response.trim_start()).await?;
+
+    Ok(())
+}
+
+#[command("cancel")]
+#[checks(Writable)]
+#[description = "Stops posting a scheduled announcement"]
+#[usage = "<announcement ID>"]
+async fn cancel_announcement(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+
+    let removed = library
+        .decode_raw_uuid(&input)
+        .and_then(|uuid| library.announcements.shift_remove(&uuid));
+    match removed {
+        Some(announcement) => {
+            response::success(ctx, msg, format!("Cancelled \"{}\"", announcement.message)).await?
+        }
+        None => response::error(ctx, msg, format!("No announcement with ID {}", input)).await?,
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Sets the message new members are DMed when they join. {user}, {server} and {prefix} are filled in. With off, no message is sent and with default the built in one is used again"]
+#[usage = "<message>|off|default"]
+#[example = "Welcome {user}! Come say hi in #general"]
+async fn welcome(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = args.rest().trim().to_owned();
+    if text.is_empty() {
+        let current = {
+            let library = library_for(ctx, msg.guild_id).await;
+            let library = library.read().await;
+            if library.config.welcome_disabled {
+                "Welcome messages are turned off".to_owned()
+            } else {
+                library
+                    .config
+                    .welcome_message
+                    .clone()
+                    .unwrap_or_else(|| welcome::DEFAULT_WELCOME.to_owned())
+            }
+        };
+        response::info(ctx, msg, current).await?;
+        return Ok(());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let reply = match text.as_str() {
+        "off" => {
+            library.config.welcome_disabled = true;
+            "New members won't be sent a welcome message"
+        }
+        "default" => {
+            library.config.welcome_disabled = false;
+            library.config.welcome_message = None;
+            "New members will be sent the default welcome message"
+        }
+        _ => {
+            library.config.welcome_disabled = false;
+            library.config.welcome_message = Some(text.clone());
+            "Welcome message updated"
+        }
+    };
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Set the welcome message to {}", text),
+    );
+
+    response::success(ctx, msg, reply).await?;
+
+    Ok(())
+}
+
+#[command("k-factor")]
+#[description = "Sets how many rating points a game between members is worth with Elo ratings, or goes back to the default. Higher values make ratings move faster. Members' first games count double"]
+#[usage = "<points|default>"]
+#[example = "32"]
+async fn k_factor(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let k_factor = if input.eq_ignore_ascii_case("default") {
+        None
+    } else {
+        match input.parse::<u32>() {
+            Ok(k_factor) if (1..=ratings::MAX_K_FACTOR).contains(&k_factor) => Some(k_factor),
+            _ => {
+                response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "The K-factor has to be a number from 1 to {}, or default",
+                        ratings::MAX_K_FACTOR
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    library.config.k_factor = k_factor;
+    let points = k_factor.unwrap_or(ratings::DEFAULT_K_FACTOR);
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Set the rating K-factor to {}", points),
+    );
+
+    response::success(
+        ctx,
+        msg,
+        format!("Rated games are now worth up to {} points", points),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("rating-system")]
+#[description = "Sets how club ratings are worked out: elo, or glicko2, which also tracks how sure each rating is. Glicko-2 suits clubs whose members play now and then, since ratings of members who haven't played in a while move faster. Ratings carry over when switching"]
+#[usage = "<elo|glicko2>"]
+#[example = "glicko2"]
+async fn rating_system(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let system = match ratings::RatingSystem::parse(&input) {
+        Some(system) => system,
+        None => {
+            let names: Vec<&str> = ratings::RATING_SYSTEMS
+                .iter()
+                .map(|system| system.name())
+                .collect();
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown rating system \"{}\". Expected one of {}",
+                    input,
+                    names.join(", ")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    library.config.rating_system = system;
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Set the rating system to {}", system.name()),
+    );
+
+    response::success(
+        ctx,
+        msg,
+        format!("Club ratings will be worked out with {}", system.name()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("board")]
+#[description = "Sets how chess boards are shown: as an image, as text for servers where uploading images is slow or not allowed, or with the server's own piece emojis. Emoji boards need an emoji for every piece named like chess_wk for the white king and chess_bp for a black pawn, and fall back to text when one is missing"]
+#[usage = "<image|text|emoji>"]
+#[example = "text"]
+async fn board_style(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let style = match BoardStyle::parse(&input) {
+        Some(style) => style,
+        None => {
+            let names: Vec<&str> = games::BOARD_STYLES
+                .iter()
+                .map(|style| style.name())
+                .collect();
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown board style \"{}\". Expected one of {}",
+                    input,
+                    names.join(", ")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    library.config.board_style = style;
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Set the chess board style to {}", style.name()),
+    );
+
+    response::success(
+        ctx,
+        msg,
+        format!("Chess boards will be shown as {}", style.name()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Turns maintenance mode on or off. While it is on, commands that change the library are refused"]
+#[usage = "<on|off>"]
+async fn maintenance(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let state: String = args.single::<String>()?;
+    let enabled = match state.to_ascii_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            response::error(ctx, msg, "Expected on or off").await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut library = library_arc.write().await;
+    library.maintenance = enabled;
+
+    if enabled {
+        response::success(
+            ctx,
+            msg,
+            "Maintenance mode is on. The library is read only until !admin maintenance off",
+        )
+        .await?;
+    } else {
+        response::success(ctx, msg, "Maintenance mode is off").await?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Uploads the library as a json file that can be loaded back with !admin restore. Pass dm to get it in a direct message instead of this channel"]
+#[usage = "[dm]"]
+async fn snapshot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let channel = match args.rest().trim() {
+        "" => msg.channel_id,
+        "dm" => msg.author.create_dm_channel(ctx).await?.id,
+        _ => {
+            response::error(ctx, msg, "Expected nothing or dm").await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let json = {
+        let library = library_arc.read().await;
+        serde_json::to_vec_pretty(&*library)?
+    };
+    //Snapshots hold the same member names and ids as the saved file, so they get the same
+    //protection
+    let data = crypto::encrypt(json)?;
+    let file_name = format!(
+        "library-snapshot-{}.json",
+        chrono::Local::now().format("%Y-%m-%d-%H%M")
+    );
+
+    channel
+        .send_message(ctx, |m| {
+            m.content(if crypto::is_encrypted(&data) {
+                "Library snapshot (encrypted with LIBRARY_ENCRYPTION_KEY)"
+            } else {
+                "Library snapshot"
+            });
+            m.add_file((data.as_slice(), file_name.as_str()))
+        })
+        .await?;
+
+    Ok(())
+}
+
+//How many of the most common themes are listed after an import
+const LISTED_THEMES: usize = 15;
+
+#[command("import-puzzles")]
+#[description = "Replaces the puzzles with the ones in the Lichess puzzle database, the decompressed CSV from database.lichess.org. Attach the file or give the path to it on the bot's machine"]
+#[usage = "[path to CSV]"]
+async fn import_puzzles(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    let report = match msg.attachments.first() {
+        Some(attachment) => {
+            let data = attachment.download().await?;
+            tokio::task::spawn_blocking(move || puzzles::import(std::io::Cursor::new(data)))
+                .await??
+        }
+        None => {
+            let path = args.rest().trim().to_owned();
+            if path.is_empty() {
+                response::error(ctx, msg, "Attach the puzzle CSV or give the path to it").await?;
+                return Ok(());
+            }
+            let file = std::fs::File::open(&path)?;
+            tokio::task::spawn_blocking(move || puzzles::import(std::io::BufReader::new(file)))
+                .await??
+        }
+    };
+
+    let mut response = format!(
+        "Imported {} puzzles. {} were left out for being unpopular or not played enough, and {} lines couldn't be read",
+        report.imported, report.filtered, report.invalid
+    );
+    if !report.themes.is_empty() {
+        response.push_str("\nMost common themes:");
+        for (theme, count) in report.themes.iter().take(LISTED_THEMES) {
+            let _ = write!(response, "\n- {}: {}", theme, count);
+        }
+    }
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command("backup-status")]
+#[description = "Shows when the library was last backed up to each remote backup target"]
+async fn backup_status(ctx: &Context, msg: &Message) -> CommandResult {
+    let backups = {
+        ctx.data
+            .read()
+            .await
+            .get::<backup::BackupData>()
+            .unwrap()
+            .clone()
+    };
+    let report = backups.status_report();
+    if report.is_empty() {
+        response::info(ctx, msg, "No remote backup targets are configured").await?;
+    } else {
+        response::info(ctx, msg, report.join("\n")).await?;
+    }
+
+    Ok(())
+}
+
+//Keeps long lists of problems under discord's message length limit
+const MAX_PROBLEMS_SHOWN: usize = 20;
+
+fn problem_list(header: &str, problems: &[String]) -> String {
+    let mut response = String::from(header);
+    for problem in problems.iter().take(MAX_PROBLEMS_SHOWN) {
+        let _ = write!(response, "\n{}", problem);
+    }
+    if problems.len() > MAX_PROBLEMS_SHOWN {
+        let _ = write!(
+            response,
+            "\n...and {} more",
+            problems.len() - MAX_PROBLEMS_SHOWN
+        );
+    }
+    response
+}
+
+#[command]
+#[description = "Checks the library for records that point at things which don't exist, over-lent books and duplicate ids. Pass repair to fix what can be fixed automatically"]
+#[usage = "[repair]"]
+async fn fsck(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let repair = match args.rest().trim() {
+        "" => false,
+        "repair" => true,
+        _ => {
+            response::error(ctx, msg, "Expected nothing or repair").await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let problems = library_arc.read().await.check_consistency();
+    if problems.is_empty() {
+        response::success(ctx, msg, "No problems found").await?;
+        return Ok(());
+    }
+    if !repair {
+        response::info(
+            ctx,
+            msg,
+            problem_list(&format!("Found {} problem(s):", problems.len()), &problems),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if !confirm(
+        ctx,
+        msg,
+        format!(
+            "Found {} problem(s). Repair what can be repaired?",
+            problems.len()
+        ),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    let (repairs, remaining) = {
+        let mut library = library_arc.write().await;
+        let repairs = library.repair_consistency();
+        for repair in &repairs {
+            library.audit(msg.author.id.to_string(), format!("fsck: {}", repair));
+        }
+        (repairs, library.check_consistency())
+    };
+
+    let mut response = problem_list(&format!("Made {} repair(s):", repairs.len()), &repairs);
+    if !remaining.is_empty() {
+        response.push_str("\n\n");
+        response.push_str(&problem_list(
+            &format!("{} problem(s) need fixing by hand:", remaining.len()),
+            &remaining,
+        ));
+    }
+    response::success(ctx, msg, response).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Replaces the library with a json dump, like the ones written when saving fails. Attach the dump or give the path to it on the bot's machine"]
+#[usage = "[path to dump]"]
+async fn restore(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let data = match msg.attachments.first() {
+        Some(attachment) => attachment.download().await?,
+        None => {
+            let path = args.rest().trim();
+            if path.is_empty() {
+                response::error(ctx, msg, "Attach a json dump or give the path to one").await?;
+                return Ok(());
+            }
+            tokio::fs::read(path).await?
+        }
+    };
+
+    let data = crypto::decrypt(data)?;
+    let mut restored = match library::Database::from_json(&data) {
+        Ok(restored) => restored,
+        Err(err) => {
+            response::error(
+                ctx,
+                msg,
+                format!("That isn't a valid library dump: {}", err),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let problems = restored.check_consistency();
+    if !problems.is_empty() {
+        response::error(
+            ctx,
+            msg,
+            problem_list("Refusing to restore a dump with problems:", &problems),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let prompt = {
+        let library = library_arc.read().await;
+        format!(
+            "Replace the library ({} books, {} members, {} checkouts) with the dump ({} books, {} members, {} checkouts)?",
+            library.books.len(),
+            library.users.len(),
+            library.checkouts.len() + library.archived_checkouts.len(),
+            restored.books.len(),
+            restored.users.len(),
+            restored.checkouts.len() + restored.archived_checkouts.len()
+        )
+    };
+    if !confirm(ctx, msg, prompt).await? {
+        return Ok(());
+    }
+
+    let mut library = library_arc.write().await;
+    restored.guild = library.guild;
+    *library = restored;
+    library.audit(
+        msg.author.id.to_string(),
+        "Restored the library from a json dump".to_owned(),
+    );
+    library.try_save().await;
+
+    response::success(ctx, msg, "Library restored").await?;
+
+    Ok(())
+}
+
+#[command]
+#[bucket = "lookup"]
+#[description = "Shows your library record: registered name, loans, and reading history"]
+async fn profile(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, guild_of(ctx, msg).await).await;
+
+    let fields = {
+        let library = library_arc.read().await;
+
+        let user = match library.find_user_by_discord_id(&msg.author.id.to_string()) {
+            Some(user) => user,
+            None => {
+                response::error(
+                    ctx,
+                    msg,
+                    "You aren't registered with the library yet. Use !library register <your name> to sign up",
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let now = chrono::Local::now();
+        let mut loans = String::new();
+        let mut overdue = String::new();
+        for checkout in library.active_checkouts_of_user(user.uuid) {
+            let name = checkout_book_name(&library, checkout);
+            match checkout.due_date {
+                Some(due_date) if due_date < now => {
+                    writeln!(overdue, "*{}* - due {}", name, due_date.format("%b %-d"))?
+                }
+                Some(due_date) => {
+                    writeln!(loans, "*{}* - due {}", name, due_date.format("%b %-d"))?
+                }
+                None => writeln!(loans, "*{}* - {}", name, checkout.status)?,
+            }
+        }
+        if loans.is_empty() {
+            loans.push_str("None");
+        }
+        if overdue.is_empty() {
+            overdue.push_str("None");
+        }
+
+        let mut fields = vec![
+            ("Name", user.read_name.clone(), true),
+            (
+                "Registered",
+                user.registered.format("%b %-d, %Y").to_string(),
+                true,
+            ),
+            (
+                "Books read",
+                library.books_read(user.uuid).to_string(),
+                true,
+            ),
+            ("Current loans", loans, false),
+            ("Overdue", overdue, false),
+        ];
+        if user.suspended {
+            fields.push(("Status", "Borrowing suspended".to_owned(), false));
+        }
+        if let Some(lichess) = &user.lichess {
+            let mut account = format!("[{}]({}/@/{})", lichess, lichess::base_url(), lichess);
+            let ratings = library
+                .lichess_ratings
+                .get(&user.discord_id)
+                .and_then(|snapshots| snapshots.last())
+                .and_then(|snapshot| snapshot.describe());
+            if let Some(ratings) = ratings {
+                let _ = write!(account, "\n{}", ratings);
+            }
+            fields.push(("Lichess", account, true));
+        }
+        if let Some(chesscom) = &user.chesscom {
+            fields.push((
+                "Chess.com",
+                format!("[{0}](https://www.chess.com/member/{0})", chesscom),
+                true,
+            ));
+        }
+        if let Some(rating) = library.puzzle_ratings.get(&msg.author.id.to_string()) {
+            fields.push((
+                "Puzzle rating",
+                format!(
+                    "{}{} ({} solved, {} failed)",
+                    rating.rating,
+                    if rating.is_provisional() { "?" } else { "" },
+                    rating.solved,
+                    rating.failed
+                ),
+                true,
+            ));
+        }
+        if let Some(streak) = library.puzzle_streaks.get(&msg.author.id.to_string()) {
+            fields.push((
+                "Daily puzzle streak",
+                format!(
+                    "{} (best {})",
+                    streak.current(chrono::Local::now().date_naive()),
+                    streak.best
+                ),
+                true,
+            ));
+        }
+        fields
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title(format!("{}'s profile", msg.author.name))
+                    .thumbnail(msg.author.face())
+                    .fields(fields)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+//The players, the game's id and position and whose turn it is or how the game ended
+fn game_summary(game: &Game) -> String {
+    let mut summary = if game.is_analysis() {
+        format!("Analysis board of <@{}>\n", game.white)
+    } else if let Some(level) = game.engine_level {
+        format!(
+            "<@{}> (white) vs <@{}> (black), engine level {}\n",
+            game.white, game.black, level
+        )
+    } else {
+        format!("<@{}> (white) vs <@{}> (black)\n", game.white, game.black)
+    };
+    let state = game.state();
+    summary.push_str(&format!(
+        "Game ID: {}\nFEN: `{}`\n",
+        library::Database::encode_uuid(game.uuid),
+        state.fen()
+    ));
+    if let Some(number) = game.chess960() {
+        summary.push_str(&format!("Variant: Chess960, position {}\n", number));
+    }
+    if let Some(opening) = game.opening() {
+        summary.push_str(&format!("Opening: {}\n", opening));
+    }
+    if game.casual {
+        summary.push_str("Casual game, not rated\n");
+    }
+    if !game.hints.is_empty() {
+        summary.push_str(&format!("Hints used: {}\n", game.hints.len()));
+    }
+    if let Some(days) = game.days_per_move {
+        summary.push_str(&format!("Time control: {} day(s) per move\n", days));
+    }
+    if let (Some(clock), Some(white), Some(black)) = (
+        game.clock,
+        game.time_left(shakmaty::Color::White),
+        game.time_left(shakmaty::Color::Black),
+    ) {
+        summary.push_str(&format!(
+            "Time control: {}\nClock: white {}, black {}\n",
+            clock,
+            clocks::format_ms(white),
+            clocks::format_ms(black)
+        ));
+    }
+    match game.status {
+        GameStatus::Playing => {
+            summary.push_str(&format!("<@{}> to move", game.to_move()));
+            if state.is_check() {
+                summary.push_str(", in check");
+            }
+            if let Some(deadline) = game.move_deadline() {
+                summary.push_str(&format!(", by {}", deadline.format("%b %-d at %H:%M")));
+            }
+            if let Some(player) = &game.draw_offer {
+                summary.push_str(&format!("\n<@{}> offers a draw", player));
+            }
+            if let Some(player) = &game.takeback_request {
+                summary.push_str(&format!("\n<@{}> asks to take back their move", player));
+            }
+        }
+        status => {
+            summary.push_str(&format!("Result: {}", status));
+            if let Some(end) = state.end() {
+                summary.push_str(&format!(" by {}", end));
+            } else if game.lost_on_time() {
+                summary.push_str(" on time");
+            } else if status == GameStatus::Drawn {
+                summary.push_str(" by agreement");
+            } else if status != GameStatus::Declined {
+                summary.push_str(" by resignation");
+            }
+        }
+    }
+    if !game.moves.is_empty() {
+        summary.push_str(&format!("\n{}", game.move_list()));
+    }
+    summary
+}
+
+//The guild's custom emojis, by name, as they are written in messages
+async fn guild_emojis(
+    ctx: &Context,
+    guild: Option<GuildId>,
+) -> serenity::Result<indexmap::IndexMap<String, String>> {
+    let mut emojis = indexmap::IndexMap::new();
+    if let Some(guild) = guild {
+        for emoji in guild.emojis(ctx).await? {
+            emojis.insert(emoji.name.clone(), emoji.to_string());
+        }
+    }
+    Ok(emojis)
+}
+
+//How a member is named in PGNs. Falls back to their id when they can't be looked up
+async fn player_name(ctx: &Context, guild: Option<GuildId>, discord_id: &str) -> String {
+    let user = match discord_id.parse::<u64>() {
+        Ok(id) => UserId(id).to_user(ctx).await.ok(),
+        Err(_) => None,
+    };
+    match user {
+        Some(user) => display_name(ctx, guild, &user).await,
+        None => discord_id.to_owned(),
+    }
+}
+
+//The game in PGN, with the players' current names and the guild's name as the event
+async fn game_pgn(ctx: &Context, guild: Option<GuildId>, game: &Game) -> String {
+    let club = match guild {
+        Some(guild) => guild.name(ctx).await,
+        None => None,
+    };
+    let event = match club {
+        Some(club) => format!("{} club game", club),
+        None => "Club game".to_owned(),
+    };
+    let mut white = player_name(ctx, guild, &game.white).await;
+    let mut black = player_name(ctx, guild, &game.black).await;
+    if let Some(level) = game.engine_level {
+        let engine = if game.engine_player() == Some(game.white.as_str()) {
+            &mut white
+        } else {
+            &mut black
+        };
+        engine.push_str(&format!(" (level {})", level));
+    }
+    game.to_pgn(&event, &white, &black)
+}
+
+//Saves the PGN of a game that just ended
+async fn record_pgn(ctx: &Context, guild: Option<GuildId>, game: &mut Game) {
+    if game.status.is_over() && game.pgn.is_none() {
+        let pgn = game_pgn(ctx, guild, game).await;
+        game.pgn = Some(pgn);
+    }
+}
+
+//Posts the game with its board, drawn in the guild's board style, in reply to `msg`, and its PGN
+//once it is over. `notify` is mentioned outside of the embed so that they are pinged
+async fn send_game(
+    ctx: &Context,
+    msg: &Message,
+    game: &Game,
+    notify: Option<&str>,
+    flipped: bool,
+    style: BoardStyle,
+) -> CommandResult {
+    let mut text = game_summary(game);
+    let pgn_name = format!("{}.pgn", library::Database::encode_uuid(game.uuid));
+    let mut png = None;
+    match style {
+        BoardStyle::Image => png = Some(game.render_image(flipped)?),
+        BoardStyle::Text => text = format!("{}\n{}", game.render_text(flipped), text),
+        BoardStyle::Emoji => {
+            let emojis = guild_emojis(ctx, msg.guild_id).await?;
+            let board = game
+                .render_emoji(flipped, &emojis)
+                .unwrap_or_else(|| game.render_text(flipped));
+            text = format!("{}\n{}", board, text);
+        }
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg);
+            if let Some(notify) = notify {
+                m.content(format!("<@{}>", notify));
+            }
+            if let Some(png) = &png {
+                m.add_file((png.as_slice(), "board.png"));
+            }
+            if let Some(pgn) = &game.pgn {
+                m.add_file((pgn.as_bytes(), pgn_name.as_str()));
+            }
+            m.embed(|e| {
+                e.colour(response::Tone::Info.colour()).description(text);
+                if png.is_some() {
+                    e.image("attachment://board.png");
+                }
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+//Correspondence games can give each move at most this many days
+const MAX_DAYS_PER_MOVE: u32 = 14;
+
+//Reads chess960, for a random Chess960 starting position, or chess960:<number> for one of them, and
+//returns the position's FEN. None when `word` asks for neither, and an error when the number isn't
+//one of them
+fn parse_chess960(word: &str) -> Option<Result<String, String>> {
+    let (name, number) = match word.split_once(':') {
+        Some((name, number)) => (name, Some(number)),
+        None => (word, None),
+    };
+    if !name.eq_ignore_ascii_case("chess960") {
+        return None;
+    }
+    let number = match number {
+        Some(number) => number.parse::<u32>().ok(),
+        None => Some(rand::random::<u32>() % rules::CHESS960_POSITIONS),
+    };
+    let fen = number
+        .and_then(rules::chess960_fen)
+        .and_then(|fen| rules::GameState::from_fen(&fen))
+        //Saved as written by the bot, so that all games store their positions alike
+        .map(|state| state.fen());
+    Some(fen.ok_or_else(|| {
+        format!(
+            "Chess960 starting positions go from 0 to {}",
+            rules::CHESS960_POSITIONS - 1
+        )
+    }))
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Challenges a member to a game of chess. You play white or black as asked, or a random colour. Give a number of days to play by correspondence, with that long for each move: the bot reminds players whose time is running out, and they lose the game if it runs out. Or give a time control like 10+5 to play with clocks, with 10 minutes each and 5 seconds added after every move. Add chess960 to play Chess960 from a random starting position, or chess960:<0-959> to pick one. Add casual for a game that isn't rated, where moves can be taken back"]
+#[usage = "<@member> [white|black] [days per move|minutes+increment] [chess960[:position]] [casual]"]
+#[example = "@Magnus white 3"]
+async fn challenge(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent: UserId = args.single::<UserId>()?;
+    let mut colour = None;
+    let mut days_per_move = None;
+    let mut clock = None;
+    let mut start_fen = None;
+    let mut casual = false;
+    for word in args.iter::<String>().filter_map(|word| word.ok()) {
+        if word.eq_ignore_ascii_case("casual") {
+            casual = true;
+            continue;
+        }
+        match parse_chess960(&word) {
+            Some(Ok(fen)) => {
+                start_fen = Some(fen);
+                continue;
+            }
+            Some(Err(err)) => {
+                response::error(ctx, msg, err).await?;
+                return Ok(());
+            }
+            None => {}
+        }
+        if word.contains('+') {
+            clock = clocks::Clock::parse(&word);
+            if clock.is_none() {
+                response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "\"{}\" isn't a time control. Write them like 10+5, with 1 to {} minutes and an increment of up to {} seconds",
+                        word,
+                        clocks::MAX_MINUTES,
+                        clocks::MAX_INCREMENT_SECS
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+            continue;
+        }
+        match word.parse::<u32>() {
+            Ok(days) if (1..=MAX_DAYS_PER_MOVE).contains(&days) => days_per_move = Some(days),
+            Ok(_) => {
+                response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "Correspondence games can have 1 to {} days per move",
+                        MAX_DAYS_PER_MOVE
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+            Err(_) => colour = Some(word.to_lowercase()),
+        }
+    }
+    if days_per_move.is_some() && clock.is_some() {
+        response::error(
+            ctx,
+            msg,
+            "Games are played either by correspondence or with clocks, not both",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let challenger_white = match colour.as_deref() {
+        Some("white") => true,
+        Some("black") => false,
+        None | Some("random") => rand::random(),
+        Some(other) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown colour \"{}\". Use white or black", other),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    send_challenge(
+        ctx,
+        msg,
+        opponent,
+        challenger_white,
+        start_fen,
+        days_per_move,
+        clock,
+        casual,
+    )
+    .await
+}
+
+//Challenges `opponent` to a game against whoever sent `msg`, from `start_fen` when given and
+//with `days_per_move` for each move when played by correspondence or `clock` when played with
+//clocks. Casual games aren't rated
+async fn send_challenge(
+    ctx: &Context,
+    msg: &Message,
+    opponent: UserId,
+    challenger_white: bool,
+    start_fen: Option<String>,
+    days_per_move: Option<u32>,
+    clock: Option<clocks::Clock>,
+    casual: bool,
+) -> CommandResult {
+    if opponent == msg.author.id {
+        return Err(library::ManipulationError::new(
+            library::ManipulationErrorType::CantPlayYourself,
+        )
+        .into());
+    }
+    if opponent.to_user(ctx).await?.bot {
+        response::error(ctx, msg, "Bots can't be challenged").await?;
+        return Ok(());
+    }
+
+    let me = msg.author.id.to_string();
+    let them = opponent.to_string();
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    {
+        let mut library = library_arc.write().await;
+        if library.find_game(&me, Some(&them), |_| true).is_ok() {
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::AlreadyPlaying(them),
+            )
+            .into());
+        }
+        let (white, black) = if challenger_white {
+            (me.clone(), them.clone())
+        } else {
+            (them.clone(), me.clone())
+        };
+        let uuid = library.new_game_uuid();
+        let mut game = Game::new(uuid, white, black, me, msg.channel_id.0);
+        game.start_fen = start_fen.clone();
+        game.days_per_move = days_per_move;
+        game.clock = clock;
+        game.casual = casual;
+        library.games.insert(uuid, game);
+    }
+
+    let time_control = match (days_per_move, clock) {
+        (Some(days), _) => format!(" with {} day(s) per move", days),
+        (None, Some(clock)) => format!(" with a {} clock", clock),
+        (None, None) => String::new(),
+    };
+    let variant = match start_fen.as_deref().and_then(rules::chess960_number) {
+        Some(number) => format!(" from Chess960 position {}", number),
+        None => String::new(),
+    };
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg).content(format!(
+                "<@{}> you've been challenged to a {}game of chess, playing {}{}{}! Answer with !chess accept or !chess decline",
+                opponent,
+                if casual { "casual " } else { "" },
+                if challenger_white { "black" } else { "white" },
+                variant,
+                time_control
+            ))
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Sets up the position given in Forsyth-Edwards Notation on an analysis board, where you play both sides. It replaces your previous analysis board. Mention a member to challenge them to play the position out instead, with you playing the side to move"]
+#[usage = "<FEN> [@member]"]
+#[example = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"]
+async fn fen(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut words: Vec<&str> = args.rest().split_whitespace().collect();
+    //Plain numbers would also be read as user ids, and FENs end with numbers
+    let opponent = match words.last() {
+        Some(word) if word.starts_with("<@") => Some(word.parse::<UserId>()?),
+        _ => None,
+    };
+    if opponent.is_some() {
+        words.pop();
+    }
+    let fen = words.join(" ");
+    let state = match rules::GameState::from_fen(&fen) {
+        Some(state) => state,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!("\"{}\" isn't the FEN of a legal position", fen),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    //Saved as written by the bot, so that all games store their positions alike
+    let start_fen = state.fen();
+
+    if let Some(opponent) = opponent {
+        let challenger_white = state.turn() == shakmaty::Color::White;
+        return send_challenge(
+            ctx,
+            msg,
+            opponent,
+            challenger_white,
+            Some(start_fen),
+            None,
+            None,
+            false,
+        )
+        .await;
+    }
+
+    let me = msg.author.id.to_string();
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    library
+        .games
+        .retain(|_, game| !(game.is_analysis() && game.white == me && !game.status.is_over()));
+    let uuid = library.new_game_uuid();
+    let mut game = Game::new(uuid, me.clone(), me.clone(), me, msg.channel_id.0);
+    game.start_fen = Some(start_fen);
+    game.status = GameStatus::Playing;
+    let style = library.config.board_style;
+    library.games.insert(uuid, game);
+    send_game(ctx, msg, &library.games[&uuid], None, false, style).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Accepts a challenge to a game of chess"]
+#[usage = "[@challenger]"]
+async fn accept(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Challenged && game.challenged() == me
+    })?;
+    let style = library.config.board_style;
+    let game = library.games.get_mut(&uuid).unwrap();
+    game.status = GameStatus::Playing;
+    game.started = chrono::Local::now();
+    let white = game.white.clone();
+    send_game(ctx, msg, game, Some(&white), false, style).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Declines a challenge to a game of chess, or takes back one you made"]
+#[usage = "[@member]"]
+async fn decline(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Challenged
+    })?;
+    let game = library.games.get_mut(&uuid).unwrap();
+    game.finish(GameStatus::Declined);
+    let text = if game.challenger == me {
+        format!("Took back your challenge to <@{}>", game.opponent_of(&me))
+    } else {
+        format!("Declined <@{}>'s challenge", game.challenger)
+    };
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("move")]
+#[checks(Writable)]
+#[description = "Plays a move in your game, written like Nf3 or g1f3"]
+#[usage = "<move> [@opponent]"]
+#[example = "Nf3"]
+async fn move_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let uuid = {
+        let mut library = library_arc.write().await;
+        let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+            game.status == GameStatus::Playing
+        })?;
+        let style = library.config.board_style;
+        let game = library.games.get_mut(&uuid).unwrap();
+        game.play(&me, &input, msg.timestamp.with_timezone(&chrono::Local))?;
+        record_pgn(ctx, msg.guild_id, game).await;
+        //Nobody has to be told about moves on an analysis board, or against the engine
+        let notify = Some(game.opponent_of(&me).to_owned())
+            .filter(|_| !game.is_analysis() && game.engine_level.is_none());
+        //Against the engine the board stays the member's way up
+        let flipped = game.engine_level.is_some();
+        send_game(ctx, msg, game, notify.as_deref(), flipped, style).await?;
+        broadcast::update(&ctx.http, &library, uuid).await;
+        send_engine_record(ctx, msg, &library, uuid).await?;
+        library.archive_discord_game(uuid);
+        send_rating_changes(ctx, msg, &mut library, uuid).await?;
+        send_simul_results(ctx, &library, uuid).await?;
+        uuid
+    };
+    engine_reply(ctx, msg, uuid).await
+}
+
+#[command]
+#[description = "Lists the moves that can be played in your game"]
+#[usage = "[@opponent]"]
+async fn moves(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let game = &library.games[&uuid];
+    let legal = game.state().legal_moves();
+    response::info(
+        ctx,
+        msg,
+        format!(
+            "<@{}> can play {} moves:\n{}",
+            game.to_move(),
+            legal.len(),
+            legal.join(" ")
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows the board of your game"]
+#[usage = "[@opponent]"]
+async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let game = &library.games[&uuid];
+    send_game(ctx, msg, game, None, false, library.config.board_style).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Shows the board of any game, from the side to move unless flip is given"]
+#[usage = "<game ID> [flip]"]
+async fn show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let flipped = args
+        .single::<String>()
+        .map_or(false, |arg| arg.eq_ignore_ascii_case("flip"));
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let game = library
+        .decode_raw_uuid(&input)
+        .and_then(|uuid| library.games.get(&uuid))
+        .ok_or_else(|| {
+            library::ManipulationError::new(library::ManipulationErrorType::UnknownGame(input))
+        })?;
+    send_game(ctx, msg, game, None, flipped, library.config.board_style).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Sends a game in Portable Game Notation, to import into other chess tools"]
+#[usage = "<game ID>"]
+async fn pgn(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let game = library
+        .decode_raw_uuid(&input)
+        .and_then(|uuid| library.games.get(&uuid))
+        .ok_or_else(|| {
+            library::ManipulationError::new(library::ManipulationErrorType::UnknownGame(input))
+        })?;
+    //Games that are still going get one with their result left open
+    let pgn = match &game.pgn {
+        Some(pgn) => pgn.clone(),
+        None => game_pgn(ctx, msg.guild_id, game).await,
+    };
+    let file_name = format!("{}.pgn", library::Database::encode_uuid(game.uuid));
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg);
+            m.add_file((pgn.as_bytes(), file_name.as_str()))
+        })
+        .await?;
+
+    Ok(())
+}
+
+//Longer PGN files are refused, since only one game is read from them anyway
+const MAX_PGN_SIZE: u64 = 256 * 1024;
+
+#[command("replay")]
+#[description = "Steps through a game given in PGN, either attached as a file or pasted after the command"]
+#[usage = "[PGN]"]
+async fn replay_command(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = match msg.attachments.first() {
+        Some(attachment) if attachment.size > MAX_PGN_SIZE => {
+            response::error(ctx, msg, "That file is too big to be a PGN").await?;
+            return Ok(());
+        }
+        Some(attachment) => String::from_utf8_lossy(&attachment.download().await?).into_owned(),
+        //Pasted games may be in a code block
+        None => args
+            .rest()
+            .trim()
+            .trim_start_matches("```pgn")
+            .trim_matches('`')
+            .to_owned(),
+    };
+    if text.trim().is_empty() {
+        response::error(
+            ctx,
+            msg,
+            "Attach a PGN file or paste the game after the command",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let game = pgn::parse(&text)?;
+    let style = library_for(ctx, msg.guild_id)
+        .await
+        .read()
+        .await
+        .config
+        .board_style;
+    let emojis = match style {
+        BoardStyle::Emoji => Some(guild_emojis(ctx, msg.guild_id).await?),
+        _ => None,
+    };
+    replay::start(ctx, msg, game, emojis).await?;
+
+    Ok(())
+}
+
+//The engine's line from the position in `state`, in SAN with move numbers
+fn principal_variation(state: &rules::GameState, pv: &[String]) -> String {
+    let mut state = match rules::GameState::from_fen(&state.fen()) {
+        Some(state) => state,
+        None => return String::new(),
+    };
+    let mut line = String::new();
+    for (i, uci) in pv.iter().enumerate() {
+        let m = match state.parse_move(uci) {
+            Some(m) => m,
+            None => break,
+        };
+        if state.turn() == shakmaty::Color::White {
+            let _ = write!(line, "{}. ", state.move_number());
+        } else if i == 0 {
+            let _ = write!(line, "{}... ", state.move_number());
+        }
+        line.push_str(&state.play(&m));
+        line.push(' ');
+    }
+    line.trim_end().to_owned()
+}
+
+//The engine's score from white's point of view, like +0.35 or #-3
+fn evaluation(score: engine::Score, turn: shakmaty::Color) -> String {
+    let sign = if turn == shakmaty::Color::White {
+        1
+    } else {
+        -1
+    };
+    match score {
+        engine::Score::Centipawns(cp) => format!("{:+.2}", (cp * sign) as f64 / 100.0),
+        engine::Score::Mate(moves) => format!("#{}", moves * sign),
+    }
+}
+
+#[command]
+#[bucket = "engine"]
+#[description = "Asks the chess engine for the best move in a position, given as a FEN, a game ID or a game in PGN, which is analysed from its final position. PGN files can also be attached. Endgames with up to 7 pieces are looked up in the tablebase instead, for the exact result"]
+#[usage = "<FEN|game ID|PGN>"]
+#[example = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"]
+async fn analyze(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = match msg.attachments.first() {
+        Some(attachment) if attachment.size > MAX_PGN_SIZE => {
+            response::error(ctx, msg, "That file is too big to be a PGN").await?;
+            return Ok(());
+        }
+        Some(attachment) => String::from_utf8_lossy(&attachment.download().await?).into_owned(),
+        None => args
+            .rest()
+            .trim()
+            .trim_start_matches("```pgn")
+            .trim_matches('`')
+            .to_owned(),
+    };
+    if text.trim().is_empty() {
+        response::error(
+            ctx,
+            msg,
+            "Give a FEN, a game ID or a game in PGN to analyse",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let game = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        library
+            .decode_raw_uuid(text.trim())
+            .and_then(|uuid| library.games.get(&uuid))
+            .map(|game| game.state())
+    };
+    let state = match game.or_else(|| rules::GameState::from_fen(&text)) {
+        Some(state) => state,
+        None => {
+            let game = pgn::parse(&text)?;
+            rules::GameState::replay(game.start_fen.as_deref(), &game.moves)
+        }
+    };
+    if let Some(end) = state.end() {
+        response::error(ctx, msg, format!("The game is already over by {}", end)).await?;
+        return Ok(());
+    }
+
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    if let Some(probe) = tablebase::probe(&state).await {
+        let best_move = probe
+            .best_move
+            .as_deref()
+            .and_then(|uci| state.parse_move(uci))
+            .map(|m| shakmaty::san::SanPlus::from_move(state.position().clone(), &m).to_string())
+            .unwrap_or_default();
+        response::info(
+            ctx,
+            msg,
+            format!(
+                "FEN: `{}`\nBest move: **{}**\nTablebase: {}",
+                state.fen(),
+                best_move,
+                probe.describe(state.turn())
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    let analysis = engine::analyse(&state.fen(), engine::default_limit()).await?;
+    let best_move = state
+        .parse_move(&analysis.best_move)
+        .map(|m| shakmaty::san::SanPlus::from_move(state.position().clone(), &m).to_string())
+        .unwrap_or_else(|| analysis.best_move.clone());
+    response::info(
+        ctx,
+        msg,
+        format!(
+            "FEN: `{}`\nBest move: **{}**\nEvaluation: {} (depth {})\nLine: {}",
+            state.fen(),
+            best_move,
+            evaluation(analysis.score, state.turn()),
+            analysis.depth,
+            principal_variation(&state, &analysis.pv)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+//How many of the judged moves are listed in the reply to !chess blundercheck. The annotated PGN has
+//all of them
+const MAX_FLAGGED_SHOWN: usize = 20;
+
+#[command]
+#[bucket = "engine"]
+#[description = "Runs the chess engine over every move of a game in PGN, attached as a file or pasted after the command, and points out the inaccuracies, mistakes and blunders. Sends the game back annotated"]
+#[usage = "[PGN]"]
+async fn blundercheck(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = match msg.attachments.first() {
+        Some(attachment) if attachment.size > MAX_PGN_SIZE => {
+            response::error(ctx, msg, "That file is too big to be a PGN").await?;
+            return Ok(());
+        }
+        Some(attachment) => String::from_utf8_lossy(&attachment.download().await?).into_owned(),
+        None => args
+            .rest()
+            .trim()
+            .trim_start_matches("```pgn")
+            .trim_matches('`')
+            .to_owned(),
+    };
+    if text.trim().is_empty() {
+        response::error(
+            ctx,
+            msg,
+            "Attach a PGN file or paste the game after the command",
+        )
+        .await?;
+        return Ok(());
+    }
+    let game = pgn::parse(&text)?;
+    if game.moves.is_empty() {
+        response::error(ctx, msg, "That game has no moves to check").await?;
+        return Ok(());
+    }
+
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    let report = blunders::check(&game).await?;
+
+    let mut text = String::new();
+    if let Some(opening) = openings::identify(game.start_fen.as_deref(), &game.moves) {
+        let _ = writeln!(text, "Opening: {}", opening);
+    }
+    for (i, (colour, name)) in [
+        (shakmaty::Color::White, game.tag("White")),
+        (shakmaty::Color::Black, game.tag("Black")),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let _ = writeln!(
+            text,
+            "**{}** ({}): {} inaccuracies, {} mistakes, {} blunders, average centipawn loss {:.0}",
+            name,
+            if i == 0 { "white" } else { "black" },
+            report.count(*colour, blunders::Judgement::Inaccuracy),
+            report.count(*colour, blunders::Judgement::Mistake),
+            report.count(*colour, blunders::Judgement::Blunder),
+            report.average_loss[i]
+        );
+    }
+    text.push('\n');
+    for flagged in report.flagged.iter().take(MAX_FLAGGED_SHOWN) {
+        let _ = writeln!(
+            text,
+            "{} {}{} {} (lost {:.0}% winning chances). Best was {}",
+            flagged.number,
+            flagged.san,
+            flagged.judgement.symbol(),
+            flagged.judgement,
+            flagged.loss * 100.0,
+            flagged.best
+        );
+    }
+    if report.flagged.len() > MAX_FLAGGED_SHOWN {
+        let _ = writeln!(
+            text,
+            "…and {} more in the annotated PGN",
+            report.flagged.len() - MAX_FLAGGED_SHOWN
+        );
+    }
+    if report.flagged.is_empty() {
+        text.push_str("No inaccuracies, mistakes or blunders. Well played!\n");
+    }
+    if report.plies < game.moves.len() {
+        let _ = writeln!(
+            text,
+            "Only the first {} moves were checked",
+            blunders::MAX_PLIES
+        );
+    }
+
+    let annotated = report.annotated_pgn(&game);
+    let title = format!(
+        "Blunder check: {} vs {}",
+        game.tag("White"),
+        game.tag("Black")
+    );
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .add_file((annotated.as_bytes(), "annotated.pgn"))
+                .embed(|e| {
+                    e.colour(response::Tone::Info.colour())
+                        .title(title)
+                        .description(text)
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+//How long !chess eval searches for, so that it answers in the middle of a conversation
+const EVAL_TIME: std::time::Duration = std::time::Duration::from_millis(1500);
+//Squares in the bar drawn by eval_bar
+const EVAL_BAR_WIDTH: usize = 16;
+
+//A bar showing how much better white stands, white's share in white squares and black's in black
+//squares. It is drawn from the side to move's winning chances rather than the score, so that the
+//bar only fills up for positions that are lost, and mates fill it for whoever is mating
+fn eval_bar(chances: f64, turn: shakmaty::Color) -> String {
+    let white_share = if turn == shakmaty::Color::White {
+        chances
+    } else {
+        1.0 - chances
+    };
+    let white = (white_share * EVAL_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{}{}",
+        "⬜".repeat(white),
+        "⬛".repeat(EVAL_BAR_WIDTH - white)
+    )
+}
+
+#[command("eval")]
+#[bucket = "engine"]
+#[description = "Quickly evaluates a position given as a FEN, showing the board with the engine's best move and a bar of who stands better. Endgames with up to 7 pieces are looked up in the tablebase for the exact result. Use !chess analyze for a deeper look"]
+#[usage = "<FEN>"]
+#[example = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"]
+async fn eval(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let fen = args.rest().trim().trim_matches('`');
+    if fen.is_empty() {
+        response::error(ctx, msg, "Give the FEN of a position to evaluate").await?;
+        return Ok(());
+    }
+    let state = match rules::GameState::from_fen(fen) {
+        Some(state) => state,
+        None => {
+            response::error(ctx, msg, "That isn't a valid FEN").await?;
+            return Ok(());
+        }
+    };
+    if let Some(end) = state.end() {
+        response::error(ctx, msg, format!("The game is already over by {}", end)).await?;
+        return Ok(());
+    }
+
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    let (chances, verdict, best_uci) = match tablebase::probe(&state).await {
+        Some(probe) => (
+            probe.wdl.winning_chances(),
+            format!("Tablebase: {}", probe.describe(state.turn())),
+            probe.best_move.unwrap_or_default(),
+        ),
+        None => {
+            let analysis =
+                engine::analyse(&state.fen(), engine::Limit::MoveTime(EVAL_TIME)).await?;
+            (
+                analysis.score.winning_chances(),
+                evaluation(analysis.score, state.turn()),
+                analysis.best_move,
+            )
+        }
+    };
+    let best_move = state.parse_move(&best_uci);
+    let best_san = best_move
+        .as_ref()
+        .map(|m| shakmaty::san::SanPlus::from_move(state.position().clone(), m).to_string())
+        .unwrap_or(best_uci);
+    let png = board_image::render_board(
+        state.position().board(),
+        best_move.as_ref().and_then(Game::move_squares),
+        state.turn() == shakmaty::Color::Black,
+    )?;
+    let text = format!(
+        "{} {}\nBest move: **{}**, {} to move",
+        eval_bar(chances, state.turn()),
+        verdict,
+        best_san,
+        if state.turn() == shakmaty::Color::White {
+            "white"
+        } else {
+            "black"
+        }
+    );
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .add_file((png.as_slice(), "board.png"))
+                .embed(|e| {
+                    e.colour(response::Tone::Info.colour())
+                        .description(text)
+                        .image("attachment://board.png")
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+//Games against the engine are at this level when no other is asked for
+const DEFAULT_ENGINE_LEVEL: u8 = 3;
+
+#[command("play-bot")]
+#[checks(Writable)]
+#[description = "Starts a game against the chess engine at a level from 1 to 8, where it answers each of your moves. You play white or black as asked, or a random colour. Add chess960 to play Chess960 from a random starting position, or chess960:<0-959> to pick one"]
+#[usage = "[level 1-8] [white|black] [chess960[:position]]"]
+#[example = "3 white"]
+async fn play_bot(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let level = args.single::<u8>().unwrap_or(DEFAULT_ENGINE_LEVEL);
+    if !(1..=engine::LEVELS).contains(&level) {
+        response::error(ctx, msg, format!("Levels go from 1 to {}", engine::LEVELS)).await?;
+        return Ok(());
+    }
+    let mut colour = None;
+    let mut start_fen = None;
+    for word in args.iter::<String>().filter_map(|word| word.ok()) {
+        match parse_chess960(&word) {
+            Some(Ok(fen)) => start_fen = Some(fen),
+            Some(Err(err)) => {
+                response::error(ctx, msg, err).await?;
+                return Ok(());
+            }
+            None => colour = Some(word.to_lowercase()),
+        }
+    }
+    let member_white = match colour.as_deref() {
+        Some("white") => true,
+        Some("black") => false,
+        None | Some("random") => rand::random(),
+        Some(other) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown colour \"{}\". Use white or black", other),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let me = msg.author.id.to_string();
+    let bot = ctx.cache.current_user_id().await.to_string();
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let uuid = {
+        let mut library = library_arc.write().await;
+        if let Ok(uuid) = library.find_game(&me, Some(&bot), |_| true) {
+            //The engine may not have answered yet, for example when it couldn't be started. Asking
+            //again gives it another try
+            if library.games[&uuid].to_move() == bot {
+                drop(library);
+                return engine_reply(ctx, msg, uuid).await;
+            }
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::AlreadyPlaying(bot),
+            )
+            .into());
+        }
+        let (white, black) = if member_white {
+            (me.clone(), bot.clone())
+        } else {
+            (bot.clone(), me.clone())
+        };
+        let uuid = library.new_game_uuid();
+        let mut game = Game::new(uuid, white, black, me, msg.channel_id.0);
+        game.engine_level = Some(level);
+        game.start_fen = start_fen;
+        game.status = GameStatus::Playing;
+        let style = library.config.board_style;
+        library.games.insert(uuid, game);
+        //With black, the board is shown once the engine has played its first move
+        if member_white {
+            send_game(ctx, msg, &library.games[&uuid], None, false, style).await?;
+        }
+        uuid
+    };
+    engine_reply(ctx, msg, uuid).await
+}
+
+//Has the engine play its move in game `uuid`, when it is against the engine and its turn
+async fn engine_reply(ctx: &Context, msg: &Message, uuid: games::GameUuid) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let (fen, level, played) = {
+        let library = library_arc.read().await;
+        let game = match library.games.get(&uuid) {
+            Some(game) => game,
+            None => return Ok(()),
+        };
+        match (game.engine_player(), game.engine_level) {
+            (Some(engine), Some(level))
+                if game.status == GameStatus::Playing && game.to_move() == engine =>
+            {
+                (game.state().fen(), level, game.moves.len())
+            }
+            _ => return Ok(()),
+        }
+    };
+    //The library isn't held while the engine thinks
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    let reply = engine::play(&fen, level).await?;
+
+    let mut library = library_arc.write().await;
+    let style = library.config.board_style;
+    let game = match library.games.get_mut(&uuid) {
+        Some(game) => game,
+        None => return Ok(()),
+    };
+    //The member may have resigned in the meantime
+    if game.status != GameStatus::Playing || game.moves.len() != played {
+        return Ok(());
+    }
+    let engine = game.challenged().to_owned();
+    game.play(&engine, &reply, chrono::Local::now())?;
+    record_pgn(ctx, msg.guild_id, game).await;
+    let member = game.challenger.clone();
+    send_game(ctx, msg, game, Some(&member), false, style).await?;
+    broadcast::update(&ctx.http, &library, uuid).await;
+    send_engine_record(ctx, msg, &library, uuid).await?;
+
+    Ok(())
+}
+
+//Tells the member how they have done against the engine's level, once game `uuid` against it ends
+async fn send_engine_record(
+    ctx: &Context,
+    msg: &Message,
+    library: &library::Database,
+    uuid: games::GameUuid,
+) -> CommandResult {
+    let game = &library.games[&uuid];
+    let level = match game.engine_level {
+        Some(level) if game.status.is_over() => level,
+        _ => return Ok(()),
+    };
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for other in library.games.values().filter(|other| {
+        other.engine_level == Some(level)
+            && other.challenger == game.challenger
+            && other.status.is_over()
+    }) {
+        let member_white = other.white == other.challenger;
+        match other.status {
+            GameStatus::Drawn => draws += 1,
+            GameStatus::WhiteWon if member_white => wins += 1,
+            GameStatus::BlackWon if !member_white => wins += 1,
+            _ => losses += 1,
+        }
+    }
+    response::info(
+        ctx,
+        msg,
+        format!(
+            "<@{}>'s record against level {}: {} won, {} drawn, {} lost",
+            game.challenger, level, wins, draws, losses
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+//How deep the engine looks for hints. Shallow, so that hints are a nudge rather than perfect play
+const HINT_DEPTH: u32 = 8;
+
+#[command]
+#[checks(Writable)]
+#[bucket = "engine"]
+#[description = "Asks for a hint in your game against the engine. Level 1 shows which piece to move, level 2 the whole move. Hints are noted in the game's PGN"]
+#[usage = "[1|2]"]
+#[example = "2"]
+async fn hint(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let level = args.single::<u8>().unwrap_or(1);
+    if !(1..=2).contains(&level) {
+        response::error(
+            ctx,
+            msg,
+            "Hints are level 1, for the piece to move, or level 2, for the whole move",
+        )
+        .await?;
+        return Ok(());
+    }
+    let me = msg.author.id.to_string();
+    let bot = ctx.cache.current_user_id().await.to_string();
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let (uuid, fen, played) = {
+        let library = library_arc.read().await;
+        let uuid = library.find_game(&me, Some(&bot), |game| game.status == GameStatus::Playing)?;
+        let game = &library.games[&uuid];
+        if game.to_move() != me {
+            response::error(ctx, msg, "It isn't your move").await?;
+            return Ok(());
+        }
+        (uuid, game.state().fen(), game.moves.len())
+    };
+
+    //The library isn't held while the engine thinks
+    let _typing = msg.channel_id.start_typing(&ctx.http);
+    let analysis = engine::analyse(&fen, engine::Limit::Depth(HINT_DEPTH)).await?;
+    let state = rules::GameState::start(Some(&fen));
+    let best = match state.parse_move(&analysis.best_move) {
+        Some(best) => best,
+        None => {
+            response::error(ctx, msg, "The engine couldn't come up with a hint").await?;
+            return Ok(());
+        }
+    };
+
+    {
+        let mut library = library_arc.write().await;
+        match library.games.get_mut(&uuid) {
+            //The member may have moved in the meantime
+            Some(game) if game.status == GameStatus::Playing && game.moves.len() == played => {
+                game.record_hint(level == 2)
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    let text = if level == 1 {
+        let piece = match best.role() {
+            shakmaty::Role::Pawn => "pawn",
+            shakmaty::Role::Knight => "knight",
+            shakmaty::Role::Bishop => "bishop",
+            shakmaty::Role::Rook => "rook",
+            shakmaty::Role::Queen => "queen",
+            shakmaty::Role::King => "king",
+        };
+        match best.from() {
+            Some(from) => format!("Hint: move your {} on {}", piece, from),
+            None => format!("Hint: move your {}", piece),
+        }
+    } else {
+        format!(
+            "Hint: play **{}**",
+            shakmaty::san::SanPlus::from_move(state.position().clone(), &best)
+        )
+    };
+    response::info(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+//Posts a position that isn't part of a game, like a puzzle, drawn in the guild's board style with
+//`text` under it. The board is shown from the side to move
+async fn send_position(
+    ctx: &Context,
+    msg: &Message,
+    state: &rules::GameState,
+    text: String,
+    style: BoardStyle,
+) -> CommandResult {
+    let board = state.position().board();
+    let black_below = state.turn() == shakmaty::Color::Black;
+    let mut text = text;
+    let mut png = None;
+    match style {
+        BoardStyle::Image => {
+            png = Some(board_image::render_board(
+                board,
+                Game::last_squares(state),
+                black_below,
+            )?)
+        }
+        BoardStyle::Text => text = format!("{}\n{}", games::render_text(board, black_below), text),
+        BoardStyle::Emoji => {
+            let emojis = guild_emojis(ctx, msg.guild_id).await?;
+            let diagram = games::render_emoji(board, black_below, &emojis)
+                .unwrap_or_else(|| games::render_text(board, black_below));
+            text = format!("{}\n{}", diagram, text);
+        }
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg);
+            if let Some(png) = &png {
+                m.add_file((png.as_slice(), "board.png"));
+            }
+            m.embed(|e| {
+                e.colour(response::Tone::Info.colour()).description(text);
+                if png.is_some() {
+                    e.image("attachment://board.png");
+                }
+                e
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+//Counts `puzzle` as solved or failed for whoever sent `msg`, and towards their streak if it was
+//the daily puzzle of `daily`. Returns their new rating, how much it changed and, after a daily
+//puzzle, their streak. Reaching a streak milestone is celebrated in a DM
+async fn record_puzzle(
+    ctx: &Context,
+    msg: &Message,
+    puzzle: &puzzles::Puzzle,
+    daily: Option<chrono::NaiveDate>,
+    solved: bool,
+) -> (u32, i32, Option<u32>) {
+    let (rating, change, streak, milestone) = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let mut library = library_arc.write().await;
+        let member = msg.author.id.to_string();
+        let rating = library.puzzle_ratings.entry(member.clone()).or_default();
+        let change = rating.record(puzzle.rating, solved);
+        let rating = rating.rating;
+        let (streak, milestone) = match daily {
+            Some(day) => {
+                let streak = library.puzzle_streaks.entry(member).or_default();
+                let milestone = streak.record(day, solved);
+                (Some(streak.current), milestone)
+            }
+            None => (None, false),
+        };
+        (rating, change, streak, milestone)
+    };
+
+    if let (Some(days), true) = (streak, milestone) {
+        let sent = msg
+            .author
+            .direct_message(ctx, |m| {
+                m.content(format!(
+                    "🔥 You have solved the daily puzzle {} days in a row! Keep your streak going with !chess daily tomorrow",
+                    days
+                ))
+            })
+            .await;
+        if let Err(err) = sent {
+            println!(
+                "Failed to DM {} their puzzle streak: {:?}",
+                msg.author.id, err
+            );
+        }
+    }
+    (rating, change, streak)
+}
+
+//What to say about a member's streak after a daily puzzle
+fn describe_streak(streak: Option<u32>) -> String {
+    match streak {
+        Some(0) => "\nYour daily puzzle streak is over. Start a new one tomorrow".to_owned(),
+        Some(1) => "\nDaily puzzle streak: 1 day".to_owned(),
+        Some(days) => format!("\nDaily puzzle streak: {} days", days),
+        None => String::new(),
+    }
+}
+
+//Starts `puzzle` for whoever sent `msg`, giving up the one they were solving, and posts it
+async fn send_puzzle(
+    ctx: &Context,
+    msg: &Message,
+    puzzle: puzzles::Puzzle,
+    daily: Option<chrono::NaiveDate>,
+    style: BoardStyle,
+) -> CommandResult {
+    let guild = msg.guild_id.map_or(0, |guild| guild.0);
+    let (previous, state) = puzzles::start(guild, msg.author.id.0, puzzle.clone(), daily);
+    let mut text = String::new();
+    if let Some((previous, previous_daily)) = previous {
+        let (rating, change, streak) =
+            record_puzzle(ctx, msg, &previous, previous_daily, false).await;
+        let _ = writeln!(
+            text,
+            "You gave up on your last puzzle. Your puzzle rating is now {} ({:+}){}\n",
+            rating,
+            change,
+            describe_streak(streak)
+        );
+    }
+    let _ = write!(
+        text,
+        "{} [{}](https://lichess.org/training/{}), rated {}\n{} to play. Answer with !chess solve <move>",
+        if daily.is_some() {
+            "Daily puzzle"
+        } else {
+            "Puzzle"
+        },
+        puzzle.id,
+        puzzle.id,
+        puzzle.rating,
+        if state.turn() == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        }
+    );
+    send_position(ctx, msg, &state, text, style).await
+}
+
+//How far from the member's rating puzzles are looked for, widened until one is found
+const PUZZLE_RANGES: [u32; 5] = [100, 200, 400, 800, 4000];
+
+#[command]
+#[checks(Writable)]
+#[description = "Gives you a puzzle close to your puzzle rating, with the theme given if any. Solve it with !chess solve. Starting another puzzle gives up on the one you were solving"]
+#[usage = "[theme]"]
+#[example = "fork"]
+async fn puzzle(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let theme = args.single::<String>().ok();
+    let store = puzzles::store();
+    if store.len() == 0 {
+        response::error(ctx, msg, "No puzzles have been imported yet").await?;
+        return Ok(());
+    }
+
+    let (rating, style) = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        let rating = library
+            .puzzle_ratings
+            .get(&msg.author.id.to_string())
+            .cloned()
+            .unwrap_or_default()
+            .rating;
+        (rating, library.config.board_style)
+    };
+    let puzzle = PUZZLE_RANGES.iter().find_map(|range| {
+        store.pick(
+            rating.saturating_sub(*range),
+            rating + range,
+            theme.as_deref(),
+        )
+    });
+    let puzzle = match puzzle {
+        Some(puzzle) => puzzle.clone(),
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "There are no puzzles with the theme \"{}\"",
+                    theme.unwrap_or_default()
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    puzzles::stop_training(msg.guild_id.map_or(0, |guild| guild.0), msg.author.id.0);
+    send_puzzle(ctx, msg, puzzle, None, style).await
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Gives you today's daily puzzle, the same for everyone. You get one try a day. Solving it on consecutive days builds your streak, shown on !leaderboard streaks"]
+async fn daily(ctx: &Context, msg: &Message) -> CommandResult {
+    let today = chrono::Local::now().date_naive();
+    let puzzle = match puzzles::store().daily(today) {
+        Some(puzzle) => puzzle.clone(),
+        None => {
+            response::error(ctx, msg, "No puzzles have been imported yet").await?;
+            return Ok(());
+        }
+    };
+
+    let (streak, style) = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        let streak = library
+            .puzzle_streaks
+            .get(&msg.author.id.to_string())
+            .cloned()
+            .unwrap_or_default();
+        (streak, library.config.board_style)
+    };
+    if streak.tried_on(today) {
+        let text = if streak.current > 0 {
+            format!(
+                "You already solved today's puzzle, making your streak {} {}. Come back tomorrow for the next one",
+                streak.current,
+                if streak.current == 1 { "day" } else { "days" }
+            )
+        } else {
+            "You already tried today's puzzle. Come back tomorrow for the next one".to_owned()
+        };
+        response::info(ctx, msg, text).await?;
+        return Ok(());
+    }
+    puzzles::stop_training(msg.guild_id.map_or(0, |guild| guild.0), msg.author.id.0);
+    send_puzzle(ctx, msg, puzzle, Some(today), style).await
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Plays a move in the puzzle you are solving"]
+#[usage = "<move>"]
+#[example = "Qxf7+"]
+async fn solve(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let guild = msg.guild_id.map_or(0, |guild| guild.0);
+    let (puzzle, daily, step, state) = match puzzles::solve(guild, msg.author.id.0, &input) {
+        Some(solved) => solved,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                "You aren't solving a puzzle. Start one with !chess puzzle",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let style = library_for(ctx, msg.guild_id)
+        .await
+        .read()
+        .await
+        .config
+        .board_style;
+
+    let (mut text, solved) = match step {
+        puzzles::Step::Continue(reply) => {
+            let text = format!("Right! The opponent answers {}. What now?", reply);
+            return send_position(ctx, msg, &state, text, style).await;
+        }
+        puzzles::Step::Solved => {
+            let (rating, change, streak) = record_puzzle(ctx, msg, &puzzle, daily, true).await;
+            let text = format!(
+                "Solved! Your puzzle rating is now {} ({:+}). Themes: {}{}",
+                rating,
+                change,
+                puzzle.themes.join(", "),
+                describe_streak(streak)
+            );
+            (text, true)
+        }
+        puzzles::Step::Wrong(solution) => {
+            let (rating, change, streak) = record_puzzle(ctx, msg, &puzzle, daily, false).await;
+            let text = format!(
+                "{} isn't it. The solution was {}. Your puzzle rating is now {} ({:+}){}",
+                input,
+                solution.join(" "),
+                rating,
+                change,
+                describe_streak(streak)
+            );
+            (text, false)
+        }
+    };
+
+    //Members who are training go straight on to the next puzzle
+    let training = puzzles::count_training(guild, msg.author.id.0, solved);
+    if let Some(training) = &training {
+        let _ = write!(
+            text,
+            "\nTraining: {} solved, {} failed. Stop with !puzzle stop",
+            training.solved, training.failed
+        );
+    }
+    send_position(ctx, msg, &state, text, style).await?;
+    if let Some(training) = training {
+        match training.pick(&puzzles::store()) {
+            Some(next) => send_puzzle(ctx, msg, next.clone(), None, style).await?,
+            None => {
+                puzzles::stop_training(guild, msg.author.id.0);
+            }
+        }
+    }
+    Ok(())
+}
+
+//Reads a rating range like 1200-1600
+fn parse_rating_range(input: &str) -> Option<(u32, u32)> {
+    let (min, max) = input.split_once('-')?;
+    let min = min.trim().parse::<u32>().ok()?;
+    let max = max.trim().parse::<u32>().ok()?;
+    Some((min.min(max), min.max(max)))
+}
+
+//How far either side of the member's puzzle rating training puzzles are, when no range is given
+const TRAINING_RANGE: u32 = 200;
+
+#[command("train")]
+#[checks(Writable)]
+#[description = "Starts tactics training: puzzles with the theme given, if any, rated in the range given, or close to your puzzle rating. Each puzzle is played out move by move with !chess solve, and the next one follows as soon as it is over. !puzzle themes lists the themes"]
+#[usage = "[theme] [min-max]"]
+#[example = "fork 1200-1600"]
+async fn puzzle_train(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let store = puzzles::store();
+    if store.len() == 0 {
+        response::error(ctx, msg, "No puzzles have been imported yet").await?;
+        return Ok(());
+    }
+    let (mut theme, mut range) = (None, None);
+    for arg in args.iter::<String>().filter_map(|arg| arg.ok()) {
+        match parse_rating_range(&arg) {
+            Some(parsed) => range = Some(parsed),
+            None => theme = Some(arg),
+        }
+    }
+
+    let (rating, style) = {
+        let library_arc = library_for(ctx, msg.guild_id).await;
+        let library = library_arc.read().await;
+        let rating = library
+            .puzzle_ratings
+            .get(&msg.author.id.to_string())
+            .cloned()
+            .unwrap_or_default()
+            .rating;
+        (rating, library.config.board_style)
+    };
+    let (min, max) = range.unwrap_or((
+        rating.saturating_sub(TRAINING_RANGE),
+        rating + TRAINING_RANGE,
+    ));
+    let training = puzzles::Training::new(theme, min, max);
+    let puzzle = match training.pick(&store) {
+        Some(puzzle) => puzzle.clone(),
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "There are no puzzles to train on. Looked for {}",
+                    training.describe()
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let guild = msg.guild_id.map_or(0, |guild| guild.0);
+    response::info(
+        ctx,
+        msg,
+        format!(
+            "Training on {}. Stop with !puzzle stop",
+            training.describe()
+        ),
+    )
+    .await?;
+    puzzles::train(guild, msg.author.id.0, training);
+    send_puzzle(ctx, msg, puzzle, None, style).await
+}
+
+#[command("stop")]
+#[description = "Stops tactics training. The puzzle you are solving can still be finished"]
+async fn puzzle_stop(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.map_or(0, |guild| guild.0);
+    match puzzles::stop_training(guild, msg.author.id.0) {
+        Some(training) => {
+            response::success(
+                ctx,
+                msg,
+                format!(
+                    "Stopped training. You solved {} and failed {}",
+                    training.solved, training.failed
+                ),
+            )
+            .await?
+        }
+        None => response::error(ctx, msg, "You aren't training").await?,
+    }
+
+    Ok(())
+}
+
+#[command("themes")]
+#[bucket = "listing"]
+#[description = "Lists the most common puzzle themes, to train on with !puzzle train"]
+async fn puzzle_themes(ctx: &Context, msg: &Message) -> CommandResult {
+    let themes = puzzles::store().themes();
+    if themes.is_empty() {
+        response::error(ctx, msg, "No puzzles have been imported yet").await?;
+        return Ok(());
+    }
+    let mut text = "Most common themes:".to_owned();
+    for (theme, count) in themes.iter().take(LISTED_THEMES) {
+        let _ = write!(text, "\n- {}: {} puzzles", theme, count);
+    }
+    response::info(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+//Updates the club ratings once rated game `uuid` ended, and says how they changed
+async fn send_rating_changes(
+    ctx: &Context,
+    msg: &Message,
+    library: &mut library::Database,
+    uuid: games::GameUuid,
+) -> CommandResult {
+    if let Some([white, black]) = library.rate_game(uuid) {
+        response::info(ctx, msg, format!("Club ratings: {}, {}", white, black)).await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Gives up your game"]
+#[usage = "[@opponent]"]
+async fn resign(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let style = library.config.board_style;
+    let game = library.games.get_mut(&uuid).unwrap();
+    game.resign(&me);
+    record_pgn(ctx, msg.guild_id, game).await;
+    let notify = Some(game.opponent_of(&me).to_owned()).filter(|_| game.engine_level.is_none());
+    send_game(ctx, msg, game, notify.as_deref(), false, style).await?;
+    broadcast::update(&ctx.http, &library, uuid).await;
+    send_engine_record(ctx, msg, &library, uuid).await?;
+    library.archive_discord_game(uuid);
+    send_rating_changes(ctx, msg, &mut library, uuid).await?;
+    send_simul_results(ctx, &library, uuid).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Offers your opponent a draw, or accepts the draw they offered. Playing a move turns the offer down"]
+#[usage = "[@opponent]"]
+async fn draw(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let style = library.config.board_style;
+    let game = library.games.get_mut(&uuid).unwrap();
+    if !game.offer_draw(&me)? {
+        let them = game.opponent_of(&me).to_owned();
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.reference_message(msg).content(format!(
+                    "<@{}> <@{}> offers you a draw. Accept with !chess draw, or play a move to turn it down",
+                    them, me
+                ))
+            })
+            .await?;
+        return Ok(());
+    }
+    record_pgn(ctx, msg.guild_id, game).await;
+    let notify = game.opponent_of(&me).to_owned();
+    send_game(ctx, msg, game, Some(notify.as_str()), false, style).await?;
+    broadcast::update(&ctx.http, &library, uuid).await;
+    library.archive_discord_game(uuid);
+    send_rating_changes(ctx, msg, &mut library, uuid).await?;
+    send_simul_results(ctx, &library, uuid).await?;
+
+    Ok(())
+}
+
+#[command]
+#[checks(Writable)]
+#[description = "Asks your opponent to let you take back your last move, or lets them take back theirs when they asked. Moves can only be taken back in casual games, against the engine and on analysis boards, where nobody has to be asked"]
+#[usage = "[@opponent]"]
+async fn takeback(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let style = library.config.board_style;
+    let game = library.games.get_mut(&uuid).unwrap();
+    if !game.request_takeback(&me)? {
+        let them = game.opponent_of(&me).to_owned();
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.reference_message(msg).content(format!(
+                    "<@{}> <@{}> asks to take back their last move. Allow it with !chess takeback, or play a move to refuse",
+                    them, me
+                ))
+            })
+            .await?;
+        return Ok(());
+    }
+    let notify = Some(game.to_move().to_owned())
+        .filter(|player| *player != me && game.engine_level.is_none() && !game.is_analysis());
+    send_game(ctx, msg, game, notify.as_deref(), false, style).await?;
+    broadcast::update(&ctx.http, &library, uuid).await;
+
+    Ok(())
+}
+
+#[command("broadcast")]
+#[checks(Writable)]
+#[description = "Broadcasts your game in the server's broadcast channel, where every move is posted for spectators to follow. Use it again to stop"]
+#[usage = "[@opponent]"]
+async fn broadcast_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let opponent = args.single::<UserId>().ok().map(|id| id.to_string());
+    let me = msg.author.id.to_string();
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_game(&me, opponent.as_deref(), |game| {
+        game.status == GameStatus::Playing
+    })?;
+    let channel = match library.channel(library::ChannelKind::Broadcast) {
+        Some(channel) => channel,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                "This server has no broadcast channel. Admins can set one with !config channel broadcast #channel",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let game = library.games.get_mut(&uuid).unwrap();
+    game.broadcast = !game.broadcast;
+    if game.broadcast {
+        response::success(
+            ctx,
+            msg,
+            format!("Your game is being broadcast in <#{}>", channel),
+        )
+        .await?;
+        broadcast::update(&ctx.http, &library, uuid).await;
+    } else {
+        response::success(ctx, msg, "Stopped broadcasting your game").await?;
+    }
+
+    Ok(())
+}
+
+//Tournaments can't have more rounds than this
+const MAX_TOURNAMENT_ROUNDS: u32 = 20;
+//Arenas can't last longer than this
+const MAX_ARENA_MINUTES: u32 = 6 * 60;
+
+#[command("create")]
+#[checks(Officer, Writable)]
+#[description = "Creates a tournament members can join until it is started. Swiss tournaments pair players on the same score each round, knockouts put players out when they lose. Arenas are played online for the given number of minutes with the given clock: players are paired as soon as they are free, and winning streaks score double"]
+#[usage = "swiss <rounds> [name] | knockout [name] | arena <minutes> <minutes+increment> [name]"]
+#[example = "swiss 5 Autumn rapid"]
+async fn create_tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let format: String = args.single::<String>()?;
+    let format = match format.to_lowercase().as_str() {
+        "swiss" => {
+            let rounds: u32 = args.single::<u32>()?;
+            if rounds == 0 || rounds > MAX_TOURNAMENT_ROUNDS {
+                response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "Tournaments have between 1 and {} rounds",
+                        MAX_TOURNAMENT_ROUNDS
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+            tournaments::TournamentFormat::Swiss { rounds }
+        }
+        "knockout" => tournaments::TournamentFormat::Knockout,
+        "arena" => {
+            let minutes: u32 = args.single::<u32>()?;
+            if minutes == 0 || minutes > MAX_ARENA_MINUTES {
+                response::error(
+                    ctx,
+                    msg,
+                    format!("Arenas last between 1 and {} minutes", MAX_ARENA_MINUTES),
+                )
+                .await?;
+                return Ok(());
+            }
+            let time_control: String = args.single::<String>()?;
+            let clock = match clocks::Clock::parse(&time_control) {
+                Some(clock) => clock,
+                None => {
+                    response::error(
+                        ctx,
+                        msg,
+                        format!(
+                            "\"{}\" isn't a time control. Write them like 3+2, with 1 to {} minutes and an increment of up to {} seconds",
+                            time_control,
+                            clocks::MAX_MINUTES,
+                            clocks::MAX_INCREMENT_SECS
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            };
+            tournaments::TournamentFormat::Arena { minutes, clock }
+        }
+        _ => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown tournament format \"{}\". Expected swiss, knockout or arena",
+                    format
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let name = match args.rest().trim() {
+        "" => "Club tournament".to_owned(),
+        name => name.to_owned(),
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.new_tournament_uuid();
+    let tournament = tournaments::Tournament::new(
+        uuid,
+        name,
+        format,
+        msg.author.id.to_string(),
+        msg.channel_id.0,
+    );
+    let text = format!(
+        "Created {} ({}). Join with !tournament join {}",
+        tournament.name,
+        tournament.format,
+        library::Database::encode_uuid(uuid)
+    );
+    library.tournaments.insert(uuid, tournament);
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("join")]
+#[checks(Writable)]
+#[description = "Joins a tournament that is open for registration, or a running arena"]
+#[usage = "<tournament ID>"]
+async fn join_tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    tournament.join(&msg.author.id.to_string())?;
+    let mut text = format!(
+        "You joined {}. {} players so far",
+        tournament.name,
+        tournament.players.len()
+    );
+    if tournament.check_in_deadline.is_some() {
+        text.push_str(". Check-in is open, so you're checked in already");
+    }
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("leave")]
+#[checks(Writable)]
+#[description = "Leaves a tournament you joined, while it is still open for registration. Leaving a running arena keeps your points, and you can join again"]
+#[usage = "<tournament ID>"]
+async fn leave_tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    tournament.leave(&msg.author.id.to_string())?;
+    let text = format!("You left {}", tournament.name);
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("open-check-in")]
+#[checks(Officer, Writable)]
+#[description = "Opens check-in for a tournament, for the given number of minutes or 15. Players who haven't checked in when it starts are withdrawn"]
+#[usage = "<tournament ID> [minutes]"]
+#[example = "ABCDEFG 30"]
+async fn open_check_in(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let minutes = args
+        .single::<i64>()
+        .unwrap_or(tournaments::DEFAULT_CHECK_IN_MINUTES);
+    if minutes <= 0 || minutes > tournaments::MAX_CHECK_IN_MINUTES {
+        response::error(
+            ctx,
+            msg,
+            format!(
+                "Check-in can be open for between 1 and {} minutes",
+                tournaments::MAX_CHECK_IN_MINUTES
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    let deadline = tournament.open_check_in(minutes)?;
+    let mentions: Vec<String> = tournament
+        .players
+        .iter()
+        .map(|player| format!("<@{}>", player))
+        .collect();
+    let id = library::Database::encode_uuid(uuid);
+    let text = format!(
+        "Check-in for **{}** is open until {}. Press the button or use !tournament check-in {} to confirm you're playing, or you'll be withdrawn when it starts\n{}",
+        tournament.name,
+        deadline.format("%H:%M"),
+        id,
+        mentions.join(" ")
+    );
+    let channel = ChannelId(tournament.channel);
+    let message = channel
+        .send_message(ctx, |m| {
+            m.content(text).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.style(ButtonStyle::Success)
+                            .label("Check in")
+                            .custom_id(format!("tournament-check-in:{}", id))
+                    })
+                })
+            })
+        })
+        .await?;
+    flows::expire_components(
+        &message,
+        std::time::Duration::from_secs(minutes as u64 * 60),
+    );
+    if channel != msg.channel_id {
+        response::success(ctx, msg, format!("Opened check-in in <#{}>", channel.0)).await?;
+    }
+
+    Ok(())
+}
+
+#[command("check-in")]
+#[checks(Writable)]
+#[description = "Confirms you're playing in a tournament you joined, once check-in is open"]
+#[usage = "<tournament ID>"]
+async fn check_in(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    let text = if tournament.check_in(&msg.author.id.to_string())? {
+        format!("You're checked in for {}. Good luck!", tournament.name)
+    } else {
+        format!("You were already checked in for {}", tournament.name)
+    };
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("start")]
+#[checks(Officer, Writable)]
+#[description = "Closes registration and pairs the first round. If check-in was opened, players who didn't check in are withdrawn"]
+#[usage = "<tournament ID>"]
+async fn start_tournament(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let library = &mut *library;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    let withdrawn = tournament.start(&library.club_ratings)?;
+    let mut text = format!(
+        "Started {} with {} players",
+        tournament.name,
+        tournament.players.len()
+    );
+    if !withdrawn.is_empty() {
+        let mentions: Vec<String> = withdrawn
+            .iter()
+            .map(|player| format!("<@{}>", player))
+            .collect();
+        write!(
+            text,
+            ". Withdrawn for not checking in: {}",
+            mentions.join(", ")
+        )?;
+    }
+    response::success(ctx, msg, text).await?;
+    tournaments::update_bracket(&ctx.http, library, uuid).await;
+    tournaments::post_round(&ctx.http, library, uuid).await;
+
+    Ok(())
+}
+
+#[command("result")]
+#[checks(Writable)]
+#[description = "Reports the result of your game in the current round. It counts once your opponent confirms it, with the button or by reporting the same result. Officers' results count straight away, to settle disputes. Once every result is in, the next round is paired"]
+#[usage = "<tournament ID> <board> <1-0|0-1|½-½>"]
+#[example = "ABCDEFG 3 1/2-1/2"]
+async fn tournament_result(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let board: usize = args.single::<usize>()?;
+    let result: String = args.single::<String>()?;
+    let outcome = match tournaments::Outcome::parse(&result) {
+        Some(outcome) => outcome,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown result \"{}\". Expected 1-0, 0-1 or ½-½", result),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let arbiter = is_officer(ctx, msg.guild_id, msg.author.id).await;
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_tournament(&input)?;
+    let library = &mut *library;
+    let tournament = library.tournaments.get_mut(&uuid).unwrap();
+    let round = tournament.rounds.len();
+    let round_over = if arbiter {
+        tournament.set_result(board, outcome, &library.club_ratings)?
+    } else {
+        match tournament.report(
+            board,
+            outcome,
+            &msg.author.id.to_string(),
+            &library.club_ratings,
+        )? {
+            tournaments::Report::Confirmed { round_over } => round_over,
+            tournaments::Report::Claimed { opponent } => {
+                let id = format!(
+                    "{}:{}:{}:{}",
+                    library::Database::encode_uuid(uuid),
+                    round,
+                    board,
+                    outcome
+                );
+                let text = format!(
+                    "<@{}> reported {} on board {} of {}. <@{}>, is that right?",
+                    msg.author.id, outcome, board, tournament.name, opponent
+                );
+                let message = msg
+                    .channel_id
+                    .send_message(ctx, |m| {
+                        m.reference_message(msg).content(text).components(|c| {
+                            c.create_action_row(|row| {
+                                row.create_button(|b| {
+                                    b.style(ButtonStyle::Success)
+                                        .label("Confirm")
+                                        .custom_id(format!("tournament-result:{}:confirm", id))
+                                });
+                                row.create_button(|b| {
+                                    b.style(ButtonStyle::Danger)
+                                        .label("Dispute")
+                                        .custom_id(format!("tournament-result:{}:dispute", id))
+                                })
+                            })
+                        })
+                    })
+                    .await?;
+                flows::expire_components(&message, tournaments::CLAIM_TIMEOUT);
+                return Ok(());
+            }
+        }
+    };
+    let text = format!("Board {} of {}: {}", board, tournament.name, outcome);
+    library.archive_tournament_game(uuid, round, board);
+    response::success(ctx, msg, text).await?;
+    tournaments::update_bracket(&ctx.http, library, uuid).await;
+    if round_over {
+        tournaments::post_round(&ctx.http, library, uuid).await;
+    }
+
+    Ok(())
+}
+
+#[command("pairings")]
+#[bucket = "lookup"]
+#[description = "Shows the boards of the current round of a tournament, or of an earlier one"]
+#[usage = "<tournament ID> [round]"]
+async fn tournament_pairings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let uuid = library.find_tournament(&input)?;
+    let tournament = &library.tournaments[&uuid];
+    let round = args.single::<usize>().unwrap_or(tournament.rounds.len());
+    let text = match tournament.round_text(round) {
+        Some(text) => text,
+        None if tournament.rounds.is_empty() => {
+            format!("{} hasn't started yet", tournament.name)
+        }
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "{} has only had {} rounds",
+                    tournament.name,
+                    tournament.rounds.len()
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .content(text)
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("standings")]
+#[bucket = "lookup"]
+#[description = "Shows the standings of a tournament, or the bracket of a knockout. Ties are broken by Buchholz, the sum of each player's opponents' scores"]
+#[usage = "<tournament ID>"]
+async fn tournament_standings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let text = {
+        let library = library_arc.read().await;
+        let uuid = library.find_tournament(&input)?;
+        library.tournaments[&uuid].standings_text()
+    };
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .content(text)
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[bucket = "listing"]
+#[description = "Lists the tournaments that are open for registration or running"]
+async fn list_tournaments(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+
+    let mut response = String::new();
+    {
+        let library = library_arc.read().await;
+        for tournament in library.tournaments.values() {
+            if tournament.status == tournaments::TournamentStatus::Finished {
+                continue;
+            }
+            write!(
+                response,
+                "\n{} - {} ({}), {} with {} players",
+                library::Database::encode_uuid(tournament.uuid),
+                tournament.name,
+                tournament.format,
+                tournament.status,
+                tournament.players.len()
+            )?;
+            if let (tournaments::TournamentStatus::Running, Some(ends)) =
+                (tournament.status, tournament.ends)
+            {
+                write!(response, " until {}", ends.format("%H:%M"))?;
+            }
+            if let (tournaments::TournamentStatus::Registration, Some(deadline)) =
+                (tournament.status, tournament.check_in_deadline)
+            {
+                write!(
+                    response,
+                    ", {} checked in by {}",
+                    tournament.checked_in.len(),
+                    deadline.format("%H:%M")
+                )?;
+            }
+        }
+    }
+    if response.is_empty() {
+        response.push_str("No tournaments are open or running");
+    }
+
+    response::info(ctx, msg, response.trim_start()).await?;
+
+    Ok(())
+}
+
+#[command("show")]
+#[bucket = "lookup"]
+#[description = "Shows your club rating, or another member's, from the games played in the club. Ratings with a ? are provisional, until enough games have been played"]
+#[usage = "[@member]"]
+async fn rating(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let member = args.single::<UserId>().unwrap_or(msg.author.id);
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let (rating, system) = {
+        let library = library_arc.read().await;
+        (
+            library.club_ratings.get(&member.to_string()).cloned(),
+            library.config.rating_system,
+        )
+    };
+    let text = match rating {
+        Some(rating) => format!(
+            "<@{}>'s club rating is {} after {} games: {} won, {} drawn, {} lost",
+            member,
+            rating.describe(system),
+            rating.games(),
+            rating.wins,
+            rating.draws,
+            rating.losses
+        ),
+        None => format!(
+            "<@{}> hasn't played a rated game yet. Everyone starts at {}",
+            member,
+            ratings::START_RATING
+        ),
+    };
+    response::info(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("graph")]
+#[bucket = "lookup"]
+#[description = "Draws your club rating, or another member's, after each rated game they played. Add lichess to draw their Lichess blitz, rapid and classical ratings too, if they linked Lichess"]
+#[usage = "[@member] [lichess]"]
+#[example = "@Magnus lichess"]
+async fn rating_graph(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let member = args.single::<UserId>().unwrap_or(msg.author.id);
+    let lichess = args
+        .iter::<String>()
+        .filter_map(|word| word.ok())
+        .any(|word| word.eq_ignore_ascii_case("lichess"));
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let series = {
+        let library = library_arc.read().await;
+        rating_graph::history(&library, &member.to_string(), lichess)
+    };
+    if series.is_empty() {
+        response::info(
+            ctx,
+            msg,
+            format!(
+                "<@{}> has no ratings to draw yet. Club ratings are drawn from the first rated game played after rating graphs were added",
+                member
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut text = format!("<@{}>'s ratings", member);
+    for (line, points) in &series {
+        let (first, latest) = (points[0], points[points.len() - 1]);
+        text.push_str(&format!(
+            "\n{}: {:.0} on {}, {:.0} on {}",
+            line,
+            first.1,
+            first.0.format("%b %-d %Y"),
+            latest.1,
+            latest.0.format("%b %-d %Y")
+        ));
+    }
+    if lichess
+        && series
+            .iter()
+            .all(|(line, _)| *line == rating_graph::Line::Club)
+    {
+        text.push_str("\nThere are no Lichess ratings to draw. They are looked up for members who linked Lichess with !lichess link");
+    }
+    let png = rating_graph::render_graph(&series)?;
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .add_file((png.as_slice(), "rating.png"))
+                .embed(|e| {
+                    e.colour(response::Tone::Info.colour())
+                        .description(text)
+                        .image("attachment://rating.png")
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("report-result")]
+#[only_in(guilds)]
+#[checks(Writable)]
+#[description = "Reports the result of a game you played over the board. It counts towards club ratings once your opponent confirms it with the button. Results officers report count straight away"]
+#[usage = "<@white> <@black> <1-0|0-1|½-½>"]
+#[example = "@Magnus @Hikaru 1-0"]
+async fn report_result(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let white: UserId = args.single::<UserId>()?;
+    let black: UserId = args.single::<UserId>()?;
+    let result: String = args.single::<String>()?;
+    let outcome = match tournaments::Outcome::parse(&result) {
+        Some(outcome) => outcome,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown result \"{}\". Expected 1-0, 0-1 or ½-½", result),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let officer = is_officer(ctx, msg.guild_id, msg.author.id).await;
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let (uuid, changes) = library.report_otb_game(
+        white.to_string(),
+        black.to_string(),
+        outcome,
+        msg.author.id.to_string(),
+        officer,
+    )?;
+    let game = &library.otb_games[&uuid];
+    if let Some([white_change, black_change]) = changes {
+        let text = format!(
+            "{}\nClub ratings: {}, {}\n{}",
+            game.describe(),
+            white_change,
+            black_change,
+            library.head_to_head_text(&game.white, &game.black)
+        );
+        response::success(ctx, msg, text).await?;
+        return Ok(());
+    }
+
+    let id = library::Database::encode_uuid(uuid);
+    let text = format!(
+        "<@{}> reported {}. <@{}>, is that right?",
+        msg.author.id,
+        game.describe(),
+        game.confirmer()
+    );
+    let message = msg
+        .channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg).content(text).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.style(ButtonStyle::Success)
+                            .label("Confirm")
+                            .custom_id(format!("otb-result:{}:confirm", id))
+                    });
+                    row.create_button(|b| {
+                        b.style(ButtonStyle::Danger)
+                            .label("Dispute")
+                            .custom_id(format!("otb-result:{}:dispute", id))
+                    })
+                })
+            })
+        })
+        .await?;
+    flows::expire_components(&message, otb::CONFIRM_TIMEOUT);
+
+    Ok(())
+}
+
+#[command("settle-result")]
+#[only_in(guilds)]
+#[checks(Officer, Writable)]
+#[description = "Settles a reported over the board result that was disputed or never confirmed, with the result it should have, or void to throw it out"]
+#[usage = "<result ID> <1-0|0-1|½-½|void>"]
+#[example = "ABCDEFG 0-1"]
+async fn settle_result(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let result: String = args.single::<String>()?;
+    let outcome = match tournaments::Outcome::parse(&result) {
+        Some(outcome) => Some(outcome),
+        None if result.eq_ignore_ascii_case("void") => None,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Unknown result \"{}\". Expected 1-0, 0-1, ½-½ or void",
+                    result
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_otb_game(&input)?;
+    let changes = library.settle_otb_game(uuid, outcome)?;
+    let game = &library.otb_games[&uuid];
+    let text = match changes {
+        Some([white_change, black_change]) => format!(
+            "{}\nClub ratings: {}, {}\n{}",
+            game.describe(),
+            white_change,
+            black_change,
+            library.head_to_head_text(&game.white, &game.black)
+        ),
+        None => format!(
+            "Threw out the result of <@{}> vs <@{}>",
+            game.white, game.black
+        ),
+    };
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Settled reported result {} as {}", input, result),
+    );
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+//How many games !archive history lists
+const HISTORY_LEN: usize = 15;
+
+#[command("history")]
+#[bucket = "listing"]
+#[description = "Lists your latest archived games, or another member's, wherever they were played"]
+#[usage = "[@member]"]
+async fn archive_history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let member = args.single::<UserId>().unwrap_or(msg.author.id);
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let history = library.history(&member.to_string());
+    if history.is_empty() {
+        response::info(ctx, msg, format!("<@{}> has no archived games yet", member)).await?;
+        return Ok(());
+    }
+
+    let mut text = format!("**<@{}>'s games**", member);
+    for game in history.iter().take(HISTORY_LEN) {
+        let _ = write!(text, "\n{}", game.describe());
+    }
+    if history.len() > HISTORY_LEN {
+        let _ = write!(text, "\n…and {} more", history.len() - HISTORY_LEN);
+    }
+    let _ = write!(text, "\nSee a game's moves with !archive show <id>");
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| e.colour(response::Tone::Info.colour()).description(text))
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("show")]
+#[bucket = "lookup"]
+#[description = "Shows an archived game, with its PGN if its moves are known"]
+#[usage = "<game ID>"]
+async fn archive_show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let uuid = library.find_archived_game(&input)?;
+    let game = &library.archive[&uuid];
+    let pgn_name = format!("{}.pgn", input);
+    let mut text = game.describe();
+    if let Some(opening) = &game.opening {
+        let _ = write!(text, "\nOpening: {}", opening);
+    }
+    if game.pgn.is_none() {
+        text.push_str("\nIts moves weren't recorded");
+    }
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| e.colour(response::Tone::Info.colour()).description(text))
+                .allowed_mentions(|a| a.empty_parse());
+            if let Some(pgn) = &game.pgn {
+                m.add_file((pgn.as_bytes(), pgn_name.as_str()));
+            }
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("opening")]
+#[bucket = "listing"]
+#[description = "Lists the latest archived games played in an opening, found by its ECO code, or the start of one, or by a word in its name"]
+#[usage = "<ECO code|name>"]
+#[example = "B33"]
+async fn archive_opening(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let query = args.rest().trim();
+    if query.is_empty() {
+        response::error(
+            ctx,
+            msg,
+            "Give an ECO code, like B33, or part of an opening's name",
+        )
+        .await?;
+        return Ok(());
+    }
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let games = library.games_in_opening(query);
+    if games.is_empty() {
+        response::info(
+            ctx,
+            msg,
+            format!("No archived games were played in \"{}\"", query),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut text = format!("**Games in \"{}\"**", query);
+    for game in games.iter().take(HISTORY_LEN) {
+        let _ = write!(
+            text,
+            "\n{} · {}",
+            game.describe(),
+            game.opening.as_deref().unwrap_or_default()
+        );
+    }
+    if games.len() > HISTORY_LEN {
+        let _ = write!(text, "\n…and {} more", games.len() - HISTORY_LEN);
+    }
+    let _ = write!(text, "\nSee a game's moves with !archive show <id>");
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| e.colour(response::Tone::Info.colour()).description(text))
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("lichess")]
+#[checks(Writable)]
+#[description = "Archives a finished Lichess game between two members who have linked their Lichess accounts"]
+#[usage = "<Lichess game address>"]
+#[example = "https://lichess.org/abcdEFGH"]
+async fn archive_lichess(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let id = match lichess::game_id(args.rest().trim()) {
+        Some(id) => id,
+        None => {
+            response::error(ctx, msg, "Expected the address of a Lichess game").await?;
+            return Ok(());
+        }
+    };
+    let pgn = match lichess::export_game(&id).await {
+        Ok(Some(pgn)) => pgn,
+        Ok(None) => {
+            response::error(ctx, msg, format!("Lichess has no game {}", id)).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            println!("Failed to export Lichess game {}: {:?}", id, err);
+            response::error(ctx, msg, "Couldn't reach Lichess. Try again later").await?;
+            return Ok(());
+        }
+    };
+    let game = match pgn::parse(&pgn) {
+        Ok(game) => game,
+        Err(err) => {
+            response::error(ctx, msg, format!("Couldn't read the game: {}", err)).await?;
+            return Ok(());
+        }
+    };
+    let outcome = match tournaments::Outcome::parse(game.tag("Result")) {
+        Some(outcome) => outcome,
+        None => {
+            response::error(ctx, msg, "That game hasn't finished").await?;
+            return Ok(());
+        }
+    };
+    let played = chrono::NaiveDateTime::parse_from_str(
+        &format!("{} {}", game.tag("UTCDate"), game.tag("UTCTime")),
+        "%Y.%m.%d %H:%M:%S",
+    )
+    .map(|played| chrono::DateTime::<chrono::Utc>::from_utc(played, chrono::Utc).into())
+    .unwrap_or_else(|_| chrono::Local::now());
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    //Only games between members whose accounts are known to be theirs are archived
+    let member_of = |username: &str| {
+        library
+            .users
+            .values()
+            .find(|user| {
+                user.lichess
+                    .as_deref()
+                    .map_or(false, |lichess| lichess.eq_ignore_ascii_case(username))
+            })
+            .map(|user| user.discord_id.clone())
+    };
+    let (white, black) = match (member_of(game.tag("White")), member_of(game.tag("Black"))) {
+        (Some(white), Some(black)) => (white, black),
+        _ => {
+            response::error(
+                ctx,
+                msg,
+                format!(
+                    "Only games between members who have linked their Lichess accounts with !lichess link can be archived. {} played {}",
+                    game.tag("White"),
+                    game.tag("Black")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let source = archive::GameSource::Lichess(id);
+    if library
+        .archive
+        .values()
+        .any(|archived| archived.source == source)
+    {
+        response::error(ctx, msg, "That game is already archived").await?;
+        return Ok(());
+    }
+    let uuid = library.archive_game(
+        (white, black),
+        played,
+        Some(game.tag("Event").to_owned()),
+        outcome,
+        Some(pgn),
+        source,
+    );
+    let text = format!("Archived {}", library.archive[&uuid].describe());
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+#[command("leaderboard")]
+#[bucket = "listing"]
+#[description = "Shows the members with the best club ratings, puzzle ratings, daily puzzle streaks or Lichess ratings, or who have read the most books"]
+#[usage = "[rating|puzzles|streaks|books-read|lichess-blitz|lichess-rapid|lichess-classical]"]
+#[example = "puzzles"]
+async fn leaderboard_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let board = match args.single::<String>() {
+        Ok(input) => match leaderboard::Board::parse(&input) {
+            Some(board) => board,
+            None => {
+                let names: Vec<&str> = leaderboard::BOARDS
+                    .iter()
+                    .map(|board| board.name())
+                    .collect();
+                response::error(
+                    ctx,
+                    msg,
+                    format!(
+                        "Unknown leaderboard \"{}\". Expected one of {}",
+                        input,
+                        names.join(", ")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        Err(_) => leaderboard::Board::Rating,
+    };
+    leaderboard::send(ctx, msg, board).await?;
+
+    Ok(())
+}
+
+//How many of the latest results !h2h lists
+const HEAD_TO_HEAD_RECENT: usize = 5;
+
+#[command("h2h")]
+#[only_in(guilds)]
+#[bucket = "lookup"]
+#[description = "Compares two members' archived games against each other: the score, how each did with white, how long their games last and their latest results. With one member mentioned, compares you with them"]
+#[usage = "@member [@member]"]
+#[example = "@Magnus @Hikaru"]
+async fn head_to_head_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let first = args.single::<UserId>()?;
+    let (a, b) = match args.single::<UserId>() {
+        Ok(second) => (first.to_string(), second.to_string()),
+        Err(_) => (msg.author.id.to_string(), first.to_string()),
+    };
+    if a == b {
+        response::error(ctx, msg, "Mention two different members").await?;
+        return Ok(());
+    }
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let games = library.games_between(&a, &b);
+    if games.is_empty() {
+        response::info(
+            ctx,
+            msg,
+            format!("<@{}> and <@{}> haven't played each other yet", a, b),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let score: f64 = games.iter().map(|game| game.score_of(&a)).sum();
+    let mut text = format!(
+        "**<@{}> vs <@{}>**\nScore: {} - {} from {} game(s)",
+        a,
+        b,
+        tournaments::format_points(score),
+        tournaments::format_points(games.len() as f64 - score),
+        games.len()
+    );
+    for player in [&a, &b] {
+        let (mut wins, mut draws, mut losses) = (0, 0, 0);
+        for game in games.iter().filter(|game| game.white == *player) {
+            match game.outcome {
+                tournaments::Outcome::WhiteWon => wins += 1,
+                tournaments::Outcome::Draw => draws += 1,
+                tournaments::Outcome::BlackWon => losses += 1,
+            }
+        }
+        let _ = write!(
+            text,
+            "\n<@{}> with white: +{} ={} -{}",
+            player, wins, draws, losses
+        );
+    }
+    //Only games whose moves were recorded have a length
+    let lengths: Vec<usize> = games
+        .iter()
+        .filter_map(|game| pgn::parse(game.pgn.as_deref()?).ok())
+        .map(|imported| (imported.moves.len() + 1) / 2)
+        .collect();
+    if !lengths.is_empty() {
+        let _ = write!(
+            text,
+            "\nAverage length: {:.0} moves, over the {} game(s) with their moves recorded",
+            lengths.iter().sum::<usize>() as f64 / lengths.len() as f64,
+            lengths.len()
+        );
+    }
+    let _ = write!(text, "\n\nLatest results:");
+    for game in games.iter().take(HEAD_TO_HEAD_RECENT) {
+        let _ = write!(text, "\n{}", game.describe());
+    }
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .embed(|e| e.colour(response::Tone::Info.colour()).description(text))
+                .allowed_mentions(|a| a.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("link")]
+#[checks(Writable)]
+#[description = "Links your Lichess account. Without an address, DMs you a link to approve on Lichess. Lichess then sends you to an address that doesn't load, which you paste back here"]
+#[usage = "[address]"]
+async fn lichess_link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let me = msg.author.id.to_string();
+    let pasted = args.rest().trim();
+    if pasted.is_empty() {
+        let guild = guild_of(ctx, msg).await;
+        let registered = library_for(ctx, guild)
+            .await
+            .read()
+            .await
+            .find_user_by_discord_id(&me)
+            .is_some();
+        if !registered {
+            response::error(
+                ctx,
+                msg,
+                "You aren't registered with the library yet. Use !library register <your name> to sign up",
+            )
+            .await?;
+            return Ok(());
+        }
+        let url = match lichess::start(msg.author.id.0, guild.map(|guild| guild.0)) {
+            Ok(url) => url,
+            Err(err) => {
+                response::error(ctx, msg, format!("Linking failed: {}", err)).await?;
+                return Ok(());
+            }
+        };
+        let sent = msg
+            .author
+            .direct_message(ctx, |m| {
+                m.content(format!(
+                    "Approve linking your Lichess account at {}\nLichess will then send you to an address that doesn't load. Copy the whole address and send it here as !lichess link <address>. The link works for 10 minutes",
+                    url
+                ))
+            })
+            .await;
+        match (sent, msg.guild_id) {
+            (Err(_), _) => {
+                response::error(
+                    ctx,
+                    msg,
+                    "I couldn't DM you. Allow DMs from server members and try again",
+                )
+                .await?
+            }
+            (Ok(_), Some(_)) => response::success(ctx, msg, "Check your DMs").await?,
+            (Ok(_), None) => {}
+        }
+        return Ok(());
+    }
+
+    let (guild, username) = match lichess::finish(msg.author.id.0, pasted).await {
+        Ok((guild, username)) => (guild.map(GuildId), username),
+        Err(err) => {
+            response::error(ctx, msg, format!("Linking failed: {}", err)).await?;
+            return Ok(());
+        }
+    };
+    let library_arc = library_for(ctx, guild).await;
+    let linked = {
+        let mut library = library_arc.write().await;
+        let taken = library.users.values().find(|user| {
+            user.discord_id != me
+                && user
+                    .lichess
+                    .as_deref()
+                    .map_or(false, |lichess| lichess.eq_ignore_ascii_case(&username))
+        });
+        let linked = match taken {
+            Some(other) => Err(format!(
+                "{} is already linked to <@{}>",
+                username, other.discord_id
+            )),
+            None => match library
+                .users
+                .values_mut()
+                .find(|user| user.discord_id == me)
+            {
+                Some(user) => Ok(user.lichess.replace(username.clone())),
+                None => Err("You aren't registered with the library any more".to_owned()),
+            },
+        };
+        if let Ok(previous) = &linked {
+            let relinked = previous
+                .as_deref()
+                .map_or(false, |previous| previous.eq_ignore_ascii_case(&username));
+            //Ratings synced for a different account don't belong to this one
+            if !relinked {
+                library.lichess_ratings.remove(&me);
+            }
+        }
+        linked
+    };
+    //The account belongs to the guild linking was started from, which isn't this one in DMs
+    save_after_change(ctx, guild).await;
+    match linked {
+        Ok(_) => {
+            response::success(
+                ctx,
+                msg,
+                format!("Linked your Lichess account {}", username),
+            )
+            .await?;
+            //Show the ratings straight away rather than after the next sync
+            if let Err(err) = lichess::sync_ratings(&library_arc).await {
+                println!("Failed to sync Lichess ratings after linking: {:?}", err);
+            }
+        }
+        Err(why) => response::error(ctx, msg, why).await?,
+    }
+
+    Ok(())
+}
+
+#[command("unlink")]
+#[checks(Writable)]
+#[description = "Unlinks your Lichess account"]
+async fn lichess_unlink(ctx: &Context, msg: &Message) -> CommandResult {
+    let me = msg.author.id.to_string();
+    let guild = guild_of(ctx, msg).await;
+    let library_arc = library_for(ctx, guild).await;
+    let unlinked = {
+        let mut library = library_arc.write().await;
+        library.lichess_ratings.remove(&me);
+        library.lichess_live.remove(&me);
+        library
+            .users
+            .values_mut()
+            .find(|user| user.discord_id == me)
+            .and_then(|user| user.lichess.take())
+    };
+    save_after_change(ctx, guild).await;
+    match unlinked {
+        Some(username) => {
+            response::success(
+                ctx,
+                msg,
+                format!("Unlinked your Lichess account {}", username),
+            )
+            .await?
+        }
+        None => response::error(ctx, msg, "You haven't linked a Lichess account").await?,
+    }
+
+    Ok(())
+}
+
+#[command("live")]
+#[checks(Writable)]
+#[description = "Turns on or off posts in the server's lichess-live channel whenever you start a game on Lichess, with a link to watch it. You're posted about at most every half hour. Without arguments, shows whether it is on"]
+#[usage = "[on|off]"]
+#[example = "on"]
+async fn lichess_live_command(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let me = msg.author.id.to_string();
+    let guild = guild_of(ctx, msg).await;
+    let library_arc = library_for(ctx, guild).await;
+    let choice = args.rest().trim().to_lowercase();
+    if choice.is_empty() {
+        let on = library_arc.read().await.lichess_live.contains_key(&me);
+        let text = if on {
+            "Your Lichess games are posted. Turn it off with !lichess live off"
+        } else {
+            "Your Lichess games aren't posted. Turn it on with !lichess live on"
+        };
+        response::info(ctx, msg, text).await?;
+        return Ok(());
+    }
+
+    let reply = {
+        let mut library = library_arc.write().await;
+        match choice.as_str() {
+            "on" => {
+                let linked = library
+                    .find_user_by_discord_id(&me)
+                    .map_or(false, |user| user.lichess.is_some());
+                if !linked {
+                    Err("Link your Lichess account with !lichess link first".to_owned())
+                } else {
+                    library.lichess_live.entry(me.clone()).or_default();
+                    let mut reply =
+                        "Your Lichess games will be posted, with a link to watch them".to_owned();
+                    if library.channel(library::ChannelKind::LichessLive).is_none() {
+                        reply.push_str(". They start once an admin sets the channel with !config channel lichess-live");
+                    }
+                    Ok(reply)
+                }
+            }
+            "off" => {
+                library.lichess_live.remove(&me);
+                Ok("Your Lichess games won't be posted any more".to_owned())
+            }
+            _ => Err("Use on or off".to_owned()),
+        }
+    };
+    match reply {
+        Ok(reply) => {
+            save_after_change(ctx, guild).await;
+            response::success(ctx, msg, reply).await?;
+        }
+        Err(why) => response::error(ctx, msg, why).await?,
+    }
+
+    Ok(())
+}
+
+#[command("team")]
+#[only_in(guilds)]
+#[checks(Admin)]
+#[description = "Keeps the club's Lichess team in step with the server. Members who linked their Lichess account and are in the team get the role, and lose it when they leave. Officers are told about team members who haven't linked. Without arguments, shows the team and who hasn't linked. With off, the team is no longer synced and members keep the role"]
+#[usage = "[<team> <@role>|off]"]
+#[example = "https://lichess.org/team/my-club @Team"]
+async fn lichess_team_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = match msg.guild_id {
+        Some(guild) => guild,
+        None => return Ok(()),
+    };
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let input = args.single::<String>().unwrap_or_default();
+    if input.is_empty() {
+        let text = match &library_arc.read().await.lichess_team {
+            Some(team) => team.describe(),
+            None => "No Lichess team is set. Set one with !lichess team <team> <@role>".to_owned(),
+        };
+        response::info(ctx, msg, text).await?;
+        return Ok(());
+    }
+
+    if input.eq_ignore_ascii_case("off") {
+        let removed = {
+            let mut library = library_arc.write().await;
+            let removed = library.lichess_team.take();
+            if let Some(team) = &removed {
+                library.audit(
+                    msg.author.id.to_string(),
+                    format!("Stopped syncing with Lichess team {}", team.id),
+                );
+            }
+            removed
+        };
+        match removed {
+            Some(team) => {
+                save_after_change(ctx, msg.guild_id).await;
+                response::success(
+                    ctx,
+                    msg,
+                    format!(
+                        "Stopped syncing with Lichess team {}. Members who were given <@&{}> keep it",
+                        team.id, team.role
+                    ),
+                )
+                .await?;
+            }
+            None => response::error(ctx, msg, "No Lichess team is set").await?,
+        }
+        return Ok(());
+    }
+
+    let id = match lichess_team::parse_team(&input) {
+        Some(id) => id,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                format!("{} isn't a Lichess team or team address", input),
+            )
+            .await?;
+            return Ok(());
         }
     };
-    let (name, uuid) = result?;
-    match library.remove_book(uuid) {
-        Ok(book) => {
-            msg.reply(
+    let role = match args
+        .single::<String>()
+        .ok()
+        .and_then(|role| serenity::utils::parse_role(&role))
+    {
+        Some(role) => role,
+        None => {
+            response::error(ctx, msg, "Mention the role to give the team's members").await?;
+            return Ok(());
+        }
+    };
+    {
+        let mut library = library_arc.write().await;
+        //Members keep the role they were given when only the team changes, so that they can still
+        //lose it
+        let granted = match &library.lichess_team {
+            Some(team) if team.role == role => team.granted.clone(),
+            _ => Vec::new(),
+        };
+        let mut team = lichess_team::LichessTeam::new(id.clone(), role);
+        team.granted = granted;
+        library.lichess_team = Some(team);
+        library.audit(
+            msg.author.id.to_string(),
+            format!("Set the Lichess team to {} with role {}", id, role),
+        );
+    }
+    save_after_change(ctx, msg.guild_id).await;
+
+    let result = match lichess_team::sync(&ctx.http, guild, &library_arc).await {
+        Ok(Some(result)) => result,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            response::error(
                 ctx,
+                msg,
                 format!(
-                    "Book \"{}\" ({}) was removed",
-                    &name,
-                    library::Database::encode_uuid(uuid),
+                    "Set the Lichess team to {}, but its members couldn't be fetched from Lichess: {}. They are fetched again every few hours",
+                    id, err
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let mut text = format!(
+        "Synced with Lichess team {}: {} member(s) given <@&{}>, {} had it taken away",
+        id, result.granted, role, result.removed
+    );
+    if result.failed > 0 {
+        text.push_str(&format!(
+            "\n{} role change(s) failed. Check that I can manage roles and that <@&{}> is below my highest role",
+            result.failed, role
+        ));
+    }
+    if let Some(team) = &library_arc.read().await.lichess_team {
+        text.push_str(&format!("\n{}", team.describe()));
+    }
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("relay")]
+#[only_in(guilds)]
+#[checks(Officer)]
+#[description = "Relays the standings of a Lichess arena or Swiss tournament to this channel, updated every minute until it ends. Without arguments, lists the tournaments being relayed. With stop, stops relaying one"]
+#[usage = "[<tournament address>|stop <tournament address>]"]
+#[example = "https://lichess.org/swiss/Xy12AbCd"]
+async fn lichess_relay_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let input = args.single::<String>().unwrap_or_default();
+    if input.is_empty() {
+        let relays = library_arc.read().await.lichess_relays.clone();
+        let text = if relays.is_empty() {
+            "No Lichess tournaments are being relayed. Start one with !lichess relay <tournament address>".to_owned()
+        } else {
+            relays
+                .iter()
+                .map(|relay| {
+                    format!(
+                        "{} in <#{}>, started by <@{}> on {}",
+                        relay.url(),
+                        relay.channel,
+                        relay.started_by,
+                        relay.started.format("%b %-d at %H:%M")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        response::info(ctx, msg, text).await?;
+        return Ok(());
+    }
+
+    let stopping = input.eq_ignore_ascii_case("stop");
+    let address = if stopping {
+        args.single::<String>().unwrap_or_default()
+    } else {
+        input
+    };
+    let (kind, id) = match lichess_relay::parse_url(&address) {
+        Some(tournament) => tournament,
+        None => {
+            response::error(
+                ctx,
+                msg,
+                "Give the address of a Lichess arena or Swiss tournament, like https://lichess.org/tournament/<id>",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if stopping {
+        let stopped = {
+            let mut library = library_arc.write().await;
+            let before = library.lichess_relays.len();
+            library.lichess_relays.retain(|relay| relay.id != id);
+            let stopped = library.lichess_relays.len() < before;
+            if stopped {
+                library.audit(
+                    msg.author.id.to_string(),
+                    format!("Stopped relaying Lichess tournament {}", id),
+                );
+            }
+            stopped
+        };
+        if stopped {
+            save_after_change(ctx, msg.guild_id).await;
+            response::success(ctx, msg, format!("Stopped relaying {}", address)).await?;
+        } else {
+            response::error(ctx, msg, format!("{} isn't being relayed", address)).await?;
+        }
+        return Ok(());
+    }
+
+    let relayed_here = library_arc
+        .read()
+        .await
+        .lichess_relays
+        .iter()
+        .any(|relay| relay.id == id && relay.channel == msg.channel_id.0);
+    if relayed_here {
+        response::error(ctx, msg, "That tournament is already being relayed here").await?;
+        return Ok(());
+    }
+    let standings = match lichess_relay::fetch(kind, &id).await {
+        Ok(standings) => standings,
+        Err(err) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Couldn't fetch that tournament from Lichess: {}", err),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    if standings.finished {
+        response::error(ctx, msg, format!("**{}** is already over", standings.name)).await?;
+        return Ok(());
+    }
+
+    let message = lichess_relay::post(&ctx.http, msg.channel_id, kind, &id, &standings).await?;
+    {
+        let mut library = library_arc.write().await;
+        library.lichess_relays.push(lichess_relay::LichessRelay {
+            kind,
+            id: id.clone(),
+            channel: msg.channel_id.0,
+            message,
+            started_by: msg.author.id.to_string(),
+            started: chrono::Local::now(),
+        });
+        library.audit(
+            msg.author.id.to_string(),
+            format!("Started relaying Lichess tournament {}", id),
+        );
+    }
+    save_after_change(ctx, msg.guild_id).await;
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Relaying **{}** here, updated every minute until it ends",
+            standings.name
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("stats")]
+#[bucket = "listing"]
+#[description = "Shows a chess.com player's ratings and latest games. Without a username, shows yours if you have linked your account with !chesscom link"]
+#[usage = "[username]"]
+#[example = "hikaru"]
+async fn chesscom_stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let username = match args.rest().trim() {
+        "" => {
+            let me = msg.author.id.to_string();
+            let linked = library_for(ctx, guild_of(ctx, msg).await)
+                .await
+                .read()
+                .await
+                .find_user_by_discord_id(&me)
+                .and_then(|user| user.chesscom.clone());
+            match linked {
+                Some(username) => username,
+                None => {
+                    response::error(
+                        ctx,
+                        msg,
+                        "Give a chess.com username, or link yours with !chesscom link <username>",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+        username => username.to_owned(),
+    };
+    if !chesscom::is_valid_username(&username) {
+        response::error(ctx, msg, format!("{} isn't a chess.com username", username)).await?;
+        return Ok(());
+    }
+
+    let stats = match chesscom::fetch(&username).await {
+        Ok(Some(stats)) => stats,
+        Ok(None) => {
+            response::error(
+                ctx,
+                msg,
+                format!("chess.com has no player called {}", username),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => {
+            println!(
+                "Failed to fetch chess.com stats for {}: {:?}",
+                username, err
+            );
+            response::error(ctx, msg, "Couldn't reach chess.com. Try again later").await?;
+            return Ok(());
+        }
+    };
+
+    let mut fields: Vec<(String, String, bool)> = stats
+        .ratings
+        .iter()
+        .map(|rating| {
+            let mut value = rating.rating.to_string();
+            if let Some(best) = rating.best {
+                let _ = write!(value, " (best {})", best);
+            }
+            let _ = write!(
+                value,
+                "\n{}W {}L {}D",
+                rating.wins, rating.losses, rating.draws
+            );
+            (rating.time_control.to_owned(), value, true)
+        })
+        .collect();
+    if fields.is_empty() {
+        fields.push(("Ratings".to_owned(), "No rated games yet".to_owned(), false));
+    }
+    if !stats.recent.is_empty() {
+        let recent = stats
+            .recent
+            .iter()
+            .map(|game| {
+                format!(
+                    "[{}]({}) against {} ({})",
+                    game.result, game.url, game.opponent, game.time_class
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        fields.push(("Recent games".to_owned(), recent, false));
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg).embed(|e| {
+                e.colour(response::Tone::Info.colour())
+                    .title(format!("{} on chess.com", stats.username))
+                    .url(&stats.profile_url)
+                    .fields(fields);
+                if let Some(avatar) = &stats.avatar {
+                    e.thumbnail(avatar);
+                }
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("link")]
+#[checks(Writable)]
+#[description = "Links your chess.com account, so that !chesscom shows your stats and !profile links to it"]
+#[usage = "<username>"]
+#[example = "hikaru"]
+async fn chesscom_link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let username = args.rest().trim();
+    if !chesscom::is_valid_username(username) {
+        response::error(ctx, msg, "Give your chess.com username").await?;
+        return Ok(());
+    }
+    let username = match chesscom::find_player(username).await {
+        Ok(Some(username)) => username,
+        Ok(None) => {
+            response::error(
+                ctx,
+                msg,
+                format!("chess.com has no player called {}", username),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(err) => {
+            println!("Failed to look up chess.com player {}: {:?}", username, err);
+            response::error(ctx, msg, "Couldn't reach chess.com. Try again later").await?;
+            return Ok(());
+        }
+    };
+
+    let me = msg.author.id.to_string();
+    let guild = guild_of(ctx, msg).await;
+    let library_arc = library_for(ctx, guild).await;
+    let linked = {
+        let mut library = library_arc.write().await;
+        let taken = library.users.values().find(|user| {
+            user.discord_id != me
+                && user
+                    .chesscom
+                    .as_deref()
+                    .map_or(false, |chesscom| chesscom.eq_ignore_ascii_case(&username))
+        });
+        match taken {
+            Some(other) => Err(format!(
+                "{} is already linked to <@{}>",
+                username, other.discord_id
+            )),
+            None => match library
+                .users
+                .values_mut()
+                .find(|user| user.discord_id == me)
+            {
+                Some(user) => {
+                    user.chesscom = Some(username.clone());
+                    Ok(())
+                }
+                None => Err(
+                    "You aren't registered with the library yet. Use !library register <your name> to sign up"
+                        .to_owned(),
                 ),
+            },
+        }
+    };
+    save_after_change(ctx, guild).await;
+    match linked {
+        Ok(()) => {
+            response::success(
+                ctx,
+                msg,
+                format!("Linked your chess.com account {}", username),
             )
             .await?
         }
-        Err(err) => Err(err)?,
+        Err(why) => response::error(ctx, msg, why).await?,
+    }
+
+    Ok(())
+}
+
+#[command("unlink")]
+#[checks(Writable)]
+#[description = "Unlinks your chess.com account"]
+async fn chesscom_unlink(ctx: &Context, msg: &Message) -> CommandResult {
+    let me = msg.author.id.to_string();
+    let guild = guild_of(ctx, msg).await;
+    let library_arc = library_for(ctx, guild).await;
+    let unlinked = library_arc
+        .write()
+        .await
+        .users
+        .values_mut()
+        .find(|user| user.discord_id == me)
+        .and_then(|user| user.chesscom.take());
+    save_after_change(ctx, guild).await;
+    match unlinked {
+        Some(username) => {
+            response::success(
+                ctx,
+                msg,
+                format!("Unlinked your chess.com account {}", username),
+            )
+            .await?
+        }
+        None => response::error(ctx, msg, "You haven't linked a chess.com account").await?,
+    }
+
+    Ok(())
+}
+
+#[command("start")]
+#[checks(Officer, Writable)]
+#[description = "Starts a game of vote chess in this channel, with the server playing the engine at a level from 1 to 8. The server plays white or black as asked, or a random colour"]
+#[usage = "[level 1-8] [white|black]"]
+#[example = "5 white"]
+async fn vote_start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let level = args.single::<u8>().unwrap_or(DEFAULT_ENGINE_LEVEL);
+    if !(1..=engine::LEVELS).contains(&level) {
+        response::error(ctx, msg, format!("Levels go from 1 to {}", engine::LEVELS)).await?;
+        return Ok(());
+    }
+    let colour = args.single::<String>().ok().map(|c| c.to_lowercase());
+    let server_white = match colour.as_deref() {
+        Some("white") => true,
+        Some("black") => false,
+        None | Some("random") => rand::random(),
+        Some(other) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown colour \"{}\". Use white or black", other),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let game = {
+        let mut library = library_arc.write().await;
+        if let Some(game) = &library.vote_game {
+            if game.status == GameStatus::Playing {
+                return Err(library::ManipulationError::new(
+                    library::ManipulationErrorType::VoteGameInProgress,
+                )
+                .into());
+            }
+        }
+        let game = vote_chess::VoteGame::new(
+            msg.channel_id.0,
+            server_white,
+            level,
+            msg.author.id.to_string(),
+        );
+        library.vote_game = Some(game.clone());
+        library.audit(
+            msg.author.id.to_string(),
+            format!(
+                "Started a vote chess game in <#{}> against engine level {}",
+                msg.channel_id, level
+            ),
+        );
+        game
+    };
+
+    let mut text = format!(
+        "<@{}> started a game between the server, playing {}, and the engine at level {}. Everyone can vote on the server's moves, and the move with the most votes is played when voting closes each day",
+        msg.author.id,
+        if server_white { "white" } else { "black" },
+        level
+    );
+    if server_white {
+        let _ = write!(text, "\n{}", game.describe_vote());
+    } else {
+        text.push_str("\nThe engine moves first");
+    }
+    vote_chess::post(&ctx.http, msg.guild_id, msg.channel_id, &game, text).await;
+    //Has the engine play its first move when it is white
+    vote_chess::advance(&ctx.http, msg.guild_id, &library_arc).await;
+
+    Ok(())
+}
+
+#[command("for")]
+#[checks(Writable)]
+#[description = "Votes for the server's next move in vote chess. Voting again changes your vote"]
+#[usage = "<move>"]
+#[example = "Nf3"]
+async fn vote_for(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let game = match &mut library.vote_game {
+        Some(game) if game.status == GameStatus::Playing => game,
+        _ => {
+            return Err(
+                library::ManipulationError::new(library::ManipulationErrorType::NoVoteGame).into(),
+            )
+        }
     };
+    let san = game.vote(msg.author.id.to_string(), &input)?;
+    let text = format!("Voted for **{}**\n{}", san, game.describe_vote());
+    response::success(ctx, msg, text).await?;
 
     Ok(())
 }
 
-#[command]
-#[description = "Starts a checkout transaction for a book. Use this to checkout a book in the library"]
-async fn checkout(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    msg.reply(ctx, "TODO").await?;
+#[command("show")]
+#[bucket = "lookup"]
+#[description = "Shows the board of the vote chess game and how the vote on the server's next move is going"]
+async fn vote_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let game = library_for(ctx, msg.guild_id)
+        .await
+        .read()
+        .await
+        .vote_game
+        .clone()
+        .ok_or_else(|| {
+            library::ManipulationError::new(library::ManipulationErrorType::NoVoteGame)
+        })?;
+    let text = if game.status.is_over() {
+        format!("{} after {} moves", game.result(), game.moves.len())
+    } else if game.server_to_move() {
+        format!("The server to move\n{}", game.describe_vote())
+    } else {
+        "The engine to move".to_owned()
+    };
+    vote_chess::post(&ctx.http, msg.guild_id, msg.channel_id, &game, text).await;
 
     Ok(())
 }
 
-#[command("return")]
-#[description = "Used to indicate that you have returned a book to an officer"]
-async fn return_command(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    msg.reply(ctx, "TODO").await?;
+#[command("stop")]
+#[checks(Officer, Writable)]
+#[description = "Stops the vote chess game, leaving it unfinished"]
+async fn vote_stop(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let moves = match &library.vote_game {
+        Some(game) if game.status == GameStatus::Playing => game.moves.len(),
+        _ => {
+            return Err(
+                library::ManipulationError::new(library::ManipulationErrorType::NoVoteGame).into(),
+            )
+        }
+    };
+    library.vote_game = None;
+    library.audit(
+        msg.author.id.to_string(),
+        "Stopped the vote chess game".to_owned(),
+    );
+    response::success(
+        ctx,
+        msg,
+        format!("Stopped the vote chess game after {} moves", moves),
+    )
+    .await?;
+
+    Ok(())
+}
+
+//Posts the results of the simul in its channel when game `uuid`, which just ended, was the last of
+//its games still being played
+async fn send_simul_results(
+    ctx: &Context,
+    library: &library::Database,
+    uuid: games::GameUuid,
+) -> CommandResult {
+    let simul = match &library.simul {
+        Some(simul) if simul.games.contains(&uuid) && simul.is_over(&library.games) => simul,
+        _ => return Ok(()),
+    };
+    let text = simul.summary(&library.games);
+    ChannelId(simul.channel)
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.colour(response::Tone::Info.colour())
+                    .title("The simul is over")
+                    .description(text)
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+//The guild's simul, or NoSimul
+fn simul_of(
+    library: &mut library::Database,
+) -> Result<&mut simul::Simul, library::ManipulationError> {
+    library
+        .simul
+        .as_mut()
+        .ok_or_else(|| library::ManipulationError::new(library::ManipulationErrorType::NoSimul))
+}
+
+#[command("host")]
+#[checks(Writable)]
+#[description = "Opens sign-up for a simul in this channel, where you play everyone who joins at once. You play white on every board, or black if asked"]
+#[usage = "[white|black]"]
+#[example = "black"]
+async fn simul_host(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let colour = args.single::<String>().ok().map(|c| c.to_lowercase());
+    let host_white = match colour.as_deref() {
+        None | Some("white") => true,
+        Some("black") => false,
+        Some(other) => {
+            response::error(
+                ctx,
+                msg,
+                format!("Unknown colour \"{}\". Use white or black", other),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    if let Some(simul) = &library.simul {
+        if !simul.is_over(&library.games) {
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::SimulInProgress(simul.host.clone()),
+            )
+            .into());
+        }
+    }
+    library.simul = Some(simul::Simul::new(
+        msg.author.id.to_string(),
+        msg.channel_id.0,
+        host_white,
+    ));
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "<@{}> is hosting a simul, playing {} on every board! Join with !simul join. The host starts the games with !simul start",
+            msg.author.id,
+            if host_white { "white" } else { "black" }
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("join")]
+#[checks(Writable)]
+#[description = "Joins the simul while sign-up is open"]
+async fn simul_join(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let simul = simul_of(&mut library)?;
+    simul.join(&msg.author.id.to_string())?;
+    let text = format!(
+        "You joined <@{}>'s simul. {} challenger(s) so far",
+        simul.host,
+        simul.challengers.len()
+    );
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("leave")]
+#[checks(Writable)]
+#[description = "Leaves the simul, while sign-up is still open"]
+async fn simul_leave(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let simul = simul_of(&mut library)?;
+    simul.leave(&msg.author.id.to_string())?;
+    let text = format!("You left <@{}>'s simul", simul.host);
+    response::success(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("start")]
+#[checks(Writable)]
+#[description = "Closes sign-up for your simul and starts a game against each challenger, each with its own thread in the simul's channel"]
+async fn simul_start(ctx: &Context, msg: &Message) -> CommandResult {
+    let me = msg.author.id.to_string();
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    //The games are made first, then their threads are opened without holding the library
+    let (channel, boards) = {
+        let mut library = library_arc.write().await;
+        let simul = simul_of(&mut library)?.clone();
+        if simul.host != me {
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::NotSimulHost(simul.host),
+            )
+            .into());
+        }
+        if simul.status != simul::SimulStatus::SignUp {
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::SimulSignUpClosed,
+            )
+            .into());
+        }
+        if simul.challengers.is_empty() {
+            return Err(library::ManipulationError::new(
+                library::ManipulationErrorType::NoChallengers,
+            )
+            .into());
+        }
+        for challenger in &simul.challengers {
+            if library.find_game(&me, Some(challenger), |_| true).is_ok() {
+                return Err(library::ManipulationError::new(
+                    library::ManipulationErrorType::AlreadyPlaying(challenger.clone()),
+                )
+                .into());
+            }
+        }
+
+        let mut boards = Vec::new();
+        for challenger in &simul.challengers {
+            let (white, black) = if simul.host_white {
+                (me.clone(), challenger.clone())
+            } else {
+                (challenger.clone(), me.clone())
+            };
+            let uuid = library.new_game_uuid();
+            let mut game = Game::new(uuid, white, black, me.clone(), simul.channel);
+            game.status = GameStatus::Playing;
+            library.games.insert(uuid, game);
+            boards.push((uuid, challenger.clone()));
+        }
+        let simul = library.simul.as_mut().unwrap();
+        simul.games = boards.iter().map(|(uuid, _)| *uuid).collect();
+        simul.status = simul::SimulStatus::Playing;
+        (ChannelId(simul.channel), boards)
+    };
+
+    let host_name = display_name(ctx, msg.guild_id, &msg.author).await;
+    let mut opened = Vec::new();
+    for (i, (uuid, challenger)) in boards.iter().enumerate() {
+        let board = match channel
+            .say(
+                ctx,
+                format!("Simul board {}: <@{}> vs <@{}>", i + 1, me, challenger),
+            )
+            .await
+        {
+            Ok(board) => board,
+            Err(err) => {
+                println!("Failed to post simul board {}: {:?}", i + 1, err);
+                continue;
+            }
+        };
+        let name = format!(
+            "Simul board {}: {} vs {}",
+            i + 1,
+            host_name,
+            player_name(ctx, msg.guild_id, challenger).await
+        );
+        let thread = match threads::start(ctx, &board, &name).await {
+            Some(thread) => thread,
+            None => continue,
+        };
+        //Mentioning the players adds them to the thread
+        let text = format!(
+            "<@{}> <@{}> This thread is for your game. Play your moves with !chess move <move> @opponent",
+            me, challenger
+        );
+        if let Err(err) = thread.say(ctx, text).await {
+            println!("Failed to post in simul thread: {:?}", err);
+        }
+        opened.push((*uuid, thread));
+    }
+    {
+        let mut library = library_arc.write().await;
+        for (uuid, thread) in opened {
+            if let Some(game) = library.games.get_mut(&uuid) {
+                game.channel = thread.0;
+            }
+        }
+    }
+
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "The simul has started on {} board(s). !simul queue lists the boards waiting on your move",
+            boards.len()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("queue")]
+#[bucket = "lookup"]
+#[description = "Lists the simul's boards that are waiting on the host's move, the one that has waited longest first"]
+async fn simul_queue(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let simul = library
+        .simul
+        .as_ref()
+        .ok_or_else(|| library::ManipulationError::new(library::ManipulationErrorType::NoSimul))?;
+    let queue = simul.queue(&library.games);
+    if queue.is_empty() {
+        response::info(
+            ctx,
+            msg,
+            format!("No boards are waiting on <@{}>", simul.host),
+        )
+        .await?;
+        return Ok(());
+    }
+    let mut text = format!("{} board(s) waiting on <@{}>:", queue.len(), simul.host);
+    for (board, game) in queue {
+        let waiting = game.last_move_at.unwrap_or(game.started);
+        let last_move = match game.moves.last() {
+            Some(san) => format!("after {}", san),
+            None => "to play the first move".to_owned(),
+        };
+        let _ = write!(
+            text,
+            "\nBoard {}: <@{}> in <#{}>, {}, waiting since {}",
+            board,
+            game.opponent_of(&simul.host),
+            game.channel,
+            last_move,
+            waiting.format("%b %-d at %H:%M")
+        );
+    }
+    response::info(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("show")]
+#[bucket = "lookup"]
+#[description = "Shows who has joined the simul, or how each of its games is going"]
+async fn simul_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let simul = library
+        .simul
+        .as_ref()
+        .ok_or_else(|| library::ManipulationError::new(library::ManipulationErrorType::NoSimul))?;
+    let text = match simul.status {
+        simul::SimulStatus::SignUp if simul.challengers.is_empty() => format!(
+            "<@{}> is hosting a simul. Nobody has joined yet",
+            simul.host
+        ),
+        simul::SimulStatus::SignUp => format!(
+            "<@{}> is hosting a simul. Challengers: {}",
+            simul.host,
+            simul
+                .challengers
+                .iter()
+                .map(|challenger| format!("<@{}>", challenger))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        simul::SimulStatus::Playing if simul.is_over(&library.games) => format!(
+            "<@{}>'s simul is over\n{}",
+            simul.host,
+            simul.summary(&library.games)
+        ),
+        simul::SimulStatus::Playing => format!(
+            "<@{}>'s simul\n{}",
+            simul.host,
+            simul.summary(&library.games)
+        ),
+    };
+    response::info(ctx, msg, text).await?;
+
+    Ok(())
+}
+
+#[command("cancel")]
+#[checks(Writable)]
+#[description = "Cancels the simul. Only its host or an officer can. Games that have started go on as ordinary games"]
+async fn simul_cancel(ctx: &Context, msg: &Message) -> CommandResult {
+    let officer = is_officer(ctx, msg.guild_id, msg.author.id).await;
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let host = match &library.simul {
+        Some(simul) if !simul.is_over(&library.games) => simul.host.clone(),
+        _ => {
+            return Err(
+                library::ManipulationError::new(library::ManipulationErrorType::NoSimul).into(),
+            )
+        }
+    };
+    if host != msg.author.id.to_string() && !officer {
+        return Err(
+            library::ManipulationError::new(library::ManipulationErrorType::NotSimulHost(host))
+                .into(),
+        );
+    }
+    library.simul = None;
+    response::success(ctx, msg, format!("Cancelled <@{}>'s simul", host)).await?;
+
+    Ok(())
+}
+
+#[command("nominate")]
+#[checks(Officer, Writable)]
+#[description = "Nominates a game from the archive for game of the week. The week's first nomination opens voting for seven days"]
+#[usage = "<archived game ID>"]
+async fn gotw_nominate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_archived_game(&input)?;
+    let id = library::Database::encode_uuid(uuid);
+    let game = &library.archive[&uuid];
+    if game.pgn.is_none() {
+        return Err(
+            library::ManipulationError::new(library::ManipulationErrorType::NoMoves(id)).into(),
+        );
+    }
+    let description = game.describe();
+    library
+        .game_of_the_week
+        .nominate(uuid, msg.author.id.to_string(), id.clone())?;
+    library.audit(
+        msg.author.id.to_string(),
+        format!("Nominated game {} for game of the week", id),
+    );
+    let closes = library.game_of_the_week.closes.unwrap();
+    response::success(
+        ctx,
+        msg,
+        format!(
+            "Nominated {}\nVoting closes {}. Vote with !gotw vote {}",
+            description,
+            closes.format("%b %-d at %H:%M"),
+            id
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("vote")]
+#[checks(Writable)]
+#[description = "Votes for a nominated game to be game of the week. Voting again changes your vote"]
+#[usage = "<archived game ID>"]
+async fn gotw_vote(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let input: String = args.single::<String>()?;
+
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let mut library = library_arc.write().await;
+    let uuid = library.find_archived_game(&input)?;
+    let id = library::Database::encode_uuid(uuid);
+    library
+        .game_of_the_week
+        .vote(msg.author.id.to_string(), uuid, id.clone())?;
+    response::success(ctx, msg, format!("Voted for game {}", id)).await?;
+
+    Ok(())
+}
+
+#[command("show")]
+#[bucket = "lookup"]
+#[description = "Shows the games nominated for game of the week and their votes, and last week's winner"]
+async fn gotw_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let library_arc = library_for(ctx, msg.guild_id).await;
+    let library = library_arc.read().await;
+    let gotw = &library.game_of_the_week;
+    let mut text = match gotw.closes {
+        Some(closes) => {
+            let mut text = format!(
+                "Nominated games, voting closes {}:",
+                closes.format("%b %-d at %H:%M")
+            );
+            for (uuid, votes) in gotw.tally() {
+                if let Some(game) = library.archive.get(&uuid) {
+                    let _ = write!(text, "\n{} vote(s): {}", votes, game.describe());
+                }
+            }
+            text.push_str("\nVote with !gotw vote <ID>");
+            text
+        }
+        None => {
+            "Nothing has been nominated this week. Officers nominate games with !gotw nominate <ID>"
+                .to_owned()
+        }
+    };
+    if let Some((uuid, won)) = gotw.winners.last() {
+        if let Some(game) = library.archive.get(uuid) {
+            let _ = write!(
+                text,
+                "\n\nGame of the week {}: {}",
+                won.format("%b %-d"),
+                game.describe()
+            );
+        }
+    }
+    response::info(ctx, msg, text).await?;
 
     Ok(())
 }
@@ -396,7 +7953,7 @@ async fn return_command(ctx: &Context, msg: &Message, mut args: Args) -> Command
 #[command]
 #[description = "Checks the status of the bot. Replies mate if the bot is online and operational"]
 async fn check(ctx: &Context, msg: &Message) -> CommandResult {
-    msg.reply(ctx, "mate").await?;
+    response::success(ctx, msg, "mate").await?;
 
     Ok(())
 }
@@ -0,0 +1,4057 @@
+use crate::library::Database;
+
+//Version of the layout written by Database::save. Bincode isn't self describing, so this has to be
+//bumped whenever anything that gets serialized changes (a field is added, removed, reordered or
+//changes type). When bumping it:
+//  1. Copy the old definitions of every changed struct into a `vN` module in this file, with
+//     Database renamed so that it still describes the previous layout
+//  2. Add a match arm to `upgrade` that deserializes the payload as `vN::Database` and converts it
+//     into the current one, filling in defaults for the new fields
+pub const CURRENT_VERSION: u32 = 41;
+
+//Files start with these bytes followed by the version as a little endian u32. Anything without
+//them was written before versioning was added and is version 0
+const MAGIC: &[u8; 4] = b"CBDB";
+
+#[derive(Debug)]
+pub enum LoadError {
+    //The file was written by a newer build of the bot than this one
+    TooNew(u32),
+    Corrupt(u32, bincode::Error),
+}
+
+impl std::error::Error for LoadError {}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            LoadError::TooNew(version) => write!(
+                fmt,
+                "Database is version {} but this build only understands up to version {}",
+                version, CURRENT_VERSION
+            ),
+            LoadError::Corrupt(version, err) => {
+                write!(fmt, "Failed to read version {} database: {}", version, err)
+            }
+        }
+    }
+}
+
+pub fn encode(db: &Database) -> bincode::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut data, db)?;
+    Ok(data)
+}
+
+//Splits a saved file into its version and bincode payload
+pub fn version_of(data: &[u8]) -> (u32, &[u8]) {
+    if data.len() >= 8 && &data[..4] == MAGIC {
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&data[4..8]);
+        (u32::from_le_bytes(version), &data[8..])
+    } else {
+        (0, data)
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Database, LoadError> {
+    let (version, payload) = version_of(data);
+    upgrade(version, payload)
+}
+
+fn upgrade(version: u32, payload: &[u8]) -> Result<Database, LoadError> {
+    match version {
+        0 => bincode::deserialize::<v0::Database>(payload)
+            .map(v0::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        1 => bincode::deserialize::<v1::Database>(payload)
+            .map(v1::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        2 => bincode::deserialize::<v2::Database>(payload)
+            .map(v2::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        3 => bincode::deserialize::<v3::Database>(payload)
+            .map(v3::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        4 => bincode::deserialize::<v4::Database>(payload)
+            .map(v4::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        5 => bincode::deserialize::<v5::Database>(payload)
+            .map(v5::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        6 => bincode::deserialize::<v6::Database>(payload)
+            .map(v6::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        7 => bincode::deserialize::<v7::Database>(payload)
+            .map(v7::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        8 => bincode::deserialize::<v8::Database>(payload)
+            .map(v8::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        9 => bincode::deserialize::<v9::Database>(payload)
+            .map(v9::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        10 => bincode::deserialize::<v10::Database>(payload)
+            .map(v10::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        11 => bincode::deserialize::<v11::Database>(payload)
+            .map(v11::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        12 => bincode::deserialize::<v12::Database>(payload)
+            .map(v12::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        13 => bincode::deserialize::<v13::Database>(payload)
+            .map(v13::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        14 => bincode::deserialize::<v14::Database>(payload)
+            .map(v14::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        15 => bincode::deserialize::<v15::Database>(payload)
+            .map(v15::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        16 => bincode::deserialize::<v16::Database>(payload)
+            .map(v16::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        17 => bincode::deserialize::<v17::Database>(payload)
+            .map(v17::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        18 => bincode::deserialize::<v18::Database>(payload)
+            .map(v18::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        19 => bincode::deserialize::<v19::Database>(payload)
+            .map(v19::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        20 => bincode::deserialize::<v20::Database>(payload)
+            .map(v20::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        21 => bincode::deserialize::<v21::Database>(payload)
+            .map(v21::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        22 => bincode::deserialize::<v22::Database>(payload)
+            .map(v22::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        23 => bincode::deserialize::<v23::Database>(payload)
+            .map(v23::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        24 => bincode::deserialize::<v24::Database>(payload)
+            .map(v24::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        25 => bincode::deserialize::<v25::Database>(payload)
+            .map(v25::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        26 => bincode::deserialize::<v26::Database>(payload)
+            .map(v26::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        27 => bincode::deserialize::<v27::Database>(payload)
+            .map(v27::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        28 => bincode::deserialize::<v28::Database>(payload)
+            .map(v28::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        29 => bincode::deserialize::<v29::Database>(payload)
+            .map(v29::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        30 => bincode::deserialize::<v30::Database>(payload)
+            .map(v30::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        31 => bincode::deserialize::<v31::Database>(payload)
+            .map(v31::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        32 => bincode::deserialize::<v32::Database>(payload)
+            .map(v32::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        33 => bincode::deserialize::<v33::Database>(payload)
+            .map(v33::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        34 => bincode::deserialize::<v34::Database>(payload)
+            .map(v34::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        35 => bincode::deserialize::<v35::Database>(payload)
+            .map(v35::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        36 => bincode::deserialize::<v36::Database>(payload)
+            .map(v36::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        37 => bincode::deserialize::<v37::Database>(payload)
+            .map(v37::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        38 => bincode::deserialize::<v38::Database>(payload)
+            .map(v38::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        39 => bincode::deserialize::<v39::Database>(payload)
+            .map(v39::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        40 => bincode::deserialize::<v40::Database>(payload)
+            .map(v40::Database::upgrade)
+            .map_err(|err| LoadError::Corrupt(version, err)),
+        41 => bincode::deserialize(payload).map_err(|err| LoadError::Corrupt(version, err)),
+        _ => Err(LoadError::TooNew(version)),
+    }
+}
+
+//Files written before versioning, by the bot as it was before it could do more than list, add and
+//remove books. Everything added since then starts out empty
+mod v0 {
+    use crate::library::{BookUuid, CheckoutStatus, CheckoutUuid, TimeType, UserUuid};
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Book {
+        uuid: BookUuid,
+        name: String,
+        author: String,
+        quantity: u32,
+    }
+
+    //Approvals used to point at the officer's library account instead of their discord id
+    #[derive(Deserialize)]
+    pub struct OfficerApproval {
+        user: UserUuid,
+        time: TimeType,
+    }
+
+    impl OfficerApproval {
+        fn upgrade(self, users: &IndexMap<UserUuid, User>) -> crate::library::OfficerApproval {
+            let discord_id = match users.get(&self.user) {
+                Some(user) => user.discord_id.clone(),
+                None => self.user.to_string(),
+            };
+            crate::library::OfficerApproval::new(discord_id, self.time)
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct CheckoutInstance {
+        uuid: CheckoutUuid,
+        rentee: UserUuid,
+        book: BookUuid,
+        status: CheckoutStatus,
+        due_date: Option<TimeType>,
+        checkout_approval: Option<OfficerApproval>,
+        checkin_approval: Option<OfficerApproval>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct User {
+        discord_id: String,
+        read_name: String,
+        uuid: UserUuid,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+    }
+
+    impl Database {
+        //Completed checkouts are moved into the archive when the indices are rebuilt after loading
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self
+                .books
+                .into_iter()
+                .map(|(uuid, book)| {
+                    let book =
+                        crate::library::Book::new(book.uuid, book.name, book.author, book.quantity);
+                    (uuid, book)
+                })
+                .collect();
+            let users = &self.users;
+            db.checkouts = self
+                .checkouts
+                .into_iter()
+                .map(|(uuid, old)| {
+                    let mut checkout =
+                        crate::library::CheckoutInstance::new(old.uuid, old.rentee, old.book);
+                    checkout.status = old.status;
+                    checkout.due_date = old.due_date;
+                    checkout.checkout_approval = old
+                        .checkout_approval
+                        .map(|approval| approval.upgrade(users));
+                    checkout.checkin_approval =
+                        old.checkin_approval.map(|approval| approval.upgrade(users));
+                    (uuid, checkout)
+                })
+                .collect();
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| {
+                    let user =
+                        crate::library::User::new(user.discord_id, user.read_name, user.uuid);
+                    (uuid, user)
+                })
+                .collect();
+            db
+        }
+    }
+}
+
+//Before completed checkouts were moved to archived_checkouts
+mod v1 {
+    use super::v20::User;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+    }
+
+    impl Database {
+        //Completed checkouts are moved into the archive when the indices are rebuilt after loading
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db
+        }
+    }
+}
+
+//Before guilds had their own settings
+mod v2 {
+    use super::v20::User;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db
+        }
+    }
+}
+
+//Before officer and admin roles could be configured
+mod v3 {
+    use super::v20::User;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config.prefix = self.config.prefix;
+            db
+        }
+    }
+}
+
+//Before permission tiers, when officers and admins were only given by role
+mod v4 {
+    use super::v20::User;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        officer_roles: Vec<u64>,
+        admin_roles: Vec<u64>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+    }
+
+    impl Database {
+        //The roles become grants of the matching tier
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config.prefix = self.config.prefix;
+            for role in self.config.officer_roles {
+                db.config.role_tiers.insert(role, Tier::Officer);
+            }
+            for role in self.config.admin_roles {
+                db.config.role_tiers.insert(role, Tier::Admin);
+            }
+            db
+        }
+    }
+}
+
+//Before guilds could pick the channels the bot posts in
+mod v5 {
+    use super::v20::User;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        role_tiers: IndexMap<u64, Tier>,
+        member_tiers: IndexMap<u64, Tier>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config.prefix = self.config.prefix;
+            db.config.role_tiers = self.config.role_tiers;
+            db.config.member_tiers = self.config.member_tiers;
+            db
+        }
+    }
+}
+
+//Before scheduled announcements
+mod v6 {
+    use super::v20::User;
+    use super::v7::GuildConfig;
+    use crate::library::{
+        AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid, EscalationStep,
+        ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule, WishUuid,
+        WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db
+        }
+    }
+}
+
+//Before welcome messages
+mod v7 {
+    use super::v20::User;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, ChannelKind, CheckoutInstance,
+        CheckoutUuid, EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        role_tiers: IndexMap<u64, Tier>,
+        member_tiers: IndexMap<u64, Tier>,
+        channels: IndexMap<ChannelKind, u64>,
+    }
+
+    impl GuildConfig {
+        pub fn upgrade(self) -> crate::library::GuildConfig {
+            crate::library::GuildConfig {
+                prefix: self.prefix,
+                role_tiers: self.role_tiers,
+                member_tiers: self.member_tiers,
+                channels: self.channels,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db
+        }
+    }
+}
+
+//Before chess games
+mod v8 {
+    use super::v20::User;
+    use super::v9::GuildConfig;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule,
+        WishUuid, WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db
+        }
+    }
+}
+
+//Before board styles
+mod v9 {
+    use super::v10::Game;
+    use super::v20::User;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, ChannelKind, CheckoutInstance,
+        CheckoutUuid, EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        role_tiers: IndexMap<u64, Tier>,
+        member_tiers: IndexMap<u64, Tier>,
+        channels: IndexMap<ChannelKind, u64>,
+        welcome_message: Option<String>,
+        welcome_disabled: bool,
+    }
+
+    impl GuildConfig {
+        pub fn upgrade(self) -> crate::library::GuildConfig {
+            crate::library::GuildConfig {
+                prefix: self.prefix,
+                role_tiers: self.role_tiers,
+                member_tiers: self.member_tiers,
+                channels: self.channels,
+                welcome_message: self.welcome_message,
+                welcome_disabled: self.welcome_disabled,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before games could start from any position
+mod v10 {
+    use super::v14::GuildConfig;
+    use super::v20::User;
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule,
+        WishUuid, WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before PGNs were saved with finished games
+mod v11 {
+    use super::v14::GuildConfig;
+    use super::v20::User;
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule,
+        WishUuid, WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before games could be played against the engine
+mod v12 {
+    use super::v14::GuildConfig;
+    use super::v20::User;
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule,
+        WishUuid, WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before members had puzzle ratings
+mod v13 {
+    use super::v14::GuildConfig;
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid, WeeklySchedule,
+        WishUuid, WishlistEntry,
+    };
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before club ratings
+mod v14 {
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::{BoardStyle, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, ChannelKind, CheckoutInstance,
+        CheckoutUuid, EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use crate::puzzles::PuzzleRating;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        role_tiers: IndexMap<u64, Tier>,
+        member_tiers: IndexMap<u64, Tier>,
+        channels: IndexMap<ChannelKind, u64>,
+        welcome_message: Option<String>,
+        welcome_disabled: bool,
+        board_style: BoardStyle,
+    }
+
+    impl GuildConfig {
+        pub fn upgrade(self) -> crate::library::GuildConfig {
+            crate::library::GuildConfig {
+                prefix: self.prefix,
+                role_tiers: self.role_tiers,
+                member_tiers: self.member_tiers,
+                channels: self.channels,
+                welcome_message: self.welcome_message,
+                welcome_disabled: self.welcome_disabled,
+                board_style: self.board_style,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db
+        }
+    }
+}
+
+//Before Glicko-2 ratings
+mod v15 {
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::{BoardStyle, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, ChannelKind, CheckoutInstance,
+        CheckoutUuid, EscalationStep, ExtensionRequest, ExtensionUuid, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::permissions::Tier;
+    use crate::puzzles::PuzzleRating;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct GuildConfig {
+        prefix: Option<String>,
+        role_tiers: IndexMap<u64, Tier>,
+        member_tiers: IndexMap<u64, Tier>,
+        channels: IndexMap<ChannelKind, u64>,
+        welcome_message: Option<String>,
+        welcome_disabled: bool,
+        board_style: BoardStyle,
+        k_factor: Option<u32>,
+    }
+
+    impl GuildConfig {
+        pub fn upgrade(self) -> crate::library::GuildConfig {
+            crate::library::GuildConfig {
+                prefix: self.prefix,
+                role_tiers: self.role_tiers,
+                member_tiers: self.member_tiers,
+                channels: self.channels,
+                welcome_message: self.welcome_message,
+                welcome_disabled: self.welcome_disabled,
+                board_style: self.board_style,
+                k_factor: self.k_factor,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct ClubRating {
+        rating: f64,
+        wins: u32,
+        draws: u32,
+        losses: u32,
+    }
+
+    impl ClubRating {
+        pub fn upgrade(self) -> crate::ratings::ClubRating {
+            crate::ratings::ClubRating {
+                rating: self.rating,
+                wins: self.wins,
+                draws: self.draws,
+                losses: self.losses,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config.upgrade();
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self
+                .club_ratings
+                .into_iter()
+                .map(|(member, rating)| (member, rating.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before tournaments
+mod v16 {
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db
+        }
+    }
+}
+
+//Before knockout brackets were kept up to date in a message
+mod v17 {
+    use super::v19::Round;
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::{TournamentFormat, TournamentStatus, TournamentUuid};
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Tournament {
+        uuid: TournamentUuid,
+        name: String,
+        format: TournamentFormat,
+        organiser: String,
+        channel: u64,
+        status: TournamentStatus,
+        players: Vec<String>,
+        rounds: Vec<Round>,
+        created: TimeType,
+    }
+
+    impl Tournament {
+        pub fn upgrade(self) -> crate::tournaments::Tournament {
+            crate::tournaments::Tournament {
+                uuid: self.uuid,
+                name: self.name,
+                format: self.format,
+                organiser: self.organiser,
+                channel: self.channel,
+                status: self.status,
+                players: self.players,
+                rounds: self.rounds.into_iter().map(Round::upgrade).collect(),
+                created: self.created,
+                bracket_message: None,
+                check_in_deadline: None,
+                checked_in: Vec::new(),
+                ends: None,
+                paused: Vec::new(),
+                games: Vec::new(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before tournament check-in
+mod v18 {
+    use super::v19::Round;
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::{TournamentFormat, TournamentStatus, TournamentUuid};
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Tournament {
+        uuid: TournamentUuid,
+        name: String,
+        format: TournamentFormat,
+        organiser: String,
+        channel: u64,
+        status: TournamentStatus,
+        players: Vec<String>,
+        rounds: Vec<Round>,
+        created: TimeType,
+        bracket_message: Option<u64>,
+    }
+
+    impl Tournament {
+        pub fn upgrade(self) -> crate::tournaments::Tournament {
+            crate::tournaments::Tournament {
+                uuid: self.uuid,
+                name: self.name,
+                format: self.format,
+                organiser: self.organiser,
+                channel: self.channel,
+                status: self.status,
+                players: self.players,
+                rounds: self.rounds.into_iter().map(Round::upgrade).collect(),
+                created: self.created,
+                bracket_message: self.bracket_message,
+                check_in_deadline: None,
+                checked_in: Vec::new(),
+                ends: None,
+                paused: Vec::new(),
+                games: Vec::new(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before players reported their own tournament results
+mod v19 {
+    use super::v20::User;
+    use super::v27::Game;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::{Outcome, TournamentFormat, TournamentStatus, TournamentUuid};
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Pairing {
+        white: String,
+        black: Option<String>,
+        result: Option<Outcome>,
+    }
+
+    impl Pairing {
+        pub fn upgrade(self) -> crate::tournaments::Pairing {
+            crate::tournaments::Pairing {
+                white: self.white,
+                black: self.black,
+                result: self.result,
+                claim: None,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Round {
+        pairings: Vec<Pairing>,
+    }
+
+    impl Round {
+        pub fn upgrade(self) -> crate::tournaments::Round {
+            crate::tournaments::Round {
+                pairings: self.pairings.into_iter().map(Pairing::upgrade).collect(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Tournament {
+        uuid: TournamentUuid,
+        name: String,
+        format: TournamentFormat,
+        organiser: String,
+        channel: u64,
+        status: TournamentStatus,
+        players: Vec<String>,
+        rounds: Vec<Round>,
+        created: TimeType,
+        bracket_message: Option<u64>,
+        check_in_deadline: Option<TimeType>,
+        checked_in: Vec<String>,
+    }
+
+    impl Tournament {
+        pub fn upgrade(self) -> crate::tournaments::Tournament {
+            crate::tournaments::Tournament {
+                uuid: self.uuid,
+                name: self.name,
+                format: self.format,
+                organiser: self.organiser,
+                channel: self.channel,
+                status: self.status,
+                players: self.players,
+                rounds: self.rounds.into_iter().map(Round::upgrade).collect(),
+                created: self.created,
+                bracket_message: self.bracket_message,
+                check_in_deadline: self.check_in_deadline,
+                checked_in: self.checked_in,
+                ends: None,
+                paused: Vec::new(),
+                games: Vec::new(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before Lichess accounts could be linked
+mod v20 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct User {
+        discord_id: String,
+        read_name: String,
+        uuid: UserUuid,
+        suspended: bool,
+        registered: TimeType,
+    }
+
+    impl User {
+        pub fn upgrade(self) -> crate::library::User {
+            crate::library::User {
+                discord_id: self.discord_id,
+                read_name: self.read_name,
+                uuid: self.uuid,
+                suspended: self.suspended,
+                registered: self.registered,
+                lichess: None,
+                chesscom: None,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before Lichess ratings were synced
+mod v21 {
+    use super::v22::User;
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before chess.com accounts could be linked
+mod v22 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct User {
+        discord_id: String,
+        read_name: String,
+        uuid: UserUuid,
+        suspended: bool,
+        registered: TimeType,
+        lichess: Option<String>,
+    }
+
+    impl User {
+        pub fn upgrade(self) -> crate::library::User {
+            crate::library::User {
+                discord_id: self.discord_id,
+                read_name: self.read_name,
+                uuid: self.uuid,
+                suspended: self.suspended,
+                registered: self.registered,
+                lichess: self.lichess,
+                chesscom: None,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self
+                .users
+                .into_iter()
+                .map(|(uuid, user)| (uuid, user.upgrade()))
+                .collect();
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db
+        }
+    }
+}
+
+//Before results of games played over the board could be reported
+mod v23 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db
+        }
+    }
+}
+
+//Before finished games were archived
+mod v24 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db
+        }
+    }
+}
+
+//Before archived games were tagged with their opening
+mod v25 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, GameSource};
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::{Outcome, TournamentUuid};
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct ArchivedGame {
+        uuid: ArchiveUuid,
+        white: String,
+        black: String,
+        played: TimeType,
+        event: Option<String>,
+        outcome: Outcome,
+        pgn: Option<String>,
+        source: GameSource,
+    }
+
+    impl ArchivedGame {
+        //The opening is named when the library is loaded
+        pub fn upgrade(self) -> crate::archive::ArchivedGame {
+            crate::archive::ArchivedGame {
+                uuid: self.uuid,
+                white: self.white,
+                black: self.black,
+                played: self.played,
+                event: self.event,
+                outcome: self.outcome,
+                pgn: self.pgn,
+                source: self.source,
+                opening: None,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self
+                .archive
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db
+        }
+    }
+}
+
+//Before vote chess
+mod v26 {
+    use super::v27::Game;
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db
+        }
+    }
+}
+
+//Before correspondence time controls
+mod v27 {
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db
+        }
+    }
+}
+
+//Before simuls
+mod v28 {
+    use super::v29::Game;
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db
+        }
+    }
+}
+
+//Before real-time clocks
+mod v29 {
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+        days_per_move: Option<u32>,
+        last_move_at: Option<TimeType>,
+        reminded: bool,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game.days_per_move = self.days_per_move;
+            game.last_move_at = self.last_move_at;
+            game.reminded = self.reminded;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db
+        }
+    }
+}
+
+//Before broadcasts
+mod v30 {
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::clocks::Clock;
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+        days_per_move: Option<u32>,
+        last_move_at: Option<TimeType>,
+        reminded: bool,
+        clock: Option<Clock>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game.days_per_move = self.days_per_move;
+            game.last_move_at = self.last_move_at;
+            game.reminded = self.reminded;
+            game.clock = self.clock;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db
+        }
+    }
+}
+
+//Before game of the week
+mod v31 {
+    use super::v33::Game;
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db
+        }
+    }
+}
+
+//Before puzzle streaks
+mod v32 {
+    use super::v33::Game;
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::PuzzleRating;
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db
+        }
+    }
+}
+
+//Before hints
+mod v33 {
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::clocks::Clock;
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::{GameStatus, GameUuid};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+        days_per_move: Option<u32>,
+        last_move_at: Option<TimeType>,
+        reminded: bool,
+        clock: Option<Clock>,
+        broadcast: bool,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game.days_per_move = self.days_per_move;
+            game.last_move_at = self.last_move_at;
+            game.reminded = self.reminded;
+            game.clock = self.clock;
+            game.broadcast = self.broadcast;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db
+        }
+    }
+}
+
+//Before draw offers, takebacks and casual games
+mod v34 {
+    use super::v35::Tournament;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::clocks::Clock;
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::{GameStatus, GameUuid, Hint};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::TournamentUuid;
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+        days_per_move: Option<u32>,
+        last_move_at: Option<TimeType>,
+        reminded: bool,
+        clock: Option<Clock>,
+        broadcast: bool,
+        hints: Vec<Hint>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game.days_per_move = self.days_per_move;
+            game.last_move_at = self.last_move_at;
+            game.reminded = self.reminded;
+            game.clock = self.clock;
+            game.broadcast = self.broadcast;
+            game.hints = self.hints;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db
+        }
+    }
+}
+
+//Before arenas
+mod v35 {
+    use super::v40::Game;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::{Round, TournamentFormat, TournamentStatus, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Tournament {
+        uuid: TournamentUuid,
+        name: String,
+        format: TournamentFormat,
+        organiser: String,
+        channel: u64,
+        status: TournamentStatus,
+        players: Vec<String>,
+        rounds: Vec<Round>,
+        created: TimeType,
+        bracket_message: Option<u64>,
+        check_in_deadline: Option<TimeType>,
+        checked_in: Vec<String>,
+    }
+
+    impl Tournament {
+        pub fn upgrade(self) -> crate::tournaments::Tournament {
+            crate::tournaments::Tournament {
+                uuid: self.uuid,
+                name: self.name,
+                format: self.format,
+                organiser: self.organiser,
+                channel: self.channel,
+                status: self.status,
+                players: self.players,
+                rounds: self.rounds,
+                created: self.created,
+                bracket_message: self.bracket_message,
+                check_in_deadline: self.check_in_deadline,
+                checked_in: self.checked_in,
+                ends: None,
+                paused: Vec::new(),
+                games: Vec::new(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self
+                .tournaments
+                .into_iter()
+                .map(|(uuid, tournament)| (uuid, tournament.upgrade()))
+                .collect();
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db
+        }
+    }
+}
+
+//Before rating history
+mod v36 {
+    use super::v40::Game;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::ClubRating;
+    use crate::simul::Simul;
+    use crate::tournaments::{Tournament, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self.tournaments;
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db
+        }
+    }
+}
+
+//Before Lichess team sync
+mod v37 {
+    use super::v40::Game;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::{ClubRating, RatingSnapshot};
+    use crate::simul::Simul;
+    use crate::tournaments::{Tournament, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+        rating_history: IndexMap<String, Vec<RatingSnapshot>>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self.tournaments;
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db.rating_history = self.rating_history;
+            db
+        }
+    }
+}
+
+//Before Lichess tournament relays
+mod v38 {
+    use super::v40::Game;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::lichess_team::LichessTeam;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::{ClubRating, RatingSnapshot};
+    use crate::simul::Simul;
+    use crate::tournaments::{Tournament, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+        rating_history: IndexMap<String, Vec<RatingSnapshot>>,
+        lichess_team: Option<LichessTeam>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self.tournaments;
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db.rating_history = self.rating_history;
+            db.lichess_team = self.lichess_team;
+            db
+        }
+    }
+}
+
+//Before live Lichess game notifications
+mod v39 {
+    use super::v40::Game;
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::GameUuid;
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::lichess_relay::LichessRelay;
+    use crate::lichess_team::LichessTeam;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::{ClubRating, RatingSnapshot};
+    use crate::simul::Simul;
+    use crate::tournaments::{Tournament, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+        rating_history: IndexMap<String, Vec<RatingSnapshot>>,
+        lichess_team: Option<LichessTeam>,
+        lichess_relays: Vec<LichessRelay>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self.tournaments;
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db.rating_history = self.rating_history;
+            db.lichess_team = self.lichess_team;
+            db.lichess_relays = self.lichess_relays;
+            db
+        }
+    }
+}
+
+//Before move timestamps
+mod v40 {
+    use crate::archive::{ArchiveUuid, ArchivedGame};
+    use crate::clocks::Clock;
+    use crate::game_of_the_week::GameOfTheWeek;
+    use crate::games::{GameStatus, GameUuid, Hint};
+    use crate::library::{
+        Announcement, AnnouncementUuid, AuditEntry, Book, BookUuid, CheckoutInstance, CheckoutUuid,
+        EscalationStep, ExtensionRequest, ExtensionUuid, GuildConfig, TimeType, User, UserUuid,
+        WeeklySchedule, WishUuid, WishlistEntry,
+    };
+    use crate::lichess::LichessRatings;
+    use crate::lichess_live::LiveWatch;
+    use crate::lichess_relay::LichessRelay;
+    use crate::lichess_team::LichessTeam;
+    use crate::otb::{OtbGame, OtbUuid};
+    use crate::puzzles::{PuzzleRating, PuzzleStreak};
+    use crate::ratings::{ClubRating, RatingSnapshot};
+    use crate::simul::Simul;
+    use crate::tournaments::{Tournament, TournamentUuid};
+    use crate::vote_chess::VoteGame;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Game {
+        uuid: GameUuid,
+        white: String,
+        black: String,
+        challenger: String,
+        channel: u64,
+        status: GameStatus,
+        moves: Vec<String>,
+        started: TimeType,
+        finished: Option<TimeType>,
+        start_fen: Option<String>,
+        pgn: Option<String>,
+        engine_level: Option<u8>,
+        days_per_move: Option<u32>,
+        last_move_at: Option<TimeType>,
+        reminded: bool,
+        clock: Option<Clock>,
+        broadcast: bool,
+        hints: Vec<Hint>,
+        casual: bool,
+        draw_offer: Option<String>,
+        takeback_request: Option<String>,
+    }
+
+    impl Game {
+        pub fn upgrade(self) -> crate::games::Game {
+            let mut game = crate::games::Game::new(
+                self.uuid,
+                self.white,
+                self.black,
+                self.challenger,
+                self.channel,
+            );
+            game.status = self.status;
+            game.moves = self.moves;
+            game.started = self.started;
+            game.finished = self.finished;
+            game.start_fen = self.start_fen;
+            game.pgn = self.pgn;
+            game.engine_level = self.engine_level;
+            game.days_per_move = self.days_per_move;
+            game.last_move_at = self.last_move_at;
+            game.reminded = self.reminded;
+            game.clock = self.clock;
+            game.broadcast = self.broadcast;
+            game.hints = self.hints;
+            game.casual = self.casual;
+            game.draw_offer = self.draw_offer;
+            game.takeback_request = self.takeback_request;
+            game
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Database {
+        books: IndexMap<BookUuid, Book>,
+        checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        archived_checkouts: IndexMap<CheckoutUuid, CheckoutInstance>,
+        users: IndexMap<UserUuid, User>,
+        wishlist: IndexMap<WishUuid, WishlistEntry>,
+        extension_requests: IndexMap<ExtensionUuid, ExtensionRequest>,
+        audit_log: Vec<AuditEntry>,
+        escalation_policy: Vec<EscalationStep>,
+        maintenance: bool,
+        digest_schedule: WeeklySchedule,
+        last_digest: Option<TimeType>,
+        config: GuildConfig,
+        announcements: IndexMap<AnnouncementUuid, Announcement>,
+        games: IndexMap<GameUuid, Game>,
+        puzzle_ratings: IndexMap<String, PuzzleRating>,
+        club_ratings: IndexMap<String, ClubRating>,
+        tournaments: IndexMap<TournamentUuid, Tournament>,
+        lichess_ratings: IndexMap<String, Vec<LichessRatings>>,
+        otb_games: IndexMap<OtbUuid, OtbGame>,
+        archive: IndexMap<ArchiveUuid, ArchivedGame>,
+        vote_game: Option<VoteGame>,
+        simul: Option<Simul>,
+        game_of_the_week: GameOfTheWeek,
+        puzzle_streaks: IndexMap<String, PuzzleStreak>,
+        rating_history: IndexMap<String, Vec<RatingSnapshot>>,
+        lichess_team: Option<LichessTeam>,
+        lichess_relays: Vec<LichessRelay>,
+        lichess_live: IndexMap<String, LiveWatch>,
+    }
+
+    impl Database {
+        pub fn upgrade(self) -> crate::library::Database {
+            let mut db = crate::library::Database::new();
+            db.books = self.books;
+            db.checkouts = self.checkouts;
+            db.archived_checkouts = self.archived_checkouts;
+            db.users = self.users;
+            db.wishlist = self.wishlist;
+            db.extension_requests = self.extension_requests;
+            db.audit_log = self.audit_log;
+            db.escalation_policy = self.escalation_policy;
+            db.maintenance = self.maintenance;
+            db.digest_schedule = self.digest_schedule;
+            db.last_digest = self.last_digest;
+            db.config = self.config;
+            db.announcements = self.announcements;
+            db.games = self
+                .games
+                .into_iter()
+                .map(|(uuid, game)| (uuid, game.upgrade()))
+                .collect();
+            db.puzzle_ratings = self.puzzle_ratings;
+            db.club_ratings = self.club_ratings;
+            db.tournaments = self.tournaments;
+            db.lichess_ratings = self.lichess_ratings;
+            db.otb_games = self.otb_games;
+            db.archive = self.archive;
+            db.vote_game = self.vote_game;
+            db.simul = self.simul;
+            db.game_of_the_week = self.game_of_the_week;
+            db.puzzle_streaks = self.puzzle_streaks;
+            db.rating_history = self.rating_history;
+            db.lichess_team = self.lichess_team;
+            db.lichess_relays = self.lichess_relays;
+            db.lichess_live = self.lichess_live;
+            db
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::{Book, WeeklySchedule};
+    use chrono::Weekday;
+
+    //A saved empty library of `version`. Zeros read as empty collections, false and None, which is
+    //all an empty library is made of apart from the digest schedule, since bincode writes weekdays
+    //as their names. Every version from the second has its schedule after the same collections and
+    //the maintenance flag, the second without archived_checkouts. The first had no schedule
+    fn empty_library(version: u32, schedule: &WeeklySchedule) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&version.to_le_bytes());
+        let collections = match version {
+            0 => None,
+            1 => Some(7),
+            _ => Some(8),
+        };
+        if let Some(collections) = collections {
+            data.resize(data.len() + collections * 8 + 1, 0);
+            data.extend(bincode::serialize(schedule).unwrap());
+        }
+        //Plenty for whatever each version has after the schedule. Bincode ignores what is left over
+        data.resize(data.len() + 4096, 0);
+        data
+    }
+
+    #[test]
+    fn every_version_upgrades_and_saves_again() {
+        let schedule = WeeklySchedule::new(Weekday::Wed, 7, 30);
+        for version in 0..=CURRENT_VERSION {
+            let db = decode(&empty_library(version, &schedule))
+                .unwrap_or_else(|err| panic!("version {}: {}", version, err));
+            if version > 0 {
+                assert_eq!(
+                    db.digest_schedule.weekday,
+                    Weekday::Wed,
+                    "version {}",
+                    version
+                );
+                assert_eq!(db.digest_schedule.minute, 30, "version {}", version);
+            }
+
+            let data = encode(&db).unwrap();
+            assert_eq!(version_of(&data).0, CURRENT_VERSION);
+            let again = decode(&data).unwrap_or_else(|err| panic!("version {}: {}", version, err));
+            assert_eq!(again.digest_schedule.weekday, db.digest_schedule.weekday);
+            assert_eq!(again.digest_schedule.hour, db.digest_schedule.hour);
+        }
+    }
+
+    #[test]
+    fn the_current_version_keeps_its_contents() {
+        let mut db = Database::new();
+        db.add_book(Book::new(
+            1,
+            "My System".to_owned(),
+            "Nimzowitsch".to_owned(),
+            2,
+        ))
+        .unwrap();
+        db.register_user("1234".to_owned(), "Aron".to_owned())
+            .unwrap();
+        db.maintenance = true;
+
+        let again = decode(&encode(&db).unwrap()).unwrap();
+        assert_eq!(again.books[&1].name, "My System");
+        assert_eq!(again.books[&1].quantity, 2);
+        assert_eq!(
+            again.find_user_by_discord_id("1234").unwrap().read_name,
+            "Aron"
+        );
+        assert!(again.maintenance);
+    }
+
+    #[test]
+    fn files_without_a_version_are_the_first_one() {
+        let unversioned = vec![0u8; 24];
+        assert_eq!(version_of(&unversioned).0, 0);
+        assert!(decode(&unversioned).unwrap().books.is_empty());
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let schedule = WeeklySchedule::new(Weekday::Sun, 18, 0);
+        let newer = empty_library(CURRENT_VERSION + 1, &schedule);
+        assert!(matches!(decode(&newer), Err(LoadError::TooNew(v)) if v == CURRENT_VERSION + 1));
+    }
+}
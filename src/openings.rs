@@ -0,0 +1,414 @@
+use once_cell::sync::Lazy;
+
+use std::collections::HashMap;
+
+use crate::rules::GameState;
+
+//Names the opening a game was played in, with its code in the Encyclopaedia of Chess Openings.
+//Openings are recognised by position rather than by move order, so that games which transpose into
+//one are named after it too, and a game is named after the last of its positions that is in the
+//table. The table only has the openings club members are likely to meet, named as Lichess does
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+}
+
+impl std::fmt::Display for Opening {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "{} {}", self.eco, self.name)
+    }
+}
+
+//ECO code, name, and the moves from the usual starting position that reach it
+const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A00", "Hungarian Opening", "g3"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird Opening", "f4"),
+    ("A03", "Bird Opening: Dutch Variation", "f4 d5"),
+    ("A04", "Zukertort Opening", "Nf3"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A10", "English Opening", "c4"),
+    ("A13", "English Opening: Agincourt Defense", "c4 e6"),
+    ("A15", "English Opening: Anglo-Indian Defense", "c4 Nf6"),
+    ("A20", "English Opening: King's English Variation", "c4 e5"),
+    ("A30", "English Opening: Symmetrical Variation", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A40", "Englund Gambit", "d4 e5"),
+    ("A43", "Benoni Defense: Old Benoni", "d4 c5"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    ("A46", "Indian Defense: Knights Variation", "d4 Nf6 Nf3"),
+    ("A48", "London System", "d4 Nf6 Nf3 g6 Bf4"),
+    ("A50", "Indian Defense: Normal Variation", "d4 Nf6 c4"),
+    ("A51", "Indian Defense: Budapest Defense", "d4 Nf6 c4 e5"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    ("B00", "Owen Defense", "e4 b6"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    (
+        "B01",
+        "Scandinavian Defense: Mieses-Kotroc Variation",
+        "e4 d5 exd5 Qxd5",
+    ),
+    (
+        "B01",
+        "Scandinavian Defense: Modern Variation",
+        "e4 d5 exd5 Nf6",
+    ),
+    ("B02", "Alekhine Defense", "e4 Nf6"),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6 Nc3 g6"),
+    (
+        "B08",
+        "Pirc Defense: Classical Variation",
+        "e4 d6 d4 Nf6 Nc3 g6 Nf3 Bg7",
+    ),
+    (
+        "B09",
+        "Pirc Defense: Austrian Attack",
+        "e4 d6 d4 Nf6 Nc3 g6 f4 Bg7",
+    ),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    (
+        "B11",
+        "Caro-Kann Defense: Two Knights Attack",
+        "e4 c6 Nc3 d5 Nf3",
+    ),
+    (
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    (
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    ("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+    (
+        "B18",
+        "Caro-Kann Defense: Classical Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5",
+    ),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    (
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    ("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defense", "e4 c5 Nf3"),
+    ("B30", "Sicilian Defense: Old Sicilian", "e4 c5 Nf3 Nc6"),
+    (
+        "B30",
+        "Sicilian Defense: Rossolimo Variation",
+        "e4 c5 Nf3 Nc6 Bb5",
+    ),
+    (
+        "B32",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    (
+        "B33",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6",
+    ),
+    (
+        "B33",
+        "Sicilian Defense: Sveshnikov Variation",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5",
+    ),
+    (
+        "B34",
+        "Sicilian Defense: Accelerated Dragon",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6",
+    ),
+    (
+        "B36",
+        "Sicilian Defense: Accelerated Dragon, Maroczy Bind",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6 c4",
+    ),
+    ("B40", "Sicilian Defense: French Variation", "e4 c5 Nf3 e6"),
+    (
+        "B41",
+        "Sicilian Defense: Kan Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6",
+    ),
+    (
+        "B44",
+        "Sicilian Defense: Taimanov Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6",
+    ),
+    ("B50", "Sicilian Defense: Modern Variations", "e4 c5 Nf3 d6"),
+    (
+        "B51",
+        "Sicilian Defense: Moscow Variation",
+        "e4 c5 Nf3 d6 Bb5+",
+    ),
+    ("B54", "Sicilian Defense: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    (
+        "B56",
+        "Sicilian Defense: Classical Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6",
+    ),
+    (
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    (
+        "B75",
+        "Sicilian Defense: Dragon Variation, Yugoslav Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3",
+    ),
+    (
+        "B80",
+        "Sicilian Defense: Scheveningen Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation, English Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    (
+        "C01",
+        "French Defense: Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    ("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    (
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    (
+        "C10",
+        "French Defense: Paulsen Variation",
+        "e4 e6 d4 d5 Nc3",
+    ),
+    (
+        "C10",
+        "French Defense: Rubinstein Variation",
+        "e4 e6 d4 d5 Nc3 dxe4",
+    ),
+    (
+        "C11",
+        "French Defense: Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    (
+        "C15",
+        "French Defense: Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+    ("C22", "Center Game", "e4 e5 d4 exd4 Qxd4"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    (
+        "C42",
+        "Petrov's Defense: Stafford Gambit",
+        "e4 e5 Nf3 Nf6 Nxe5 Nc6",
+    ),
+    (
+        "C44",
+        "King's Knight Opening: Normal Variation",
+        "e4 e5 Nf3 Nc6",
+    ),
+    ("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Italian Game: Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    (
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 b4",
+    ),
+    (
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6",
+    ),
+    (
+        "C57",
+        "Italian Game: Two Knights Defense, Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    (
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    (
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4",
+    ),
+    (
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    (
+        "C89",
+        "Ruy Lopez: Marshall Attack",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+    (
+        "D00",
+        "Queen's Pawn Game: Accelerated London System",
+        "d4 d5 Bf4",
+    ),
+    ("D02", "Queen's Pawn Game: Zukertort Variation", "d4 d5 Nf3"),
+    (
+        "D02",
+        "Queen's Pawn Game: London System",
+        "d4 d5 Nf3 Nf6 Bf4",
+    ),
+    (
+        "D05",
+        "Queen's Pawn Game: Colle System",
+        "d4 d5 Nf3 Nf6 e3 e6 Bd3",
+    ),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    (
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    (
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    (
+        "D35",
+        "Queen's Gambit Declined: Exchange Variation",
+        "d4 d5 c4 e6 Nc3 Nf6 cxd5",
+    ),
+    ("D43", "Semi-Slav Defense", "d4 d5 c4 e6 Nc3 Nf6 Nf3 c6"),
+    ("D80", "Grünfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    (
+        "D85",
+        "Grünfeld Defense: Exchange Variation",
+        "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5",
+    ),
+    ("E00", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E10", "Indian Defense", "d4 Nf6 c4 e6 Nf3"),
+    ("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    (
+        "E32",
+        "Nimzo-Indian Defense: Classical Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 Qc2",
+    ),
+    (
+        "E40",
+        "Nimzo-Indian Defense: Rubinstein Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 e3",
+    ),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    (
+        "E70",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6",
+    ),
+    (
+        "E76",
+        "King's Indian Defense: Four Pawns Attack",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4",
+    ),
+    (
+        "E80",
+        "King's Indian Defense: Sämisch Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3",
+    ),
+    (
+        "E92",
+        "King's Indian Defense: Classical Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5",
+    ),
+];
+
+//The parts of a FEN that say what the position is, leaving out the move counters
+fn position_key(state: &GameState) -> String {
+    state
+        .fen()
+        .split_whitespace()
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//Every position in the table, with the opening it belongs to
+static POSITIONS: Lazy<HashMap<String, Opening>> = Lazy::new(|| {
+    let mut positions = HashMap::new();
+    for &(eco, name, moves) in OPENINGS {
+        let moves: Vec<String> = moves.split_whitespace().map(str::to_owned).collect();
+        positions.insert(
+            position_key(&GameState::replay(None, &moves)),
+            Opening { eco, name },
+        );
+    }
+    positions
+});
+
+//The opening of the game that started from `start_fen`, or the usual position, and went `moves`,
+//given in SAN. None if it left the book before reaching any opening in the table
+pub fn identify(start_fen: Option<&str>, moves: &[String]) -> Option<Opening> {
+    let mut state = GameState::start(start_fen);
+    let mut opening = None;
+    for input in moves {
+        let m = match state.parse_move(input) {
+            Some(m) => m,
+            None => break,
+        };
+        state.play(&m);
+        if let Some(found) = POSITIONS.get(&position_key(&state)) {
+            opening = Some(*found);
+        }
+    }
+    opening
+}
+
+//The opening of the first game in `pgn`, if it can be read
+pub fn of_pgn(pgn: &str) -> Option<Opening> {
+    let game = crate::pgn::parse(pgn).ok()?;
+    identify(game.start_fen.as_deref(), &game.moves)
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::interactions::{
+        message_component::MessageComponentInteraction,
+        InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+    },
+    prelude::*,
+};
+
+use std::time::Duration;
+
+use crate::library::TimeType;
+use crate::tournaments::Outcome;
+
+//Games members play over the board at club meetings. The bot doesn't see them being played, so
+//one of the players reports the result with !report-result and the other confirms it with the
+//buttons under the report, whose custom ids are of the form otb-result:<id>:<confirm|dispute>.
+//Confirmed results count towards club ratings and head to head records like games played with the
+//bot. Disputed results wait for an officer to settle them with !settle-result, and results officers
+//report count straight away
+
+pub type OtbUuid = u32;
+
+//The buttons under a reported result are taken off by flows.rs after this long. The result can
+//still be settled by an officer afterwards
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtbStatus {
+    //Waiting on the opponent of whoever reported it
+    Reported,
+    Confirmed,
+    //Waiting on an officer
+    Disputed,
+    //Thrown out by an officer
+    Voided,
+}
+
+impl std::fmt::Display for OtbStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            OtbStatus::Reported => write!(fmt, "waiting to be confirmed"),
+            OtbStatus::Confirmed => write!(fmt, "confirmed"),
+            OtbStatus::Disputed => write!(fmt, "disputed"),
+            OtbStatus::Voided => write!(fmt, "voided"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, new)]
+pub struct OtbGame {
+    pub uuid: OtbUuid,
+    //Discord ids of the players
+    pub white: String,
+    pub black: String,
+    pub outcome: Outcome,
+    //Discord id of whoever reported the result
+    pub reporter: String,
+    #[new(value = "OtbStatus::Reported")]
+    pub status: OtbStatus,
+    #[new(value = "chrono::Local::now()")]
+    pub reported: TimeType,
+}
+
+impl OtbGame {
+    pub fn has_player(&self, discord_id: &str) -> bool {
+        self.white == discord_id || self.black == discord_id
+    }
+
+    //Puts discord id `to` wherever the game has `from`, for members who are merged or forgotten
+    pub fn replace_player(&mut self, from: &str, to: &str) {
+        for id in vec![&mut self.white, &mut self.black, &mut self.reporter] {
+            if *id == from {
+                *id = to.to_owned();
+            }
+        }
+    }
+
+    //Whoever has to confirm the result. Results reported by officers who didn't play are confirmed
+    //when they are reported, so it doesn't matter who that would have been
+    pub fn confirmer(&self) -> &str {
+        if self.reporter == self.white {
+            &self.black
+        } else {
+            &self.white
+        }
+    }
+
+    //White vs black and the result, for messages
+    pub fn describe(&self) -> String {
+        format!("<@{}> vs <@{}>: {}", self.white, self.black, self.outcome)
+    }
+}
+
+//Called when someone presses confirm or dispute under a reported result
+pub async fn handle_answer(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let (input, confirm) = match id.split_once(':') {
+        Some((input, answer)) => (input, answer == "confirm"),
+        None => return,
+    };
+
+    let result = {
+        let library_arc = crate::library_for(ctx, component.guild_id).await;
+        let mut library = library_arc.write().await;
+        if library.maintenance {
+            Err(crate::MAINTENANCE_MESSAGE.to_owned())
+        } else {
+            let player = component.user.id.to_string();
+            library
+                .find_otb_game(input)
+                .and_then(|uuid| {
+                    library
+                        .answer_otb_game(uuid, &player, confirm)
+                        .map(|changes| (uuid, changes))
+                })
+                .map(|(uuid, changes)| {
+                    let game = &library.otb_games[&uuid];
+                    if confirm {
+                        let mut text = format!(
+                            "{}, confirmed by <@{}>",
+                            game.describe(),
+                            component.user.id
+                        );
+                        if let Some([white, black]) = changes {
+                            text.push_str(&format!("\nClub ratings: {}, {}", white, black));
+                        }
+                        text.push_str(&format!(
+                            "\n{}",
+                            library.head_to_head_text(&game.white, &game.black)
+                        ));
+                        text
+                    } else {
+                        format!(
+                            "<@{}> disputed {}. An officer can settle it with !settle-result {} <1-0|0-1|½-½|void>",
+                            component.user.id,
+                            game.describe(),
+                            input
+                        )
+                    }
+                })
+                .map_err(|why| format!("Error: {}", why))
+        }
+    };
+    crate::save_after_change(ctx, component.guild_id).await;
+
+    let response = match result {
+        Ok(text) => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(text)
+                                .allowed_mentions(|a| a.empty_parse())
+                                .components(|c| c)
+                        })
+                })
+                .await
+        }
+        Err(text) => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content(text)
+                                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                        })
+                })
+                .await
+        }
+    };
+    if let Err(err) = response {
+        println!("Failed to answer a reported result: {:?}", err);
+    }
+}
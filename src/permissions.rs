@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    framework::standard::{macros::check, Args, CommandOptions, Reason},
+    model::{
+        channel::Message,
+        id::{GuildId, UserId},
+        permissions::Permissions,
+    },
+    prelude::*,
+};
+
+use crate::library;
+
+//Who can do what in a guild. Every member can browse and borrow books, officers run the library
+//and admins also change the bot's settings. Higher tiers can do everything lower ones can
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Member,
+    Officer,
+    Admin,
+}
+
+impl Tier {
+    pub fn parse(input: &str) -> Option<Tier> {
+        match input.to_ascii_lowercase().as_str() {
+            "member" => Some(Tier::Member),
+            "officer" => Some(Tier::Officer),
+            "admin" => Some(Tier::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Tier {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Tier::Member => write!(fmt, "member"),
+            Tier::Officer => write!(fmt, "officer"),
+            Tier::Admin => write!(fmt, "admin"),
+        }
+    }
+}
+
+//The highest tier the user has in the guild. Tiers come from what was granted to the user or one
+//of their roles with !config grant. On top of that the guild's owner and anyone with the
+//Administrator permission are always admins, and while no role has been made officer the role
+//named DEFAULT_OFFICER_ROLE is
+pub async fn tier_of(ctx: &Context, guild_id: Option<GuildId>, user_id: UserId) -> Tier {
+    let guild_id = match guild_id {
+        Some(id) => id,
+        None => return Tier::Member,
+    };
+    let member = match guild_id.member(ctx, user_id).await {
+        Ok(member) => member,
+        Err(_) => return Tier::Member,
+    };
+    let roles = member.roles(ctx).await.unwrap_or_default();
+
+    let owner = guild_id
+        .to_guild_cached(ctx)
+        .await
+        .map_or(false, |guild| guild.owner_id == user_id);
+    if owner
+        || roles
+            .iter()
+            .any(|role| role.permissions.contains(Permissions::ADMINISTRATOR))
+    {
+        return Tier::Admin;
+    }
+
+    let library_arc = crate::library_for(ctx, Some(guild_id)).await;
+    let library = library_arc.read().await;
+    let config = &library.config;
+
+    let mut tier = config
+        .member_tiers
+        .get(&user_id.0)
+        .copied()
+        .unwrap_or(Tier::Member);
+    for role in &roles {
+        if let Some(granted) = config.role_tiers.get(&role.id.0) {
+            tier = tier.max(*granted);
+        }
+    }
+    let officer_role_set = config
+        .role_tiers
+        .values()
+        .any(|tier| *tier >= Tier::Officer);
+    if !officer_role_set
+        && roles
+            .iter()
+            .any(|role| role.name == library::DEFAULT_OFFICER_ROLE)
+    {
+        tier = tier.max(Tier::Officer);
+    }
+    tier
+}
+
+pub async fn is_officer(ctx: &Context, guild_id: Option<GuildId>, user_id: UserId) -> bool {
+    tier_of(ctx, guild_id, user_id).await >= Tier::Officer
+}
+
+async fn require(ctx: &Context, msg: &Message, needed: Tier) -> Result<(), Reason> {
+    if tier_of(ctx, msg.guild_id, msg.author.id).await >= needed {
+        Ok(())
+    } else {
+        Err(Reason::User(format!(
+            "Only library {}s can use this command",
+            needed
+        )))
+    }
+}
+
+#[check]
+#[name = "Officer"]
+pub async fn officer_check(
+    ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    _options: &CommandOptions,
+) -> Result<(), Reason> {
+    require(ctx, msg, Tier::Officer).await
+}
+
+#[check]
+#[name = "Admin"]
+pub async fn admin_check(
+    ctx: &Context,
+    msg: &Message,
+    _args: &mut Args,
+    _options: &CommandOptions,
+) -> Result<(), Reason> {
+    require(ctx, msg, Tier::Admin).await
+}
@@ -0,0 +1,156 @@
+use indexmap::IndexMap;
+
+use crate::rules::GameState;
+
+//Reads games in Portable Game Notation, like the ones club members record over the board or
+//export from other sites. Only the main line of the first game is kept; comments, variations and
+//annotations are skipped
+
+pub struct ImportedGame {
+    //The tag pairs, like White and Event, in the order they were given
+    pub tags: IndexMap<String, String>,
+    //From the FEN tag, for games that didn't start from the usual position
+    pub start_fen: Option<String>,
+    //The moves in SAN, as the bot writes them
+    pub moves: Vec<String>,
+}
+
+impl ImportedGame {
+    pub fn tag(&self, name: &str) -> &str {
+        self.tags.get(name).map_or("?", |value| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnError {
+    //The FEN tag doesn't describe a legal position
+    BadFen(String),
+    //Holds the move number and the move as written
+    IllegalMove(String, String),
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            PgnError::BadFen(fen) => write!(fmt, "\"{}\" isn't the FEN of a legal position", fen),
+            PgnError::IllegalMove(number, input) => {
+                write!(fmt, "Move {} \"{}\" can't be played", number, input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+//Reads `[Name "Value"]`, with `line` starting after the opening bracket
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let line = line.trim().trim_end_matches(']');
+    let (name, value) = line.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((
+        name.to_owned(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+//The move text without comments, variations and numeric annotations
+fn strip_movetext(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars().peekable();
+    let mut variation_depth: usize = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+                stripped.push(' ');
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                stripped.push(' ');
+            }
+            '(' => variation_depth += 1,
+            ')' => {
+                variation_depth = variation_depth.saturating_sub(1);
+                stripped.push(' ');
+            }
+            _ if variation_depth > 0 => {}
+            '$' => {
+                while chars.peek().map_or(false, |c| c.is_ascii_digit()) {
+                    chars.next();
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+pub fn parse(text: &str) -> Result<ImportedGame, PgnError> {
+    let mut tags = IndexMap::new();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        match trimmed.strip_prefix('[') {
+            //Tags after the move text belong to the next game
+            Some(tag) if movetext.trim().is_empty() => {
+                if let Some((name, value)) = parse_tag(tag) {
+                    tags.insert(name, value);
+                }
+            }
+            Some(_) => break,
+            None => {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+    }
+
+    let start_fen = tags.get("FEN").cloned();
+    let mut state = match &start_fen {
+        Some(fen) => GameState::from_fen(fen).ok_or_else(|| PgnError::BadFen(fen.clone()))?,
+        None => GameState::default(),
+    };
+
+    let mut moves = Vec::new();
+    for token in strip_movetext(&movetext).split_whitespace() {
+        if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            break;
+        }
+        //Move numbers can be written apart from the move, like "12. e4", or next to it, like
+        //"12.e4" or "12...Nf6"
+        let input = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        //Annotations like ! and ?! aren't part of the move
+        let input = input.trim_end_matches(|c| c == '!' || c == '?');
+        if input.is_empty() {
+            continue;
+        }
+        let m = state.parse_move(input).ok_or_else(|| {
+            let number = format!(
+                "{}{}",
+                state.move_number(),
+                if state.turn() == shakmaty::Color::White {
+                    "."
+                } else {
+                    "..."
+                }
+            );
+            PgnError::IllegalMove(number, input.to_owned())
+        })?;
+        moves.push(state.play(&m));
+    }
+
+    Ok(ImportedGame {
+        tags,
+        start_fen,
+        moves,
+    })
+}
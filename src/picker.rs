@@ -0,0 +1,266 @@
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serenity::{
+    builder::CreateComponents,
+    model::{
+        channel::Message,
+        interactions::{
+            message_component::MessageComponentInteraction,
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::library::{self, BookUuid, CheckoutUuid, CopyUuid};
+use crate::{flows, Invocation};
+
+//When what a member typed matches several books, they are shown a select menu of the candidates
+//and the command carries on with whichever one they pick. Menus have custom ids of the form
+//pick:<id>, where the id points at the command waiting on the answer
+
+//Menus that haven't been answered by then are forgotten and taken off their message by flows.rs
+const PICK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const EXPIRED: &str = "This menu has expired. Please run the command again";
+//Discord's limits for select menus
+const MAX_OPTIONS: usize = 25;
+const MAX_LABEL_LEN: usize = 100;
+
+//What to carry on with once the member has picked. Both keep what was settled before the menu
+//was shown and the inputs that haven't been looked at yet
+pub enum PendingAction {
+    Checkout {
+        books: Vec<(BookUuid, Option<CopyUuid>)>,
+        remaining: Vec<String>,
+    },
+    Return {
+        checkouts: Vec<CheckoutUuid>,
+        remaining: Vec<String>,
+    },
+}
+
+struct Pending {
+    invocation: Invocation,
+    action: PendingAction,
+    created: Instant,
+}
+
+static PENDING: Lazy<Mutex<HashMap<u32, Pending>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(new)]
+pub struct PickOption {
+    pub label: String,
+    pub description: String,
+    //The uuid of whatever the option stands for
+    pub value: u32,
+}
+
+pub struct Picker {
+    id: u32,
+    options: Vec<PickOption>,
+}
+
+//What a command answers with. Usually just text, but it can come with a menu to pick from
+pub struct Reply {
+    pub content: String,
+    pub picker: Option<Picker>,
+}
+
+impl From<String> for Reply {
+    fn from(content: String) -> Reply {
+        Reply {
+            content,
+            picker: None,
+        }
+    }
+}
+
+fn truncate(text: &str) -> String {
+    text.chars().take(MAX_LABEL_LEN).collect()
+}
+
+impl Reply {
+    //Has the menu taken off `message` if it isn't answered in time. Called once the reply was sent
+    pub fn track(&self, message: &Message) {
+        match self.picker {
+            Some(_) => flows::expire_components(message, PICK_TIMEOUT),
+            None => flows::finished(message.id),
+        }
+    }
+
+    //Adds the menu, if there is one. Leaving the components empty removes the menu from a message
+    //that had one
+    pub fn components<'a>(&self, components: &'a mut CreateComponents) -> &'a mut CreateComponents {
+        let picker = match &self.picker {
+            Some(picker) => picker,
+            None => return components,
+        };
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(format!("pick:{:x}", picker.id))
+                    .placeholder("Pick one")
+                    .options(|options| {
+                        for option in &picker.options {
+                            options.create_option(|o| {
+                                o.label(truncate(&option.label))
+                                    .description(truncate(&option.description))
+                                    .value(option.value.to_string())
+                            });
+                        }
+                        options
+                    })
+            })
+        })
+    }
+}
+
+//Puts `action` on hold until whoever used the command picks one of `options`
+pub fn ask(
+    invocation: &Invocation,
+    prompt: String,
+    mut options: Vec<PickOption>,
+    action: PendingAction,
+) -> Reply {
+    options.truncate(MAX_OPTIONS);
+    let id: u32 = rand::thread_rng().gen();
+
+    let mut pending = PENDING.lock().unwrap();
+    pending.insert(
+        id,
+        Pending {
+            invocation: invocation.clone(),
+            action,
+            created: Instant::now(),
+        },
+    );
+
+    Reply {
+        content: prompt,
+        picker: Some(Picker { id, options }),
+    }
+}
+
+//Drops the commands whose menus were never answered
+pub fn forget_expired() {
+    PENDING
+        .lock()
+        .unwrap()
+        .retain(|_, pending| pending.created.elapsed() < PICK_TIMEOUT);
+}
+
+async fn tell_picker(ctx: &Context, component: &MessageComponentInteraction, text: &str) {
+    let _ = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+}
+
+//Called when someone picks from one of the menus
+pub async fn handle_pick(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let pending = {
+        let mut pending = PENDING.lock().unwrap();
+        let waiting = u32::from_str_radix(id, 16).ok().and_then(|id| {
+            pending
+                .get(&id)
+                .map(|waiting| (id, waiting.invocation.author.id))
+        });
+        match waiting {
+            None => Err(EXPIRED),
+            Some((_, author)) if author != component.user.id => {
+                Err("Only the member who used the command can pick from this menu")
+            }
+            Some((id, _)) => Ok(pending.remove(&id).unwrap()),
+        }
+    };
+    let pending = match pending {
+        Ok(pending) if pending.created.elapsed() < PICK_TIMEOUT => pending,
+        Ok(_) => {
+            tell_picker(ctx, component, EXPIRED).await;
+            return;
+        }
+        Err(text) => {
+            tell_picker(ctx, component, text).await;
+            return;
+        }
+    };
+    let picked = match component
+        .data
+        .values
+        .first()
+        .and_then(|value| value.parse::<u32>().ok())
+    {
+        Some(picked) => picked,
+        None => return,
+    };
+
+    let deferred = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::DeferredUpdateMessage)
+        })
+        .await;
+    if let Err(err) = deferred {
+        println!("Failed to respond to menu pick: {:?}", err);
+        return;
+    }
+
+    let invocation = pending.invocation;
+    let maintenance = crate::library_for(ctx, invocation.guild)
+        .await
+        .read()
+        .await
+        .maintenance;
+    let result = if maintenance {
+        Ok(Reply::from(crate::MAINTENANCE_MESSAGE.to_owned()))
+    } else {
+        match pending.action {
+            PendingAction::Checkout {
+                mut books,
+                remaining,
+            } => {
+                books.push((picked, None));
+                crate::checkout_books(ctx, &invocation, books, remaining).await
+            }
+            PendingAction::Return {
+                mut checkouts,
+                remaining,
+            } => {
+                checkouts.push(picked);
+                crate::return_books(ctx, &invocation, checkouts, remaining).await
+            }
+        }
+    };
+    crate::save_after_change(ctx, invocation.guild).await;
+
+    let reply = match result {
+        Ok(reply) => reply,
+        Err(why) => {
+            println!("Continuing after a menu pick failed: {:?}", why);
+            Reply::from(format!("Error: {}", why))
+        }
+    };
+    //Replace the menu with the outcome, or the next menu if something else was ambiguous too
+    let edited = component
+        .edit_original_interaction_response(&ctx.http, |r| {
+            r.content(&reply.content)
+                .components(|c| reply.components(c))
+        })
+        .await;
+    match edited {
+        Ok(message) => reply.track(&message),
+        Err(err) => println!("Failed to update menu message: {:?}", err),
+    }
+}
+
+//The label and description a book is shown with in a menu
+pub fn book_option(book: &library::Book) -> PickOption {
+    PickOption::new(book.name.clone(), format!("by {}", book.author), book.uuid)
+}
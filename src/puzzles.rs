@@ -0,0 +1,540 @@
+use chrono::{Datelike, NaiveDate};
+use once_cell::sync::Lazy;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::rules::GameState;
+
+//Puzzles come from the Lichess puzzle database, a CSV with one puzzle per line. They are imported
+//with !admin import-puzzles into a store that is shared by every guild and saved next to the
+//libraries, so that puzzles can be picked by rating and theme without going online. The whole
+//database has millions of puzzles, so only the popular ones are kept, up to PUZZLE_LIMIT of them
+
+const DEFAULT_PATH: &str = "puzzles.bin";
+const DEFAULT_LIMIT: usize = 200_000;
+//Lichess players vote on puzzles. Popularity goes from -100 to 100
+const DEFAULT_MIN_POPULARITY: i32 = 50;
+//Puzzles played less than this have ratings that can't be trusted yet
+const DEFAULT_MIN_PLAYS: u32 = 100;
+
+//Members start out with this puzzle rating
+const START_RATING: u32 = 1500;
+//How much a rating moves after each puzzle. Ratings move faster for the first few puzzles, to
+//find where the member stands
+const K_FACTOR: f64 = 20.0;
+const PROVISIONAL_K_FACTOR: f64 = 40.0;
+const PROVISIONAL_PUZZLES: u32 = 20;
+const MIN_RATING: u32 = 100;
+//Puzzles that weren't finished are forgotten after this long, without counting as failed
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+//The daily puzzle is picked from this rating range, so that most members have a fair chance at it
+const DAILY_MIN_RATING: u32 = 1200;
+const DAILY_MAX_RATING: u32 = 2000;
+//Moves the daily puzzle far through the store from one day to the next. Any large odd number works
+const DAILY_STRIDE: u64 = 2_654_435_761;
+//Members are DMed when their daily puzzle streak reaches one of these many days
+const STREAK_MILESTONES: [u32; 5] = [7, 30, 100, 200, 365];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Puzzle {
+    //The id on Lichess, so that puzzles can be looked up at lichess.org/training/<id>
+    pub id: String,
+    //The position before the first move
+    pub fen: String,
+    //In UCI notation. The first move is played by the opponent to set up the puzzle, then the
+    //solution and the opponent's replies alternate
+    pub moves: Vec<String>,
+    pub rating: u32,
+    //Motifs like fork, pin or mateIn2, as Lichess names them
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    pub fn has_theme(&self, theme: &str) -> bool {
+        self.themes.iter().any(|t| t.eq_ignore_ascii_case(theme))
+    }
+}
+
+//The puzzles, sorted by rating
+#[derive(Serialize, Deserialize, Default)]
+pub struct PuzzleStore {
+    puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleStore {
+    pub fn len(&self) -> usize {
+        self.puzzles.len()
+    }
+
+    //A random puzzle rated from `min` to `max`, with `theme` if given
+    pub fn pick(&self, min: u32, max: u32, theme: Option<&str>) -> Option<&Puzzle> {
+        let start = self.puzzles.partition_point(|p| p.rating < min);
+        let end = self.puzzles.partition_point(|p| p.rating <= max);
+        self.puzzles
+            .get(start..end)?
+            .iter()
+            .filter(|p| theme.map_or(true, |theme| p.has_theme(theme)))
+            .choose(&mut rand::thread_rng())
+    }
+
+    //How many puzzles have each theme, most common first
+    pub fn themes(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for theme in self.puzzles.iter().flat_map(|p| &p.themes) {
+            *counts.entry(theme).or_default() += 1;
+        }
+        let mut themes: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(theme, count)| (theme.to_owned(), count))
+            .collect();
+        themes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        themes
+    }
+
+    //The daily puzzle of `day`. Every member in every guild gets the same one
+    pub fn daily(&self, day: NaiveDate) -> Option<&Puzzle> {
+        let start = self
+            .puzzles
+            .partition_point(|p| p.rating < DAILY_MIN_RATING);
+        let end = self
+            .puzzles
+            .partition_point(|p| p.rating <= DAILY_MAX_RATING);
+        let (start, end) = if start < end {
+            (start, end)
+        } else {
+            (0, self.puzzles.len())
+        };
+        if start == end {
+            return None;
+        }
+        let offset =
+            (day.num_days_from_ce() as u64).wrapping_mul(DAILY_STRIDE) % (end - start) as u64;
+        self.puzzles.get(start + offset as usize)
+    }
+}
+
+fn path() -> String {
+    env::var("PUZZLES_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_owned())
+}
+
+fn load() -> PuzzleStore {
+    let path = path();
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return PuzzleStore::default(),
+        Err(err) => {
+            println!("Failed to read puzzles from {}: {}", path, err);
+            return PuzzleStore::default();
+        }
+    };
+    match bincode::deserialize(&data) {
+        Ok(store) => store,
+        Err(err) => {
+            println!("{} doesn't hold puzzles: {}", path, err);
+            PuzzleStore::default()
+        }
+    }
+}
+
+static STORE: Lazy<RwLock<Arc<PuzzleStore>>> = Lazy::new(|| RwLock::new(Arc::new(load())));
+
+//The puzzles imported so far. Read from disk the first time puzzles are needed
+pub fn store() -> Arc<PuzzleStore> {
+    STORE.read().unwrap().clone()
+}
+
+fn env_or<T: std::str::FromStr>(var: &str, default: T) -> T {
+    match env::var(var).map(|value| value.parse::<T>()) {
+        Ok(Ok(value)) => value,
+        Ok(Err(_)) => {
+            println!("{} isn't a valid number. Using the default", var);
+            default
+        }
+        Err(_) => default,
+    }
+}
+
+pub struct ImportReport {
+    pub imported: usize,
+    //Lines that weren't puzzles or whose moves couldn't be played
+    pub invalid: usize,
+    //Puzzles left out for being unpopular or not played enough
+    pub filtered: usize,
+    //How many puzzles have each theme, most common first
+    pub themes: Vec<(String, usize)>,
+}
+
+//Reads a line of the CSV, which has the columns PuzzleId, FEN, Moves, Rating, RatingDeviation,
+//Popularity, NbPlays, Themes, GameUrl and OpeningTags
+fn parse_line(line: &str) -> Option<(Puzzle, i32, u32)> {
+    let columns: Vec<&str> = line.trim().split(',').collect();
+    if columns.len() < 8 {
+        return None;
+    }
+    let puzzle = Puzzle {
+        id: columns[0].to_owned(),
+        fen: columns[1].to_owned(),
+        moves: columns[2].split_whitespace().map(str::to_owned).collect(),
+        rating: columns[3].parse().ok()?,
+        themes: columns[7].split_whitespace().map(str::to_owned).collect(),
+    };
+    //Checked here, so that a broken line can't leave a puzzle that can't be solved
+    let mut state = GameState::from_fen(&puzzle.fen)?;
+    if puzzle.moves.len() < 2 {
+        return None;
+    }
+    for input in &puzzle.moves {
+        let m = state.parse_move(input)?;
+        state.play(&m);
+    }
+    Some((puzzle, columns[5].parse().ok()?, columns[6].parse().ok()?))
+}
+
+//Replaces the store with the puzzles in `csv`, and saves it. Blocks, so it should be run with
+//spawn_blocking
+pub fn import(csv: impl BufRead) -> io::Result<ImportReport> {
+    let limit = env_or("PUZZLE_LIMIT", DEFAULT_LIMIT);
+    let min_popularity = env_or("PUZZLE_MIN_POPULARITY", DEFAULT_MIN_POPULARITY);
+    let min_plays = env_or("PUZZLE_MIN_PLAYS", DEFAULT_MIN_PLAYS);
+
+    let mut puzzles = Vec::new();
+    let mut invalid = 0;
+    let mut filtered = 0;
+    for line in csv.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with("PuzzleId") {
+            continue;
+        }
+        match parse_line(&line) {
+            Some((puzzle, popularity, plays)) => {
+                if popularity < min_popularity || plays < min_plays {
+                    filtered += 1;
+                } else if puzzles.len() < limit {
+                    puzzles.push(puzzle);
+                } else {
+                    filtered += 1;
+                }
+            }
+            None => invalid += 1,
+        }
+    }
+    puzzles.sort_by_key(|p| p.rating);
+
+    let store = PuzzleStore { puzzles };
+    let themes = store.themes();
+    let data =
+        bincode::serialize(&store).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    //Written next to the old store first, so that a failed write doesn't lose it
+    let path = path();
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, data)?;
+    std::fs::rename(&temp, &path)?;
+
+    let imported = store.len();
+    *STORE.write().unwrap() = Arc::new(store);
+    Ok(ImportReport {
+        imported,
+        invalid,
+        filtered,
+        themes,
+    })
+}
+
+//A member's puzzle rating. It goes up when they solve puzzles and down when they fail them, by
+//more the further the puzzle's rating was from theirs, like Elo
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PuzzleRating {
+    pub rating: u32,
+    pub solved: u32,
+    pub failed: u32,
+}
+
+impl Default for PuzzleRating {
+    fn default() -> Self {
+        PuzzleRating {
+            rating: START_RATING,
+            solved: 0,
+            failed: 0,
+        }
+    }
+}
+
+impl PuzzleRating {
+    //Ratings are provisional until enough puzzles were tried to trust them
+    pub fn is_provisional(&self) -> bool {
+        self.solved + self.failed < PROVISIONAL_PUZZLES
+    }
+
+    //Counts a puzzle rated `puzzle` as solved or failed, and returns how much the rating changed
+    pub fn record(&mut self, puzzle: u32, solved: bool) -> i32 {
+        let k = if self.is_provisional() {
+            PROVISIONAL_K_FACTOR
+        } else {
+            K_FACTOR
+        };
+        let expected = 1.0 / (1.0 + 10f64.powf((puzzle as f64 - self.rating as f64) / 400.0));
+        let score = if solved { 1.0 } else { 0.0 };
+        let change = (k * (score - expected)).round() as i32;
+        let old = self.rating;
+        self.rating = (self.rating as i32 + change).max(MIN_RATING as i32) as u32;
+        if solved {
+            self.solved += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.rating as i32 - old as i32
+    }
+
+    //Folds in the puzzle rating of a duplicate account. Results add up, and the rating is taken
+    //from whichever of the two tried more puzzles, since it says more about the member
+    pub fn merge(&mut self, other: PuzzleRating) {
+        if other.solved + other.failed > self.solved + self.failed {
+            self.rating = other.rating;
+        }
+        self.solved += other.solved;
+        self.failed += other.failed;
+    }
+}
+
+//How many days in a row a member has solved the daily puzzle. Members get one try a day, and
+//failing or giving up on it ends the streak
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PuzzleStreak {
+    //Days in a row the daily puzzle was solved, up to last_day. 0 if it was failed that day
+    pub current: u32,
+    pub best: u32,
+    //The last day the member tried the daily puzzle
+    pub last_day: Option<NaiveDate>,
+}
+
+impl PuzzleStreak {
+    pub fn tried_on(&self, day: NaiveDate) -> bool {
+        self.last_day == Some(day)
+    }
+
+    //The streak as of `today`. It is still alive until the end of the day after the last solve
+    pub fn current(&self, today: NaiveDate) -> u32 {
+        match self.last_day {
+            Some(last) if last >= today - chrono::Duration::days(1) => self.current,
+            _ => 0,
+        }
+    }
+
+    //Counts the daily puzzle of `day` as solved or failed. Returns whether the streak reached a
+    //milestone
+    pub fn record(&mut self, day: NaiveDate, solved: bool) -> bool {
+        if self.tried_on(day) {
+            return false;
+        }
+        self.current = match (solved, self.last_day) {
+            (false, _) => 0,
+            (true, Some(last)) if last == day - chrono::Duration::days(1) => self.current + 1,
+            (true, _) => 1,
+        };
+        self.best = self.best.max(self.current);
+        self.last_day = Some(day);
+        solved && STREAK_MILESTONES.contains(&self.current)
+    }
+
+    //Folds in the streak of a duplicate account. The current streak is whichever of the two was
+    //played last, as the days can't be told apart to join them
+    pub fn merge(&mut self, other: PuzzleStreak) {
+        self.best = self.best.max(other.best);
+        if other.last_day > self.last_day {
+            self.current = other.current;
+            self.last_day = other.last_day;
+        }
+    }
+}
+
+//A puzzle a member is solving. Kept in memory only, since a restart in the middle of one is no
+//great loss
+struct Attempt {
+    puzzle: Puzzle,
+    //The day it is the daily puzzle of, if it is one
+    daily: Option<NaiveDate>,
+    //How many of the puzzle's moves have been played, including the opponent's first one
+    played: usize,
+    started: Instant,
+}
+
+impl Attempt {
+    fn state(&self) -> GameState {
+        let mut state = GameState::from_fen(&self.puzzle.fen).unwrap_or_default();
+        for input in &self.puzzle.moves[..self.played] {
+            if let Some(m) = state.parse_move(input) {
+                state.play(&m);
+            }
+        }
+        state
+    }
+}
+
+//Attempts by guild (0 for direct messages) and member
+static ATTEMPTS: Lazy<Mutex<HashMap<(u64, u64), Attempt>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+//Starts `puzzle` for `member`, with the opponent's first move played, as the daily puzzle of
+//`daily` if given. Returns the puzzle they were solving before, which they gave up on, with the day
+//it was the daily puzzle of, and the position to solve
+pub fn start(
+    guild: u64,
+    member: u64,
+    puzzle: Puzzle,
+    daily: Option<NaiveDate>,
+) -> (Option<(Puzzle, Option<NaiveDate>)>, GameState) {
+    let attempt = Attempt {
+        puzzle,
+        daily,
+        played: 1,
+        started: Instant::now(),
+    };
+    let state = attempt.state();
+    let previous = ATTEMPTS
+        .lock()
+        .unwrap()
+        .insert((guild, member), attempt)
+        .map(|attempt| (attempt.puzzle, attempt.daily));
+    (previous, state)
+}
+
+pub enum Step {
+    //The move was right and the opponent answered with the move in SAN
+    Continue(String),
+    Solved,
+    //Holds the rest of the solution in SAN, starting with the move that should have been played
+    Wrong(Vec<String>),
+}
+
+//Checks `input` as the next move of the puzzle `member` is solving. Returns the puzzle, the day it
+//is the daily puzzle of if it is one, the step and the position after it, or None when they aren't
+//solving one
+pub fn solve(
+    guild: u64,
+    member: u64,
+    input: &str,
+) -> Option<(Puzzle, Option<NaiveDate>, Step, GameState)> {
+    let mut attempts = ATTEMPTS.lock().unwrap();
+    let attempt = attempts.get_mut(&(guild, member))?;
+    let mut state = attempt.state();
+    let expected = state.parse_move(&attempt.puzzle.moves[attempt.played])?;
+    let played = state.parse_move(input);
+
+    let mut check = GameState::from_fen(&state.fen())?;
+    let is_mate = played.as_ref().map_or(false, |m| {
+        check.play(m);
+        check.position().is_checkmate()
+    });
+    if played.as_ref() != Some(&expected) && !is_mate {
+        let attempt = attempts.remove(&(guild, member))?;
+        let mut solution = Vec::new();
+        for input in &attempt.puzzle.moves[attempt.played..] {
+            match state.parse_move(input) {
+                Some(m) => solution.push(state.play(&m)),
+                None => break,
+            }
+        }
+        let state = attempt.state();
+        return Some((attempt.puzzle, attempt.daily, Step::Wrong(solution), state));
+    }
+
+    //Any mate finishes the puzzle, even if it isn't the one Lichess had in mind
+    state.play(&played.unwrap_or(expected));
+    attempt.played += 1;
+    match attempt.puzzle.moves.get(attempt.played) {
+        Some(reply) if !is_mate => {
+            let reply = state.parse_move(reply)?;
+            let san = state.play(&reply);
+            attempt.played += 1;
+            Some((
+                attempt.puzzle.clone(),
+                attempt.daily,
+                Step::Continue(san),
+                state,
+            ))
+        }
+        _ => {
+            let attempt = attempts.remove(&(guild, member))?;
+            Some((attempt.puzzle, attempt.daily, Step::Solved, state))
+        }
+    }
+}
+
+//A tactics training session, in which a member is given puzzles of a theme and rating range one
+//after another, until they stop or leave one unfinished. Kept in memory only, like attempts
+#[derive(Debug, Clone)]
+pub struct Training {
+    pub theme: Option<String>,
+    pub min: u32,
+    pub max: u32,
+    pub solved: u32,
+    pub failed: u32,
+}
+
+impl Training {
+    pub fn new(theme: Option<String>, min: u32, max: u32) -> Training {
+        Training {
+            theme,
+            min,
+            max,
+            solved: 0,
+            failed: 0,
+        }
+    }
+
+    //The next puzzle of the session
+    pub fn pick<'a>(&self, store: &'a PuzzleStore) -> Option<&'a Puzzle> {
+        store.pick(self.min, self.max, self.theme.as_deref())
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!("puzzles rated {}-{}", self.min, self.max);
+        if let Some(theme) = &self.theme {
+            text.push_str(&format!(" with the theme {}", theme));
+        }
+        text
+    }
+}
+
+//Training sessions by guild (0 for direct messages) and member
+static TRAINING: Lazy<Mutex<HashMap<(u64, u64), Training>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+//Starts a training session for `member`, replacing the one they had
+pub fn train(guild: u64, member: u64, training: Training) {
+    TRAINING.lock().unwrap().insert((guild, member), training);
+}
+
+//Ends `member`'s training session, returning it if they had one
+pub fn stop_training(guild: u64, member: u64) -> Option<Training> {
+    TRAINING.lock().unwrap().remove(&(guild, member))
+}
+
+//Counts a puzzle of `member`'s training session as solved or failed. Returns the session, or None
+//when they aren't training
+pub fn count_training(guild: u64, member: u64, solved: bool) -> Option<Training> {
+    let mut sessions = TRAINING.lock().unwrap();
+    let training = sessions.get_mut(&(guild, member))?;
+    if solved {
+        training.solved += 1;
+    } else {
+        training.failed += 1;
+    }
+    Some(training.clone())
+}
+
+//Drops the attempts nobody finished, and the training sessions they were part of
+pub fn forget_expired() {
+    let mut attempts = ATTEMPTS.lock().unwrap();
+    attempts.retain(|_, attempt| attempt.started.elapsed() < ATTEMPT_TIMEOUT);
+    TRAINING
+        .lock()
+        .unwrap()
+        .retain(|member, _| attempts.contains_key(member));
+}
@@ -0,0 +1,274 @@
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgb, RgbImage};
+
+use crate::label::glyph;
+use crate::library::{Database, TimeType};
+
+//Rating graphs show a member's club rating after each of their rated games as a line chart, with
+//the dates along the bottom. Members who linked Lichess can add a line for each Lichess time
+//control they play, from the snapshots the leaderboard takes, to compare the two. The chart is
+//drawn pixel by pixel like the board images, with the same 5x7 font
+
+const WIDTH: u32 = 720;
+const HEIGHT: u32 = 360;
+//Space around the plot for the legend above it, the ratings left of it and the dates below it
+const LEFT: u32 = 64;
+const RIGHT: u32 = 24;
+const TOP: u32 = 40;
+const BOTTOM: u32 = 32;
+const TEXT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+//Ratings are marked every STEPS[i] points, using the smallest step that needs no more than
+//MAX_TICKS marks
+const STEPS: [f64; 6] = [25.0, 50.0, 100.0, 200.0, 500.0, 1000.0];
+const MAX_TICKS: f64 = 6.0;
+
+const BACKGROUND: Rgb<u8> = Rgb([49, 46, 43]);
+const GRID: Rgb<u8> = Rgb([75, 72, 68]);
+const TEXT: Rgb<u8> = Rgb([200, 200, 200]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line {
+    Club,
+    Blitz,
+    Rapid,
+    Classical,
+}
+
+impl Line {
+    fn name(self) -> &'static str {
+        match self {
+            Line::Club => "CLUB",
+            Line::Blitz => "BLITZ",
+            Line::Rapid => "RAPID",
+            Line::Classical => "CLASSICAL",
+        }
+    }
+
+    fn colour(self) -> Rgb<u8> {
+        match self {
+            Line::Club => Rgb([129, 182, 76]),
+            Line::Blitz => Rgb([230, 145, 56]),
+            Line::Rapid => Rgb([86, 156, 214]),
+            Line::Classical => Rgb([197, 120, 200]),
+        }
+    }
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let name = match self {
+            Line::Club => "Club rating",
+            Line::Blitz => "Lichess blitz",
+            Line::Rapid => "Lichess rapid",
+            Line::Classical => "Lichess classical",
+        };
+        write!(fmt, "{}", name)
+    }
+}
+
+//The ratings of one line of the chart, oldest first
+pub type Series = (Line, Vec<(TimeType, f64)>);
+
+//`member`'s club rating history, and their Lichess rating history for every time control they
+//have played when `lichess` is set. Lines with no ratings are left out
+pub fn history(library: &Database, member: &str, lichess: bool) -> Vec<Series> {
+    let mut series = Vec::new();
+    if let Some(snapshots) = library.rating_history.get(member) {
+        series.push((
+            Line::Club,
+            snapshots
+                .iter()
+                .map(|snapshot| (snapshot.taken, snapshot.rating))
+                .collect(),
+        ));
+    }
+    if lichess {
+        let snapshots = library
+            .lichess_ratings
+            .get(member)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for line in [Line::Blitz, Line::Rapid, Line::Classical].iter() {
+            let points = snapshots
+                .iter()
+                .filter_map(|snapshot| {
+                    let rating = match line {
+                        Line::Blitz => snapshot.blitz,
+                        Line::Rapid => snapshot.rapid,
+                        _ => snapshot.classical,
+                    }?;
+                    Some((snapshot.taken, rating.rating as f64))
+                })
+                .collect();
+            series.push((*line, points));
+        }
+    }
+    series.retain(|(_, points): &Series| !points.is_empty());
+    series
+}
+
+fn fill_rect(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, colour: Rgb<u8>) {
+    for dy in 0..height {
+        for dx in 0..width {
+            image.put_pixel(x + dx, y + dy, colour);
+        }
+    }
+}
+
+fn text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * (GLYPH_WIDTH + 1) * TEXT_SCALE
+}
+
+//Writes `text` with its top left corner at (x, y). Characters without a glyph are left as gaps
+fn draw_text(image: &mut RgbImage, text: &str, x: u32, y: u32, colour: Rgb<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let rows = match glyph(c) {
+            Some(rows) => rows,
+            None => continue,
+        };
+        let glyph_x = x + i as u32 * (GLYPH_WIDTH + 1) * TEXT_SCALE;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    fill_rect(
+                        image,
+                        glyph_x + col * TEXT_SCALE,
+                        y + row as u32 * TEXT_SCALE,
+                        TEXT_SCALE,
+                        TEXT_SCALE,
+                        colour,
+                    );
+                }
+            }
+        }
+    }
+}
+
+//Draws a line two pixels thick from `from` to `to`
+fn draw_line(image: &mut RgbImage, from: (u32, u32), to: (u32, u32), colour: Rgb<u8>) {
+    let (dx, dy) = (to.0 as f64 - from.0 as f64, to.1 as f64 - from.1 as f64);
+    let steps = dx.abs().max(dy.abs()).max(1.0) as u32;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        let x = (from.0 as f64 + dx * t).round() as u32;
+        let y = (from.1 as f64 + dy * t).round() as u32;
+        fill_rect(image, x, y, 2, 2, colour);
+    }
+}
+
+//Renders `series` as a PNG line chart, with a legend naming each line
+pub fn render_graph(series: &[Series]) -> Result<Vec<u8>, image::ImageError> {
+    let mut image: RgbImage = ImageBuffer::from_pixel(WIDTH, HEIGHT, BACKGROUND);
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let plot_height = HEIGHT - TOP - BOTTOM;
+
+    let points = series.iter().flat_map(|(_, points)| points.iter());
+    let first = points.clone().map(|(taken, _)| *taken).min();
+    let last = points.clone().map(|(taken, _)| *taken).max();
+    let lowest = points
+        .clone()
+        .map(|(_, rating)| *rating)
+        .fold(f64::MAX, f64::min);
+    let highest = points.map(|(_, rating)| *rating).fold(f64::MIN, f64::max);
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            let mut png = Vec::new();
+            DynamicImage::ImageRgb8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+            return Ok(png);
+        }
+    };
+
+    //The rating axis goes from a mark below the lowest rating to one above the highest
+    let step = STEPS
+        .iter()
+        .copied()
+        .find(|step| (highest - lowest) / step <= MAX_TICKS - 1.0)
+        .unwrap_or(STEPS[STEPS.len() - 1]);
+    let bottom = (lowest / step).floor() * step;
+    let mut top = (highest / step).ceil() * step;
+    if top <= bottom {
+        top = bottom + step;
+    }
+    let span = std::cmp::max((last - first).num_seconds(), 1) as f64;
+    let to_pixel = |taken: TimeType, rating: f64| {
+        let x = if last == first {
+            plot_width / 2
+        } else {
+            ((taken - first).num_seconds() as f64 / span * plot_width as f64) as u32
+        };
+        let y = ((top - rating) / (top - bottom) * plot_height as f64) as u32;
+        (
+            LEFT + std::cmp::min(x, plot_width - 2),
+            TOP + std::cmp::min(y, plot_height - 2),
+        )
+    };
+
+    let glyph_height = GLYPH_HEIGHT * TEXT_SCALE;
+    let mut mark = bottom;
+    while mark <= top {
+        let y = TOP + ((top - mark) / (top - bottom) * plot_height as f64) as u32;
+        let y = std::cmp::min(y, TOP + plot_height - 1);
+        fill_rect(&mut image, LEFT, y, plot_width, 1, GRID);
+        let label = format!("{:.0}", mark);
+        let x = LEFT.saturating_sub(text_width(&label) + 6);
+        draw_text(
+            &mut image,
+            &label,
+            x,
+            y.saturating_sub(glyph_height / 2),
+            TEXT,
+        );
+        mark += step;
+    }
+
+    //Dates go under the start and end of the chart
+    let date_y = TOP + plot_height + (BOTTOM - glyph_height) / 2;
+    let start = first.format("%b %Y").to_string().to_uppercase();
+    let end = last.format("%b %Y").to_string().to_uppercase();
+    draw_text(&mut image, &start, LEFT, date_y, TEXT);
+    if end != start {
+        let x = LEFT + plot_width - text_width(&end);
+        draw_text(&mut image, &end, x, date_y, TEXT);
+    }
+
+    let mut legend_x = LEFT;
+    let legend_y = (TOP - glyph_height) / 2;
+    for (line, points) in series {
+        fill_rect(
+            &mut image,
+            legend_x,
+            legend_y + 2,
+            glyph_height - 4,
+            glyph_height - 4,
+            line.colour(),
+        );
+        legend_x += glyph_height;
+        draw_text(&mut image, line.name(), legend_x, legend_y, TEXT);
+        legend_x += text_width(line.name()) + 2 * glyph_height;
+
+        let pixels: Vec<(u32, u32)> = points
+            .iter()
+            .map(|(taken, rating)| to_pixel(*taken, *rating))
+            .collect();
+        for pair in pixels.windows(2) {
+            draw_line(&mut image, pair[0], pair[1], line.colour());
+        }
+        //A dot on the latest rating, which is all there is of lines with one rating
+        if let Some(&(x, y)) = pixels.last() {
+            fill_rect(
+                &mut image,
+                x.saturating_sub(2),
+                y.saturating_sub(2),
+                6,
+                6,
+                line.colour(),
+            );
+        }
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png)
+}
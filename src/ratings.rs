@@ -0,0 +1,408 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::library::TimeType;
+
+//Members' club ratings, from the games they play each other. Guilds pick how ratings are worked
+//out with !config rating-system:
+//  - Elo: after each game the winner takes points from the loser, more of them the more surprising
+//    the result was. How many points a game is worth is the K-factor, which admins can change with
+//    !config k-factor. A member's first games move their rating twice as fast, so that it gets to
+//    where they stand sooner
+//  - Glicko-2: every rating also has a deviation, saying how sure it is. It shrinks as members
+//    play and grows while they don't, so that members who come back after a break move quickly
+//    again, which suits clubs whose members play now and then
+//Until a rating can be trusted it is shown with a ?
+
+pub const START_RATING: f64 = 1500.0;
+pub const DEFAULT_K_FACTOR: u32 = 20;
+pub const MAX_K_FACTOR: u32 = 100;
+//Elo ratings are provisional for this many games
+const PROVISIONAL_GAMES: u32 = 10;
+
+//Glicko-2 ratings start out this unsure, which is also as unsure as they get
+const START_DEVIATION: f64 = 350.0;
+const START_VOLATILITY: f64 = 0.06;
+//Glicko-2 ratings are provisional while their deviation is above this
+const PROVISIONAL_DEVIATION: f64 = 110.0;
+//How much volatility can change after a game. Glickman suggests 0.3 to 1.2
+const TAU: f64 = 0.5;
+//Deviations grow as if this many days were one rating period without games
+const RATING_PERIOD_DAYS: f64 = 7.0;
+//Converts between the Glicko and Glicko-2 scales
+const GLICKO2_SCALE: f64 = 173.7178;
+const CONVERGENCE: f64 = 0.000001;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatingSystem {
+    Elo,
+    Glicko2,
+}
+
+pub const RATING_SYSTEMS: [RatingSystem; 2] = [RatingSystem::Elo, RatingSystem::Glicko2];
+
+impl Default for RatingSystem {
+    fn default() -> Self {
+        RatingSystem::Elo
+    }
+}
+
+impl RatingSystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            RatingSystem::Elo => "elo",
+            RatingSystem::Glicko2 => "glicko2",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<RatingSystem> {
+        RATING_SYSTEMS
+            .iter()
+            .copied()
+            .find(|system| system.name().eq_ignore_ascii_case(input))
+    }
+}
+
+//The parts of a rating only Glicko-2 needs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Glicko {
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Glicko {
+    fn default() -> Self {
+        Glicko {
+            deviation: START_DEVIATION,
+            volatility: START_VOLATILITY,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClubRating {
+    pub rating: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    //None until the member plays a game rated with Glicko-2
+    pub glicko: Option<Glicko>,
+    pub last_played: Option<TimeType>,
+}
+
+impl Default for ClubRating {
+    fn default() -> Self {
+        ClubRating {
+            rating: START_RATING,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            glicko: None,
+            last_played: None,
+        }
+    }
+}
+
+impl ClubRating {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    //Folds in the rating of a duplicate account. Results add up, and the rating itself is taken
+    //from whichever of the two played last
+    pub fn merge(&mut self, other: ClubRating) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+        if other.last_played > self.last_played {
+            self.rating = other.rating;
+            self.glicko = other.glicko;
+            self.last_played = other.last_played;
+        }
+    }
+
+    pub fn is_provisional(&self, system: RatingSystem) -> bool {
+        match system {
+            RatingSystem::Elo => self.games() < PROVISIONAL_GAMES,
+            RatingSystem::Glicko2 => self.glicko(None).deviation > PROVISIONAL_DEVIATION,
+        }
+    }
+
+    //The rating rounded, with a ? while provisional. Glicko-2 ratings also show their deviation
+    pub fn describe(&self, system: RatingSystem) -> String {
+        let provisional = if self.is_provisional(system) { "?" } else { "" };
+        match system {
+            RatingSystem::Elo => format!("{:.0}{}", self.rating, provisional),
+            RatingSystem::Glicko2 => format!(
+                "{:.0}{} ±{:.0}",
+                self.rating,
+                provisional,
+                self.glicko(None).deviation
+            ),
+        }
+    }
+
+    //The Glicko-2 deviation and volatility, with the deviation grown for the time since the last
+    //game when `now` is given
+    fn glicko(&self, now: Option<TimeType>) -> Glicko {
+        let mut glicko = self.glicko.unwrap_or_default();
+        if let (Some(now), Some(last_played)) = (now, self.last_played) {
+            let periods = (now - last_played).num_seconds().max(0) as f64
+                / (RATING_PERIOD_DAYS * 24.0 * 60.0 * 60.0);
+            glicko.deviation = (glicko.deviation.powi(2)
+                + glicko.volatility.powi(2) * GLICKO2_SCALE.powi(2) * periods)
+                .sqrt()
+                .min(START_DEVIATION);
+        }
+        glicko
+    }
+
+    fn count(&mut self, score: f64, now: TimeType) {
+        if score > 0.5 {
+            self.wins += 1;
+        } else if score < 0.5 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+        self.last_played = Some(now);
+    }
+
+    //Counts a game with `score` (1 for a win, ½ for a draw, 0 for a loss) against `opponent`
+    //with Elo. Returns how much the rating changed
+    fn record_elo(&mut self, opponent: f64, score: f64, k_factor: u32, now: TimeType) -> f64 {
+        let k_factor = if self.is_provisional(RatingSystem::Elo) {
+            2.0 * k_factor as f64
+        } else {
+            k_factor as f64
+        };
+        let expected = 1.0 / (1.0 + 10f64.powf((opponent - self.rating) / 400.0));
+        let change = k_factor * (score - expected);
+        self.rating += change;
+        self.count(score, now);
+        change
+    }
+
+    //Counts a game with `score` against an opponent rated `opponent` with Glicko-2, following
+    //Glickman's paper with the game as a rating period of its own. `mine` and `opponent` are the
+    //ratings' Glicko-2 parts before the game. Returns how much the rating changed
+    fn record_glicko(
+        &mut self,
+        mine: Glicko,
+        opponent: (f64, Glicko),
+        score: f64,
+        now: TimeType,
+    ) -> f64 {
+        let mu = (self.rating - START_RATING) / GLICKO2_SCALE;
+        let phi = mine.deviation / GLICKO2_SCALE;
+        let sigma = mine.volatility;
+        let opponent_mu = (opponent.0 - START_RATING) / GLICKO2_SCALE;
+        let opponent_phi = opponent.1.deviation / GLICKO2_SCALE;
+
+        let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / std::f64::consts::PI.powi(2)).sqrt();
+        let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+        let v = 1.0 / (g.powi(2) * expected * (1.0 - expected));
+        let delta = v * g * (score - expected);
+
+        //The new volatility, found with the Illinois algorithm
+        let a = sigma.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - v - ex) / (2.0 * (phi.powi(2) + v + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+        let mut low = a;
+        let mut high = if delta.powi(2) > phi.powi(2) + v {
+            (delta.powi(2) - phi.powi(2) - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_low = f(low);
+        let mut f_high = f(high);
+        while (high - low).abs() > CONVERGENCE {
+            let c = low + (low - high) * f_low / (f_high - f_low);
+            let f_c = f(c);
+            if f_c * f_high <= 0.0 {
+                low = high;
+                f_low = f_high;
+            } else {
+                f_low /= 2.0;
+            }
+            high = c;
+            f_high = f_c;
+        }
+        let volatility = (low / 2.0).exp();
+
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi.powi(2) * g * (score - expected);
+
+        let old = self.rating;
+        self.rating = new_mu * GLICKO2_SCALE + START_RATING;
+        self.glicko = Some(Glicko {
+            deviation: (new_phi * GLICKO2_SCALE).min(START_DEVIATION),
+            volatility,
+        });
+        self.count(score, now);
+        self.rating - old
+    }
+}
+
+//A member's club rating right after one of their rated games, for !rating graph
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct RatingSnapshot {
+    pub taken: TimeType,
+    pub rating: f64,
+}
+
+//How a game changed one player's rating
+pub struct RatingChange {
+    //Discord id of the player
+    pub member: String,
+    pub rating: ClubRating,
+    pub change: f64,
+    pub system: RatingSystem,
+}
+
+impl std::fmt::Display for RatingChange {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            fmt,
+            "<@{}> {} ({:+.0})",
+            self.member,
+            self.rating.describe(self.system),
+            self.change
+        )
+    }
+}
+
+//Updates the ratings of `white` and `black` after a game that white scored `white_score` in
+pub fn rate(
+    ratings: &mut IndexMap<String, ClubRating>,
+    system: RatingSystem,
+    k_factor: u32,
+    white: &str,
+    black: &str,
+    white_score: f64,
+) -> [RatingChange; 2] {
+    let now = chrono::Local::now();
+    let mut white_rating = ratings.get(white).cloned().unwrap_or_default();
+    let mut black_rating = ratings.get(black).cloned().unwrap_or_default();
+    //Both changes are worked out from the ratings before the game
+    let before = (white_rating.rating, black_rating.rating);
+    let (white_change, black_change) = match system {
+        RatingSystem::Elo => (
+            white_rating.record_elo(before.1, white_score, k_factor, now),
+            black_rating.record_elo(before.0, 1.0 - white_score, k_factor, now),
+        ),
+        RatingSystem::Glicko2 => {
+            let white_glicko = white_rating.glicko(Some(now));
+            let black_glicko = black_rating.glicko(Some(now));
+            (
+                white_rating.record_glicko(
+                    white_glicko,
+                    (before.1, black_glicko),
+                    white_score,
+                    now,
+                ),
+                black_rating.record_glicko(
+                    black_glicko,
+                    (before.0, white_glicko),
+                    1.0 - white_score,
+                    now,
+                ),
+            )
+        }
+    };
+    ratings.insert(white.to_owned(), white_rating.clone());
+    ratings.insert(black.to_owned(), black_rating.clone());
+    [
+        RatingChange {
+            member: white.to_owned(),
+            rating: white_rating,
+            change: white_change,
+            system,
+        },
+        RatingChange {
+            member: black.to_owned(),
+            rating: black_rating,
+            change: black_change,
+            system,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn established(rating: f64) -> ClubRating {
+        ClubRating {
+            rating,
+            wins: PROVISIONAL_GAMES,
+            ..ClubRating::default()
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.5
+    }
+
+    #[test]
+    fn elo_moves_provisional_ratings_twice_as_fast() {
+        let mut ratings = IndexMap::new();
+        let [white, black] = rate(&mut ratings, RatingSystem::Elo, 20, "a", "b", 1.0);
+        assert!(close(white.change, 20.0));
+        assert!(close(black.change, -20.0));
+        assert!(close(ratings["a"].rating, 1520.0));
+        assert!(close(ratings["b"].rating, 1480.0));
+        assert_eq!((ratings["a"].wins, ratings["b"].losses), (1, 1));
+    }
+
+    #[test]
+    fn elo_draws_favour_the_lower_rated() {
+        let mut ratings = IndexMap::new();
+        ratings.insert("a".to_owned(), established(1600.0));
+        ratings.insert("b".to_owned(), established(1400.0));
+        let [white, black] = rate(&mut ratings, RatingSystem::Elo, 20, "a", "b", 0.5);
+        //White was expected to score 1 / (1 + 10^(-200/400)) = 0.76
+        assert!(close(white.change, -5.19));
+        assert!(close(black.change, 5.19));
+        assert_eq!((ratings["a"].draws, ratings["b"].draws), (1, 1));
+    }
+
+    #[test]
+    fn glicko_matches_the_reference_numbers() {
+        let mut ratings = IndexMap::new();
+        let [white, black] = rate(&mut ratings, RatingSystem::Glicko2, 0, "a", "b", 1.0);
+        //Two new players, 1500 ±350, after one game worked out by hand from Glickman's paper
+        assert!(close(white.rating.rating, 1662.31));
+        assert!(close(black.rating.rating, 1337.69));
+        let deviation = ratings["a"].glicko.unwrap().deviation;
+        assert!(close(deviation, 290.32));
+        assert!(ratings["a"].is_provisional(RatingSystem::Glicko2));
+    }
+
+    #[test]
+    fn glicko_draws_between_equals_change_nothing_but_the_deviation() {
+        let mut ratings = IndexMap::new();
+        rate(&mut ratings, RatingSystem::Glicko2, 0, "a", "b", 0.5);
+        assert!(close(ratings["a"].rating, START_RATING));
+        assert!(ratings["a"].glicko.unwrap().deviation < START_DEVIATION);
+    }
+
+    #[test]
+    fn merging_keeps_the_latest_rating_and_adds_up_results() {
+        let mut rating = established(1600.0);
+        rating.last_played = Some(chrono::Local::now() - chrono::Duration::days(1));
+        let mut newer = established(1700.0);
+        newer.last_played = Some(chrono::Local::now());
+        rating.merge(newer);
+        assert!(close(rating.rating, 1700.0));
+        assert_eq!(rating.games(), 2 * PROVISIONAL_GAMES);
+    }
+}
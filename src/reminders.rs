@@ -0,0 +1,136 @@
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::RwLock,
+};
+
+use std::sync::Arc;
+
+use crate::guilds::Libraries;
+use crate::library;
+use crate::threads;
+
+//How often the reminder task wakes up to look for overdue checkouts
+const REMINDER_INTERVAL_SECS: u64 = 60 * 60;
+
+//Background task that periodically runs the overdue escalation policy over every library and
+//carries out whatever actions are due
+pub async fn reminder_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(REMINDER_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            run_escalations(&http, guild, &library_arc).await;
+        }
+    }
+}
+
+async fn run_escalations(
+    http: &Arc<Http>,
+    guild: Option<GuildId>,
+    library_arc: &RwLock<library::Database>,
+) {
+    //Work out what needs to be sent while holding the lock, then drop it before talking to
+    //discord so that commands aren't blocked on network requests
+    let (officers_channel, messages) = {
+        let mut library = library_arc.write().await;
+        let pending = library.evaluate_escalations(chrono::Local::now());
+        crate::autosave::persist_change(&library).await;
+
+        let officers_channel = library
+            .channel(library::ChannelKind::Overdue)
+            .map(ChannelId);
+        let messages = pending
+            .into_iter()
+            .map(|escalation| {
+                let book_name = library
+                    .books
+                    .get(&escalation.book)
+                    .map(|book| book.name.clone())
+                    .unwrap_or_else(|| library::Database::encode_uuid(escalation.book));
+                let user = library.users.get(&escalation.rentee);
+                let discord_id = user.and_then(|user| user.discord_id.parse::<u64>().ok());
+                let rentee_name = user
+                    .map(|user| user.read_name.clone())
+                    .unwrap_or_else(|| library::Database::encode_uuid(escalation.rentee));
+                let approval_message = library
+                    .checkout(escalation.checkout)
+                    .and_then(|checkout| checkout.approval_message);
+                (
+                    escalation,
+                    book_name,
+                    rentee_name,
+                    discord_id,
+                    approval_message,
+                )
+            })
+            .collect::<Vec<_>>();
+        (officers_channel, messages)
+    };
+
+    for (escalation, book_name, rentee_name, discord_id, approval_message) in messages {
+        println!(
+            "Overdue escalation for checkout {}: {}",
+            library::Database::encode_uuid(escalation.checkout),
+            escalation.action
+        );
+        let result = match escalation.action {
+            //Reminders go to the checkout's thread, which pings the rentee, or to their DMs if it
+            //has none
+            library::EscalationAction::DirectMessage => match discord_id {
+                Some(id) => {
+                    let text = format!(
+                        "Your copy of \"{}\" is {} day(s) overdue. Please return it to an officer and use !library return {}",
+                        book_name,
+                        escalation.days_overdue,
+                        library::Database::encode_uuid(escalation.checkout)
+                    );
+                    let in_thread = format!("<@{}> {}", id, text);
+                    if threads::post(http, approval_message, &in_thread).await {
+                        Ok(())
+                    } else {
+                        send_dm(http, UserId(id), text).await
+                    }
+                }
+                None => {
+                    println!("No discord id for rentee {}, can't DM them", rentee_name);
+                    Ok(())
+                }
+            },
+            library::EscalationAction::OfficerChannel
+            | library::EscalationAction::SuspendBorrowing => match officers_channel {
+                Some(channel) => {
+                    let mut text = format!(
+                        "{} has had \"{}\" for {} day(s) past its due date. Checkout ID: {}",
+                        rentee_name,
+                        book_name,
+                        escalation.days_overdue,
+                        library::Database::encode_uuid(escalation.checkout)
+                    );
+                    if escalation.action == library::EscalationAction::SuspendBorrowing {
+                        text.push_str("\nTheir borrowing privileges have been suspended");
+                    }
+                    channel.say(http, text).await.map(|_| ())
+                }
+                None => {
+                    println!(
+                        "No overdue channel set for guild {:?}, can't tell the officers",
+                        guild
+                    );
+                    Ok(())
+                }
+            },
+        };
+        if let Err(err) = result {
+            println!("Failed to send overdue escalation: {:?}", err);
+        }
+    }
+}
+
+async fn send_dm(http: &Arc<Http>, user: UserId, text: String) -> serenity::Result<()> {
+    let channel = user.create_dm_channel(http).await?;
+    channel.say(http, text).await?;
+    Ok(())
+}
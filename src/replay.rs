@@ -0,0 +1,219 @@
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serenity::{
+    builder::CreateComponents,
+    model::{
+        channel::Message,
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::games::{render_emoji, render_text};
+use crate::pgn::ImportedGame;
+use crate::rules::GameState;
+
+//Games imported with !chess replay are shown in a message with buttons to step through them one
+//move at a time, for going over games played at the club. Buttons have custom ids of the form
+//replay:<id>:<step>, where step is first, prev, next or last. Files can't be swapped out when a
+//button is pressed, so the board is drawn as text, or with the guild's emojis when it uses them
+
+//Replays are forgotten and their buttons taken off by flows.rs after this long
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const EXPIRED: &str = "This replay has expired. Please run the command again";
+
+struct Replay {
+    title: String,
+    start_fen: Option<String>,
+    moves: Vec<String>,
+    //How many of the moves have been played on the board shown
+    ply: usize,
+    //The guild's piece emojis, for guilds that show boards with them
+    emojis: Option<IndexMap<String, String>>,
+    created: Instant,
+}
+
+static REPLAYS: Lazy<Mutex<HashMap<u32, Replay>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl Replay {
+    fn content(&self) -> String {
+        let mut state = GameState::replay(
+            self.start_fen.as_deref(),
+            &self.moves[..self.ply.saturating_sub(1)],
+        );
+        let position = match self.ply.checked_sub(1).map(|last| &self.moves[last]) {
+            Some(san) => {
+                let dots = if state.turn() == shakmaty::Color::White {
+                    "."
+                } else {
+                    "..."
+                };
+                let label = format!("After {}{} {}", state.move_number(), dots, san);
+                if let Some(m) = state.parse_move(san) {
+                    state.play(&m);
+                }
+                label
+            }
+            None => "Starting position".to_owned(),
+        };
+
+        let board = state.position().board();
+        let diagram = self
+            .emojis
+            .as_ref()
+            .and_then(|emojis| render_emoji(board, false, emojis))
+            .unwrap_or_else(|| render_text(board, false));
+        format!(
+            "**{}**\n{}\n{} (move {} of {})",
+            self.title,
+            diagram,
+            position,
+            self.ply,
+            self.moves.len()
+        )
+    }
+
+    fn components<'a>(
+        &self,
+        id: u32,
+        components: &'a mut CreateComponents,
+    ) -> &'a mut CreateComponents {
+        let at_start = self.ply == 0;
+        let at_end = self.ply == self.moves.len();
+        components.create_action_row(|row| {
+            for (step, label, disabled) in [
+                ("first", "⏮", at_start),
+                ("prev", "◀", at_start),
+                ("next", "▶", at_end),
+                ("last", "⏭", at_end),
+            ] {
+                row.create_button(|b| {
+                    b.style(ButtonStyle::Secondary)
+                        .label(label)
+                        .custom_id(format!("replay:{:x}:{}", id, step))
+                        .disabled(disabled)
+                });
+            }
+            row
+        })
+    }
+}
+
+//Shows `game` in reply to `msg`, from its starting position. `emojis` are the guild's piece emojis
+//if it draws boards with them
+pub async fn start(
+    ctx: &Context,
+    msg: &Message,
+    game: ImportedGame,
+    emojis: Option<IndexMap<String, String>>,
+) -> serenity::Result<()> {
+    let mut title = format!(
+        "{} vs {}, {} {} ({})",
+        game.tag("White"),
+        game.tag("Black"),
+        game.tag("Event"),
+        game.tag("Date"),
+        game.tag("Result")
+    );
+    if let Some(opening) = crate::openings::identify(game.start_fen.as_deref(), &game.moves) {
+        title.push_str(&format!(", {}", opening));
+    }
+    let replay = Replay {
+        title,
+        start_fen: game.start_fen,
+        moves: game.moves,
+        ply: 0,
+        emojis,
+        created: Instant::now(),
+    };
+    let id: u32 = rand::thread_rng().gen();
+    let content = replay.content();
+
+    let message = msg
+        .channel_id
+        .send_message(ctx, |m| {
+            m.reference_message(msg)
+                .content(content)
+                .components(|c| replay.components(id, c))
+        })
+        .await?;
+    crate::flows::expire_components(&message, REPLAY_TIMEOUT);
+    REPLAYS.lock().unwrap().insert(id, replay);
+    Ok(())
+}
+
+//Drops the replays whose buttons were taken off
+pub fn forget_expired() {
+    REPLAYS
+        .lock()
+        .unwrap()
+        .retain(|_, replay| replay.created.elapsed() < REPLAY_TIMEOUT);
+}
+
+//Called when someone presses one of the buttons under a replay. Anyone can, so that the whole
+//club can go over a game together
+pub async fn handle_step(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let updated = {
+        let mut replays = REPLAYS.lock().unwrap();
+        let (id, step) = match id.split_once(':') {
+            Some(parts) => parts,
+            None => return,
+        };
+        let id = match u32::from_str_radix(id, 16) {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        match replays.get_mut(&id) {
+            Some(replay) => {
+                replay.ply = match step {
+                    "first" => 0,
+                    "prev" => replay.ply.saturating_sub(1),
+                    "next" => (replay.ply + 1).min(replay.moves.len()),
+                    _ => replay.moves.len(),
+                };
+                let mut components = CreateComponents::default();
+                replay.components(id, &mut components);
+                Some((replay.content(), components))
+            }
+            None => None,
+        }
+    };
+
+    let response = match updated {
+        Some((content, components)) => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(content).components(|c| {
+                                *c = components;
+                                c
+                            })
+                        })
+                })
+                .await
+        }
+        None => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content(EXPIRED)
+                                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                        })
+                })
+                .await
+        }
+    };
+    if let Err(err) = response {
+        println!("Failed to respond to replay button: {:?}", err);
+    }
+}
@@ -0,0 +1,80 @@
+use serenity::{model::channel::Message, prelude::*, utils::Colour};
+
+//Commands answer with an embed whose colour says how things went, so that the outcome can be seen
+//at a glance: green when something was done, red when it wasn't and blue for plain information
+
+//Discord refuses embeds with longer descriptions
+const MAX_DESCRIPTION_LEN: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    Success,
+    Error,
+    Info,
+}
+
+impl Tone {
+    pub fn colour(self) -> Colour {
+        match self {
+            Tone::Success => Colour::DARK_GREEN,
+            Tone::Error => Colour::RED,
+            Tone::Info => Colour::BLUE,
+        }
+    }
+}
+
+//Splits `text` into pieces that each fit in an embed, breaking between lines where possible
+fn split_text(text: &str) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    for line in text.lines() {
+        let piece = pieces.last_mut().unwrap();
+        if !piece.is_empty() && piece.len() + 1 + line.len() > MAX_DESCRIPTION_LEN {
+            pieces.push(String::new());
+        }
+        let piece = pieces.last_mut().unwrap();
+        if !piece.is_empty() {
+            piece.push('\n');
+        }
+        //A single line that is too long on its own is cut, on a character boundary
+        for c in line.chars() {
+            if pieces.last().unwrap().len() + c.len_utf8() > MAX_DESCRIPTION_LEN {
+                pieces.push(String::new());
+            }
+            pieces.last_mut().unwrap().push(c);
+        }
+    }
+    pieces
+}
+
+//Replies to `msg` with `text` in an embed of the given tone. Text too long for one embed is sent
+//over several messages
+pub async fn send(
+    ctx: &Context,
+    msg: &Message,
+    tone: Tone,
+    text: impl AsRef<str>,
+) -> serenity::Result<()> {
+    for (i, piece) in split_text(text.as_ref()).into_iter().enumerate() {
+        msg.channel_id
+            .send_message(ctx, |m| {
+                if i == 0 {
+                    m.reference_message(msg);
+                }
+                m.embed(|e| e.colour(tone.colour()).description(piece))
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn success(ctx: &Context, msg: &Message, text: impl AsRef<str>) -> serenity::Result<()> {
+    send(ctx, msg, Tone::Success, text).await
+}
+
+pub async fn error(ctx: &Context, msg: &Message, text: impl AsRef<str>) -> serenity::Result<()> {
+    send(ctx, msg, Tone::Error, text).await
+}
+
+pub async fn info(ctx: &Context, msg: &Message, text: impl AsRef<str>) -> serenity::Result<()> {
+    send(ctx, msg, Tone::Info, text).await
+}
@@ -0,0 +1,414 @@
+use shakmaty::{
+    fen::Fen,
+    san::{San, SanPlus},
+    uci::Uci,
+    Bitboard, Board, CastlingMode, Chess, Color, EnPassantMode, Move, Position, Square,
+};
+
+//The rules of chess, on top of shakmaty's move generation. Positions alone don't say whether a
+//game is drawn by repetition, so GameState keeps the positions that were reached along the way.
+//The rest of the bot only deals with moves as text, in standard algebraic notation (SAN)
+
+//How many times a position has to be reached for the game to be drawn
+const REPETITIONS: usize = 3;
+//Half moves without a capture or pawn move after which the game is drawn
+const FIFTY_MOVES: u32 = 100;
+//Chess960 starting positions are numbered from 0 to 959
+pub const CHESS960_POSITIONS: u32 = 960;
+//The Chess960 number of the usual starting position. Games from it are ordinary chess
+const STANDARD_CHESS960: u32 = 518;
+//Where the knights go among the five squares left once the bishops and queen are placed, by the
+//usual Chess960 numbering
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+//The parts of a position that decide whether it was reached before
+#[derive(PartialEq, Eq)]
+struct RepetitionKey {
+    board: Board,
+    turn: Color,
+    castling: Bitboard,
+    en_passant: Option<Square>,
+}
+
+impl RepetitionKey {
+    fn of(position: &Chess) -> RepetitionKey {
+        RepetitionKey {
+            board: position.board().clone(),
+            turn: position.turn(),
+            castling: position.castles().castling_rights(),
+            en_passant: position.ep_square(EnPassantMode::Legal),
+        }
+    }
+}
+
+//How a game ended on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEnd {
+    //Holds the winner
+    Checkmate(Color),
+    Stalemate,
+    InsufficientMaterial,
+    Repetition,
+    FiftyMoves,
+}
+
+impl GameEnd {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameEnd::Checkmate(winner) => Some(winner),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GameEnd {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            GameEnd::Checkmate(_) => write!(fmt, "checkmate"),
+            GameEnd::Stalemate => write!(fmt, "stalemate"),
+            GameEnd::InsufficientMaterial => write!(fmt, "insufficient material"),
+            GameEnd::Repetition => write!(fmt, "threefold repetition"),
+            GameEnd::FiftyMoves => write!(fmt, "the fifty-move rule"),
+        }
+    }
+}
+
+pub struct GameState {
+    position: Chess,
+    //Every position reached so far, including the current one
+    history: Vec<RepetitionKey>,
+    last_move: Option<Move>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::from_position(Chess::default())
+    }
+}
+
+impl GameState {
+    fn from_position(position: Chess) -> GameState {
+        GameState {
+            history: vec![RepetitionKey::of(&position)],
+            position,
+            last_move: None,
+        }
+    }
+
+    //Sets up the position described by `fen`, which can be any legal position, including Chess960
+    //ones
+    pub fn from_fen(fen: &str) -> Option<GameState> {
+        parse_fen(fen).map(|(position, _)| GameState::from_position(position))
+    }
+
+    //The position a game starts from: `start_fen` if it has one, the usual one otherwise
+    pub fn start(start_fen: Option<&str>) -> GameState {
+        start_fen.and_then(GameState::from_fen).unwrap_or_default()
+    }
+
+    //Plays `moves`, given in SAN, from the game's starting position. Stops at the first move that
+    //can't be played
+    pub fn replay(start_fen: Option<&str>, moves: &[String]) -> GameState {
+        let mut state = GameState::start(start_fen);
+        for input in moves {
+            let m = match input
+                .parse::<SanPlus>()
+                .ok()
+                .and_then(|san| san.san.to_move(&state.position).ok())
+            {
+                Some(m) => m,
+                None => break,
+            };
+            state.play(&m);
+        }
+        state
+    }
+
+    pub fn position(&self) -> &Chess {
+        &self.position
+    }
+
+    pub fn turn(&self) -> Color {
+        self.position.turn()
+    }
+
+    //The number of the move being played, which goes up after black moves
+    pub fn move_number(&self) -> u32 {
+        self.position.fullmoves().get()
+    }
+
+    //The position in Forsyth-Edwards Notation, which other chess tools can read
+    pub fn fen(&self) -> String {
+        Fen::from_position(self.position.clone(), EnPassantMode::Legal).to_string()
+    }
+
+    pub fn last_move(&self) -> Option<&Move> {
+        self.last_move.as_ref()
+    }
+
+    //Works out the legal move meant by `input`, which can be in SAN, like Nf3, or in UCI, like
+    //g1f3. Piece letters may be typed in lower case
+    pub fn parse_move(&self, input: &str) -> Option<Move> {
+        let input = input.trim();
+        let san = |text: &str| {
+            text.parse::<SanPlus>()
+                .ok()
+                .and_then(|san| san.san.to_move(&self.position).ok())
+        };
+        if let Some(m) = san(input) {
+            return Some(m);
+        }
+        if let Some(m) = input
+            .parse::<Uci>()
+            .ok()
+            .and_then(|uci| uci.to_move(&self.position).ok())
+        {
+            return Some(m);
+        }
+        //Only tried once the above failed, since bxc3 is also a pawn capture
+        let mut chars = input.chars();
+        match chars.next() {
+            Some(first) if "nbrqk".contains(first) => {
+                san(&format!("{}{}", first.to_ascii_uppercase(), chars.as_str()))
+            }
+            _ => None,
+        }
+    }
+
+    //Plays `m`, which has to be legal, and returns it in SAN with + or # added for check and mate
+    pub fn play(&mut self, m: &Move) -> String {
+        let san = SanPlus::from_move_and_play_unchecked(&mut self.position, m);
+        self.history.push(RepetitionKey::of(&self.position));
+        self.last_move = Some(m.clone());
+        san.to_string()
+    }
+
+    pub fn is_check(&self) -> bool {
+        self.position.is_check()
+    }
+
+    //How the game ended, if the last move ended it
+    pub fn end(&self) -> Option<GameEnd> {
+        if self.position.is_checkmate() {
+            return Some(GameEnd::Checkmate(!self.position.turn()));
+        }
+        if self.position.is_stalemate() {
+            return Some(GameEnd::Stalemate);
+        }
+        if self.position.is_insufficient_material() {
+            return Some(GameEnd::InsufficientMaterial);
+        }
+        let current = self.history.last()?;
+        if self.history.iter().filter(|key| *key == current).count() >= REPETITIONS {
+            return Some(GameEnd::Repetition);
+        }
+        if self.position.halfmoves() >= FIFTY_MOVES {
+            return Some(GameEnd::FiftyMoves);
+        }
+        None
+    }
+
+    //Every legal move in SAN
+    pub fn legal_moves(&self) -> Vec<String> {
+        self.position
+            .legal_moves()
+            .iter()
+            .map(|m| San::from_move(&self.position, m).to_string())
+            .collect()
+    }
+}
+
+//Reads `fen` with the castling rules it needs. Positions whose castling rights don't fit the usual
+//rules, because a king or rook that can castle isn't on its usual square, are read with Chess960's
+fn parse_fen(fen: &str) -> Option<(Chess, CastlingMode)> {
+    let fen = fen.trim().parse::<Fen>().ok()?;
+    [CastlingMode::Standard, CastlingMode::Chess960]
+        .iter()
+        .find_map(|mode| Some((fen.clone().into_position(*mode).ok()?, *mode)))
+}
+
+//Whether the position described by `fen` needs Chess960 castling rules. Engines have to be told
+pub fn is_chess960_fen(fen: &str) -> bool {
+    matches!(parse_fen(fen), Some((_, CastlingMode::Chess960)))
+}
+
+//The FEN of Chess960 starting position `number`, from 0 to 959
+pub fn chess960_fen(number: u32) -> Option<String> {
+    if number >= CHESS960_POSITIONS {
+        return None;
+    }
+    let mut rank = [' '; 8];
+    let mut n = number as usize;
+    //One bishop on a light square and one on a dark square
+    rank[n % 4 * 2 + 1] = 'b';
+    n /= 4;
+    rank[n % 4 * 2] = 'b';
+    n /= 4;
+    let free = |rank: &[char; 8]| -> Vec<usize> { (0..8).filter(|i| rank[*i] == ' ').collect() };
+    rank[free(&rank)[n % 6]] = 'q';
+    n /= 6;
+    let (first, second) = CHESS960_KNIGHTS[n];
+    let left = free(&rank);
+    rank[left[first]] = 'n';
+    rank[left[second]] = 'n';
+    //The king goes between the rooks
+    for (square, piece) in free(&rank).into_iter().zip("rkr".chars()) {
+        rank[square] = piece;
+    }
+
+    let black: String = rank.iter().collect();
+    Some(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        black,
+        black.to_uppercase()
+    ))
+}
+
+//The number of the Chess960 starting position a game from `start_fen` started from, if it did. The
+//usual starting position doesn't count, since games from it are ordinary chess
+pub fn chess960_number(start_fen: &str) -> Option<u32> {
+    let board = start_fen.split_whitespace().next()?;
+    (0..CHESS960_POSITIONS)
+        .filter(|number| *number != STANDARD_CHESS960)
+        .find(|number| {
+            chess960_fen(*number).map_or(false, |fen| fen.split_whitespace().next() == Some(board))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    //Counts the positions `depth` half moves away, which is compared against counts everyone
+    //agrees on to check move generation
+    fn perft(position: &Chess, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        position
+            .legal_moves()
+            .iter()
+            .map(|m| {
+                let mut next = position.clone();
+                next.play_unchecked(m);
+                perft(&next, depth - 1)
+            })
+            .sum()
+    }
+
+    fn moves(sans: &[&str]) -> Vec<String> {
+        sans.iter().map(|san| san.to_string()).collect()
+    }
+
+    #[test]
+    fn perft_from_the_start() {
+        let state = GameState::default();
+        assert_eq!(perft(state.position(), 1), 20);
+        assert_eq!(perft(state.position(), 2), 400);
+        assert_eq!(perft(state.position(), 3), 8902);
+    }
+
+    #[test]
+    fn perft_with_castling_en_passant_and_promotions() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let state = GameState::from_fen(kiwipete).unwrap();
+        assert_eq!(perft(state.position(), 1), 48);
+        assert_eq!(perft(state.position(), 2), 2039);
+
+        let endgame = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        let state = GameState::from_fen(endgame).unwrap();
+        assert_eq!(perft(state.position(), 3), 2812);
+    }
+
+    #[test]
+    fn moves_are_read_in_san_and_uci() {
+        let state = GameState::default();
+        assert!(state.parse_move("e4").is_some());
+        assert!(state.parse_move("e2e4").is_some());
+        assert!(state.parse_move("Nf3").is_some());
+        assert!(state.parse_move("nf3").is_some());
+        assert!(state.parse_move("e5").is_none());
+        assert!(state.parse_move("Ke2").is_none());
+        assert!(state.parse_move("e2e5").is_none());
+        assert_eq!(state.legal_moves().len(), 20);
+    }
+
+    #[test]
+    fn pinned_pieces_cant_move() {
+        let state = GameState::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert!(state.parse_move("Bd3").is_none());
+        assert!(state.parse_move("Kd1").is_some());
+    }
+
+    #[test]
+    fn checkmate_ends_the_game() {
+        let mut state = GameState::replay(None, &moves(&["f3", "e5", "g4"]));
+        assert_eq!(state.end(), None);
+        let mate = state.parse_move("Qh4").unwrap();
+        assert_eq!(state.play(&mate), "Qh4#");
+        assert!(state.is_check());
+        assert_eq!(state.end(), Some(GameEnd::Checkmate(Color::Black)));
+        assert_eq!(state.end().unwrap().winner(), Some(Color::Black));
+    }
+
+    #[test]
+    fn stalemate_and_insufficient_material_are_draws() {
+        let stalemate = GameState::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(stalemate.end(), Some(GameEnd::Stalemate));
+        assert_eq!(stalemate.end().unwrap().winner(), None);
+
+        let kings = GameState::from_fen("8/8/8/4k3/8/8/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(kings.end(), Some(GameEnd::InsufficientMaterial));
+    }
+
+    #[test]
+    fn threefold_repetition_is_a_draw() {
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"];
+        let twice = GameState::replay(None, &moves(&shuffle[..7]));
+        assert_eq!(twice.end(), None);
+        let three_times = GameState::replay(None, &moves(&shuffle));
+        assert_eq!(three_times.end(), Some(GameEnd::Repetition));
+    }
+
+    #[test]
+    fn fifty_moves_without_progress_are_a_draw() {
+        let mut state = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert_eq!(state.end(), None);
+        let m = state.parse_move("Ra2").unwrap();
+        state.play(&m);
+        assert_eq!(state.end(), Some(GameEnd::FiftyMoves));
+    }
+
+    #[test]
+    fn chess960_518_is_the_usual_start() {
+        assert_eq!(chess960_fen(STANDARD_CHESS960).unwrap(), STANDARD_FEN);
+        assert_eq!(chess960_number(STANDARD_FEN), None);
+        assert!(!is_chess960_fen(STANDARD_FEN));
+        assert_eq!(chess960_fen(CHESS960_POSITIONS), None);
+    }
+
+    #[test]
+    fn chess960_positions_are_numbered_both_ways() {
+        let first = chess960_fen(0).unwrap();
+        assert!(first.starts_with("bbqnnrkr/"));
+        assert!(is_chess960_fen(&first));
+        for number in (0..CHESS960_POSITIONS).filter(|n| *n != STANDARD_CHESS960) {
+            let fen = chess960_fen(number).unwrap();
+            assert_eq!(chess960_number(&fen), Some(number), "{}", fen);
+            assert!(GameState::from_fen(&fen).is_some(), "{}", fen);
+        }
+    }
+}
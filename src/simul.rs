@@ -0,0 +1,160 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::games::{Game, GameStatus, GameUuid};
+use crate::library::{ManipulationError, ManipulationErrorType, TimeType};
+
+//Simultaneous exhibitions, where a coach or strong member plays everyone who signs up at once.
+//The host opens sign-up with !simul host, challengers join with !simul join, and !simul start
+//starts a game against each of them, with its own thread in the simul's channel. The games are
+//ordinary games, played with !chess move, so the host mentions the challenger when moving. The
+//host's queue lists the boards waiting on them, longest waiting first. When the last game ends the
+//simul's results are posted. Each guild has one simul at a time, saved with the library
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulStatus {
+    //Challengers can join or leave
+    SignUp,
+    //The games have started
+    Playing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Simul {
+    //Discord id of the host
+    pub host: String,
+    //Where sign-up was opened. The boards' threads are started there
+    pub channel: u64,
+    //Whether the host plays white on every board
+    pub host_white: bool,
+    //Discord ids of the challengers, in the order they joined
+    pub challengers: Vec<String>,
+    //The game on each board once the simul has started, in the same order as challengers
+    pub games: Vec<GameUuid>,
+    pub status: SimulStatus,
+    pub created: TimeType,
+}
+
+impl Simul {
+    pub fn new(host: String, channel: u64, host_white: bool) -> Simul {
+        Simul {
+            host,
+            channel,
+            host_white,
+            challengers: Vec::new(),
+            games: Vec::new(),
+            status: SimulStatus::SignUp,
+            created: chrono::Local::now(),
+        }
+    }
+
+    pub fn join(&mut self, player: &str) -> Result<(), ManipulationError> {
+        if self.status != SimulStatus::SignUp {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::SimulSignUpClosed,
+            ));
+        }
+        if player == self.host {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CantPlayYourself,
+            ));
+        }
+        if self.challengers.iter().any(|c| c == player) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::AlreadyInSimul,
+            ));
+        }
+        self.challengers.push(player.to_owned());
+        Ok(())
+    }
+
+    pub fn leave(&mut self, player: &str) -> Result<(), ManipulationError> {
+        if self.status != SimulStatus::SignUp {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::SimulSignUpClosed,
+            ));
+        }
+        let before = self.challengers.len();
+        self.challengers.retain(|c| c != player);
+        if self.challengers.len() == before {
+            return Err(ManipulationError::new(ManipulationErrorType::NotInSimul));
+        }
+        Ok(())
+    }
+
+    //Whether the simul has started and every game in it has ended
+    pub fn is_over(&self, games: &IndexMap<GameUuid, Game>) -> bool {
+        self.status == SimulStatus::Playing
+            && self
+                .games
+                .iter()
+                .all(|uuid| games.get(uuid).map_or(true, |game| game.status.is_over()))
+    }
+
+    //The boards where it is the host's move, numbered from 1, with the one that has waited longest
+    //first
+    pub fn queue<'a>(&self, games: &'a IndexMap<GameUuid, Game>) -> Vec<(usize, &'a Game)> {
+        let mut queue: Vec<(usize, &Game)> = self
+            .games
+            .iter()
+            .enumerate()
+            .filter_map(|(i, uuid)| Some((i + 1, games.get(uuid)?)))
+            .filter(|(_, game)| game.status == GameStatus::Playing && game.to_move() == self.host)
+            .collect();
+        queue.sort_by_key(|(_, game)| game.last_move_at.unwrap_or(game.started));
+        queue
+    }
+
+    //The host's wins, draws and losses in the games that have ended
+    pub fn score(&self, games: &IndexMap<GameUuid, Game>) -> (usize, usize, usize) {
+        let (mut wins, mut draws, mut losses) = (0, 0, 0);
+        for game in self.games.iter().filter_map(|uuid| games.get(uuid)) {
+            match (game.status, game.white == self.host) {
+                (GameStatus::WhiteWon, true) | (GameStatus::BlackWon, false) => wins += 1,
+                (GameStatus::Drawn, _) => draws += 1,
+                (GameStatus::WhiteWon, false) | (GameStatus::BlackWon, true) => losses += 1,
+                _ => {}
+            }
+        }
+        (wins, draws, losses)
+    }
+
+    //Every board with its result so far, and the host's score
+    pub fn summary(&self, games: &IndexMap<GameUuid, Game>) -> String {
+        let mut text = String::new();
+        for (i, (challenger, uuid)) in self.challengers.iter().zip(&self.games).enumerate() {
+            let game = match games.get(uuid) {
+                Some(game) => game,
+                None => continue,
+            };
+            let result = if game.status.is_over() {
+                game.status.to_string()
+            } else {
+                format!(
+                    "<@{}> to play move {}",
+                    game.to_move(),
+                    game.state().move_number()
+                )
+            };
+            text.push_str(&format!(
+                "Board {}: <@{}> in <#{}>, {}\n",
+                i + 1,
+                challenger,
+                game.channel,
+                result
+            ));
+        }
+        let (wins, draws, losses) = self.score(games);
+        let points = wins as f64 + draws as f64 / 2.0;
+        text.push_str(&format!(
+            "<@{}> scored {} from {} games (+{} ={} -{})",
+            self.host,
+            points,
+            wins + draws + losses,
+            wins,
+            draws,
+            losses
+        ));
+        text
+    }
+}
@@ -0,0 +1,522 @@
+use serenity::{
+    builder::CreateApplicationCommandOption,
+    framework::standard::CommandError,
+    model::{
+        id::UserId,
+        interactions::{
+            application_command::{
+                ApplicationCommand, ApplicationCommandInteraction,
+                ApplicationCommandInteractionDataOption, ApplicationCommandOptionType,
+                ApplicationCommandType, ResolvedTarget,
+            },
+            autocomplete::AutocompleteInteraction,
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use crate::permissions::is_officer;
+use crate::picker::Reply;
+use crate::{library, library_for, save_after_change, BookSort, Invocation};
+
+//The member facing library commands are also available as /library <subcommand>. They run the
+//same code as their ! counterparts. Officer and admin commands are still ! only, apart from
+//looking up a member from their right click menu
+
+//Shown when right clicking a member, under Apps
+const VIEW_LOANS: &str = "Library: view loans";
+
+//Discord refuses longer messages
+const MAX_MESSAGE_LEN: usize = 2000;
+
+//Subcommands that change the library and so are refused in maintenance mode
+const WRITES: &[&str] = &[
+    "checkout", "reserve", "return", "extend", "register", "wish",
+];
+
+//Subcommands that only look things up. Used in DMs, they are about the guild the member is
+//registered in
+const QUERIES: &[&str] = &["list", "mine", "wishlist"];
+
+//Options that take a book and suggest titles from the catalog as they are typed
+const BOOK_OPTIONS: &[&str] = &["book", "book-2", "book-3"];
+
+//Discord shows at most 25 suggestions, and refuses longer names for them
+const MAX_SUGGESTIONS: usize = 25;
+const MAX_SUGGESTION_LEN: usize = 100;
+
+fn subcommand<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+    description: &str,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .kind(ApplicationCommandOptionType::SubCommand)
+        .name(name)
+        .description(description)
+}
+
+fn argument<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    kind: ApplicationCommandOptionType,
+    name: &str,
+    description: &str,
+    required: bool,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .kind(kind)
+        .name(name)
+        .description(description)
+        .required(required);
+    if BOOK_OPTIONS.contains(&name) {
+        option.set_autocomplete(true);
+    }
+    option
+}
+
+//Called once the bot is connected. Discord can take up to an hour to show changes to global
+//commands
+pub async fn register_commands(ctx: &Context) {
+    use ApplicationCommandOptionType as Kind;
+
+    let result = ApplicationCommand::set_global_application_commands(&ctx.http, |commands| {
+        commands
+            .create_application_command(|command| {
+                command
+                    .name("library")
+                    .description("Browse and borrow books from the club library")
+                    .create_option(|o| {
+                        subcommand(
+                            o,
+                            "list",
+                            "Lists the books in the library and their availability",
+                        )
+                        .create_sub_option(|o| {
+                            argument(
+                                o,
+                                Kind::String,
+                                "author",
+                                "Only show books by this author",
+                                false,
+                            )
+                        })
+                        .create_sub_option(|o| {
+                            argument(o, Kind::String, "sort", "What to sort the books by", false)
+                                .add_string_choice("Title", "title")
+                                .add_string_choice("Author", "author")
+                                .add_string_choice("Date added", "added")
+                                .add_string_choice("Popularity", "popularity")
+                        })
+                        .create_sub_option(|o| {
+                            argument(o, Kind::String, "order", "Which way to sort", false)
+                                .add_string_choice("Ascending", "asc")
+                                .add_string_choice("Descending", "desc")
+                        })
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "mine", "Shows the books you currently have checked out")
+                    })
+                    .create_option(|o| {
+                        subcommand(
+                            o,
+                            "checkout",
+                            "Starts a checkout for up to 3 books or a series",
+                        )
+                        .create_sub_option(|o| {
+                            argument(o, Kind::String, "book", "Book name, ID or series", true)
+                        })
+                        .create_sub_option(|o| {
+                            argument(
+                                o,
+                                Kind::String,
+                                "book-2",
+                                "Another book to check out",
+                                false,
+                            )
+                        })
+                        .create_sub_option(|o| {
+                            argument(
+                                o,
+                                Kind::String,
+                                "book-3",
+                                "Another book to check out",
+                                false,
+                            )
+                        })
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "reserve", "Reserves a specific copy of a book")
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "book", "Book name or ID", true)
+                            })
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "copy", "Copy ID or edition", true)
+                            })
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "return", "Tells the officers you have given books back")
+                            .create_sub_option(|o| {
+                                argument(
+                                    o,
+                                    Kind::String,
+                                    "checkout",
+                                    "Checkout ID from /library mine or the book's name",
+                                    true,
+                                )
+                            })
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "checkout-2", "Another checkout", false)
+                            })
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "checkout-3", "Another checkout", false)
+                            })
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "extend", "Asks the officers for more time with a book")
+                            .create_sub_option(|o| {
+                                argument(
+                                    o,
+                                    Kind::String,
+                                    "checkout",
+                                    "Checkout ID from /library mine",
+                                    true,
+                                )
+                            })
+                            .create_sub_option(|o| {
+                                argument(
+                                    o,
+                                    Kind::Integer,
+                                    "days",
+                                    "How many more days you need",
+                                    true,
+                                )
+                            })
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "reason", "Why you need more time", true)
+                            })
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "register", "Registers you with the library")
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "name", "Your real name", false)
+                            })
+                            .create_sub_option(|o| {
+                                argument(
+                                    o,
+                                    Kind::User,
+                                    "member",
+                                    "Officers only: who to register",
+                                    false,
+                                )
+                            })
+                    })
+                    .create_option(|o| {
+                        subcommand(
+                            o,
+                            "wishlist",
+                            "Lists the books members would like the club to buy",
+                        )
+                    })
+                    .create_option(|o| {
+                        subcommand(o, "wish", "Suggests a book for the club to buy")
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "title", "The book's title", true)
+                            })
+                            .create_sub_option(|o| {
+                                argument(o, Kind::String, "author", "The book's author", true)
+                            })
+                    })
+            })
+            .create_application_command(|command| {
+                command.name(VIEW_LOANS).kind(ApplicationCommandType::User)
+            })
+    })
+    .await;
+
+    if let Err(err) = result {
+        println!("Failed to register slash commands: {:?}", err);
+    }
+}
+
+fn find_option<'a>(
+    options: &'a [ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Option<&'a serde_json::Value> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+}
+
+fn string_option(
+    options: &[ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Option<String> {
+    find_option(options, name)
+        .and_then(|value| value.as_str())
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+fn required_string(
+    options: &[ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Result<String, CommandError> {
+    string_option(options, name).ok_or_else(|| format!("Missing {}", name).into())
+}
+
+fn user_option(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Option<UserId> {
+    find_option(options, name)
+        .and_then(|value| value.as_str())
+        .and_then(|id| id.parse::<u64>().ok())
+        .map(UserId)
+}
+
+async fn run_subcommand(
+    ctx: &Context,
+    invocation: &Invocation,
+    name: &str,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> Result<Reply, CommandError> {
+    if WRITES.contains(&name)
+        && library_for(ctx, invocation.guild)
+            .await
+            .read()
+            .await
+            .maintenance
+    {
+        return Ok(Reply::from(crate::MAINTENANCE_MESSAGE.to_owned()));
+    }
+
+    let response = match name {
+        "list" => {
+            let sort = string_option(options, "sort").and_then(|sort| BookSort::parse(&sort));
+            let descending = string_option(options, "order").map(|order| order == "desc");
+            crate::list_books(
+                ctx,
+                invocation.guild,
+                string_option(options, "author"),
+                sort,
+                descending,
+            )
+            .await
+        }
+        "mine" => crate::my_checkouts(ctx, invocation.guild, invocation.author.id).await,
+        "checkout" => {
+            let inputs = ["book", "book-2", "book-3"]
+                .iter()
+                .filter_map(|name| string_option(options, name))
+                .collect();
+            //Can ask which book was meant, so it answers with more than text
+            return crate::checkout_books(ctx, invocation, Vec::new(), inputs).await;
+        }
+        "reserve" => {
+            let reservation = crate::find_copy_to_reserve(
+                ctx,
+                invocation.guild,
+                required_string(options, "book")?,
+                required_string(options, "copy")?,
+            )
+            .await?;
+            crate::start_checkout(ctx, invocation, vec![reservation]).await
+        }
+        "return" => {
+            let inputs = ["checkout", "checkout-2", "checkout-3"]
+                .iter()
+                .filter_map(|name| string_option(options, name))
+                .collect();
+            return crate::return_books(ctx, invocation, Vec::new(), inputs).await;
+        }
+        "extend" => {
+            let days = find_option(options, "days")
+                .and_then(|days| days.as_u64())
+                .filter(|days| *days > 0 && *days <= u32::MAX as u64)
+                .ok_or("days has to be a positive number")?;
+            crate::ask_for_extension(
+                ctx,
+                invocation,
+                required_string(options, "checkout")?,
+                days as u32,
+                required_string(options, "reason")?,
+            )
+            .await
+        }
+        "register" => {
+            let target = user_option(options, "member").unwrap_or(invocation.author.id);
+            let name = string_option(options, "name").unwrap_or_default();
+            crate::register_member(ctx, invocation, target, name).await
+        }
+        "wishlist" => crate::wishlist_text(ctx, invocation.guild).await,
+        "wish" => {
+            crate::add_wish(
+                ctx,
+                invocation,
+                required_string(options, "title")?,
+                required_string(options, "author")?,
+            )
+            .await
+        }
+        _ => Ok(format!("Unknown command \"{}\"", name)),
+    };
+    response.map(Reply::from)
+}
+
+//Suggests books from the catalog for the book option that is being typed in
+pub async fn handle_autocomplete(ctx: &Context, autocomplete: AutocompleteInteraction) {
+    if autocomplete.data.name != "library" {
+        return;
+    }
+    let focused = autocomplete
+        .data
+        .options
+        .first()
+        .and_then(|subcommand| subcommand.options.iter().find(|option| option.focused));
+    let focused = match focused {
+        Some(option) if BOOK_OPTIONS.contains(&option.name.as_str()) => option,
+        _ => return,
+    };
+    let input = focused
+        .value
+        .as_ref()
+        .and_then(|value| value.as_str())
+        .unwrap_or("");
+
+    let guild = match autocomplete.guild_id {
+        None => crate::dm_guild(ctx, autocomplete.user.id).await,
+        guild => guild,
+    };
+    let suggestions: Vec<(String, String)> = {
+        let library_arc = library_for(ctx, guild).await;
+        let library = library_arc.read().await;
+        library
+            .suggest_books(input, MAX_SUGGESTIONS)
+            .iter()
+            .map(|book| {
+                let name = format!("{} - {}", book.name, book.author);
+                (
+                    name.chars().take(MAX_SUGGESTION_LEN).collect(),
+                    library::Database::encode_uuid(book.uuid),
+                )
+            })
+            .collect()
+    };
+
+    let result = autocomplete
+        .create_autocomplete_response(&ctx.http, |r| {
+            for (name, id) in suggestions {
+                r.add_string_choice(name, id);
+            }
+            r
+        })
+        .await;
+    if let Err(err) = result {
+        println!("Failed to suggest books: {:?}", err);
+    }
+}
+
+//Officers can right click a member to see what they have borrowed, rather than typing !library
+//user-info with a mention. Only the officer sees the answer
+async fn view_loans(ctx: &Context, command: &ApplicationCommandInteraction) {
+    let user = match command.data.target() {
+        Some(ResolvedTarget::User(user, _)) => user,
+        _ => return,
+    };
+    let text = if is_officer(ctx, command.guild_id, command.user.id).await {
+        let library_arc = library_for(ctx, command.guild_id).await;
+        let library = library_arc.read().await;
+        match library.find_user_by_discord_id(&user.id.to_string()) {
+            Some(record) => crate::member_summary(&library, record.uuid, command.guild_id)
+                .unwrap_or_else(|err| format!("Error: {}", err)),
+            None => format!("{} isn't registered with the library", user.name),
+        }
+    } else {
+        "Only library officers can look up members".to_owned()
+    };
+    let text: String = text.chars().take(MAX_MESSAGE_LEN).collect();
+
+    let result = command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+    if let Err(err) = result {
+        println!("Failed to answer {}: {:?}", VIEW_LOANS, err);
+    }
+}
+
+pub async fn handle_command(ctx: &Context, command: ApplicationCommandInteraction) {
+    if command.data.name == VIEW_LOANS {
+        return view_loans(ctx, &command).await;
+    }
+    if command.data.name != "library" {
+        return;
+    }
+    let subcommand = match command.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return,
+    };
+    println!(
+        "Got slash command '{}' by user '{}'",
+        subcommand.name, command.user.name
+    );
+
+    //Replying is deferred so that posting to the officers channel doesn't run into discord's 3
+    //second limit for responding to an interaction
+    let deferred = command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+        })
+        .await;
+    if let Err(err) = deferred {
+        println!("Failed to respond to slash command: {:?}", err);
+        return;
+    }
+
+    let nick = command
+        .member
+        .as_ref()
+        .and_then(|member| member.nick.clone());
+    let guild = match command.guild_id {
+        None if QUERIES.contains(&subcommand.name.as_str()) => {
+            crate::dm_guild(ctx, command.user.id).await
+        }
+        guild => guild,
+    };
+    let invocation = Invocation {
+        guild,
+        channel: command.channel_id,
+        author: command.user.clone(),
+        display_name: nick.unwrap_or_else(|| command.user.name.clone()),
+    };
+    let result = run_subcommand(ctx, &invocation, &subcommand.name, &subcommand.options).await;
+    save_after_change(ctx, invocation.guild).await;
+
+    let reply = match result {
+        Ok(reply) => {
+            println!("Processed slash command '{}'", subcommand.name);
+            reply
+        }
+        Err(why) => {
+            println!(
+                "Slash command '{}' returned error {:?}",
+                subcommand.name, why
+            );
+            Reply::from(format!("Error: {}", why))
+        }
+    };
+    match command
+        .edit_original_interaction_response(&ctx.http, |r| {
+            r.content(&reply.content)
+                .components(|c| reply.components(c))
+        })
+        .await
+    {
+        Ok(message) => reply.track(&message),
+        Err(err) => println!("Failed to answer slash command: {:?}", err),
+    }
+}
@@ -0,0 +1,483 @@
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{Row, Transaction};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex as StdMutex;
+use tokio::sync::Mutex;
+
+use crate::library::{self, Database};
+use crate::storage::StorageError;
+
+//Set to a file path to keep the library in SQLite instead of the bincode blob. If the file
+//doesn't have a library in it yet, the blob is loaded one last time and copied over on the first
+//save
+const SQLITE_PATH_VAR: &str = "LIBRARY_SQLITE_PATH";
+
+//Every record also keeps its full json in `data` so that new fields don't need a schema change.
+//The other columns are there so the library can be queried from outside the bot
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS books (
+        uuid INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        author TEXT NOT NULL,
+        quantity INTEGER NOT NULL,
+        data TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS copies (
+        uuid INTEGER PRIMARY KEY,
+        book INTEGER NOT NULL,
+        edition TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS users (
+        uuid INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        discord_id TEXT NOT NULL,
+        read_name TEXT NOT NULL,
+        suspended INTEGER NOT NULL,
+        data TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS checkouts (
+        uuid INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        rentee INTEGER NOT NULL,
+        book INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        due_date TEXT,
+        data TEXT NOT NULL
+    )",
+    //Completed checkouts. Same columns as checkouts
+    "CREATE TABLE IF NOT EXISTS archived_checkouts (
+        uuid INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        rentee INTEGER NOT NULL,
+        book INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        due_date TEXT,
+        data TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        position INTEGER PRIMARY KEY,
+        time TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        description TEXT NOT NULL,
+        data TEXT NOT NULL
+    )",
+    //Everything that doesn't have its own table (wishlist, escalation policy, settings...), with
+    //a row per Database field
+    "CREATE TABLE IF NOT EXISTS state (
+        key TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    )",
+];
+
+//Database fields that are stored in their own tables and left out of the state document
+const TABLE_FIELDS: &[&str] = &[
+    "books",
+    "users",
+    "checkouts",
+    "archived_checkouts",
+    "audit_log",
+];
+
+//Libraries saved before every field of the state had its own row keep the whole state document
+//under this key. It is split up on the next save
+const STATE_KEY: &str = "database";
+
+//One pool per file, since every guild's library is kept in a separate file
+static POOLS: Lazy<StdMutex<HashMap<String, SqlitePool>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+//What was last written to each file, so that a save only writes the rows that changed since.
+//Holding the lock for the whole transaction also keeps saves in order
+static LAST_WRITTEN: Lazy<Mutex<HashMap<String, Written>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+//The position and json of the rows of a table, by key
+type Rows = HashMap<i64, (i64, String)>;
+
+//The rows of every table as they are in the file. The state table has a row per Database field
+//that doesn't have a table of its own
+#[derive(Default, PartialEq)]
+struct Written {
+    books: Rows,
+    users: Rows,
+    checkouts: Rows,
+    archived_checkouts: Rows,
+    //By position
+    audit_log: Rows,
+    state: HashMap<String, String>,
+}
+
+fn rows_of<'a, T: Serialize + 'a>(
+    records: impl Iterator<Item = (i64, &'a T)>,
+) -> Result<Rows, StorageError> {
+    let mut rows = HashMap::new();
+    for (position, (key, record)) in records.enumerate() {
+        rows.insert(key, (position as i64, serde_json::to_string(record)?));
+    }
+    Ok(rows)
+}
+
+impl Written {
+    //The rows `db` is written as
+    fn of(db: &Database) -> Result<Written, StorageError> {
+        let mut state = HashMap::new();
+        if let serde_json::Value::Object(fields) = serde_json::to_value(db)? {
+            for (field, value) in fields {
+                if !TABLE_FIELDS.contains(&field.as_str()) {
+                    state.insert(field, serde_json::to_string(&value)?);
+                }
+            }
+        }
+        Ok(Written {
+            books: rows_of(db.books.values().map(|book| (book.uuid as i64, book)))?,
+            users: rows_of(db.users.values().map(|user| (user.uuid as i64, user)))?,
+            checkouts: rows_of(
+                db.checkouts
+                    .values()
+                    .map(|checkout| (checkout.uuid as i64, checkout)),
+            )?,
+            archived_checkouts: rows_of(
+                db.archived_checkouts
+                    .values()
+                    .map(|checkout| (checkout.uuid as i64, checkout)),
+            )?,
+            audit_log: rows_of(
+                db.audit_log
+                    .iter()
+                    .enumerate()
+                    .map(|(position, entry)| (position as i64, entry)),
+            )?,
+            state,
+        })
+    }
+}
+
+//The keys of the rows that are new or different in `rows`, and the keys of the ones that are gone
+fn changes<K: Clone + Eq + Hash, V: PartialEq>(
+    written: &HashMap<K, V>,
+    rows: &HashMap<K, V>,
+) -> (Vec<K>, Vec<K>) {
+    let changed = rows
+        .iter()
+        .filter(|(key, row)| written.get(*key) != Some(*row))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let gone = written
+        .keys()
+        .filter(|key| !rows.contains_key(*key))
+        .cloned()
+        .collect();
+    (changed, gone)
+}
+
+pub fn configured_path() -> Option<String> {
+    std::env::var(SQLITE_PATH_VAR).ok()
+}
+
+//The home library uses LIBRARY_SQLITE_PATH as is. Other guilds get their id added before the
+//extension, so library.sqlite3 becomes library-<guild id>.sqlite3
+pub fn path_for(guild: Option<u64>) -> Option<String> {
+    let path = configured_path()?;
+    let guild = match guild {
+        Some(guild) => guild,
+        None => return Some(path),
+    };
+    let path = std::path::Path::new(&path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("library");
+    let file_name = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{}-{}.{}", stem, guild, extension),
+        None => format!("{}-{}", stem, guild),
+    };
+    Some(
+        path.with_file_name(file_name)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+async fn pool(path: &str) -> Result<SqlitePool, sqlx::Error> {
+    if let Some(pool) = POOLS.lock().unwrap().get(path) {
+        return Ok(pool.clone());
+    }
+
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    //A single connection is plenty for one bot and means transactions never wait on each other
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?;
+    for statement in SCHEMA {
+        sqlx::query(statement).execute(&pool).await?;
+    }
+
+    Ok(POOLS
+        .lock()
+        .unwrap()
+        .entry(path.to_owned())
+        .or_insert(pool)
+        .clone())
+}
+
+//Reads the records of `table` in order, noting the rows in `written`
+async fn read_table<T: DeserializeOwned>(
+    pool: &SqlitePool,
+    table: &str,
+    key: &str,
+    written: &mut Rows,
+) -> Result<Vec<T>, StorageError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} AS key, position, data FROM {} ORDER BY position",
+        key, table
+    ))
+    .fetch_all(pool)
+    .await?;
+    let mut records = Vec::with_capacity(rows.len());
+    for row in rows {
+        let data: String = row.try_get("data")?;
+        records.push(serde_json::from_str(&data)?);
+        written.insert(row.try_get("key")?, (row.try_get("position")?, data));
+    }
+    Ok(records)
+}
+
+//Returns None if the file doesn't have a library in it yet
+pub async fn load(path: &str) -> Result<Option<Database>, StorageError> {
+    let pool = pool(path).await?;
+    let mut written = Written::default();
+
+    let rows = sqlx::query("SELECT key, data FROM state")
+        .fetch_all(&pool)
+        .await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+    let mut state = serde_json::Map::new();
+    for row in rows {
+        let key: String = row.try_get("key")?;
+        let data: String = row.try_get("data")?;
+        let value: serde_json::Value = serde_json::from_str(&data)?;
+        match value {
+            serde_json::Value::Object(fields) if key == STATE_KEY => state.extend(fields),
+            value => {
+                state.insert(key.clone(), value);
+            }
+        }
+        written.state.insert(key, data);
+    }
+    state.insert("books".to_owned(), serde_json::json!({}));
+    state.insert("users".to_owned(), serde_json::json!({}));
+    state.insert("checkouts".to_owned(), serde_json::json!({}));
+    state.insert("archived_checkouts".to_owned(), serde_json::json!({}));
+    state.insert("audit_log".to_owned(), serde_json::json!([]));
+    let mut db: Database = serde_json::from_value(serde_json::Value::Object(state))?;
+
+    let books: Vec<library::Book> = read_table(&pool, "books", "uuid", &mut written.books).await?;
+    for book in books {
+        db.books.insert(book.uuid, book);
+    }
+    let users: Vec<library::User> = read_table(&pool, "users", "uuid", &mut written.users).await?;
+    for user in users {
+        db.users.insert(user.uuid, user);
+    }
+    let checkouts: Vec<library::CheckoutInstance> =
+        read_table(&pool, "checkouts", "uuid", &mut written.checkouts).await?;
+    for checkout in checkouts {
+        db.checkouts.insert(checkout.uuid, checkout);
+    }
+    let checkouts: Vec<library::CheckoutInstance> = read_table(
+        &pool,
+        "archived_checkouts",
+        "uuid",
+        &mut written.archived_checkouts,
+    )
+    .await?;
+    for checkout in checkouts {
+        db.archived_checkouts.insert(checkout.uuid, checkout);
+    }
+    db.audit_log = read_table(&pool, "audit_log", "position", &mut written.audit_log).await?;
+
+    LAST_WRITTEN.lock().await.insert(path.to_owned(), written);
+    Ok(Some(db))
+}
+
+async fn write_checkouts(
+    tx: &mut Transaction<'_, Sqlite>,
+    table: &str,
+    checkouts: &IndexMap<library::CheckoutUuid, library::CheckoutInstance>,
+    written: &Rows,
+    rows: &Rows,
+) -> Result<(), StorageError> {
+    let (changed, gone) = changes(written, rows);
+    for uuid in gone {
+        sqlx::query(&format!("DELETE FROM {} WHERE uuid = ?", table))
+            .bind(uuid)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for uuid in changed {
+        let checkout = &checkouts[&(uuid as library::CheckoutUuid)];
+        let (position, data) = &rows[&uuid];
+        sqlx::query(&format!(
+            "INSERT OR REPLACE INTO {} (uuid, position, rentee, book, status, due_date, data) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            table
+        ))
+        .bind(uuid)
+        .bind(*position)
+        .bind(checkout.rentee as i64)
+        .bind(checkout.book as i64)
+        .bind(format!("{:?}", checkout.status))
+        .bind(checkout.due_date.map(|due| due.to_rfc3339()))
+        .bind(data.as_str())
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
+
+//Writes the rows that changed since the last save in one transaction, so a crash part way through
+//leaves the previous save intact. Saves happen after every command, so each one holds what a
+//command changed
+pub async fn save(path: &str, db: &Database) -> Result<(), StorageError> {
+    let rows = Written::of(db)?;
+    let mut last_written = LAST_WRITTEN.lock().await;
+    let empty = Written::default();
+    let written = last_written.get(path).unwrap_or(&empty);
+    if *written == rows {
+        return Ok(());
+    }
+
+    let pool = pool(path).await?;
+    let mut tx = pool.begin().await?;
+
+    let (changed, gone) = changes(&written.books, &rows.books);
+    for uuid in gone {
+        sqlx::query("DELETE FROM books WHERE uuid = ?")
+            .bind(uuid)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM copies WHERE book = ?")
+            .bind(uuid)
+            .execute(&mut tx)
+            .await?;
+    }
+    for uuid in changed {
+        let book = &db.books[&(uuid as library::BookUuid)];
+        let (position, data) = &rows.books[&uuid];
+        sqlx::query(
+            "INSERT OR REPLACE INTO books (uuid, position, name, author, quantity, data) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(uuid)
+        .bind(*position)
+        .bind(book.name.as_str())
+        .bind(book.author.as_str())
+        .bind(book.quantity as i64)
+        .bind(data.as_str())
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query("DELETE FROM copies WHERE book = ?")
+            .bind(uuid)
+            .execute(&mut tx)
+            .await?;
+        for copy in &book.copies {
+            sqlx::query("INSERT OR REPLACE INTO copies (uuid, book, edition) VALUES (?, ?, ?)")
+                .bind(copy.uuid as i64)
+                .bind(uuid)
+                .bind(copy.edition.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+    }
+
+    let (changed, gone) = changes(&written.users, &rows.users);
+    for uuid in gone {
+        sqlx::query("DELETE FROM users WHERE uuid = ?")
+            .bind(uuid)
+            .execute(&mut tx)
+            .await?;
+    }
+    for uuid in changed {
+        let user = &db.users[&(uuid as library::UserUuid)];
+        let (position, data) = &rows.users[&uuid];
+        sqlx::query(
+            "INSERT OR REPLACE INTO users (uuid, position, discord_id, read_name, suspended, data) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(uuid)
+        .bind(*position)
+        .bind(user.discord_id.as_str())
+        .bind(user.read_name.as_str())
+        .bind(user.suspended)
+        .bind(data.as_str())
+        .execute(&mut tx)
+        .await?;
+    }
+
+    write_checkouts(
+        &mut tx,
+        "checkouts",
+        &db.checkouts,
+        &written.checkouts,
+        &rows.checkouts,
+    )
+    .await?;
+    write_checkouts(
+        &mut tx,
+        "archived_checkouts",
+        &db.archived_checkouts,
+        &written.archived_checkouts,
+        &rows.archived_checkouts,
+    )
+    .await?;
+
+    //Entries are only ever added at the end, so usually only the new ones are written
+    let (changed, gone) = changes(&written.audit_log, &rows.audit_log);
+    for position in gone {
+        sqlx::query("DELETE FROM audit_log WHERE position = ?")
+            .bind(position)
+            .execute(&mut tx)
+            .await?;
+    }
+    for position in changed {
+        let entry = &db.audit_log[position as usize];
+        let (_, data) = &rows.audit_log[&position];
+        sqlx::query(
+            "INSERT OR REPLACE INTO audit_log (position, time, actor, description, data) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(position)
+        .bind(entry.time.to_rfc3339())
+        .bind(entry.actor.as_str())
+        .bind(entry.description.as_str())
+        .bind(data.as_str())
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let (changed, gone) = changes(&written.state, &rows.state);
+    for key in gone {
+        sqlx::query("DELETE FROM state WHERE key = ?")
+            .bind(key)
+            .execute(&mut tx)
+            .await?;
+    }
+    for key in changed {
+        sqlx::query("INSERT OR REPLACE INTO state (key, data) VALUES (?, ?)")
+            .bind(key.as_str())
+            .bind(rows.state[&key].as_str())
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    last_written.insert(path.to_owned(), rows);
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use once_cell::sync::Lazy;
+use serenity::async_trait;
+use tokio::io::AsyncWriteExt;
+
+use crate::library::Database;
+
+pub type StorageError = Box<dyn std::error::Error + Send + Sync>;
+
+//Where libraries are kept between runs. Every guild's library is stored separately, with None
+//standing for the home library
+#[async_trait]
+pub trait Storage: Send + Sync {
+    //Returns None if there is no saved library yet. Indices are rebuilt by the caller
+    async fn load(&self, guild: Option<u64>) -> Result<Option<Database>, StorageError>;
+
+    async fn save(&self, db: &Database) -> Result<(), StorageError>;
+
+    //Makes a change that was just made survive a crash, without necessarily saving everything.
+    //Returns whether a full save is still needed
+    async fn journal(&self, db: &Database) -> Result<bool, StorageError>;
+}
+
+//Picked with LIBRARY_STORAGE: bincode (the default), json, toml, sqlite or memory. Setting
+//LIBRARY_SQLITE_PATH on its own also picks sqlite
+static BACKEND: Lazy<Box<dyn Storage>> = Lazy::new(|| {
+    let kind = std::env::var("LIBRARY_STORAGE").unwrap_or_else(|_| {
+        if crate::sqlite::configured_path().is_some() {
+            "sqlite".to_owned()
+        } else {
+            "bincode".to_owned()
+        }
+    });
+    match kind.to_ascii_lowercase().as_str() {
+        "json" => Box::new(FileStorage::new(FileFormat::Json)),
+        "toml" => Box::new(FileStorage::new(FileFormat::Toml)),
+        //Rows are kept as plain json so they can be queried from outside the bot
+        "sqlite" if crate::crypto::enabled() => panic!(
+            "LIBRARY_ENCRYPTION_KEY can't be used with sqlite storage, which isn't encrypted"
+        ),
+        "sqlite" => Box::new(SqliteStorage),
+        "memory" => {
+            println!("Using in memory storage. Nothing will be saved when the bot stops");
+            Box::new(MemoryStorage)
+        }
+        "bincode" => Box::new(FileStorage::new(FileFormat::Bincode)),
+        other => panic!(
+            "Unknown LIBRARY_STORAGE {}. Expected bincode, json, toml, sqlite or memory",
+            other
+        ),
+    }
+});
+
+pub fn backend() -> &'static dyn Storage {
+    &**BACKEND
+}
+
+//The file a guild's library is saved in. The home library keeps the original file name
+pub fn file_name(guild: Option<u64>, extension: &str) -> String {
+    match guild {
+        Some(guild) => format!("library-db-{}.{}", guild, extension),
+        None => format!("library-db.{}", extension),
+    }
+}
+
+//Writes the new copy next to the old one and only swaps it in once it is fully on disk, so a crash
+//part way through never leaves us without a readable file. The previous copy is kept as .bak
+async fn write_atomically(file_name: &str, data: &[u8]) -> Result<(), StorageError> {
+    let temp_name = format!("{}.tmp", file_name);
+    let mut file = tokio::fs::File::create(&temp_name).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    //Copied rather than renamed so that there is never a moment without a saved library
+    if tokio::fs::metadata(file_name).await.is_ok() {
+        tokio::fs::copy(file_name, format!("{}.bak", file_name)).await?;
+    }
+    tokio::fs::rename(&temp_name, file_name).await?;
+    //Make sure the rename itself survives a power loss
+    tokio::fs::File::open(".").await?.sync_all().await?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    //Compact, with a version header so that old files can be migrated
+    Bincode,
+    //Readable and editable by hand. Being self describing, it doesn't need migrations for new
+    //fields that have defaults
+    Json,
+    //Same as Json, for those who find toml easier to edit
+    Toml,
+}
+
+const FORMATS: [FileFormat; 3] = [FileFormat::Bincode, FileFormat::Json, FileFormat::Toml];
+
+//Toml has no null, so unset fields are left out instead
+fn remove_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let nulls: Vec<String> = map
+                .iter()
+                .filter(|(_, value)| value.is_null())
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in nulls {
+                map.remove(&key);
+            }
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(list) => list.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+impl FileFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Bincode => "bin",
+            FileFormat::Json => "json",
+            FileFormat::Toml => "toml",
+        }
+    }
+
+    //Works out which format a saved (and decrypted) file is in, so that a file still loads after
+    //LIBRARY_STORAGE is changed or it is renamed
+    pub fn detect(data: &[u8]) -> FileFormat {
+        //Bincode lengths are 8 bytes wide so it is full of zero bytes, which neither text format
+        //ever contains
+        if data.contains(&0) || std::str::from_utf8(data).is_err() {
+            return FileFormat::Bincode;
+        }
+        match data.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') => FileFormat::Json,
+            _ => FileFormat::Toml,
+        }
+    }
+
+    fn encode(self, db: &Database) -> Result<Vec<u8>, StorageError> {
+        Ok(match self {
+            FileFormat::Bincode => crate::migrations::encode(db)?,
+            FileFormat::Json => serde_json::to_vec_pretty(db)?,
+            FileFormat::Toml => {
+                //Toml only allows string keys, so go through json which writes ids as strings.
+                //Converting to a toml value first puts plain values ahead of tables, which toml
+                //requires
+                let mut value = serde_json::to_value(db)?;
+                remove_nulls(&mut value);
+                toml::to_string_pretty(&toml::Value::try_from(value)?)?.into_bytes()
+            }
+        })
+    }
+
+    async fn decode(self, file_name: &str, data: &[u8]) -> Result<Database, StorageError> {
+        match self {
+            FileFormat::Bincode => {
+                let (version, _) = crate::migrations::version_of(data);
+                let db = crate::migrations::decode(data)?;
+                if version < crate::migrations::CURRENT_VERSION {
+                    //Keep the old file around in case the migration got something wrong
+                    let backup = format!("{}.v{}.bak", file_name, version);
+                    if let Err(err) = tokio::fs::copy(file_name, &backup).await {
+                        println!("Failed to back up old library file to {}: {}", backup, err);
+                    }
+                    println!(
+                        "Migrated library from version {} to {}",
+                        version,
+                        crate::migrations::CURRENT_VERSION
+                    );
+                }
+                Ok(db)
+            }
+            FileFormat::Json => Ok(serde_json::from_slice(data)?),
+            //Back through json so that the ids written as strings turn back into numbers
+            FileFormat::Toml => {
+                let value: toml::Value = toml::from_slice(data)?;
+                Ok(serde_json::from_value(serde_json::to_value(value)?)?)
+            }
+        }
+    }
+}
+
+//One file per library, encrypted if LIBRARY_ENCRYPTION_KEY is set, with changes journaled in
+//between saves
+pub struct FileStorage {
+    format: FileFormat,
+}
+
+impl FileStorage {
+    pub fn new(format: FileFormat) -> FileStorage {
+        FileStorage { format }
+    }
+
+    fn file_name(&self, guild: Option<u64>) -> String {
+        file_name(guild, self.format.extension())
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn load(&self, guild: Option<u64>) -> Result<Option<Database>, StorageError> {
+        let file_name = self.file_name(guild);
+
+        //The library might still be in the file of the format that was used before LIBRARY_STORAGE
+        //was changed. It is written in the new format from the next save on
+        let mut sources = vec![file_name.clone()];
+        sources.extend(
+            FORMATS
+                .iter()
+                .filter(|format| **format != self.format)
+                .map(|format| FileStorage::new(*format).file_name(guild)),
+        );
+        for source in sources {
+            let data = match tokio::fs::read(&source).await {
+                Ok(data) => crate::crypto::decrypt(data)?,
+                Err(_) => continue,
+            };
+            let format = FileFormat::detect(&data);
+            let mut db = format.decode(&source, &data).await?;
+            db.guild = guild;
+            if source != file_name {
+                println!(
+                    "Loaded the library from {}. It will be saved to {} from now on",
+                    source, file_name
+                );
+            }
+
+            let (db, replayed) = crate::journal::replay(&source, db).await?;
+            if replayed > 0 {
+                println!("Replayed {} journal entries on top of {}", replayed, source);
+            }
+            return Ok(Some(db));
+        }
+
+        println!("No library file found at {}", file_name);
+        //A crash before the first save leaves only the journal behind
+        let mut db = Database::new();
+        db.guild = guild;
+        let (db, replayed) = crate::journal::replay(&file_name, db).await?;
+        if replayed == 0 {
+            return Ok(None);
+        }
+        println!("Rebuilt library from {} journal entries", replayed);
+        Ok(Some(db))
+    }
+
+    async fn save(&self, db: &Database) -> Result<(), StorageError> {
+        let file_name = self.file_name(db.guild);
+        let data = crate::crypto::encrypt(self.format.encode(db)?)?;
+        write_atomically(&file_name, &data).await?;
+        //Everything in the journal is in the file now
+        crate::journal::reset(&file_name, db).await?;
+
+        println!("Saved library database successfully");
+        Ok(())
+    }
+
+    async fn journal(&self, db: &Database) -> Result<bool, StorageError> {
+        crate::journal::record(&self.file_name(db.guild), db).await
+    }
+}
+
+//Every change is saved in its own transaction, so there is no journal
+pub struct SqliteStorage;
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn load(&self, guild: Option<u64>) -> Result<Option<Database>, StorageError> {
+        let path = crate::sqlite::path_for(guild).ok_or("LIBRARY_SQLITE_PATH isn't set")?;
+        if let Some(db) = crate::sqlite::load(&path).await? {
+            return Ok(Some(db));
+        }
+
+        //Copied over on the first save
+        let bincode = FileStorage::new(FileFormat::Bincode);
+        println!(
+            "No library in {} yet. Loading {} so it can be copied over",
+            path,
+            bincode.file_name(guild)
+        );
+        bincode.load(guild).await
+    }
+
+    async fn save(&self, db: &Database) -> Result<(), StorageError> {
+        let path = crate::sqlite::path_for(db.guild).ok_or("LIBRARY_SQLITE_PATH isn't set")?;
+        crate::sqlite::save(&path, db).await
+    }
+
+    async fn journal(&self, db: &Database) -> Result<bool, StorageError> {
+        self.save(db).await?;
+        Ok(false)
+    }
+}
+
+//Keeps nothing between runs. Handy for trying the bot out and for tests
+pub struct MemoryStorage;
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn load(&self, _guild: Option<u64>) -> Result<Option<Database>, StorageError> {
+        Ok(None)
+    }
+
+    async fn save(&self, _db: &Database) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn journal(&self, _db: &Database) -> Result<bool, StorageError> {
+        Ok(false)
+    }
+}
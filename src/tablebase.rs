@@ -0,0 +1,152 @@
+use shakmaty::{Color, Position};
+
+use std::env;
+
+use crate::rules::GameState;
+
+//Endgame tablebases know the exact result of every position with few enough pieces, so positions
+//with up to MAX_PIECES pieces are looked up in the Lichess tablebase instead of being left to the
+//engine's estimate. TABLEBASE_URL can point at another server with the same API. Syzygy files on
+//the bot's machine can be handed to the engine too, by setting SYZYGY_PATH, so that its search of
+//longer endgames plays into them
+
+const DEFAULT_URL: &str = "https://tablebase.lichess.ovh";
+//The most pieces, kings included, the tablebases cover
+pub const MAX_PIECES: usize = 7;
+
+type TablebaseError = Box<dyn std::error::Error + Send + Sync>;
+
+fn base_url() -> String {
+    env::var("TABLEBASE_URL").unwrap_or_else(|_| DEFAULT_URL.to_owned())
+}
+
+//The result of a position with perfect play, for the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wdl {
+    Win,
+    //A win that takes too long, so the fifty-move rule draws it
+    CursedWin,
+    Draw,
+    //A loss that takes too long, so the fifty-move rule saves it
+    BlessedLoss,
+    Loss,
+}
+
+impl Wdl {
+    fn parse(category: &str) -> Option<Wdl> {
+        match category {
+            "win" => Some(Wdl::Win),
+            "cursed-win" => Some(Wdl::CursedWin),
+            "draw" => Some(Wdl::Draw),
+            "blessed-loss" => Some(Wdl::BlessedLoss),
+            "loss" => Some(Wdl::Loss),
+            //Lichess says maybe-win, maybe-loss or unknown when it isn't sure
+            _ => None,
+        }
+    }
+
+    //The side to move's chances of winning, like Score::winning_chances. Wins the fifty-move rule
+    //draws are draws
+    pub fn winning_chances(self) -> f64 {
+        match self {
+            Wdl::Win => 1.0,
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 0.5,
+            Wdl::Loss => 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Probe {
+    pub wdl: Wdl,
+    //Half moves until the next capture or pawn move on the way to the result, with perfect play
+    pub dtz: Option<i32>,
+    //Half moves until mate with perfect play, when Lichess knows it
+    pub dtm: Option<i32>,
+    //The best move in UCI notation, None when there are no legal moves
+    pub best_move: Option<String>,
+}
+
+impl Probe {
+    //The result from the point of view of `turn`, the side to move, like "White wins (DTZ 13, mate
+    //in 9)"
+    pub fn describe(&self, turn: Color) -> String {
+        let (mover, other) = if turn == Color::White {
+            ("White", "Black")
+        } else {
+            ("Black", "White")
+        };
+        let mut text = match self.wdl {
+            Wdl::Win => format!("{} wins", mover),
+            Wdl::CursedWin => format!(
+                "{} wins, but not before the fifty-move rule draws the game",
+                mover
+            ),
+            Wdl::Draw => "Draw".to_owned(),
+            Wdl::BlessedLoss => format!(
+                "{} wins, but not before the fifty-move rule draws the game",
+                other
+            ),
+            Wdl::Loss => format!("{} wins", other),
+        };
+        let mut details = Vec::new();
+        if let Some(dtz) = self.dtz.filter(|_| self.wdl != Wdl::Draw) {
+            details.push(format!("DTZ {}", dtz.abs()));
+        }
+        if let Some(dtm) = self.dtm {
+            details.push(format!("mate in {}", (dtm.abs() + 1) / 2));
+        }
+        if !details.is_empty() {
+            text.push_str(&format!(" ({})", details.join(", ")));
+        }
+        text
+    }
+}
+
+//Whether the tablebases cover `state`. They know nothing of castling
+pub fn covers(state: &GameState) -> bool {
+    let position = state.position();
+    position.board().occupied().count() <= MAX_PIECES
+        && position.castles().castling_rights().is_empty()
+}
+
+//Looks `state` up in the tablebase. None when it isn't covered, the tablebase isn't sure of the
+//result or it couldn't be reached, in which case the engine has to do
+pub async fn probe(state: &GameState) -> Option<Probe> {
+    if !covers(state) {
+        return None;
+    }
+    match fetch(state).await {
+        Ok(probe) => probe,
+        Err(err) => {
+            println!(
+                "Failed to look up {} in the tablebase: {}",
+                state.fen(),
+                err
+            );
+            None
+        }
+    }
+}
+
+async fn fetch(state: &GameState) -> Result<Option<Probe>, TablebaseError> {
+    let fen = state.fen();
+    let response = reqwest::Client::new()
+        .get(format!("{}/standard", base_url()))
+        .query(&[("fen", fen.as_str())])
+        .send()
+        .await?
+        .error_for_status()?;
+    let json: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+    let wdl = match json["category"].as_str().and_then(Wdl::parse) {
+        Some(wdl) => wdl,
+        None => return Ok(None),
+    };
+    Ok(Some(Probe {
+        wdl,
+        dtz: json["dtz"].as_i64().map(|dtz| dtz as i32),
+        dtm: json["dtm"].as_i64().map(|dtm| dtm as i32),
+        //Lichess lists the moves best first
+        best_move: json["moves"][0]["uci"].as_str().map(str::to_owned),
+    }))
+}
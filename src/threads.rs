@@ -0,0 +1,79 @@
+use serenity::{
+    http::Http,
+    model::{
+        channel::Message,
+        id::{ChannelId, UserId},
+    },
+};
+
+use crate::library::MessageRef;
+
+//Every checkout gets a thread on the message officers approve it with. The rentee, the officers
+//and the bot talk about the checkout there, from the handout to the return, which keeps the
+//library channel down to one message per checkout. A thread started from a message has the same
+//id as the message, so nothing has to be saved to find it again
+
+//Discord refuses longer thread names
+const MAX_NAME_LEN: usize = 100;
+//Minutes without messages before Discord archives the thread. Posting in it unarchives it
+const AUTO_ARCHIVE_MINUTES: u16 = 1440;
+
+pub fn thread_of(approval_message: MessageRef) -> ChannelId {
+    ChannelId(approval_message.message)
+}
+
+//Starts a thread named `name` on `msg`, cutting the name short if Discord would refuse it. Only
+//works in guilds
+pub async fn start(http: impl AsRef<Http>, msg: &Message, name: &str) -> Option<ChannelId> {
+    let name: String = name.chars().take(MAX_NAME_LEN).collect();
+    let thread = msg
+        .channel_id
+        .create_public_thread(&http, msg.id, |t| {
+            t.name(name).auto_archive_duration(AUTO_ARCHIVE_MINUTES)
+        })
+        .await;
+    match thread {
+        Ok(thread) => Some(thread.id),
+        Err(err) => {
+            println!("Failed to open a thread on message {}: {:?}", msg.id, err);
+            None
+        }
+    }
+}
+
+//Starts the thread for the checkout approved with `approval_msg`. Only works in guilds
+pub async fn open(http: impl AsRef<Http>, approval_msg: &Message, name: &str, rentee: UserId) {
+    let thread = match start(&http, approval_msg, name).await {
+        Some(thread) => thread,
+        None => return,
+    };
+    //Mentioning the rentee adds them to the thread
+    let text = format!(
+        "<@{}> This thread is for your checkout. You'll hear here when an officer hands you the book(s), when they are due and when the return is confirmed",
+        rentee
+    );
+    if let Err(err) = thread.say(&http, text).await {
+        println!("Failed to post in checkout thread: {:?}", err);
+    }
+}
+
+//Posts `text` in the thread of the checkout approved with `approval_message`. Returns false if
+//that didn't work, for example because the checkout was started in a DM or before checkouts
+//had threads
+pub async fn post(
+    http: impl AsRef<Http>,
+    approval_message: Option<MessageRef>,
+    text: &str,
+) -> bool {
+    let thread = match approval_message {
+        Some(approval_message) => thread_of(approval_message),
+        None => return false,
+    };
+    match thread.say(&http, text).await {
+        Ok(_) => true,
+        Err(err) => {
+            println!("Failed to post in checkout thread: {:?}", err);
+            false
+        }
+    }
+}
@@ -0,0 +1,1495 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::{
+        id::ChannelId,
+        interactions::{
+            message_component::MessageComponentInteraction,
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use std::time::Duration;
+
+use crate::clocks::Clock;
+use crate::games::GameUuid;
+use crate::library::{Database, ManipulationError, ManipulationErrorType, TimeType};
+use crate::ratings::ClubRating;
+
+//Tournaments the club runs, from registration to final standings. Officers create them, members
+//join while registration is open, and once an officer starts the tournament the bot pairs each
+//round as soon as the previous one has all its results. Games are usually played over the board,
+//so results are entered by hand rather than taken from !chess games.
+//Shortly before the start officers can open check-in, which posts a button with a custom id of the
+//form tournament-check-in:<tournament id>. Players who haven't checked in by the time the
+//tournament starts are withdrawn, so that nobody gets paired against someone who isn't there.
+//Players report their own results. A result only counts once the opponent confirms it, by reporting
+//the same result or with the buttons under the report, whose custom ids are of the form
+//tournament-result:<tournament id>:<round>:<board>:<result>:<confirm|dispute>. Officers act as
+//arbiters and their results count straight away.
+//Arenas are played online instead, with !chess games. For a fixed number of minutes after the start
+//arena.rs pairs players as soon as they are free, and takes the results from their games
+
+pub type TournamentUuid = u32;
+
+pub const DEFAULT_CHECK_IN_MINUTES: i64 = 15;
+pub const MAX_CHECK_IN_MINUTES: i64 = 24 * 60;
+//Keeps pairing a round quick, since it happens while the library is locked
+pub const MAX_PLAYERS: usize = 256;
+//How many opponents pair_up tries before giving up on the rule it was given. Enough to get around
+//a few rematches, while a round that can't be paired without breaking the rule gives up in
+//milliseconds rather than trying every way to pair the players
+const MAX_PAIRING_STEPS: usize = 20_000;
+//The buttons under a reported result are taken off by flows.rs after this long
+pub const CLAIM_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+//Points for a win. Draws are worth half
+const WIN: f64 = 1.0;
+//Points for a win in an arena, doubled on a streak. Draws are worth half
+const ARENA_WIN: f64 = 2.0;
+//Arena players are on a streak after this many wins in a row, until they don't win a game
+const STREAK_WINS: usize = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentFormat {
+    //Everyone plays every round against someone on the same score they haven't played yet
+    Swiss { rounds: u32 },
+    //Single elimination: the loser of each game is out, until one player is left. Players are
+    //seeded by rating so that the strongest only meet late on, and the top seeds get the byes
+    //when the field isn't a power of two
+    Knockout,
+    //Players are paired with whoever else is free for `minutes` after the start, playing with
+    //`clock`, and score as many points as they can
+    Arena { minutes: u32, clock: Clock },
+}
+
+impl std::fmt::Display for TournamentFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            TournamentFormat::Swiss { rounds } => write!(fmt, "Swiss, {} rounds", rounds),
+            TournamentFormat::Knockout => write!(fmt, "knockout"),
+            TournamentFormat::Arena { minutes, clock } => {
+                write!(fmt, "arena, {} minutes of {}", minutes, clock)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentStatus {
+    Registration,
+    Running,
+    Finished,
+}
+
+impl std::fmt::Display for TournamentStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            TournamentStatus::Registration => write!(fmt, "open for registration"),
+            TournamentStatus::Running => write!(fmt, "running"),
+            TournamentStatus::Finished => write!(fmt, "finished"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    WhiteWon,
+    BlackWon,
+    Draw,
+}
+
+impl Outcome {
+    pub fn parse(input: &str) -> Option<Outcome> {
+        match input.to_ascii_lowercase().as_str() {
+            "1-0" => Some(Outcome::WhiteWon),
+            "0-1" => Some(Outcome::BlackWon),
+            "½-½" | "1/2-1/2" | "draw" => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+
+    //What white scored, for ratings: 1 for a win, ½ for a draw, 0 for a loss
+    pub fn white_score(self) -> f64 {
+        self.points().0 / WIN
+    }
+
+    //The points white and black get
+    fn points(self) -> (f64, f64) {
+        match self {
+            Outcome::WhiteWon => (WIN, 0.0),
+            Outcome::BlackWon => (0.0, WIN),
+            Outcome::Draw => (WIN / 2.0, WIN / 2.0),
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Outcome::WhiteWon => write!(fmt, "1-0"),
+            Outcome::BlackWon => write!(fmt, "0-1"),
+            Outcome::Draw => write!(fmt, "½-½"),
+        }
+    }
+}
+
+//A result one of the players reported, waiting for the other to confirm it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Claim {
+    //Discord id of the player who reported it
+    pub player: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pairing {
+    //Discord ids of the players
+    pub white: String,
+    //None when white has a bye, which counts as a win and is entered as 1-0 straight away
+    pub black: Option<String>,
+    pub result: Option<Outcome>,
+    pub claim: Option<Claim>,
+}
+
+impl Pairing {
+    pub fn has_player(&self, player: &str) -> bool {
+        self.white == player || self.black.as_deref() == Some(player)
+    }
+
+    //The other player, for someone playing in this pairing. None for byes
+    pub fn opponent_of(&self, player: &str) -> Option<&str> {
+        if self.white == player {
+            self.black.as_deref()
+        } else {
+            Some(&self.white)
+        }
+    }
+
+    //Who went through, for knockout games
+    pub fn winner(&self) -> Option<&str> {
+        match (self.result?, &self.black) {
+            (Outcome::WhiteWon, _) => Some(&self.white),
+            (Outcome::BlackWon, Some(black)) => Some(black),
+            _ => None,
+        }
+    }
+
+    fn points_of(&self, player: &str) -> f64 {
+        match (self.result, &self.black) {
+            (Some(result), Some(_)) => {
+                let (white, black) = result.points();
+                if self.white == player {
+                    white
+                } else {
+                    black
+                }
+            }
+            (_, None) => WIN,
+            (None, _) => 0.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Round {
+    //In board order
+    pub pairings: Vec<Pairing>,
+}
+
+impl Round {
+    pub fn is_complete(&self) -> bool {
+        self.pairings.iter().all(|pairing| pairing.result.is_some())
+    }
+}
+
+//A player's place in the standings
+pub struct Standing {
+    pub player: String,
+    pub points: f64,
+    //The sum of the player's opponents' points, which breaks ties
+    pub buchholz: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, new)]
+pub struct Tournament {
+    pub uuid: TournamentUuid,
+    pub name: String,
+    pub format: TournamentFormat,
+    //Discord id of the officer who created it
+    pub organiser: String,
+    //Where it was created. Pairings are posted there
+    pub channel: u64,
+    #[new(value = "TournamentStatus::Registration")]
+    pub status: TournamentStatus,
+    //Discord ids, in the order they joined. Knockouts sort them by seed when they start
+    #[new(default)]
+    pub players: Vec<String>,
+    #[new(default)]
+    pub rounds: Vec<Round>,
+    #[new(value = "chrono::Local::now()")]
+    pub created: TimeType,
+    //The message in `channel` showing a knockout's bracket or an arena's standings, which is
+    //edited as results come in
+    #[new(default)]
+    pub bracket_message: Option<u64>,
+    //Set once check-in is opened. Players can check in until then
+    #[new(default)]
+    pub check_in_deadline: Option<TimeType>,
+    #[new(default)]
+    pub checked_in: Vec<String>,
+    //When an arena stops pairing players. Set when it starts
+    #[new(default)]
+    pub ends: Option<TimeType>,
+    //Arena players who left while it was running. They keep their points but aren't paired until
+    //they join again
+    #[new(default)]
+    pub paused: Vec<String>,
+    //The game of each arena pairing, in the same order as the pairings
+    #[new(default)]
+    pub games: Vec<GameUuid>,
+}
+
+impl Tournament {
+    fn id(&self) -> String {
+        Database::encode_uuid(self.uuid)
+    }
+
+    fn check_in_over(&self) -> bool {
+        self.check_in_deadline
+            .map_or(false, |deadline| chrono::Local::now() > deadline)
+    }
+
+    pub fn is_arena(&self) -> bool {
+        matches!(self.format, TournamentFormat::Arena { .. })
+    }
+
+    //Whether a running arena is still pairing players at `now`
+    pub fn arena_open(&self, now: TimeType) -> bool {
+        self.status == TournamentStatus::Running && self.ends.map_or(false, |ends| now < ends)
+    }
+
+    //Puts discord id `to` wherever the tournament has `from`, for members who are forgotten
+    pub fn replace_player(&mut self, from: &str, to: &str) {
+        let pairings = self
+            .rounds
+            .iter_mut()
+            .flat_map(|round| round.pairings.iter_mut());
+        let mut ids: Vec<&mut String> = vec![&mut self.organiser];
+        ids.extend(self.players.iter_mut());
+        ids.extend(self.checked_in.iter_mut());
+        ids.extend(self.paused.iter_mut());
+        for pairing in pairings {
+            ids.push(&mut pairing.white);
+            ids.extend(pairing.black.as_mut());
+            ids.extend(pairing.claim.as_mut().map(|claim| &mut claim.player));
+        }
+        for id in ids {
+            if *id == from {
+                *id = to.to_owned();
+            }
+        }
+        //Merged accounts that had both joined are one player now. Pairings between them stay, as
+        //the rounds they were in have been played
+        for list in vec![&mut self.players, &mut self.checked_in, &mut self.paused] {
+            let mut seen = std::collections::HashSet::new();
+            list.retain(|id| seen.insert(id.clone()));
+        }
+    }
+
+    //Players joining while check-in is open are there, so they are checked in straight away.
+    //Arenas can be joined until they end, and players who left one come back
+    pub fn join(&mut self, player: &str) -> Result<(), ManipulationError> {
+        if self.is_arena() && self.status == TournamentStatus::Running {
+            if !self.arena_open(chrono::Local::now()) {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::RegistrationClosed(self.id()),
+                ));
+            }
+            let before = self.paused.len();
+            self.paused.retain(|p| p != player);
+            if self.paused.len() == before && self.players.iter().any(|p| p == player) {
+                return Err(ManipulationError::new(
+                    ManipulationErrorType::AlreadyJoined(self.id()),
+                ));
+            }
+            if !self.players.iter().any(|p| p == player) {
+                self.check_room()?;
+                self.players.push(player.to_owned());
+            }
+            return Ok(());
+        }
+        if self.status != TournamentStatus::Registration || self.check_in_over() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::RegistrationClosed(self.id()),
+            ));
+        }
+        if self.players.iter().any(|p| p == player) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::AlreadyJoined(self.id()),
+            ));
+        }
+        self.check_room()?;
+        self.players.push(player.to_owned());
+        if self.check_in_deadline.is_some() {
+            self.checked_in.push(player.to_owned());
+        }
+        Ok(())
+    }
+
+    fn check_room(&self) -> Result<(), ManipulationError> {
+        if self.players.len() >= MAX_PLAYERS {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::TournamentFull(self.id()),
+            ));
+        }
+        Ok(())
+    }
+
+    //Opens check-in for the next `minutes`. Opening it again moves the deadline
+    pub fn open_check_in(&mut self, minutes: i64) -> Result<TimeType, ManipulationError> {
+        if self.status != TournamentStatus::Registration {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::TournamentAlreadyStarted(self.id()),
+            ));
+        }
+        let deadline = chrono::Local::now() + chrono::Duration::minutes(minutes);
+        self.check_in_deadline = Some(deadline);
+        Ok(deadline)
+    }
+
+    //Checks `player` in. Returns false if they already were
+    pub fn check_in(&mut self, player: &str) -> Result<bool, ManipulationError> {
+        if self.status != TournamentStatus::Registration || self.check_in_deadline.is_none() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CheckInNotOpen(self.id()),
+            ));
+        }
+        if self.check_in_over() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CheckInClosed(self.id()),
+            ));
+        }
+        if !self.players.iter().any(|p| p == player) {
+            return Err(ManipulationError::new(ManipulationErrorType::NotJoined(
+                self.id(),
+            )));
+        }
+        if self.checked_in.iter().any(|p| p == player) {
+            return Ok(false);
+        }
+        self.checked_in.push(player.to_owned());
+        Ok(true)
+    }
+
+    //Players leaving a running arena aren't paired again, but keep their points and finish the game
+    //they are playing
+    pub fn leave(&mut self, player: &str) -> Result<(), ManipulationError> {
+        if self.is_arena() && self.arena_open(chrono::Local::now()) {
+            if !self.players.iter().any(|p| p == player) || self.paused.iter().any(|p| p == player)
+            {
+                return Err(ManipulationError::new(ManipulationErrorType::NotJoined(
+                    self.id(),
+                )));
+            }
+            self.paused.push(player.to_owned());
+            return Ok(());
+        }
+        if self.status != TournamentStatus::Registration {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::RegistrationClosed(self.id()),
+            ));
+        }
+        let before = self.players.len();
+        self.players.retain(|p| p != player);
+        self.checked_in.retain(|p| p != player);
+        if self.players.len() == before {
+            return Err(ManipulationError::new(ManipulationErrorType::NotJoined(
+                self.id(),
+            )));
+        }
+        Ok(())
+    }
+
+    //Closes registration and pairs the first round. Ratings decide who is seeded highest. When
+    //check-in was opened, players who didn't check in are withdrawn first. Returns them
+    pub fn start(
+        &mut self,
+        ratings: &IndexMap<String, ClubRating>,
+    ) -> Result<Vec<String>, ManipulationError> {
+        if self.status != TournamentStatus::Registration {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::TournamentAlreadyStarted(self.id()),
+            ));
+        }
+        let (present, withdrawn): (Vec<String>, Vec<String>) =
+            self.players.iter().cloned().partition(|player| {
+                self.check_in_deadline.is_none() || self.checked_in.contains(player)
+            });
+        if present.len() < 2 {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::NotEnoughPlayers(self.id()),
+            ));
+        }
+        self.players = present;
+        self.status = TournamentStatus::Running;
+        if self.format == TournamentFormat::Knockout {
+            self.players = self.ranked(ratings);
+        }
+        if let TournamentFormat::Arena { minutes, .. } = self.format {
+            //Arenas have a single round, which gets a pairing every time two players are free
+            self.ends = Some(chrono::Local::now() + chrono::Duration::minutes(minutes as i64));
+            self.rounds.push(Round::default());
+        } else {
+            self.pair_next_round(ratings);
+        }
+        Ok(withdrawn)
+    }
+
+    //The game on `board`, counting from 1, in the current round, to enter `outcome` for
+    fn current_board(
+        &mut self,
+        board: usize,
+        outcome: Outcome,
+    ) -> Result<&mut Pairing, ManipulationError> {
+        if self.status != TournamentStatus::Running {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::TournamentNotRunning(self.id()),
+            ));
+        }
+        let id = self.id();
+        if self.format == TournamentFormat::Knockout && outcome == Outcome::Draw {
+            return Err(ManipulationError::new(ManipulationErrorType::NeedsWinner(
+                id,
+            )));
+        }
+        self.rounds
+            .last_mut()
+            .expect("running tournaments have a round")
+            .pairings
+            .get_mut(board.wrapping_sub(1))
+            .filter(|pairing| pairing.black.is_some())
+            .ok_or_else(|| {
+                ManipulationError::new(ManipulationErrorType::UnknownBoard(id, board.to_string()))
+            })
+    }
+
+    //Once every result of the current round is in, pairs the next round, or ends the tournament
+    //after the last one. Returns whether that happened. Arenas end once their time is up and
+    //every game has finished
+    fn finish_round(&mut self, ratings: &IndexMap<String, ClubRating>) -> bool {
+        if !self.rounds.last().map_or(false, Round::is_complete) {
+            return false;
+        }
+        if self.is_arena() {
+            if self.arena_open(chrono::Local::now()) {
+                return false;
+            }
+            self.status = TournamentStatus::Finished;
+            return true;
+        }
+        if self.rounds.len() as u32 >= self.rounds_total() {
+            self.status = TournamentStatus::Finished;
+        } else {
+            self.pair_next_round(ratings);
+        }
+        true
+    }
+
+    //Enters the result of `board` as an arbiter, replacing whatever was reported. Returns whether
+    //that finished the round
+    pub fn set_result(
+        &mut self,
+        board: usize,
+        outcome: Outcome,
+        ratings: &IndexMap<String, ClubRating>,
+    ) -> Result<bool, ManipulationError> {
+        let pairing = self.current_board(board, outcome)?;
+        pairing.result = Some(outcome);
+        pairing.claim = None;
+        Ok(self.finish_round(ratings))
+    }
+
+    //`player` reports the result of their game on `board`. It counts if their opponent already
+    //reported the same result, otherwise it waits for the opponent to confirm it
+    pub fn report(
+        &mut self,
+        board: usize,
+        outcome: Outcome,
+        player: &str,
+        ratings: &IndexMap<String, ClubRating>,
+    ) -> Result<Report, ManipulationError> {
+        let id = self.id();
+        if self.is_arena() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::ResultsFromGames(id),
+            ));
+        }
+        let pairing = self.current_board(board, outcome)?;
+        if !pairing.has_player(player) {
+            return Err(ManipulationError::new(ManipulationErrorType::NotYourBoard(
+                id,
+                board.to_string(),
+            )));
+        }
+        if pairing.result.is_some() {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::ResultAlreadyEntered(id, board.to_string()),
+            ));
+        }
+        match &pairing.claim {
+            Some(claim) if claim.player != player && claim.outcome == outcome => {
+                pairing.result = Some(outcome);
+                pairing.claim = None;
+                Ok(Report::Confirmed {
+                    round_over: self.finish_round(ratings),
+                })
+            }
+            _ => {
+                let opponent = pairing.opponent_of(player).unwrap_or_default().to_owned();
+                pairing.claim = Some(Claim {
+                    player: player.to_owned(),
+                    outcome,
+                });
+                Ok(Report::Claimed { opponent })
+            }
+        }
+    }
+
+    //Confirms or disputes `reported`, the result reported for `board` of `round`, both counting
+    //from 1. Only the reporter's opponent can, or an arbiter, who is passed as None
+    pub fn answer_claim(
+        &mut self,
+        round: usize,
+        board: usize,
+        reported: Outcome,
+        player: Option<&str>,
+        confirm: bool,
+        ratings: &IndexMap<String, ClubRating>,
+    ) -> Result<Answer, ManipulationError> {
+        let id = self.id();
+        let unknown = || {
+            ManipulationError::new(ManipulationErrorType::NoClaim(
+                id.clone(),
+                board.to_string(),
+            ))
+        };
+        if self.status != TournamentStatus::Running || round != self.rounds.len() {
+            return Err(unknown());
+        }
+        let pairing = self.rounds[round - 1]
+            .pairings
+            .get_mut(board.wrapping_sub(1))
+            .ok_or_else(unknown)?;
+        //The claim might have been replaced by a different one since the buttons were posted
+        let claim = pairing
+            .claim
+            .clone()
+            .filter(|claim| claim.outcome == reported)
+            .ok_or_else(unknown)?;
+        if player.map_or(false, |player| {
+            claim.player == player || !pairing.has_player(player)
+        }) {
+            return Err(ManipulationError::new(
+                ManipulationErrorType::CantAnswerClaim(id, board.to_string()),
+            ));
+        }
+        pairing.claim = None;
+        if !confirm {
+            return Ok(Answer::Disputed);
+        }
+        pairing.result = Some(claim.outcome);
+        Ok(Answer::Confirmed {
+            outcome: claim.outcome,
+            round_over: self.finish_round(ratings),
+        })
+    }
+
+    fn pairings_of<'a>(&'a self, player: &'a str) -> impl Iterator<Item = &'a Pairing> + 'a {
+        self.rounds
+            .iter()
+            .flat_map(|round| &round.pairings)
+            .filter(move |pairing| pairing.has_player(player))
+    }
+
+    pub fn points(&self, player: &str) -> f64 {
+        if self.is_arena() {
+            return self.arena_score(player).0;
+        }
+        self.pairings_of(player)
+            .map(|pairing| pairing.points_of(player))
+            .sum()
+    }
+
+    //An arena player's points, and whether they are on a streak. Wins score ARENA_WIN and draws
+    //half that, both doubled on a streak
+    fn arena_score(&self, player: &str) -> (f64, bool) {
+        let (mut points, mut wins) = (0.0, 0);
+        for pairing in self.pairings_of(player) {
+            if pairing.result.is_none() || pairing.black.is_none() {
+                continue;
+            }
+            let multiplier = if wins >= STREAK_WINS { 2.0 } else { 1.0 };
+            points += pairing.points_of(player) / WIN * ARENA_WIN * multiplier;
+            if pairing.winner() == Some(player) {
+                wins += 1;
+            } else {
+                wins = 0;
+            }
+        }
+        (points, wins >= STREAK_WINS)
+    }
+
+    //Whether `player` is playing a game in the arena that hasn't finished
+    fn arena_busy(&self, player: &str) -> bool {
+        self.pairings_of(player)
+            .any(|pairing| pairing.result.is_none())
+    }
+
+    //Pairs the arena players who are free with each other, best ranked first, and returns the
+    //new pairings' boards, counting from 1. `busy` says whether two players already have a game
+    //going outside the arena. Players don't get the opponent they just played when someone else is
+    //free, and when an odd number are free the lowest ranked waits for the next one
+    pub fn pair_arena(
+        &mut self,
+        ratings: &IndexMap<String, ClubRating>,
+        busy: &dyn Fn(&str, &str) -> bool,
+    ) -> Vec<usize> {
+        if !self.arena_open(chrono::Local::now()) {
+            return Vec::new();
+        }
+        let mut free: Vec<String> = self
+            .ranked(ratings)
+            .into_iter()
+            .filter(|player| !self.paused.contains(player) && !self.arena_busy(player))
+            .collect();
+        if free.len() % 2 == 1 {
+            free.pop();
+        }
+
+        let last_opponent = |player: &str| {
+            self.pairings_of(player)
+                .last()
+                .and_then(|pairing| pairing.opponent_of(player))
+                .map(str::to_owned)
+        };
+        let players: Vec<&str> = free.iter().map(String::as_str).collect();
+        let pairs = pair_up(&players, &|a, b| {
+            !busy(a, b) && last_opponent(a).as_deref() != Some(b)
+        })
+        .or_else(|| pair_up(&players, &|a, b| !busy(a, b)))
+        .unwrap_or_default();
+        let first = self.rounds.last().map_or(0, |round| round.pairings.len());
+        let pairings: Vec<Pairing> = pairs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (higher, lower))| {
+                let (white, black) = self.colours(higher, lower, first + i);
+                Pairing {
+                    white: white.to_owned(),
+                    black: Some(black.to_owned()),
+                    result: None,
+                    claim: None,
+                }
+            })
+            .collect();
+        let round = self
+            .rounds
+            .last_mut()
+            .expect("running tournaments have a round");
+        round.pairings.extend(pairings);
+        (first + 1..=round.pairings.len()).collect()
+    }
+
+    //Enters the result of the arena game on `board`, counting from 1, once it is over. Returns
+    //whether that ended the arena
+    pub fn arena_result(
+        &mut self,
+        board: usize,
+        outcome: Outcome,
+        ratings: &IndexMap<String, ClubRating>,
+    ) -> bool {
+        let pairing = match self
+            .rounds
+            .last_mut()
+            .and_then(|round| round.pairings.get_mut(board.wrapping_sub(1)))
+        {
+            Some(pairing) if pairing.result.is_none() => pairing,
+            _ => return false,
+        };
+        pairing.result = Some(outcome);
+        self.finish_round(ratings)
+    }
+
+    //Ends an arena whose time is up once its last game is over. Returns whether it ended
+    pub fn close_arena(&mut self, ratings: &IndexMap<String, ClubRating>) -> bool {
+        self.is_arena() && self.status == TournamentStatus::Running && self.finish_round(ratings)
+    }
+
+    fn have_played(&self, a: &str, b: &str) -> bool {
+        self.pairings_of(a)
+            .any(|pairing| pairing.opponent_of(a) == Some(b))
+    }
+
+    fn had_bye(&self, player: &str) -> bool {
+        self.pairings_of(player)
+            .any(|pairing| pairing.black.is_none())
+    }
+
+    //How many more games `player` had with white than with black
+    fn colour_balance(&self, player: &str) -> i32 {
+        self.pairings_of(player)
+            .filter(|pairing| pairing.black.is_some())
+            .map(|pairing| if pairing.white == player { 1 } else { -1 })
+            .sum()
+    }
+
+    //Whether `player` had white in their last game. None before their first
+    fn last_had_white(&self, player: &str) -> Option<bool> {
+        self.pairings_of(player)
+            .filter(|pairing| pairing.black.is_some())
+            .last()
+            .map(|pairing| pairing.white == player)
+    }
+
+    //Who gets white between `higher` and `lower`, the higher ranked player. Whoever had white
+    //less often gets it, then whoever had black last. In the first round colours alternate down
+    //the boards
+    fn colours<'a>(&self, higher: &'a str, lower: &'a str, board: usize) -> (&'a str, &'a str) {
+        let higher_white = match self.colour_balance(higher).cmp(&self.colour_balance(lower)) {
+            std::cmp::Ordering::Less => true,
+            std::cmp::Ordering::Greater => false,
+            std::cmp::Ordering::Equal => match self.last_had_white(higher) {
+                Some(had_white) => !had_white,
+                None => board % 2 == 0,
+            },
+        };
+        if higher_white {
+            (higher, lower)
+        } else {
+            (lower, higher)
+        }
+    }
+
+    //The players, best first: by points, then rating
+    fn ranked(&self, ratings: &IndexMap<String, ClubRating>) -> Vec<String> {
+        let rating = |player: &str| ratings.get(player).map_or(0.0, |rating| rating.rating);
+        let mut players = self.players.clone();
+        players.sort_by(|a, b| {
+            self.points(b)
+                .partial_cmp(&self.points(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    rating(b)
+                        .partial_cmp(&rating(a))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        });
+        players
+    }
+
+    fn pair_next_round(&mut self, ratings: &IndexMap<String, ClubRating>) {
+        match self.format {
+            TournamentFormat::Swiss { .. } => self.pair_swiss(ratings),
+            TournamentFormat::Knockout => self.pair_knockout(),
+            //Pairing is automatic in arenas, pair_arena pairs players as soon as they are free
+            TournamentFormat::Arena { .. } => {}
+        }
+    }
+
+    fn pair_swiss(&mut self, ratings: &IndexMap<String, ClubRating>) {
+        let mut players = self.ranked(ratings);
+        //With an odd number of players, the lowest ranked one who hasn't had a bye yet sits out
+        let bye = if players.len() % 2 == 1 {
+            let index = players
+                .iter()
+                .rposition(|player| !self.had_bye(player))
+                .unwrap_or(players.len() - 1);
+            Some(players.remove(index))
+        } else {
+            None
+        };
+
+        let players: Vec<&str> = players.iter().map(String::as_str).collect();
+        //Rematches are only allowed when there is no other way to pair everyone, which can
+        //happen in small tournaments with many rounds
+        let pairs = pair_up(&players, &|a, b| !self.have_played(a, b))
+            .or_else(|| pair_up(&players, &|_, _| true))
+            .unwrap_or_default();
+        let mut round = Round::default();
+        for (board, (higher, lower)) in pairs.into_iter().enumerate() {
+            let (white, black) = self.colours(higher, lower, board);
+            round.pairings.push(Pairing {
+                white: white.to_owned(),
+                black: Some(black.to_owned()),
+                result: None,
+                claim: None,
+            });
+        }
+        if let Some(bye) = bye {
+            round.pairings.push(Pairing {
+                white: bye,
+                black: None,
+                result: Some(Outcome::WhiteWon),
+                claim: None,
+            });
+        }
+        self.rounds.push(round);
+    }
+
+    //Players' seeds, counting from 1
+    fn seed(&self, player: &str) -> usize {
+        self.players
+            .iter()
+            .position(|p| p == player)
+            .map_or(self.players.len(), |index| index + 1)
+    }
+
+    //The first round pairs the seeds so that 1 and 2 can only meet in the final, 1 to 4 in the
+    //semi-finals and so on. Later rounds pair the winners of neighbouring boards
+    fn pair_knockout(&mut self) {
+        let through: Vec<Option<String>> = match self.rounds.last() {
+            Some(round) => round
+                .pairings
+                .iter()
+                .map(|pairing| pairing.winner().map(str::to_owned))
+                .collect(),
+            None => {
+                let mut positions = vec![1];
+                while positions.len() < self.bracket_size() {
+                    let size = positions.len() * 2;
+                    positions = positions
+                        .iter()
+                        .flat_map(|&seed| [seed, size + 1 - seed])
+                        .collect();
+                }
+                positions
+                    .into_iter()
+                    .map(|seed| self.players.get(seed - 1).cloned())
+                    .collect()
+            }
+        };
+
+        let mut round = Round::default();
+        for (board, pair) in through.chunks(2).enumerate() {
+            let pairing = match pair {
+                [Some(a), Some(b)] => {
+                    let (higher, lower) = if self.seed(a) < self.seed(b) {
+                        (a, b)
+                    } else {
+                        (b, a)
+                    };
+                    let (white, black) = self.colours(higher, lower, board);
+                    Pairing {
+                        white: white.to_owned(),
+                        black: Some(black.to_owned()),
+                        result: None,
+                        claim: None,
+                    }
+                }
+                //Seeds past the number of players are byes, which only happen in the first round
+                [Some(player), None] | [None, Some(player)] => Pairing {
+                    white: player.clone(),
+                    black: None,
+                    result: Some(Outcome::WhiteWon),
+                    claim: None,
+                },
+                _ => continue,
+            };
+            round.pairings.push(pairing);
+        }
+        self.rounds.push(round);
+    }
+
+    //The number of places in a knockout's first round
+    fn bracket_size(&self) -> usize {
+        self.players.len().next_power_of_two().max(2)
+    }
+
+    fn rounds_total(&self) -> u32 {
+        match self.format {
+            TournamentFormat::Swiss { rounds } => rounds,
+            TournamentFormat::Knockout => self.bracket_size().trailing_zeros(),
+            TournamentFormat::Arena { .. } => 1,
+        }
+    }
+
+    fn round_name(&self, round: usize) -> String {
+        let left = self.rounds_total() as usize - round;
+        match (self.format, left) {
+            (TournamentFormat::Arena { .. }, _) => "games".to_owned(),
+            (TournamentFormat::Knockout, 0) => "Final".to_owned(),
+            (TournamentFormat::Knockout, 1) => "Semi-finals".to_owned(),
+            (TournamentFormat::Knockout, 2) => "Quarter-finals".to_owned(),
+            _ => format!("Round {} of {}", round, self.rounds_total()),
+        }
+    }
+
+    //A knockout's rounds so far, with everyone's seeds and the winner once there is one
+    pub fn bracket_text(&self) -> String {
+        let mut text = format!("**{}** bracket", self.name);
+        if self.rounds.is_empty() {
+            text.push_str("\nThe bracket is drawn when the tournament starts");
+        }
+        for (index, round) in self.rounds.iter().enumerate() {
+            text.push_str(&format!("\n__{}__", self.round_name(index + 1)));
+            for pairing in &round.pairings {
+                let white = format!("({}) <@{}>", self.seed(&pairing.white), pairing.white);
+                match (&pairing.black, pairing.result) {
+                    (Some(black), result) => text.push_str(&format!(
+                        "\n{} {} <@{}> ({})",
+                        white,
+                        result.map_or("vs".to_owned(), |result| result.to_string()),
+                        black,
+                        self.seed(black)
+                    )),
+                    (None, _) => text.push_str(&format!("\n{} bye", white)),
+                }
+            }
+        }
+        let champion = match (self.status, self.rounds.last()) {
+            (TournamentStatus::Finished, Some(round)) => {
+                round.pairings.first().and_then(Pairing::winner)
+            }
+            _ => None,
+        };
+        if let Some(champion) = champion {
+            text.push_str(&format!("\n🏆 <@{}> wins!", champion));
+        }
+        text
+    }
+
+    //The boards of `round`, counting from 1, with the results entered so far
+    pub fn round_text(&self, round: usize) -> Option<String> {
+        let pairings = &self.rounds.get(round.checked_sub(1)?)?.pairings;
+        let mut text = format!("**{}** {}", self.name, self.round_name(round));
+        for (board, pairing) in pairings.iter().enumerate() {
+            match (&pairing.black, pairing.result) {
+                (Some(black), Some(result)) => text.push_str(&format!(
+                    "\nBoard {}: <@{}> {} <@{}>",
+                    board + 1,
+                    pairing.white,
+                    result,
+                    black
+                )),
+                (Some(black), None) => text.push_str(&format!(
+                    "\nBoard {}: <@{}> vs <@{}>",
+                    board + 1,
+                    pairing.white,
+                    black
+                )),
+                (None, _) => text.push_str(&format!("\nBye: <@{}>", pairing.white)),
+            }
+        }
+        Some(text)
+    }
+
+    //An arena's standings, with how long it has left and who is on a streak
+    pub fn arena_text(&self) -> String {
+        let now = chrono::Local::now();
+        let mut text = match (self.status, self.ends) {
+            (TournamentStatus::Registration, _) | (_, None) => {
+                format!("**{}** hasn't started yet", self.name)
+            }
+            (TournamentStatus::Finished, _) => format!("**{}** final standings", self.name),
+            (TournamentStatus::Running, Some(ends)) if now < ends => format!(
+                "**{}** standings, {} minute(s) left",
+                self.name,
+                (ends - now).num_minutes() + 1
+            ),
+            (TournamentStatus::Running, Some(_)) => format!(
+                "**{}** standings. Time is up, waiting for the last games to finish",
+                self.name
+            ),
+        };
+        for (place, standing) in self.standings().iter().enumerate() {
+            let (_, streak) = self.arena_score(&standing.player);
+            let games = self
+                .pairings_of(&standing.player)
+                .filter(|pairing| pairing.result.is_some())
+                .count();
+            text.push_str(&format!(
+                "\n{}. <@{}> {} from {} game(s){}{}",
+                place + 1,
+                standing.player,
+                format_points(standing.points),
+                games,
+                if streak { " 🔥" } else { "" },
+                if self.paused.contains(&standing.player) {
+                    " (left)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        text
+    }
+
+    //Standings for Swiss tournaments and arenas, the bracket for knockouts
+    pub fn standings_text(&self) -> String {
+        if self.format == TournamentFormat::Knockout {
+            return self.bracket_text();
+        }
+        if self.is_arena() {
+            return self.arena_text();
+        }
+        let mut text = match self.rounds.len() {
+            0 => format!("**{}** hasn't started yet", self.name),
+            rounds => format!("**{}** standings after round {}", self.name, rounds),
+        };
+        for (place, standing) in self.standings().iter().enumerate() {
+            text.push_str(&format!(
+                "\n{}. <@{}> {} (Buchholz {})",
+                place + 1,
+                standing.player,
+                format_points(standing.points),
+                format_points(standing.buchholz)
+            ));
+        }
+        text
+    }
+
+    //Everyone's points and tiebreak, best first
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .map(|player| Standing {
+                player: player.clone(),
+                points: self.points(player),
+                buchholz: self
+                    .pairings_of(player)
+                    .filter_map(|pairing| pairing.opponent_of(player))
+                    .map(|opponent| self.points(opponent))
+                    .sum(),
+            })
+            .collect();
+        standings.sort_by(|a, b| {
+            b.points
+                .partial_cmp(&a.points)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    b.buchholz
+                        .partial_cmp(&a.buchholz)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        });
+        standings
+    }
+}
+
+pub enum Report {
+    //Waiting for `opponent` to confirm it
+    Claimed { opponent: String },
+    //The opponent had already reported the same result
+    Confirmed { round_over: bool },
+}
+
+pub enum Answer {
+    Confirmed { outcome: Outcome, round_over: bool },
+    Disputed,
+}
+
+//Pairs `players`, which are ranked best first, so that each plays someone close in the ranking
+//that `allowed` accepts. Tries the closest opponents first and backs up when the players left
+//can't all be paired. None when there is no way to pair everyone, or when none was found within
+//MAX_PAIRING_STEPS
+fn pair_up<'a>(
+    players: &[&'a str],
+    allowed: &dyn Fn(&str, &str) -> bool,
+) -> Option<Vec<(&'a str, &'a str)>> {
+    let mut steps = 0;
+    pair_within(players, allowed, &mut steps)
+}
+
+fn pair_within<'a>(
+    players: &[&'a str],
+    allowed: &dyn Fn(&str, &str) -> bool,
+    steps: &mut usize,
+) -> Option<Vec<(&'a str, &'a str)>> {
+    let (first, rest) = match players.split_first() {
+        Some(split) => split,
+        None => return Some(Vec::new()),
+    };
+    for (i, opponent) in rest.iter().enumerate() {
+        *steps += 1;
+        if *steps > MAX_PAIRING_STEPS {
+            return None;
+        }
+        if !allowed(first, opponent) {
+            continue;
+        }
+        let mut others = rest.to_vec();
+        others.remove(i);
+        if let Some(mut pairs) = pair_within(&others, allowed, steps) {
+            pairs.insert(0, (*first, *opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+//Points with halves written as ½, like 2½
+pub fn format_points(points: f64) -> String {
+    let whole = points.floor();
+    match (whole as u32, points - whole >= 0.5) {
+        (0, true) => "½".to_owned(),
+        (whole, true) => format!("{}½", whole),
+        (whole, false) => whole.to_string(),
+    }
+}
+
+//Called when someone presses the check-in button of a tournament
+pub async fn handle_check_in(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let text = {
+        let library_arc = crate::library_for(ctx, component.guild_id).await;
+        let mut library = library_arc.write().await;
+        if library.maintenance {
+            crate::MAINTENANCE_MESSAGE.to_owned()
+        } else {
+            let player = component.user.id.to_string();
+            let result = library.find_tournament(id).and_then(|uuid| {
+                let tournament = library.tournaments.get_mut(&uuid).unwrap();
+                tournament
+                    .check_in(&player)
+                    .map(|new| (new, tournament.name.clone()))
+            });
+            match result {
+                Ok((true, name)) => format!("You're checked in for {}. Good luck!", name),
+                Ok((false, name)) => format!("You were already checked in for {}", name),
+                Err(why) => format!("Error: {}", why),
+            }
+        }
+    };
+    crate::save_after_change(ctx, component.guild_id).await;
+
+    let response = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(text)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await;
+    if let Err(err) = response {
+        println!("Failed to answer tournament check-in: {:?}", err);
+    }
+}
+
+//Posts a knockout's bracket or an arena's standings in its channel the first time, and edits that
+//message afterwards
+pub async fn update_bracket(http: &Http, library: &mut Database, uuid: TournamentUuid) {
+    let tournament = match library.tournaments.get_mut(&uuid) {
+        Some(tournament) if tournament.format == TournamentFormat::Knockout => tournament,
+        Some(tournament) if tournament.is_arena() && !tournament.rounds.is_empty() => tournament,
+        _ => return,
+    };
+    let channel = ChannelId(tournament.channel);
+    let text = tournament.standings_text();
+    let result = match tournament.bracket_message {
+        Some(message) => channel
+            .edit_message(http, message, |m| m.content(text))
+            .await
+            .map(|_| ()),
+        None => channel
+            .send_message(http, |m| {
+                m.content(text).allowed_mentions(|a| a.empty_parse())
+            })
+            .await
+            .map(|message| tournament.bracket_message = Some(message.id.0)),
+    };
+    if let Err(err) = result {
+        println!(
+            "Failed to update the bracket of tournament {}: {:?}",
+            Database::encode_uuid(uuid),
+            err
+        );
+    }
+}
+
+//Posts the pairings of the current round of `uuid` in the tournament's channel
+pub async fn post_round(http: &Http, library: &Database, uuid: TournamentUuid) {
+    let tournament = match library.tournaments.get(&uuid) {
+        Some(tournament) => tournament,
+        None => return,
+    };
+    let text = match tournament.status {
+        TournamentStatus::Finished => format!(
+            "{}\nThe tournament is over, congratulations to everyone who played!",
+            tournament.standings_text()
+        ),
+        TournamentStatus::Running if tournament.is_arena() => format!(
+            "**{}** has started! Until {} players are paired as soon as they are free, and play with !chess move. Wins score {} points, draws half that, and both are doubled after {} wins in a row. Join late with !tournament join {}, or take a break with !tournament leave",
+            tournament.name,
+            tournament
+                .ends
+                .map_or_else(String::new, |ends| ends.format("%H:%M").to_string()),
+            ARENA_WIN,
+            STREAK_WINS,
+            Database::encode_uuid(uuid)
+        ),
+        _ => match tournament.round_text(tournament.rounds.len()) {
+            Some(text) => format!(
+                "{}\nReport your result with !tournament result {} <board> <1-0|0-1|½-½>",
+                text,
+                Database::encode_uuid(uuid)
+            ),
+            None => return,
+        },
+    };
+    if let Err(err) = ChannelId(tournament.channel)
+        .send_message(http, |m| m.content(text))
+        .await
+    {
+        println!(
+            "Failed to post the pairings of tournament {}: {:?}",
+            Database::encode_uuid(uuid),
+            err
+        );
+    }
+}
+
+//Called when someone presses confirm or dispute under a reported result
+pub async fn handle_result_answer(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+    id: &str,
+) {
+    let parts: Vec<&str> = id.split(':').collect();
+    let (tournament, round, board, reported, confirm) = match parts.as_slice() {
+        [tournament, round, board, reported, answer] => {
+            match (round.parse(), board.parse(), Outcome::parse(reported)) {
+                (Ok(round), Ok(board), Some(reported)) => {
+                    (*tournament, round, board, reported, *answer == "confirm")
+                }
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+    let arbiter = crate::permissions::is_officer(ctx, component.guild_id, component.user.id).await;
+
+    let result = {
+        let library_arc = crate::library_for(ctx, component.guild_id).await;
+        let mut library = library_arc.write().await;
+        if library.maintenance {
+            Err(crate::MAINTENANCE_MESSAGE.to_owned())
+        } else {
+            //Officers answer as arbiters
+            let player = Some(component.user.id.to_string()).filter(|_| !arbiter);
+            let library = &mut *library;
+            let answered = library.find_tournament(tournament).and_then(|uuid| {
+                library
+                    .tournaments
+                    .get_mut(&uuid)
+                    .unwrap()
+                    .answer_claim(
+                        round,
+                        board,
+                        reported,
+                        player.as_deref(),
+                        confirm,
+                        &library.club_ratings,
+                    )
+                    .map(|answer| (uuid, answer))
+            });
+            match answered {
+                Ok((uuid, Answer::Confirmed { outcome, round_over })) => {
+                    library.archive_tournament_game(uuid, round, board);
+                    update_bracket(&ctx.http, library, uuid).await;
+                    if round_over {
+                        post_round(&ctx.http, library, uuid).await;
+                    }
+                    Ok(format!(
+                        "Board {}: {}, confirmed by <@{}>",
+                        board, outcome, component.user.id
+                    ))
+                }
+                Ok((_, Answer::Disputed)) => Ok(format!(
+                    "<@{}> disputed the result of board {}. Report it again, or ask an officer to enter it",
+                    component.user.id, board
+                )),
+                Err(why) => Err(format!("Error: {}", why)),
+            }
+        }
+    };
+    crate::save_after_change(ctx, component.guild_id).await;
+
+    let response = match result {
+        Ok(text) => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(text)
+                                .allowed_mentions(|a| a.empty_parse())
+                                .components(|c| c)
+                        })
+                })
+                .await
+        }
+        Err(text) => {
+            component
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content(text)
+                                .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                        })
+                })
+                .await
+        }
+    };
+    if let Err(err) = response {
+        println!("Failed to answer a tournament result: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Players p1, p2... rated so that p1 is the highest
+    fn tournament(
+        format: TournamentFormat,
+        players: usize,
+    ) -> (Tournament, IndexMap<String, ClubRating>) {
+        let mut tournament = Tournament::new(1, "Test".to_owned(), format, "o".to_owned(), 0);
+        let mut ratings = IndexMap::new();
+        for i in 1..=players {
+            let player = format!("p{}", i);
+            tournament.join(&player).unwrap();
+            let rating = ClubRating {
+                rating: 2000.0 - 50.0 * i as f64,
+                ..ClubRating::default()
+            };
+            ratings.insert(player, rating);
+        }
+        (tournament, ratings)
+    }
+
+    fn draw_round(tournament: &mut Tournament, ratings: &IndexMap<String, ClubRating>) {
+        let boards: Vec<usize> = tournament
+            .rounds
+            .last()
+            .unwrap()
+            .pairings
+            .iter()
+            .enumerate()
+            .filter(|(_, pairing)| pairing.black.is_some())
+            .map(|(index, _)| index + 1)
+            .collect();
+        for board in boards {
+            tournament
+                .set_result(board, Outcome::Draw, ratings)
+                .unwrap();
+        }
+    }
+
+    fn players_of(pairing: &Pairing) -> (String, Option<String>) {
+        let mut players = (pairing.white.clone(), pairing.black.clone());
+        if let Some(black) = &players.1 {
+            if *black < players.0 {
+                players = (black.clone(), Some(players.0));
+            }
+        }
+        players
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let (mut tournament, ratings) = tournament(TournamentFormat::Swiss { rounds: 3 }, 4);
+        tournament.start(&ratings).unwrap();
+        for _ in 0..3 {
+            draw_round(&mut tournament, &ratings);
+        }
+        assert_eq!(tournament.status, TournamentStatus::Finished);
+        let mut games: Vec<(String, Option<String>)> = tournament
+            .rounds
+            .iter()
+            .flat_map(|round| round.pairings.iter().map(players_of))
+            .collect();
+        assert_eq!(games.len(), 6);
+        games.sort();
+        games.dedup();
+        assert_eq!(
+            games.len(),
+            6,
+            "someone was paired twice with the same opponent"
+        );
+    }
+
+    #[test]
+    fn swiss_byes_go_to_the_lowest_ranked_once() {
+        let (mut tournament, ratings) = tournament(TournamentFormat::Swiss { rounds: 3 }, 5);
+        tournament.start(&ratings).unwrap();
+        let mut byes = Vec::new();
+        for _ in 0..3 {
+            let round = tournament.rounds.last().unwrap();
+            let round_byes: Vec<&Pairing> = round
+                .pairings
+                .iter()
+                .filter(|pairing| pairing.black.is_none())
+                .collect();
+            assert_eq!(round_byes.len(), 1);
+            assert_eq!(round_byes[0].result, Some(Outcome::WhiteWon));
+            byes.push(round_byes[0].white.clone());
+            draw_round(&mut tournament, &ratings);
+        }
+        assert_eq!(byes[0], "p5");
+        assert_eq!(tournament.points("p5"), 1.0 + 2.0 * WIN / 2.0);
+        byes.sort();
+        byes.dedup();
+        assert_eq!(byes.len(), 3);
+    }
+
+    #[test]
+    fn knockouts_keep_the_top_seeds_apart() {
+        let (mut tournament, ratings) = tournament(TournamentFormat::Knockout, 8);
+        tournament.start(&ratings).unwrap();
+        let first: Vec<(String, Option<String>)> = tournament.rounds[0]
+            .pairings
+            .iter()
+            .map(players_of)
+            .collect();
+        let expected = [("p1", "p8"), ("p4", "p5"), ("p2", "p7"), ("p3", "p6")];
+        assert_eq!(first.len(), expected.len());
+        for ((white, black), (higher, lower)) in first.iter().zip(expected.iter()) {
+            assert_eq!(white, higher);
+            assert_eq!(black.as_deref(), Some(*lower));
+        }
+    }
+
+    #[test]
+    fn knockout_byes_go_to_the_top_seeds() {
+        let (mut tournament, ratings) = tournament(TournamentFormat::Knockout, 6);
+        tournament.start(&ratings).unwrap();
+        let byes: Vec<&str> = tournament.rounds[0]
+            .pairings
+            .iter()
+            .filter(|pairing| pairing.black.is_none())
+            .map(|pairing| pairing.white.as_str())
+            .collect();
+        assert_eq!(byes, ["p1", "p2"]);
+        assert_eq!(tournament.rounds[0].pairings.len(), 4);
+    }
+
+    #[test]
+    fn pairing_gives_up_rather_than_searching_forever() {
+        let names: Vec<String> = (0..40).map(|i| format!("p{}", i)).collect();
+        let players: Vec<&str> = names.iter().map(String::as_str).collect();
+        assert_eq!(pair_up(&players, &|_, _| true).unwrap().len(), 20);
+        //Nobody can play p39, which without a limit would mean trying every way to pair the rest
+        assert!(pair_up(&players, &|a, b| a != "p39" && b != "p39").is_none());
+    }
+
+    #[test]
+    fn full_tournaments_refuse_players() {
+        let (mut tournament, _) = tournament(TournamentFormat::Knockout, MAX_PLAYERS);
+        assert!(tournament.join("late").is_err());
+    }
+}
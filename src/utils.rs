@@ -18,3 +18,40 @@ pub fn cmp_ignore_case_ascii(a: &str, b: &str) -> bool {
         .find(|&ordering| ordering != Ordering::Equal)
         .is_none()
 }
+
+//Lowercases, strips punctuation and collapses whitespace so that "Silman's  Complete Endgame
+//Course" and "silmans complete endgame course" compare equal
+pub fn normalize_title(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//Whether every character of `needle` appears in `haystack` in the same order, so that "mysys"
+//matches "my system"
+pub fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|wanted| haystack.any(|c| c == wanted))
+}
+
+//Number of single character insertions, deletions, or substitutions needed to turn a into b
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
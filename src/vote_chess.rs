@@ -0,0 +1,304 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, GuildId},
+    prelude::RwLock,
+};
+use shakmaty::Color;
+
+use std::sync::Arc;
+
+use crate::games::{Game, GameStatus};
+use crate::guilds::Libraries;
+use crate::library::{Database, ManipulationError, ManipulationErrorType, TimeType};
+use crate::rules::GameState;
+
+//Vote chess, where the whole server plays the engine. An officer starts a game in a channel
+//with the command !vote start, and members vote on the server's move with !vote <move> until
+//voting closes a day later. The move with the most votes is then played, ties going to whichever
+//was voted for first, and the engine answers straight away, so the game goes on at a move a day.
+//Each guild has one game at a time, saved with the library
+
+//How often the vote task checks whether voting has closed
+const VOTE_CHECK_SECS: u64 = 60;
+//How long voting on each move stays open
+const VOTE_HOURS: i64 = 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoteGame {
+    //Where the game is played
+    pub channel: u64,
+    pub server_white: bool,
+    //The engine's level, as in !chess play-bot
+    pub level: u8,
+    //In SAN, like Game's
+    pub moves: Vec<String>,
+    //Discord id of each voter and the move they voted for, in SAN. Members who change their vote
+    //are moved to the end
+    pub votes: IndexMap<String, String>,
+    //When voting on the server's move closes
+    pub deadline: TimeType,
+    pub status: GameStatus,
+    //Discord id of the officer who started it
+    pub started_by: String,
+    pub started: TimeType,
+}
+
+impl VoteGame {
+    pub fn new(channel: u64, server_white: bool, level: u8, started_by: String) -> VoteGame {
+        let now = chrono::Local::now();
+        VoteGame {
+            channel,
+            server_white,
+            level,
+            moves: Vec::new(),
+            votes: IndexMap::new(),
+            deadline: now + chrono::Duration::hours(VOTE_HOURS),
+            status: GameStatus::Playing,
+            started_by,
+            started: now,
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        GameState::replay(None, &self.moves)
+    }
+
+    pub fn server_colour(&self) -> Color {
+        if self.server_white {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    pub fn server_to_move(&self) -> bool {
+        self.status == GameStatus::Playing && self.state().turn() == self.server_colour()
+    }
+
+    pub fn engine_to_move(&self) -> bool {
+        self.status == GameStatus::Playing && self.state().turn() != self.server_colour()
+    }
+
+    //Records `voter`'s vote for `input`, replacing any vote they already made, and returns the
+    //move in SAN
+    pub fn vote(&mut self, voter: String, input: &str) -> Result<String, ManipulationError> {
+        if !self.server_to_move() {
+            return Err(ManipulationError::new(ManipulationErrorType::NotYourTurn));
+        }
+        let state = self.state();
+        let m = state.parse_move(input).ok_or_else(|| {
+            ManipulationError::new(ManipulationErrorType::IllegalMove(input.to_owned()))
+        })?;
+        let san = shakmaty::san::SanPlus::from_move(state.position().clone(), &m).to_string();
+        self.votes.shift_remove(&voter);
+        self.votes.insert(voter, san.clone());
+        Ok(san)
+    }
+
+    //The moves voted for and how many votes each has, most first. Ties are in the order the moves
+    //were first voted for
+    pub fn tally(&self) -> Vec<(String, usize)> {
+        let mut tally: IndexMap<String, usize> = IndexMap::new();
+        for san in self.votes.values() {
+            *tally.entry(san.clone()).or_insert(0) += 1;
+        }
+        let mut tally: Vec<(String, usize)> = tally.into_iter().collect();
+        tally.sort_by(|a, b| b.1.cmp(&a.1));
+        tally
+    }
+
+    //Plays `input`, in SAN or UCI, for whoever is to move. Starts the next vote and finishes the
+    //game if the move ended it. Returns the move in SAN
+    pub fn play(&mut self, input: &str) -> Option<String> {
+        let mut state = self.state();
+        let m = state.parse_move(input)?;
+        let san = state.play(&m);
+        self.moves.push(san.clone());
+        self.votes.clear();
+        self.deadline = chrono::Local::now() + chrono::Duration::hours(VOTE_HOURS);
+        if let Some(end) = state.end() {
+            self.status = match end.winner() {
+                Some(Color::White) => GameStatus::WhiteWon,
+                Some(Color::Black) => GameStatus::BlackWon,
+                None => GameStatus::Drawn,
+            };
+        }
+        Some(san)
+    }
+
+    //Plays the move with the most votes, once voting has closed, and says what happened. When
+    //nobody voted, voting stays open another day instead
+    fn close_vote(&mut self) -> String {
+        let (san, votes) = match self.tally().into_iter().next() {
+            Some(winner) => winner,
+            None => {
+                self.deadline = chrono::Local::now() + chrono::Duration::hours(VOTE_HOURS);
+                return format!(
+                    "Nobody voted for the server's move, so voting stays open until {}",
+                    self.deadline.format("%b %-d at %H:%M")
+                );
+            }
+        };
+        let text = format!(
+            "Voting closed. The server plays **{}** with {} of {} votes",
+            san,
+            votes,
+            self.votes.len()
+        );
+        self.play(&san);
+        text
+    }
+
+    //Who won, from the server's point of view
+    pub fn result(&self) -> &'static str {
+        match self.status {
+            GameStatus::WhiteWon if self.server_white => "The server won!",
+            GameStatus::BlackWon if !self.server_white => "The server won!",
+            GameStatus::WhiteWon | GameStatus::BlackWon => "The engine won",
+            GameStatus::Drawn => "The game is drawn",
+            _ => "The game is still going",
+        }
+    }
+
+    //The vote so far and when it closes, for messages
+    pub fn describe_vote(&self) -> String {
+        let tally = self.tally();
+        let mut text = if tally.is_empty() {
+            "No votes yet".to_owned()
+        } else {
+            tally
+                .iter()
+                .map(|(san, votes)| format!("{} ({})", san, votes))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        text.push_str(&format!(
+            "\nVoting closes {}. Vote with !vote <move>",
+            self.deadline.format("%b %-d at %H:%M")
+        ));
+        text
+    }
+}
+
+//Background task that closes votes that are due and has the engine answer
+pub async fn vote_task(http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(VOTE_CHECK_SECS));
+    loop {
+        interval.tick().await;
+
+        for (guild, library_arc) in libraries.all().await {
+            advance(&http, guild, &library_arc).await;
+        }
+    }
+}
+
+//Plays the server's move if voting on it has closed, then the engine's if it is its turn. Moves
+//are posted in the game's channel
+pub async fn advance(http: &Http, guild: Option<GuildId>, library_arc: &RwLock<Database>) {
+    let closed = {
+        let mut library = library_arc.write().await;
+        match &mut library.vote_game {
+            Some(game) if game.server_to_move() && chrono::Local::now() >= game.deadline => {
+                let text = game.close_vote();
+                let game = game.clone();
+                crate::autosave::persist_change(&library).await;
+                Some((game, text))
+            }
+            _ => None,
+        }
+    };
+    if let Some((game, text)) = closed {
+        let text = if game.status.is_over() {
+            format!("{}\n{}", text, game.result())
+        } else {
+            text
+        };
+        post(http, guild, ChannelId(game.channel), &game, text).await;
+    }
+
+    let (fen, level, played) = {
+        let library = library_arc.read().await;
+        match &library.vote_game {
+            Some(game) if game.engine_to_move() => {
+                (game.state().fen(), game.level, game.moves.len())
+            }
+            _ => return,
+        }
+    };
+    //The library isn't held while the engine thinks. If it fails, it is asked again next time
+    let reply = match crate::engine::play(&fen, level).await {
+        Ok(reply) => reply,
+        Err(err) => {
+            println!(
+                "The engine couldn't move in guild {:?}'s vote chess game: {:?}",
+                guild, err
+            );
+            return;
+        }
+    };
+    let answered = {
+        let mut library = library_arc.write().await;
+        let game = match &mut library.vote_game {
+            //The game may have been stopped in the meantime
+            Some(game) if game.engine_to_move() && game.moves.len() == played => game,
+            _ => return,
+        };
+        let san = match game.play(&reply) {
+            Some(san) => san,
+            None => return,
+        };
+        let game = game.clone();
+        crate::autosave::persist_change(&library).await;
+        (game, san)
+    };
+    let (game, san) = answered;
+    let text = if game.status.is_over() {
+        format!("The engine plays **{}**\n{}", san, game.result())
+    } else {
+        format!("The engine plays **{}**\n{}", san, game.describe_vote())
+    };
+    post(http, guild, ChannelId(game.channel), &game, text).await;
+}
+
+//Posts the board of `game` in `channel`, drawn from the server's side, with `text` under it
+pub async fn post(
+    http: &Http,
+    guild: Option<GuildId>,
+    channel: ChannelId,
+    game: &VoteGame,
+    text: String,
+) {
+    let state = game.state();
+    let png = match crate::board_image::render_board(
+        state.position().board(),
+        Game::last_squares(&state),
+        !game.server_white,
+    ) {
+        Ok(png) => png,
+        Err(err) => {
+            println!(
+                "Failed to draw guild {:?}'s vote chess board: {:?}",
+                guild, err
+            );
+            return;
+        }
+    };
+    let result = channel
+        .send_message(http, |m| {
+            m.add_file((png.as_slice(), "board.png")).embed(|e| {
+                e.colour(crate::response::Tone::Info.colour())
+                    .title("Vote chess: the server vs the engine")
+                    .description(text)
+                    .image("attachment://board.png")
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        println!(
+            "Failed to post guild {:?}'s vote chess board: {:?}",
+            guild, err
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::{Framework, StandardFramework},
+    http::Http,
+    model::{
+        channel::Message,
+        id::{MessageId, UserId},
+    },
+    prelude::TypeMapKey,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::guilds::Libraries;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const CHECK_INTERVAL_SECS: u64 = 5;
+//Commands that wait on the chess engine, which can take minutes: a blundercheck of a long game runs
+//for about 90s, and each engine call is only given up on after 60s. They get at least
+//ENGINE_TIMEOUT_SECS before they count as stuck
+const ENGINE_COMMANDS: &[&str] = &[
+    "analyze",
+    "blundercheck",
+    "eval",
+    "hint",
+    "move",
+    "play-bot",
+];
+const ENGINE_TIMEOUT_SECS: u64 = 180;
+
+struct InFlight {
+    started: Instant,
+    author: String,
+    content: String,
+    //Filled in by the before hook once the framework knows which command the message is for
+    command: Option<String>,
+    //How long it can run before it counts as stuck, which depends on the command
+    timeout: Duration,
+    cancel: Arc<Notify>,
+    reported: bool,
+}
+
+//Keeps track of every message the framework is currently processing so that commands which never
+//finish (usually because they are waiting on the library lock) can be noticed and cancelled
+pub struct Watchdog {
+    in_flight: Mutex<HashMap<MessageId, InFlight>>,
+    owners: HashSet<UserId>,
+    timeout: Duration,
+    cancel_stuck: bool,
+}
+
+pub struct WatchdogData;
+
+impl TypeMapKey for WatchdogData {
+    type Value = Arc<Watchdog>;
+}
+
+impl Watchdog {
+    //Reads WATCHDOG_TIMEOUT_SECS and WATCHDOG_CANCEL_STUCK from the environment
+    pub fn new(owners: HashSet<UserId>) -> Watchdog {
+        let timeout = env::var("WATCHDOG_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let cancel_stuck = env::var("WATCHDOG_CANCEL_STUCK")
+            .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Watchdog {
+            in_flight: Mutex::new(HashMap::new()),
+            owners,
+            timeout: Duration::from_secs(timeout),
+            cancel_stuck,
+        }
+    }
+
+    fn start(&self, msg: &Message) -> Arc<Notify> {
+        let cancel = Arc::new(Notify::new());
+        self.in_flight.lock().unwrap().insert(
+            msg.id,
+            InFlight {
+                started: Instant::now(),
+                author: msg.author.name.clone(),
+                content: msg.content.clone(),
+                command: None,
+                timeout: self.timeout,
+                cancel: cancel.clone(),
+                reported: false,
+            },
+        );
+        cancel
+    }
+
+    fn finish(&self, id: MessageId) {
+        let finished = self.in_flight.lock().unwrap().remove(&id);
+        if let Some(finished) = finished {
+            if finished.reported {
+                println!(
+                    "Stuck command '{}' finished after {:?}",
+                    finished.content,
+                    finished.started.elapsed()
+                );
+            }
+        }
+    }
+
+    pub fn set_command(&self, id: MessageId, command_name: &str) {
+        if let Some(entry) = self.in_flight.lock().unwrap().get_mut(&id) {
+            entry.command = Some(command_name.to_owned());
+            if ENGINE_COMMANDS.contains(&command_name) {
+                let engine_timeout = Duration::from_secs(ENGINE_TIMEOUT_SECS);
+                entry.timeout = entry.timeout.max(engine_timeout);
+            }
+        }
+    }
+
+    //Returns a diagnostic for every command that crossed the timeout since the last check
+    fn collect_stuck(&self) -> Vec<String> {
+        let in_flight = &mut *self.in_flight.lock().unwrap();
+
+        //The oldest command still running is almost always the one holding the library lock, since
+        //everything that started after it is queued up behind it
+        let oldest = in_flight
+            .iter()
+            .min_by_key(|(_, entry)| entry.started)
+            .map(|(_, entry)| describe(entry));
+
+        let mut reports = Vec::new();
+        for entry in in_flight.values_mut() {
+            if entry.reported || entry.started.elapsed() < entry.timeout {
+                continue;
+            }
+            entry.reported = true;
+
+            let mut report = format!(
+                "Command {} has been running for {}s",
+                describe(entry),
+                entry.started.elapsed().as_secs()
+            );
+            if let Some(oldest) = &oldest {
+                report.push_str(&format!("\nOldest running command: {}", oldest));
+            }
+            if self.cancel_stuck {
+                entry.cancel.notify_one();
+                report.push_str("\nThe command was cancelled");
+            }
+            reports.push(report);
+        }
+        reports
+    }
+}
+
+fn describe(entry: &InFlight) -> String {
+    format!(
+        "'{}' ({}) by {}",
+        entry.command.as_deref().unwrap_or("<unknown>"),
+        entry.content,
+        entry.author
+    )
+}
+
+//Runs the standard framework but lets the watchdog see (and cancel) every dispatch
+pub struct WatchdogFramework {
+    inner: StandardFramework,
+    watchdog: Arc<Watchdog>,
+}
+
+impl WatchdogFramework {
+    pub fn new(inner: StandardFramework, watchdog: Arc<Watchdog>) -> WatchdogFramework {
+        WatchdogFramework { inner, watchdog }
+    }
+}
+
+#[async_trait]
+impl Framework for WatchdogFramework {
+    async fn dispatch(&self, ctx: Context, msg: Message) {
+        let id = msg.id;
+        let cancel = self.watchdog.start(&msg);
+
+        let cancelled = tokio::select! {
+            _ = self.inner.dispatch(ctx.clone(), msg.clone()) => false,
+            _ = cancel.notified() => {
+                println!("Watchdog cancelled the command for message {}", id);
+                true
+            }
+        };
+
+        self.watchdog.finish(id);
+        //Cancelling skips the after hook, so whatever the command changed before it got stuck
+        //still has to be saved
+        if cancelled {
+            crate::save_after_change(&ctx, crate::guild_of(&ctx, &msg).await).await;
+        }
+    }
+}
+
+//Background task that periodically looks for stuck commands, logs what it finds and DMs the bot
+//owners about it
+pub async fn watchdog_task(watchdog: Arc<Watchdog>, http: Arc<Http>, libraries: Arc<Libraries>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+
+        let reports = watchdog.collect_stuck();
+        if reports.is_empty() {
+            continue;
+        }
+        let mut held = Vec::new();
+        for (guild, library_arc) in libraries.all().await {
+            if library_arc.try_write().is_err() {
+                held.push(match guild {
+                    Some(guild) => guild.to_string(),
+                    None => "home".to_owned(),
+                });
+            }
+        }
+        let lock_state = if held.is_empty() {
+            "The library locks are free".to_owned()
+        } else {
+            format!("The library lock is held for: {}", held.join(", "))
+        };
+
+        for report in reports {
+            let report = format!("{}\n{}", report, lock_state);
+            println!("Watchdog: {}", report);
+
+            for owner in &watchdog.owners {
+                let result = match owner.create_dm_channel(&http).await {
+                    Ok(channel) => channel
+                        .say(&http, format!("Watchdog: {}", report))
+                        .await
+                        .map(|_| ()),
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = result {
+                    println!(
+                        "Failed to notify owner {} about stuck command: {:?}",
+                        owner, err
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use serenity::{
+    model::{
+        guild::Member,
+        id::GuildId,
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionResponseType,
+        },
+    },
+    prelude::*,
+};
+
+use crate::Invocation;
+
+//Newcomers get a DM explaining how the club's library works, with a button to register straight
+//away. The button has a custom id of the form welcome-register:<guild id>
+
+//Used when the guild hasn't set its own text with !config welcome. {user}, {server} and {prefix}
+//are filled in
+pub const DEFAULT_WELCOME: &str = "Welcome to {server}, {user}! ♟️
+The club has a library of chess books that members can borrow for free. To get started, register with the button below or with {prefix}library register <your name>.
+Browse the books with {prefix}library list and borrow one with {prefix}library checkout <book>. An officer will hand it to you at the next club meeting.
+Use {prefix}help to see everything else the bot can do.";
+
+//DMs `member` the guild's welcome message, unless the guild turned it off
+pub async fn greet(ctx: &Context, guild_id: GuildId, member: &Member) {
+    let text = {
+        let library_arc = crate::library_for(ctx, Some(guild_id)).await;
+        let library = library_arc.read().await;
+        let config = &library.config;
+        if config.welcome_disabled || member.user.bot {
+            return;
+        }
+        let server = guild_id
+            .name(ctx)
+            .await
+            .unwrap_or_else(|| "the club".to_owned());
+        config
+            .welcome_message
+            .as_deref()
+            .unwrap_or(DEFAULT_WELCOME)
+            .replace("{user}", &member.user.name)
+            .replace("{server}", &server)
+            .replace("{prefix}", config.prefix())
+    };
+
+    let channel = match member.user.create_dm_channel(ctx).await {
+        Ok(channel) => channel,
+        Err(err) => {
+            println!("Failed to DM new member {}: {:?}", member.user.name, err);
+            return;
+        }
+    };
+    let sent = channel
+        .send_message(ctx, |m| {
+            m.content(text).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.style(ButtonStyle::Primary)
+                            .label("Register with the library")
+                            .custom_id(format!("welcome-register:{}", guild_id.0))
+                    })
+                })
+            })
+        })
+        .await;
+    //Members can turn off DMs from servers, which is fine
+    if let Err(err) = sent {
+        println!("Failed to DM new member {}: {:?}", member.user.name, err);
+    }
+}
+
+//Called when a newcomer presses the register button
+pub async fn handle_register(ctx: &Context, component: &MessageComponentInteraction, id: &str) {
+    let guild = match id.parse::<u64>() {
+        Ok(id) => GuildId(id),
+        Err(_) => return,
+    };
+    let invocation = Invocation {
+        guild: Some(guild),
+        channel: component.channel_id,
+        author: component.user.clone(),
+        display_name: component.user.name.clone(),
+    };
+
+    let maintenance = crate::library_for(ctx, invocation.guild)
+        .await
+        .read()
+        .await
+        .maintenance;
+    let text = if maintenance {
+        crate::MAINTENANCE_MESSAGE.to_owned()
+    } else {
+        let result =
+            crate::register_member(ctx, &invocation, component.user.id, String::new()).await;
+        crate::save_after_change(ctx, invocation.guild).await;
+        match result {
+            Ok(text) => text,
+            Err(why) => format!("Error: {}", why),
+        }
+    };
+
+    let response = component
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| d.content(text))
+        })
+        .await;
+    if let Err(err) = response {
+        println!("Failed to answer welcome registration: {:?}", err);
+    }
+}